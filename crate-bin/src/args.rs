@@ -1,10 +1,22 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use log::LevelFilter;
 
+/// Environment variable to read the archive's password from
+///
+/// The password isn't accepted as a command-line argument, as it would be visible to other users
+/// (e.g. through `ps`) and end up in the shell's history.
+pub static PASSWORD_ENV_VAR: &str = "BAF_PASSWORD";
+
 #[derive(Parser)]
-#[clap(name = "baf", version, author, about)]
+#[clap(
+    name = "baf",
+    version,
+    author,
+    about,
+    after_help = "The password to encrypt or decrypt the archive with, if any, is read from the BAF_PASSWORD environment variable."
+)]
 pub struct CmdArgs {
     #[clap(help = "Path to the archive")]
     pub path: PathBuf,
@@ -24,7 +36,13 @@ pub struct CmdArgs {
 
 #[derive(Parser)]
 pub enum Action {
-    Create,
+    Create {
+        #[clap(
+            long,
+            help = "Encrypt the names of items as well (requires a password)"
+        )]
+        encrypt_names: bool,
+    },
 
     #[clap(alias = "ls")]
     List,
@@ -53,6 +71,18 @@ pub enum Action {
             help = "Overwrite existing files if they already exist in the archive"
         )]
         overwrite_files: bool,
+
+        #[clap(long, help = "Store the content of identical files only once")]
+        dedup: bool,
+
+        #[clap(long, help = "Compress the content of files with the provided codec")]
+        compress: Option<CompressionArg>,
+
+        #[clap(
+            long,
+            help = "Encrypt the names of items if the archive is created (requires a password)"
+        )]
+        encrypt_names: bool,
     },
 
     Extract {
@@ -80,6 +110,19 @@ pub enum Action {
             help = "Overwrite existing files if they already exist in the output directory"
         )]
         overwrite_files: bool,
+
+        #[clap(
+            short = 'p',
+            long,
+            help = "Restore the permissions of the items, if they were recorded in the archive"
+        )]
+        preserve_permissions: bool,
+
+        #[clap(
+            long,
+            help = "Restore the owner and group of the items, if they were recorded in the archive"
+        )]
+        same_owner: bool,
     },
 
     Delete {
@@ -87,6 +130,10 @@ pub enum Action {
         items_to_delete: Vec<String>,
     },
 
+    Compact,
+
+    Upgrade,
+
     Zip {
         #[clap(
             long = "to",
@@ -94,4 +141,18 @@ pub enum Action {
         )]
         output: Option<PathBuf>,
     },
+
+    Repair {
+        #[clap(
+            long = "to",
+            help = "Output archive path (default: BAF path with its extension replaced by .repaired.baf)"
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CompressionArg {
+    Zstd,
+    Deflate,
 }
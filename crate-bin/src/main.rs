@@ -4,6 +4,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    env::{self, VarError},
     fs::{self, File},
     io,
     num::NonZero,
@@ -13,7 +14,10 @@ use std::{
 };
 
 use anyhow::{Context, Result, anyhow, bail};
-use baf::{Archive, ArchiveConfig, DirEntry, DirectoryIdOrRoot, ItemId, ItemIdOrRoot, Timestamp};
+use baf::{
+    Archive, ArchiveConfig, ArchiveVersion, CompressionCodec, CompressionPolicy, DirEntry,
+    DirectoryIdOrRoot, EncryptionKey, ItemId, ItemIdOrRoot, Timestamp,
+};
 use clap::Parser;
 use colored::Colorize;
 use jiff::{Zoned, civil};
@@ -22,7 +26,7 @@ use walkdir::WalkDir;
 use zip::{DateTime, ZipWriter, write::SimpleFileOptions};
 
 use self::{
-    args::{Action, CmdArgs},
+    args::{Action, CmdArgs, CompressionArg, PASSWORD_ENV_VAR},
     logger::Logger,
     tree::ArchiveContentTree,
     utils::{human_size, human_time},
@@ -31,6 +35,8 @@ use self::{
 mod args;
 mod logger;
 mod tree;
+#[cfg(unix)]
+mod unix;
 mod utils;
 
 fn main() -> ExitCode {
@@ -54,20 +60,44 @@ fn inner_main(args: CmdArgs) -> Result<()> {
         verbosity: _,
     } = args;
 
+    let password = match env::var(PASSWORD_ENV_VAR) {
+        Ok(password) => Some(password),
+        Err(VarError::NotPresent) => None,
+        Err(VarError::NotUnicode(_)) => bail!("{PASSWORD_ENV_VAR} is not valid UTF-8"),
+    };
+
+    if matches!(
+        action,
+        Action::Create {
+            encrypt_names: true
+        } | Action::Add {
+            encrypt_names: true,
+            ..
+        }
+    ) && password.is_none()
+    {
+        bail!("Encrypting names requires a password (see {PASSWORD_ENV_VAR})");
+    }
+
+    let base_config =
+        ArchiveConfig::default().with_encryption_key(password.map(EncryptionKey::Password));
+
     match action {
-        Action::Create => {
+        Action::Create { encrypt_names } => {
             if path.exists() {
                 bail!("Path {} already exists", path.display());
             }
 
-            let mut archive = Archive::create_as_file(path, ArchiveConfig::default())
-                .context("Failed to create archive")?;
+            let config = base_config.with_encrypt_names(encrypt_names);
+
+            let mut archive =
+                Archive::create_as_file(path, config).context("Failed to create archive")?;
 
             archive.flush().context("Failed to flush the archive")?;
         }
 
         Action::List => {
-            let  archive = Archive::open_from_file_readonly(path, ArchiveConfig::default())
+            let  archive = Archive::open_from_file_readonly(path, base_config)
                 .map_err(|err| anyhow!("Failed to open archive: {err:?}") /* TODO: display instead of debug */)?;
 
             for item in archive.items_iter() {
@@ -79,11 +109,21 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                         );
                     }
 
+                    DirEntry::File(file) if archive.is_symlink(file.id) => {
+                        info!(
+                            "|> {} -> {} (modified on {})",
+                            archive.with_paths().compute_file_path(file.id)?,
+                            archive.read_link(file.id)?.bright_cyan(),
+                            human_time(file.modif_time).bright_green()
+                        );
+                    }
+
                     DirEntry::File(file) => {
                         info!(
                             "|> {} ({}, modified on {})",
                             archive.with_paths().compute_file_path(file.id)?,
-                            human_size(file.content_len, Some(2)).bright_yellow(),
+                            human_size(archive.get_file_len(file.id).unwrap(), Some(2))
+                                .bright_yellow(),
                             human_time(file.modif_time).bright_green()
                         );
                     }
@@ -92,7 +132,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
         }
 
         Action::Tree => {
-            let archive = Archive::open_from_file_readonly(path, ArchiveConfig::default())
+            let archive = Archive::open_from_file_readonly(path, base_config)
                 .map_err(|err| anyhow!("Failed to open archive: {err:?}") /* TODO: display instead of debug */)?;
 
             info!("{}", ArchiveContentTree::build(&archive));
@@ -103,6 +143,9 @@ fn inner_main(args: CmdArgs) -> Result<()> {
             under_dir,
             merge_dirs,
             overwrite_files,
+            dedup,
+            compress,
+            encrypt_names,
         } => {
             for item_path in &items_path {
                 if !item_path.exists() {
@@ -110,19 +153,27 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 }
             }
 
-            let ItemsToAdd { dirs, files } = find_items_to_add(&items_path, under_dir.as_deref())?;
+            let ItemsToAdd {
+                dirs,
+                files,
+                symlinks,
+            } = find_items_to_add(&items_path, under_dir.as_deref())?;
 
-            let config = ArchiveConfig {
-                first_segment_dirs_capacity_override: Some(
+            let config = base_config
+                .with_first_segment_dirs_capacity_override(Some(
                     NonZero::new(u32::try_from(dirs.len()).unwrap() + 1).unwrap(),
-                ),
-
-                first_segment_files_capacity_override: Some(
-                    NonZero::new(u32::try_from(files.len()).unwrap() + 1).unwrap(),
-                ),
-
-                ..Default::default()
-            };
+                ))
+                .with_first_segment_files_capacity_override(Some(
+                    NonZero::new(u32::try_from(files.len() + symlinks.len()).unwrap() + 1).unwrap(),
+                ))
+                .with_deduplicate(dedup)
+                .with_compression(compress.map(|codec| {
+                    CompressionPolicy::new(match codec {
+                        CompressionArg::Zstd => CompressionCodec::Zstd,
+                        CompressionArg::Deflate => CompressionCodec::Deflate,
+                    })
+                }))
+                .with_encrypt_names(encrypt_names);
 
             let mut archive = if path.exists() {
                 // TODO: reserve space ahead of time for the computed number of files + dirs
@@ -164,7 +215,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
             for ItemToAdd {
                 real_path: _,
                 path_in_archive,
-            } in &files
+            } in files.iter().chain(&symlinks)
             {
                 if archive.with_paths().get_item_at(path_in_archive).is_some() {
                     if !overwrite_files {
@@ -181,15 +232,40 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 }
             }
 
+            #[cfg(unix)]
+            let accounts = unix::Accounts::load();
+
+            // Attributes and hard links are stored in items' records, which older versions don't have
+            #[cfg(unix)]
+            let has_records = archive.version().has_feature_flags();
+
+            #[cfg(unix)]
+            if !has_records {
+                warn!(
+                    "WARN: Unix attributes and hard links are not stored in archives before version 2, hard links are added as regular files"
+                );
+            }
+
             // Create directories first, so that files can be added into them
             for ItemToAdd {
                 real_path,
                 path_in_archive,
             } in dirs
             {
-                archive
+                #[cfg_attr(not(unix), allow(unused_variables))]
+                let dir_id = archive
                     .with_paths_mut()
                     .create_dir_at(&path_in_archive, get_item_mtime(&real_path)?)?;
+
+                #[cfg(unix)]
+                if has_records {
+                    archive
+                        .set_unix_attributes(
+                            ItemId::Directory(dir_id),
+                            Some(accounts.read_attributes(&real_path)?),
+                        )
+                        .context("Failed to store directory's attributes in archive")?;
+                }
             }
 
             // Get files size beforehand to display it
@@ -216,11 +292,43 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 human_size(files_size.values().sum::<u64>(), Some(2))
             );
 
+            // Path in the archive of the first file added for each group of hard links
+            #[cfg(unix)]
+            let mut hard_links = HashMap::<_, String>::new();
+
             for ItemToAdd {
                 real_path,
                 path_in_archive,
             } in &files
             {
+                #[cfg(unix)]
+                if let Some(inode) = unix::hard_link_inode(real_path)?.filter(|_| has_records) {
+                    if let Some(target) = hard_links.get(&inode) {
+                        debug!("> Adding hard link: {} -> {target}", real_path.display());
+
+                        // Overwriting was checked beforehand
+                        if archive.with_paths().get_file_at(path_in_archive).is_some() {
+                            archive.with_paths_mut().remove_file_at(path_in_archive)?;
+                        }
+
+                        let link_id = archive
+                            .with_paths_mut()
+                            .create_hard_link_at(path_in_archive, target)
+                            .context("Failed to add hard link to archive")?;
+
+                        archive
+                            .set_unix_attributes(
+                                ItemId::File(link_id),
+                                Some(accounts.read_attributes(real_path)?),
+                            )
+                            .context("Failed to store file's attributes in archive")?;
+
+                        continue;
+                    }
+
+                    hard_links.insert(inode, path_in_archive.clone());
+                }
+
                 debug!(
                     "> Adding file: {} ({})",
                     real_path.display(),
@@ -240,6 +348,73 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                     .with_paths_mut()
                     .write_file_at(path_in_archive, file, get_item_mtime(real_path)?)
                     .context("Failed to add file to archive")?;
+
+                #[cfg(unix)]
+                if has_records {
+                    let file_id = archive
+                        .with_paths()
+                        .get_file_at(path_in_archive)
+                        .context("Failed to find added file in archive")?
+                        .id;
+
+                    archive
+                        .set_unix_attributes(
+                            ItemId::File(file_id),
+                            Some(accounts.read_attributes(real_path)?),
+                        )
+                        .context("Failed to store file's attributes in archive")?;
+                }
+            }
+
+            if !symlinks.is_empty() {
+                info!("Adding {} symbolic links", symlinks.len());
+            }
+
+            for ItemToAdd {
+                real_path,
+                path_in_archive,
+            } in &symlinks
+            {
+                let target = fs::read_link(real_path).with_context(|| {
+                    format!("Failed to read symbolic link: {}", real_path.display())
+                })?;
+
+                let target = target.to_str().with_context(|| {
+                    format!(
+                        "Target of symbolic link '{}' contains invalid UTF-8 characters",
+                        real_path.display()
+                    )
+                })?;
+
+                debug!(
+                    "> Adding symbolic link: {} -> {target}",
+                    real_path.display()
+                );
+
+                // Overwriting was checked beforehand
+                if archive.with_paths().get_file_at(path_in_archive).is_some() {
+                    archive.with_paths_mut().remove_file_at(path_in_archive)?;
+                }
+
+                #[cfg_attr(not(unix), allow(unused_variables))]
+                let link_id = archive
+                    .with_paths_mut()
+                    .create_symlink_at(
+                        path_in_archive,
+                        target.to_owned(),
+                        get_item_mtime(real_path)?,
+                    )
+                    .context("Failed to add symbolic link to archive")?;
+
+                #[cfg(unix)]
+                if has_records {
+                    archive
+                        .set_unix_attributes(
+                            ItemId::File(link_id),
+                            Some(accounts.read_attributes(real_path)?),
+                        )
+                        .context("Failed to store symbolic link's attributes in archive")?;
+                }
             }
 
             archive.flush().context("Failed to close archive")?;
@@ -252,7 +427,13 @@ fn inner_main(args: CmdArgs) -> Result<()> {
             output_dir,
             merge_dirs,
             overwrite_files,
+            preserve_permissions,
+            same_owner,
         } => {
+            if cfg!(not(unix)) && (preserve_permissions || same_owner) {
+                bail!("Restoring permissions and ownership is only supported on Unix systems");
+            }
+
             let output_dir = match output_dir {
                 Some(dir) => {
                     if !dir.exists() {
@@ -287,7 +468,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 }
             };
 
-            let mut archive = Archive::open_from_file_readonly(path, ArchiveConfig::default())
+            let mut archive = Archive::open_from_file_readonly(path, base_config)
                 .map_err(|err| anyhow!("Failed to open archive: {err:?}") /* TODO: display instead of debug */)?;
 
             let archive_items: Vec<_> = if items_to_extract.is_empty() {
@@ -367,7 +548,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                         let path = archive.with_paths().compute_file_path(*file_id)?;
                         let output_path = output_dir.join(&path);
 
-                        if output_path.exists() {
+                        if output_path.symlink_metadata().is_ok() {
                             bail!(
                                 "Failed to extract archive: output file '{}' already exists",
                                 output_path.display()
@@ -377,7 +558,18 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 }
             }
 
+            // Attributes are restored once all items are extracted, as permissions
+            // may prevent writing inside directories
+            let mut to_restore = vec![];
+
+            // Output path of the extracted files having hard links
+            let mut extracted_links = HashMap::new();
+
             for item_id in archive_items {
+                if let Some(attributes) = archive.get_unix_attributes(item_id) {
+                    to_restore.push((item_id, attributes.clone()));
+                }
+
                 match item_id {
                     ItemId::Directory(dir_id) => {
                         let path = archive.with_paths().compute_dir_path(dir_id).unwrap();
@@ -393,12 +585,73 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                         })?;
                     }
 
-                    ItemId::File(file_id) => {
+                    ItemId::File(file_id) if archive.is_symlink(file_id) => {
                         let path = archive.with_paths().compute_file_path(file_id).unwrap();
-                        debug!("Extracting output file: {path}");
+                        let target = archive.read_link(file_id)?;
+                        debug!("Creating symbolic link: {path} -> {target}");
 
                         let output_path = output_dir.join(&path);
 
+                        // Overwriting was checked beforehand
+                        if output_path.symlink_metadata().is_ok() {
+                            fs::remove_file(&output_path).with_context(|| {
+                                format!(
+                                    "Failed to remove existing item at path '{}'",
+                                    output_path.display()
+                                )
+                            })?;
+                        }
+
+                        #[cfg(unix)]
+                        std::os::unix::fs::symlink(target, &output_path).with_context(|| {
+                            format!(
+                                "Failed to create symbolic link at path '{}'",
+                                output_path.display()
+                            )
+                        })?;
+
+                        #[cfg(not(unix))]
+                        warn!(
+                            "WARN: Symbolic links are only supported on Unix systems, skipping '{}'",
+                            output_path.display()
+                        );
+                    }
+
+                    ItemId::File(file_id) => {
+                        let path = archive.with_paths().compute_file_path(file_id).unwrap();
+                        let output_path = output_dir.join(&path);
+
+                        let has_hard_links = archive.get_hard_links(file_id).next().is_some();
+
+                        let extracted_link = archive
+                            .get_hard_links(file_id)
+                            .find_map(|link_id| extracted_links.get(&link_id));
+
+                        if let Some(link_path) = extracted_link {
+                            debug!("Creating hard link: {path}");
+
+                            // Overwriting was checked beforehand
+                            if output_path.symlink_metadata().is_ok() {
+                                fs::remove_file(&output_path).with_context(|| {
+                                    format!(
+                                        "Failed to remove existing item at path '{}'",
+                                        output_path.display()
+                                    )
+                                })?;
+                            }
+
+                            fs::hard_link(link_path, &output_path).with_context(|| {
+                                format!(
+                                    "Failed to create hard link at path '{}'",
+                                    output_path.display()
+                                )
+                            })?;
+
+                            continue;
+                        }
+
+                        debug!("Extracting output file: {path}");
+
                         let mut file = archive.read_file(file_id).with_context(|| {
                             format!("Failed to read file with id {path} from archive")
                         })?;
@@ -416,6 +669,41 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                                 output_path.display()
                             )
                         })?;
+
+                        if has_hard_links {
+                            extracted_links.insert(file_id, output_path);
+                        }
+                    }
+                }
+            }
+
+            #[cfg(unix)]
+            if preserve_permissions || same_owner {
+                let accounts = unix::Accounts::load();
+
+                // Restore children before their parent directory
+                for (item_id, attributes) in to_restore.into_iter().rev() {
+                    let path = match item_id {
+                        ItemId::Directory(dir_id) => {
+                            archive.with_paths().compute_dir_path(dir_id)?
+                        }
+                        ItemId::File(file_id) => archive.with_paths().compute_file_path(file_id)?,
+                    };
+
+                    let output_path = output_dir.join(path);
+
+                    if same_owner
+                        && let Err(err) = accounts.restore_owner(&output_path, &attributes)
+                    {
+                        warn!("WARN: {err:#}");
+                    }
+
+                    // Changing a link's permissions would change its target's instead
+                    let is_symlink =
+                        matches!(item_id, ItemId::File(file_id) if archive.is_symlink(file_id));
+
+                    if preserve_permissions && !is_symlink {
+                        unix::restore_permissions(&output_path, &attributes)?;
                     }
                 }
             }
@@ -427,7 +715,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
         }
 
         Action::Delete { items_to_delete } => {
-            let mut archive = Archive::open_from_file(&path, ArchiveConfig::default())
+            let mut archive = Archive::open_from_file(&path, base_config)
                 .map_err(|err| anyhow!("Failed to open archive: {err:?}") /* TODO: display instead of debug */)?;
 
             let mut to_delete_ids = HashSet::new();
@@ -492,6 +780,48 @@ fn inner_main(args: CmdArgs) -> Result<()> {
             info!("Successfully deleted items from archive");
         }
 
+        Action::Compact => {
+            let mut archive = Archive::open_from_file(&path, base_config)
+                .map_err(|err| anyhow!("Failed to open archive: {err}"))?;
+
+            let report = archive.compact().context("Failed to compact archive")?;
+
+            archive.flush().context("Failed to close archive")?;
+
+            info!(
+                "Successfully compacted archive: {} reclaimed (new size: {})",
+                human_size(report.reclaimed_bytes(), Some(2)).bright_yellow(),
+                human_size(report.len_after, Some(2)).bright_yellow()
+            );
+        }
+
+        Action::Upgrade => {
+            let mut archive = Archive::open_from_file(&path, base_config)
+                .map_err(|err| anyhow!("Failed to open archive: {err}"))?;
+
+            let previous = archive.version().version_number();
+
+            if *archive.version() == ArchiveVersion::LATEST {
+                info!("Archive already uses the latest version ({previous})");
+                return Ok(());
+            }
+
+            archive
+                .upgrade_to(ArchiveVersion::LATEST)
+                .context("Failed to upgrade archive")?;
+
+            archive.flush().context("Failed to close archive")?;
+
+            info!(
+                "Successfully upgraded archive from version {} to version {}",
+                previous.to_string().bright_yellow(),
+                ArchiveVersion::LATEST
+                    .version_number()
+                    .to_string()
+                    .bright_yellow()
+            );
+        }
+
         Action::Zip { output } => {
             let output = match output {
                 Some(output) => output,
@@ -509,7 +839,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                 );
             }
 
-            let mut archive = Archive::open_from_file_readonly(path, ArchiveConfig::default())
+            let mut archive = Archive::open_from_file_readonly(path, base_config)
                 .map_err(|err| anyhow!("Failed to open archive: {err:?}") /* TODO: display instead of debug */)?;
 
             let archive_items: Vec<_> = archive.items_iter().map(|item| item.id()).collect();
@@ -526,12 +856,15 @@ fn inner_main(args: CmdArgs) -> Result<()> {
             for item_id in archive_items {
                 match item_id {
                     ItemId::Directory(dir_id) => {
-                        let path = archive
-                            .with_paths()
-                            .compute_dir_path(dir_id)
-                            .with_context(|| {
-                                format!("Failed to compute path of directory with ID {dir_id:?}")
-                            })?;
+                        let path =
+                            archive
+                                .with_paths()
+                                .compute_dir_path(dir_id)
+                                .with_context(|| {
+                                    format!(
+                                        "Failed to compute path of directory with ID {dir_id:?}"
+                                    )
+                                })?;
 
                         debug!("Adding directory to ZIP: {path}");
 
@@ -549,6 +882,32 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                             .context("Failed to add directory to ZIP")?;
                     }
 
+                    ItemId::File(file_id) if archive.is_symlink(file_id) => {
+                        let path = archive
+                            .with_paths()
+                            .compute_file_path(file_id)
+                            .with_context(|| {
+                                format!("Failed to compute path of file with ID {file_id:?}")
+                            })?;
+
+                        let target = archive.read_link(file_id)?;
+                        debug!("Adding symbolic link to ZIP: {path} -> {target}");
+
+                        let modif_time = archive
+                            .get_file(file_id)
+                            .context("Failed to get file from archive")?
+                            .modif_time;
+
+                        zip_writer
+                            .add_symlink(
+                                &path,
+                                target,
+                                SimpleFileOptions::default()
+                                    .last_modified_time(zip_datetime(modif_time)?),
+                            )
+                            .context("Failed to add symbolic link to ZIP")?;
+                    }
+
                     ItemId::File(file_id) => {
                         let path = archive
                             .with_paths()
@@ -576,9 +935,8 @@ fn inner_main(args: CmdArgs) -> Result<()> {
                             )
                             .context("Failed to add file to ZIP")?;
 
-                        io::copy(&mut file, &mut zip_writer).with_context(|| {
-                            format!("Failed to write file '{path}' to ZIP")
-                        })?;
+                        io::copy(&mut file, &mut zip_writer)
+                            .with_context(|| format!("Failed to write file '{path}' to ZIP"))?;
                     }
                 }
             }
@@ -587,6 +945,64 @@ fn inner_main(args: CmdArgs) -> Result<()> {
 
             info!("Successfully converted archive to '{}'", output.display());
         }
+
+        Action::Repair { output } => {
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let mut output = path.clone();
+                    output.set_extension("repaired.baf");
+                    output
+                }
+            };
+
+            if output.exists() {
+                bail!(
+                    "Failed to repair archive: output file '{}' already exists",
+                    output.display()
+                );
+            }
+
+            let file = File::open(&path)
+                .with_context(|| format!("Failed to open file: {}", path.display()))?;
+
+            let mut salvaged = Archive::open_salvage(file, base_config.clone())
+                .map_err(|err| anyhow!("Failed to salvage archive: {err}"))?;
+
+            for diagnostic in salvaged.diagnostics() {
+                match diagnostic.addr {
+                    Some(addr) => warn!("At address {addr}: {}", diagnostic.issue),
+                    None => warn!("{}", diagnostic.issue),
+                }
+            }
+
+            let mut repaired =
+                Archive::create_as_file(&output, base_config).with_context(|| {
+                    format!("Failed to create archive at path '{}'", output.display())
+                })?;
+
+            let skipped = salvaged
+                .restore_into(&mut repaired)
+                .context("Failed to restore salvaged items")?;
+
+            for diagnostic in &skipped {
+                warn!("{}", diagnostic.issue);
+            }
+
+            repaired
+                .flush()
+                .context("Failed to close repaired archive")?;
+
+            info!(
+                "Successfully recovered {} directories and {} files into '{}' ({} problem(s) found)",
+                repaired.dirs().count().to_string().bright_yellow(),
+                repaired.files().count().to_string().bright_yellow(),
+                output.display(),
+                (salvaged.diagnostics().len() + skipped.len())
+                    .to_string()
+                    .bright_yellow()
+            );
+        }
     }
 
     Ok(())
@@ -595,6 +1011,7 @@ fn inner_main(args: CmdArgs) -> Result<()> {
 struct ItemsToAdd {
     dirs: Vec<ItemToAdd>,
     files: Vec<ItemToAdd>,
+    symlinks: Vec<ItemToAdd>,
 }
 
 struct ItemToAdd {
@@ -605,6 +1022,7 @@ struct ItemToAdd {
 fn find_items_to_add<P: AsRef<Path>>(items: &[P], under_dir: Option<&str>) -> Result<ItemsToAdd> {
     let mut dirs = vec![];
     let mut files = vec![];
+    let mut symlinks = vec![];
 
     for item_path in items {
         let item_path = item_path.as_ref();
@@ -686,6 +1104,11 @@ fn find_items_to_add<P: AsRef<Path>>(items: &[P], under_dir: Option<&str>) -> Re
                     real_path: item.path().to_owned(),
                     path_in_archive,
                 });
+            } else if item.file_type().is_symlink() {
+                symlinks.push(ItemToAdd {
+                    real_path: item.path().to_owned(),
+                    path_in_archive,
+                });
             } else {
                 warn!(
                     "WARN: Ignoring unknown item type at path '{}'",
@@ -695,12 +1118,17 @@ fn find_items_to_add<P: AsRef<Path>>(items: &[P], under_dir: Option<&str>) -> Re
         }
     }
 
-    Ok(ItemsToAdd { dirs, files })
+    Ok(ItemsToAdd {
+        dirs,
+        files,
+        symlinks,
+    })
 }
 
 fn get_item_mtime(path: &Path) -> Result<Timestamp> {
+    // Symbolic links are not followed, as they are stored as-is
     let mtime = path
-            .metadata()
+            .symlink_metadata()
             .context("Failed to get metadata for item")?
             .modified()
             .unwrap_or_else(|err| {
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    fs::{self, Permissions},
+    os::unix::fs::{MetadataExt, PermissionsExt, lchown},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use baf::UnixAttributes;
+
+/// Users and groups names, as listed in `/etc/passwd` and `/etc/group`
+pub struct Accounts {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Accounts {
+    /// Load the system's accounts (missing or unreadable files are treated as empty)
+    pub fn load() -> Self {
+        Self {
+            users: parse_accounts_file(Path::new("/etc/passwd")),
+            groups: parse_accounts_file(Path::new("/etc/group")),
+        }
+    }
+
+    /// Get the Unix attributes of an item on disk
+    pub fn read_attributes(&self, path: &Path) -> Result<UnixAttributes> {
+        let mt = fs::symlink_metadata(path).with_context(|| {
+            format!(
+                "Failed to get metadata on item at path '{}'",
+                path.display()
+            )
+        })?;
+
+        Ok(UnixAttributes {
+            mode: mt.mode(),
+            uid: mt.uid(),
+            gid: mt.gid(),
+            owner: self.users.get(&mt.uid()).cloned(),
+            group: self.groups.get(&mt.gid()).cloned(),
+        })
+    }
+
+    /// Apply the ownership described by some attributes to an item on disk
+    ///
+    /// Names are preferred over IDs when they exist on this system.
+    /// Symbolic links are not followed.
+    pub fn restore_owner(&self, path: &Path, attributes: &UnixAttributes) -> Result<()> {
+        let find_id = |names: &HashMap<u32, String>, name: &Option<String>| {
+            name.as_ref()
+                .and_then(|name| names.iter().find(|(_, candidate)| *candidate == name))
+                .map(|(id, _)| *id)
+        };
+
+        let uid = find_id(&self.users, &attributes.owner).unwrap_or(attributes.uid);
+        let gid = find_id(&self.groups, &attributes.group).unwrap_or(attributes.gid);
+
+        lchown(path, Some(uid), Some(gid)).with_context(|| {
+            format!(
+                "Failed to change owner of item at path '{}'",
+                path.display()
+            )
+        })
+    }
+}
+
+/// Get the device and inode numbers of a file on disk, if it has other hard links
+pub fn hard_link_inode(path: &Path) -> Result<Option<(u64, u64)>> {
+    let mt = fs::symlink_metadata(path).with_context(|| {
+        format!(
+            "Failed to get metadata on item at path '{}'",
+            path.display()
+        )
+    })?;
+
+    Ok((mt.nlink() > 1).then(|| (mt.dev(), mt.ino())))
+}
+
+/// Apply the permissions described by some attributes to an item on disk
+pub fn restore_permissions(path: &Path, attributes: &UnixAttributes) -> Result<()> {
+    fs::set_permissions(path, Permissions::from_mode(attributes.permissions())).with_context(|| {
+        format!(
+            "Failed to set permissions of item at path '{}'",
+            path.display()
+        )
+    })
+}
+
+/// Parse a colon-separated accounts file, where the first field is a name and the third one an ID
+fn parse_accounts_file(path: &Path) -> HashMap<u32, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    let mut accounts = HashMap::new();
+
+    for line in content.lines() {
+        let mut fields = line.split(':');
+
+        let (Some(name), Some(_), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+
+        if let Ok(id) = id.parse::<u32>()
+            && !name.is_empty()
+            && name.len() <= 255
+        {
+            accounts.entry(id).or_insert_with(|| name.to_owned());
+        }
+    }
+
+    accounts
+}
@@ -1,7 +1,8 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
     fs::{File as StdFile, OpenOptions},
-    io::{Cursor, Read, Seek, Write},
+    io::{ErrorKind, Read, Seek, Write},
     num::NonZero,
     path::Path,
 };
@@ -11,6 +12,9 @@ use thiserror::Error;
 
 use crate::{
     WithPathsMut,
+    attributes::UnixAttributes,
+    chunks::ContentHasher,
+    compression::{CompressionCodec, FileCompression},
     config::ArchiveConfig,
     coverage::{Coverage, Segment},
     data::{
@@ -20,18 +24,42 @@ use crate::{
         },
         file::{FILE_ENTRY_SIZE, FILE_NAME_OFFSET_IN_ENTRY, File, FileId},
         ft_segment::{FileTableSegment, FileTableSegmentDecodingError},
-        header::{ArchiveVersion, HEADER_SIZE, Header, HeaderDecodingError},
-        name::ItemName,
+        header::{
+            ArchiveVersion, FeatureFlags, HEADER_FEATURES_OFFSET, HEADER_JOURNAL_ADDR_OFFSET,
+            HEADER_SIZE, HEADER_VERSION_OFFSET, Header, HeaderDecodingError, SourceWithHeader,
+        },
+        name::{ItemName, MAX_ENCRYPTED_NAME_LEN, MAX_INLINE_NAME_LEN},
+        record::{ItemExtensions, encode_record},
         timestamp::Timestamp,
     },
+    encryption::{
+        ArchiveCipher, Cipher, ContentEncryptor, EncryptingReader, decrypted_content_len,
+        encrypted_content_len,
+    },
     file_reader::{FileReader, FileReaderError},
-    health::{DirContent, FileTableCorrectnessError, check_file_table_correctness},
+    file_writer::FileWriter,
+    health::{
+        DirContent, FileTableCorrectnessError, check_file_table_correctness, check_regions_layout,
+    },
     iter::ArchiveIter,
-    source::Source,
+    journal::{
+        DEFAULT_JOURNAL_CAPACITY, Journal, JournalDecodingError, JournalOverlay, JournalRecord,
+        JournalWrite,
+    },
+    salvage::SalvagedArchive,
+    source::{ReadAt, Source, SyncData, Truncate},
+    symlinks::is_valid_symlink_target,
+    transaction::Transaction,
+    undo::{UndoMap, UndoVec},
+    verify::{
+        ArchiveRegion, IntegrityError, VerificationReport, VerifyOptions, find_overlaps,
+        is_in_bounds,
+    },
     with_paths::WithPaths,
 };
 
-// TODO: ensure no files or segment overlap (= no overlap in coverage when calling .mark_as_used)
+/// Name of the directory orphaned items are reattached to (see [`Archive::open_with_lost_and_found`])
+pub static LOST_AND_FOUND_DIR_NAME: &str = "lost+found";
 
 /// Representation of an archive
 ///
@@ -40,11 +68,16 @@ pub struct Archive<S: Read + Seek> {
     conf: ArchiveConfig,
     source: Source<S>,
     header: Header,
-    file_segments: Vec<FileTableSegment>,
-    dirs: HashMap<DirectoryId, Directory>,
-    files: HashMap<FileId, File>,
-    dirs_content: HashMap<DirectoryIdOrRoot, DirContent>,
+    file_segments: UndoVec<FileTableSegment>,
+    dirs: UndoMap<DirectoryId, Directory>,
+    files: UndoMap<FileId, File>,
+    dirs_content: UndoMap<DirectoryIdOrRoot, DirContent>,
     coverage: Coverage,
+    journal: Option<Journal>,
+    staging: Option<Staging>,
+    hard_links: UndoMap<FileId, NonZero<u64>>,
+    contents: UndoMap<([u8; 32], u64), FileId>,
+    cipher: Option<ArchiveCipher>,
     next_id: NonZero<u64>,
 }
 
@@ -55,38 +88,148 @@ impl<S: Read + Seek> Archive<S> {
     ///
     /// Will read the entire archive's metadata segments before returning.
     pub fn open(source: S, conf: ArchiveConfig) -> Result<Self, ArchiveMetadataDecodingError> {
+        Self::decode(source, conf, false).map(|(archive, _)| archive)
+    }
+
+    /// Decode an archive's metadata
+    ///
+    /// If `accept_orphans` is set, items which can't be reached from the root directory are
+    /// not considered as errors, but are returned instead.
+    fn decode(
+        source: S,
+        conf: ArchiveConfig,
+        accept_orphans: bool,
+    ) -> Result<(Self, Vec<ItemId>), ArchiveMetadataDecodingError> {
         let mut source = Source::new(source);
 
-        let mut source_with_header = Header::decode(&mut source)?;
-        let header = source_with_header.header;
+        let header = Header::decode(&mut source)?.header;
+
+        let journal = header
+            .journal_addr
+            .map(|addr| Journal::decode(&mut source, addr))
+            .transpose()?;
+
+        let source_len = source
+            .seek_len()
+            .map_err(ArchiveMetadataDecodingError::IoError)?;
+
+        // If a record is pending in the journal, decode the metadata as if it had been replayed
+        let pending_writes = journal
+            .as_ref()
+            .and_then(|journal| journal.pending.as_ref())
+            .map_or(&[] as &[_], |record| &record.writes);
+
+        let mut overlay = Source::new(JournalOverlay::new(source.get_mut(), pending_writes));
+
+        // Apart from the journal's address, the header itself may be modified by the pending record
+        let header = Header::decode(&mut overlay)?.header;
+
+        let cipher = ArchiveCipher::unlock_archive(
+            header.encryption.as_ref(),
+            conf.encryption_key.as_ref(),
+        )?;
+
+        let mut source_with_header = SourceWithHeader {
+            source: &mut overlay,
+            header,
+            name_cipher: cipher.as_ref().and_then(|cipher| cipher.names.as_ref()),
+        };
+
+        let limits = &conf.open_limits;
 
         let mut file_segments = vec![];
-        let mut file_segments_addr = vec![HEADER_SIZE as u64];
-        let mut prev_segment = FileTableSegment::decode(&mut source_with_header)?;
+        let mut file_segments_addr = vec![];
+        let mut visited_segments = HashSet::new();
+        let mut entries_count = 0_u64;
+        let mut metadata_len = 0_u64;
+        let mut next_segment_addr = Some(HEADER_SIZE as u64);
+
+        while let Some(segment_addr) = next_segment_addr {
+            // A segment pointing to a previous one would make the chain loop forever
+            if !visited_segments.insert(segment_addr) {
+                return Err(ArchiveMetadataDecodingError::SegmentsCycle { addr: segment_addr });
+            }
+
+            if file_segments.len() == limits.max_segments {
+                return Err(ArchiveMetadataDecodingError::TooManySegments {
+                    max: limits.max_segments,
+                });
+            }
 
-        while let Some(next_segment) = prev_segment.consume_next_segment(&mut source_with_header) {
-            let next_segment = next_segment?;
+            // Check the limits before decoding the entries, as their count may be huge
+            let (dirs_count, files_count) =
+                FileTableSegment::decode_entries_count(&mut source_with_header, segment_addr)?;
 
-            file_segments.push(prev_segment);
+            entries_count =
+                entries_count.saturating_add(u64::from(dirs_count) + u64::from(files_count));
 
-            let (segment_addr, segment) = next_segment;
+            if entries_count > limits.max_entries {
+                return Err(ArchiveMetadataDecodingError::TooManyEntries {
+                    max: limits.max_entries,
+                });
+            }
+
+            metadata_len = metadata_len
+                .saturating_add(FileTableSegment::encoded_len_for(dirs_count, files_count));
+
+            if metadata_len > limits.max_metadata_len {
+                return Err(ArchiveMetadataDecodingError::MetadataTooLarge {
+                    max: limits.max_metadata_len,
+                });
+            }
+
+            let segment = FileTableSegment::decode(&mut source_with_header)?;
+
+            // Items' records are part of the metadata as well
+            metadata_len = segment
+                .records()
+                .fold(metadata_len, |len, record| len.saturating_add(record.len));
+
+            if metadata_len > limits.max_metadata_len {
+                return Err(ArchiveMetadataDecodingError::MetadataTooLarge {
+                    max: limits.max_metadata_len,
+                });
+            }
 
+            next_segment_addr = segment.next_segment_addr;
+            file_segments.push(segment);
             file_segments_addr.push(segment_addr);
-            prev_segment = segment;
         }
 
-        file_segments.push(prev_segment);
-
-        let coverage = compute_coverage(
-            file_segments
-                .iter()
-                .enumerate()
-                .map(|(i, segment)| (*file_segments_addr.get(i).unwrap(), segment)),
-            source
-                .seek_len()
-                .map_err(ArchiveMetadataDecodingError::IoError)?,
+        let regions = list_regions(
+            file_segments_addr.iter().copied().zip(&file_segments),
+            journal.as_ref(),
         );
 
+        let mut correctness_errors = check_regions_layout(&regions, source_len);
+
+        let (dirs_content, errors) = check_file_table_correctness(&file_segments);
+        let mut orphans = vec![];
+
+        for error in errors {
+            match error {
+                FileTableCorrectnessError::MissingParentDirectory { faulty_item_id, .. }
+                    if accept_orphans =>
+                {
+                    orphans.push(faulty_item_id)
+                }
+
+                FileTableCorrectnessError::ParentCycle { faulty_dir_id } if accept_orphans => {
+                    orphans.push(ItemId::Directory(faulty_dir_id))
+                }
+
+                _ => correctness_errors.push(error),
+            }
+        }
+
+        if !correctness_errors.is_empty() {
+            return Err(ArchiveMetadataDecodingError::FileTableCorrectnessError(
+                correctness_errors,
+            ));
+        }
+
+        let coverage = compute_coverage(&regions, source_len);
+
         let dirs = file_segments
             .iter()
             .flat_map(FileTableSegment::dirs)
@@ -101,28 +244,89 @@ impl<S: Read + Seek> Archive<S> {
             .map(|file| (file.id, file.clone()))
             .collect::<HashMap<_, _>>();
 
-        let dirs_content = check_file_table_correctness(&file_segments)
-            .map_err(ArchiveMetadataDecodingError::FileTableCorrectnessError)?;
+        let mut groups = HashMap::<_, Vec<FileId>>::new();
+
+        for file in files.values() {
+            if let Some(group) = file.extensions.hard_link_group {
+                groups
+                    .entry((group, file.content_addr, file.content_len))
+                    .or_default()
+                    .push(file.id);
+            }
+        }
+
+        let mut hard_links = HashMap::new();
+
+        for ((group, _, _), links) in groups {
+            // Files whose other links were removed or had their content replaced aren't links anymore
+            if links.len() >= 2 {
+                hard_links.extend(links.into_iter().map(|id| (id, group)));
+            }
+        }
 
+        // Hard link groups are allocated like IDs
         let max_id = dirs
             .keys()
             .map(|id| id.inner())
             .chain(files.keys().map(|id| id.inner()))
+            .chain(
+                files
+                    .values()
+                    .filter_map(|file| file.extensions.hard_link_group),
+            )
             .max();
 
         let next_id = NonZero::new(max_id.map_or(1, |max| max.get() + 1)).unwrap();
 
-        Ok(Self {
+        let contents = if conf.deduplicate {
+            files
+                .values()
+                .filter(|file| file.content_len > 0)
+                .map(|file| ((file.sha3_checksum, file.content_len), file.id))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let archive = Self {
             source,
             conf,
             header,
-            dirs,
-            files,
-            file_segments,
-            dirs_content,
+            dirs: dirs.into(),
+            files: files.into(),
+            file_segments: file_segments.into(),
+            dirs_content: dirs_content.into(),
             coverage,
+            journal,
+            staging: None,
+            hard_links: hard_links.into(),
+            contents: contents.into(),
+            cipher,
             next_id,
-        })
+        };
+
+        Ok((archive, orphans))
+    }
+
+    /// Recover as much data as possible from a damaged archive
+    ///
+    /// Undecodable entries are skipped, and decoding stops gracefully at the first broken file table segment.
+    /// Every problem encountered is reported in [`SalvagedArchive::diagnostics`].
+    ///
+    /// Only an I/O error on the source itself, or a missing or invalid encryption key
+    /// (see [`ArchiveConfig::encryption_key`]), makes this function fail.
+    pub fn open_salvage(
+        source: S,
+        conf: ArchiveConfig,
+    ) -> Result<SalvagedArchive<S>, ArchiveMetadataDecodingError> {
+        SalvagedArchive::decode(source, &conf)
+    }
+
+    /// Check if the archive has a write-ahead journal
+    ///
+    /// See [`ArchiveConfig::journal`] for more details
+    pub fn has_journal(&self) -> bool {
+        self.journal.is_some()
     }
 
     /// Get the archive's version
@@ -130,6 +334,11 @@ impl<S: Read + Seek> Archive<S> {
         &self.header.version
     }
 
+    /// Get the features the archive relies on (always empty before version 2)
+    pub fn features(&self) -> FeatureFlags {
+        self.header.features
+    }
+
     /// Get the list of all directories contained inside the archive
     pub fn dirs(&self) -> impl Iterator<Item = &Directory> {
         self.dirs.values()
@@ -150,6 +359,94 @@ impl<S: Read + Seek> Archive<S> {
         self.files.get(&id)
     }
 
+    /// Get the Unix attributes of an item, if any were stored
+    ///
+    /// See [`Archive::set_unix_attributes`]
+    pub fn get_unix_attributes(&self, id: ItemId) -> Option<&UnixAttributes> {
+        let extensions = match id {
+            ItemId::Directory(id) => &self.dirs.get(&id)?.extensions,
+            ItemId::File(id) => &self.files.get(&id)?.extensions,
+        };
+
+        extensions.unix_attributes.as_ref()
+    }
+
+    /// Check if a file is a symbolic link
+    ///
+    /// See [`Archive::create_symlink`]
+    pub fn is_symlink(&self, id: FileId) -> bool {
+        self.files
+            .get(&id)
+            .is_some_and(|file| file.extensions.symlink_target.is_some())
+    }
+
+    /// Get the target of a symbolic link
+    pub fn read_link(&self, id: FileId) -> Result<&str, ArchiveError> {
+        let file = self.files.get(&id).ok_or(ArchiveError::FileNotFound)?;
+
+        file.extensions
+            .symlink_target
+            .as_deref()
+            .ok_or(ArchiveError::NotASymlink)
+    }
+
+    /// Get the other files sharing their content with a file as hard links
+    ///
+    /// See [`Archive::create_hard_link`]
+    pub fn get_hard_links(&self, id: FileId) -> impl Iterator<Item = FileId> + '_ {
+        let group = self.hard_links.get(&id).copied();
+        let file = self.files.get(&id);
+
+        self.hard_links
+            .iter()
+            .filter(move |(other_id, other_group)| **other_id != id && Some(**other_group) == group)
+            .map(|(other_id, _)| *other_id)
+            // Links always share their content
+            .filter(move |other_id| {
+                let other = self.files.get(other_id);
+
+                file.zip(other).is_some_and(|(file, other)| {
+                    (file.content_addr, file.content_len) == (other.content_addr, other.content_len)
+                })
+            })
+    }
+
+    /// Get how a file's content is compressed, if it is
+    ///
+    /// See [`ArchiveConfig::compression`]
+    pub fn get_compression(&self, id: FileId) -> Option<FileCompression> {
+        self.files.get(&id)?.extensions.compression
+    }
+
+    /// Get the length of a file's content once decompressed and decrypted, in bytes
+    ///
+    /// Unlike [`File::content_len`], which is the length of the content as it is stored.
+    pub fn get_file_len(&self, id: FileId) -> Option<u64> {
+        let file = self.files.get(&id)?;
+
+        Some(match file.extensions.compression {
+            Some(compression) => compression.uncompressed_len,
+            None if self.is_encrypted() => decrypted_content_len(file.content_len),
+            None => file.content_len,
+        })
+    }
+
+    /// Check if the files' content is encrypted
+    ///
+    /// See [`ArchiveConfig::encryption_key`]
+    pub fn is_encrypted(&self) -> bool {
+        self.header.encryption.is_some()
+    }
+
+    /// Check if the items' names are encrypted as well
+    ///
+    /// See [`ArchiveConfig::encrypt_names`]
+    pub fn has_encrypted_names(&self) -> bool {
+        self.header
+            .encryption
+            .is_some_and(|encryption| encryption.encrypt_names)
+    }
+
     /// Read the archive using path-based APIs
     ///
     /// To get access to methods that require mutating `self`, use [`Self::with_paths_mut`] instead
@@ -218,13 +515,29 @@ impl<S: Read + Seek> Archive<S> {
     /// Get a [`FileReader`] over a file contained inside the archive
     pub fn read_file(&mut self, id: FileId) -> Result<FileReader<'_, S>, ArchiveError> {
         let file = self.files.get(&id).ok_or(ArchiveError::FileNotFound)?;
+        let cipher = self.content_cipher(file.content_len)?;
+
+        self.read_stored_file(id, cipher)
+    }
+
+    /// Get a [`FileReader`] over a file, decrypting its content with the provided cipher (if any)
+    fn read_stored_file(
+        &mut self,
+        id: FileId,
+        cipher: Option<Cipher>,
+    ) -> Result<FileReader<'_, S>, ArchiveError> {
+        let file = self.files.get(&id).ok_or(ArchiveError::FileNotFound)?;
 
         self.source.set_position(file.content_addr)?;
 
         Ok(FileReader::new(
             &mut self.source,
+            file.content_addr,
             file.content_len,
             file.sha3_checksum,
+            file.extensions.chunks.as_ref(),
+            file.extensions.compression,
+            cipher,
         ))
     }
 
@@ -255,14 +568,193 @@ impl<S: Read + Seek> Archive<S> {
     pub fn items_iter(&self) -> impl Iterator<Item = DirEntry<'_>> {
         ArchiveIter::new(self, DirectoryIdOrRoot::Root).unwrap()
     }
+
+    /// Verify the archive's integrity
+    ///
+    /// Every problem found is listed in the returned report. An error is only returned
+    /// if the archive couldn't be read.
+    pub fn verify(&mut self, options: VerifyOptions) -> Result<VerificationReport, ArchiveError> {
+        let archive_len = self.source.seek_len()?;
+
+        let mut errors = vec![];
+
+        // Read files in the order of their content to avoid seeking back and forth
+        let mut files = self
+            .files
+            .values()
+            .map(|file| (file.id, file.content_addr, file.content_len))
+            .collect::<Vec<_>>();
+
+        files.sort_by_key(|(_, content_addr, _)| *content_addr);
+
+        let mut in_bounds_files = vec![];
+
+        for (file_id, content_addr, content_len) in files {
+            let content = Segment {
+                start: content_addr,
+                len: content_len,
+            };
+
+            if is_in_bounds(content, archive_len) {
+                in_bounds_files.push(file_id);
+            } else {
+                errors.push(IntegrityError::OutOfBounds {
+                    file_id,
+                    content_addr,
+                    content_len,
+                    archive_len,
+                });
+            }
+        }
+
+        if options.overlaps {
+            errors.extend(
+                find_overlaps(self.regions())
+                    .into_iter()
+                    .map(|(region, other)| IntegrityError::Overlap { region, other }),
+            );
+        }
+
+        let mut checked_files = 0;
+
+        if options.checksums {
+            for file_id in in_bounds_files {
+                // Checksums apply to the stored content, so no key is required
+                match self.read_stored_file(file_id, None)?.verify() {
+                    Ok(()) => {}
+                    Err(FileReaderError::ChecksumMismatch { expected, actual }) => {
+                        errors.push(IntegrityError::ChecksumMismatch {
+                            file_id,
+                            expected,
+                            actual,
+                        })
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+
+                checked_files += 1;
+            }
+        }
+
+        Ok(VerificationReport {
+            checked_files,
+            errors,
+        })
+    }
+
+    /// Get a reference to the underlying stream
+    ///
+    /// Its position is not guaranteed to be anything specific.
+    pub(crate) fn stream(&self) -> &S {
+        self.source.get_ref()
+    }
+
+    /// Get a mutable reference to the underlying stream
+    ///
+    /// Its position must not be relied upon afterwards.
+    #[cfg(feature = "async")]
+    pub(crate) fn stream_mut(&mut self) -> &mut S {
+        self.source.get_mut()
+    }
+
+    /// Get the underlying stream
+    pub(crate) fn into_stream(self) -> S {
+        self.source.into_inner()
+    }
+
+    /// Get the cipher for the items' names, if they are encrypted
+    pub(crate) fn name_cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_ref()?.names.as_ref()
+    }
+
+    /// Get the cipher for a file's content of the provided length, if it is encrypted
+    ///
+    /// Empty contents are never encrypted.
+    pub(crate) fn content_cipher(&self, len: u64) -> Result<Option<Cipher>, ArchiveError> {
+        if len == 0 {
+            return Ok(None);
+        }
+
+        self.stream_cipher()
+    }
+
+    /// Get the cipher for a file's content of unknown length, if it is encrypted
+    ///
+    /// Encrypting an empty stream results in an empty content, so the content's length doesn't matter.
+    pub(crate) fn stream_cipher(&self) -> Result<Option<Cipher>, ArchiveError> {
+        if !self.is_encrypted() {
+            return Ok(None);
+        }
+
+        match &self.cipher {
+            Some(cipher) => Ok(Some(cipher.contents.clone())),
+            None => Err(ArchiveError::MissingEncryptionKey),
+        }
+    }
+
+    /// List all regions of the archive holding some data
+    fn regions(&self) -> Vec<(ArchiveRegion, Segment)> {
+        let mut file_segments = vec![];
+        let mut segment_addr = HEADER_SIZE as u64;
+
+        for segment in self.file_segments.iter() {
+            file_segments.push((segment_addr, segment));
+
+            if let Some(next_segment_addr) = segment.next_segment_addr {
+                segment_addr = next_segment_addr;
+            }
+        }
+
+        list_regions(file_segments, self.journal.as_ref())
+    }
+}
+
+impl<S: Read + Seek + ReadAt> Archive<S> {
+    /// Get a [`FileReader`] over a file contained inside the archive, without requiring an exclusive access
+    ///
+    /// The reader uses positional reads, so any number of them can be used at the same time,
+    /// including from different threads.
+    pub fn read_file_shared(&self, id: FileId) -> Result<FileReader<'_, S>, ArchiveError> {
+        let file = self.files.get(&id).ok_or(ArchiveError::FileNotFound)?;
+
+        Ok(FileReader::new_positional(
+            self.source.get_ref(),
+            file.content_addr,
+            file.content_len,
+            file.sha3_checksum,
+            file.extensions.chunks.as_ref(),
+            file.extensions.compression,
+            self.content_cipher(file.content_len)?,
+        ))
+    }
 }
 
-impl<S: Read + Write + Seek> Archive<S> {
+impl<S: Read + Write + Seek + SyncData> Archive<S> {
     /// Create a new archive
     pub fn create(source: S, conf: ArchiveConfig) -> Result<Self, ArchiveError> {
         let mut source = Source::new(source);
 
-        let header = Header::default();
+        let (cipher, encryption) = match &conf.encryption_key {
+            Some(key) => {
+                let (cipher, params) = ArchiveCipher::create(key, conf.encrypt_names);
+                (Some(cipher), Some(params))
+            }
+
+            None => (None, None),
+        };
+
+        let mut features = FeatureFlags::default();
+
+        if conf.version.has_feature_flags() {
+            features.set_required(FeatureFlags::REQUIRED_ENCRYPTION, encryption.is_some());
+        }
+
+        let header = Header {
+            version: conf.version,
+            encryption,
+            features,
+            ..Header::default()
+        };
 
         let segment = FileTableSegment {
             next_segment_addr: None,
@@ -289,19 +781,171 @@ impl<S: Read + Write + Seek> Archive<S> {
 
         source.set_position(0)?;
         source.write_all(&header.encode())?;
-        source.write_all(&segment.encode())?;
+        source.write_all(&segment.encode(header.version, None))?;
 
-        Ok(Self {
+        let journal = conf.journal;
+
+        let mut archive = Self {
             conf,
             header,
-            coverage: compute_coverage([((HEADER_SIZE as u64), &segment)], source.seek_len()?),
-            dirs: HashMap::new(),
-            files: HashMap::new(),
-            dirs_content: HashMap::from([(DirectoryIdOrRoot::Root, DirContent::default())]),
-            file_segments: vec![segment],
+            coverage: compute_coverage(
+                &list_regions([((HEADER_SIZE as u64), &segment)], None),
+                source.seek_len()?,
+            ),
+            dirs: HashMap::new().into(),
+            files: HashMap::new().into(),
+            dirs_content: HashMap::from([(DirectoryIdOrRoot::Root, DirContent::default())]).into(),
+            file_segments: vec![segment].into(),
             source,
+            journal: None,
+            staging: None,
+            hard_links: HashMap::new().into(),
+            contents: HashMap::new().into(),
+            cipher,
             next_id: NonZero::new(1).unwrap(),
-        })
+        };
+
+        if journal {
+            archive.enable_journal()?;
+        }
+
+        Ok(archive)
+    }
+
+    /// Open an existing archive, reattaching the items which can't be reached from the root directory
+    ///
+    /// Items whose parent directory doesn't exist, as well as directories whose parents chain
+    /// loops back to them, are moved inside a `lost+found` directory at the archive's root,
+    /// which is created if needed. They keep their name if possible, or are named after their ID otherwise.
+    ///
+    /// All items are reattached in a single [`Transaction`]. The reattached items are returned
+    /// alongside the archive.
+    pub fn open_with_lost_and_found(
+        source: S,
+        conf: ArchiveConfig,
+    ) -> Result<(Self, Vec<ItemId>), ArchiveMetadataDecodingError> {
+        let (mut archive, orphans) = Self::decode(source, conf, true)?;
+
+        if !orphans.is_empty() {
+            archive
+                .reattach_orphans(&orphans)
+                .map_err(ArchiveMetadataDecodingError::LostAndFound)?;
+        }
+
+        Ok((archive, orphans))
+    }
+
+    fn reattach_orphans(&mut self, orphans: &[ItemId]) -> Result<(), ArchiveError> {
+        let lost_and_found_name = ItemName::new(LOST_AND_FOUND_DIR_NAME.to_owned()).unwrap();
+
+        let lost_and_found = self
+            .dirs
+            .values()
+            .find(|dir| {
+                dir.parent_dir == DirectoryIdOrRoot::Root && dir.name == lost_and_found_name
+            })
+            .map(|dir| dir.id);
+
+        let mut tx = self.transaction();
+
+        let lost_and_found = DirectoryIdOrRoot::NonRoot(match lost_and_found {
+            Some(id) => id,
+            None => tx.create_dir(
+                DirectoryIdOrRoot::Root,
+                lost_and_found_name,
+                Timestamp::now(),
+            )?,
+        });
+
+        for orphan in orphans {
+            let (name, id) = match orphan {
+                ItemId::Directory(id) => (&tx.dirs[id].name, id.inner()),
+                ItemId::File(id) => (&tx.files[id].name, id.inner()),
+            };
+
+            let name = if tx.dirs_content[&lost_and_found].names.contains(name) {
+                ItemName::new(format!("#{id}")).unwrap()
+            } else {
+                name.clone()
+            };
+
+            match *orphan {
+                ItemId::Directory(id) => tx.move_dir(id, lost_and_found, name)?,
+                ItemId::File(id) => tx.move_file(id, lost_and_found, name)?,
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// Add a write-ahead journal to the archive, if it doesn't already have one
+    ///
+    /// See [`ArchiveConfig::journal`] for more details
+    pub fn enable_journal(&mut self) -> Result<(), ArchiveError> {
+        if self.journal.is_some() {
+            return Ok(());
+        }
+
+        self.allocate_journal(DEFAULT_JOURNAL_CAPACITY)
+    }
+
+    /// Upgrade the archive's format to a newer version, in place
+    ///
+    /// The header and the file table are rewritten for the new version, all at once if the archive is journaled.
+    /// Files' content is left untouched. Upgrading to the current version does nothing.
+    pub fn upgrade_to(&mut self, version: ArchiveVersion) -> Result<(), ArchiveError> {
+        if version == self.header.version {
+            return Ok(());
+        }
+
+        if version < self.header.version {
+            return Err(ArchiveError::VersionDowngrade {
+                current: self.header.version.version_number(),
+                requested: version.version_number(),
+            });
+        }
+
+        let mut features = self.header.features;
+
+        if version.has_feature_flags() {
+            features.set_required(
+                FeatureFlags::REQUIRED_ENCRYPTION,
+                self.header.encryption.is_some(),
+            );
+        }
+
+        let mut writes = vec![
+            JournalWrite {
+                addr: HEADER_VERSION_OFFSET,
+                data: version.encode().to_vec(),
+            },
+            JournalWrite {
+                addr: HEADER_FEATURES_OFFSET,
+                data: features.encode().to_vec(),
+            },
+        ];
+
+        // Entries are encoded differently depending on the version (e.g. modification times)
+        for (i, segment) in self.file_segments.iter().enumerate() {
+            writes.push(JournalWrite {
+                addr: self.segment_addr(i),
+                data: segment.encode(version, self.name_cipher()),
+            });
+        }
+
+        self.write_metadata(writes)?;
+
+        self.header.version = version;
+        self.header.features = features;
+
+        Ok(())
+    }
+
+    /// Start a transaction, to apply a group of changes in an all-or-nothing fashion
+    ///
+    /// See [`Transaction`] for more details
+    pub fn transaction(&mut self) -> Transaction<'_, S> {
+        Transaction::new(self)
     }
 
     /// Create a new directory
@@ -313,6 +957,7 @@ impl<S: Read + Write + Seek> Archive<S> {
         name: ItemName,
         modif_time: Timestamp,
     ) -> Result<DirectoryId, ArchiveError> {
+        self.ensure_name_fits(&name)?;
         self.ensure_no_duplicate_name(&name, parent_dir)?;
 
         let SegmentEntry {
@@ -321,19 +966,26 @@ impl<S: Read + Write + Seek> Archive<S> {
             entry_addr,
         } = self.get_addr_for_item_insert(ItemType::Directory)?;
 
+        let extensions = ItemExtensions::default();
+        let record = self.store_record(&name, &extensions)?;
+
         let id = DirectoryId(self.next_id);
         self.next_id = NonZero::new(self.next_id.get() + 1).expect("ID overflow");
 
         let dir = Directory {
             id,
             name,
+            record,
+            extensions,
             parent_dir,
-            modif_time,
+            modif_time: modif_time.for_version(self.header.version),
         };
 
         // Write the directory entry itself
-        self.source.set_position(entry_addr)?;
-        self.source.write_all(dir.encode().as_ref())?;
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: dir.encode(self.header.version, self.name_cipher()),
+        }])?;
 
         // Update names listing for parent directory
         let parent_dir_content = self.dirs_content.get_mut(&dir.parent_dir).unwrap();
@@ -355,6 +1007,8 @@ impl<S: Read + Write + Seek> Archive<S> {
 
     /// Create a new file
     ///
+    /// The content may be compressed, depending on [`ArchiveConfig::compression`].
+    ///
     /// Modification time is in seconds since Unix' Epoch
     pub fn create_file(
         &mut self,
@@ -364,36 +1018,256 @@ impl<S: Read + Write + Seek> Archive<S> {
         content: impl Read + Seek,
     ) -> Result<FileId, ArchiveError> {
         let mut content = Source::new(content);
+        let content_len = content.seek_len()?;
 
-        self.ensure_no_duplicate_name(&name, parent_dir)?;
+        if let Some((content_addr, hasher)) =
+            self.find_identical_content(&mut content, content_len)?
+        {
+            return self.create_file_with(parent_dir, name, modif_time, |archive| {
+                // Add a reference to the shared content
+                archive.coverage.mark_as_used(content_addr, content_len);
 
-        let SegmentEntry {
-            segment_index,
-            entry_index,
-            entry_addr,
-        } = self.get_addr_for_item_insert(ItemType::File)?;
+                Ok((content_addr, content_len, hasher, None))
+            });
+        }
 
-        // Write the file's content
-        let content_len = content.seek_len()?;
-        let (content_addr, sha3_checksum) = self.write_data_where_possible(content)?;
+        let codec = self.codec_for(&name, content_len);
 
-        // Get a new ID for the file
-        let id = FileId(self.next_id);
-        self.next_id = NonZero::new(self.next_id.get() + 1).expect("ID overflow");
+        self.create_file_with(parent_dir, name, modif_time, |archive| {
+            archive.write_content(content, content_len, codec)
+        })
+    }
 
-        let file = File {
-            id,
-            parent_dir,
-            name,
+    /// Create a new file from a stream of unknown length
+    ///
+    /// Unlike [`Self::create_file`], the content doesn't need to be seekable,
+    /// which allows piping data from e.g. a child process or a network socket.
+    /// It is always written after all existing data, as its length is only known once the stream ends.
+    ///
+    /// Modification time is in seconds since Unix' Epoch
+    pub fn create_file_from_reader(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        content: impl Read,
+    ) -> Result<FileId, ArchiveError> {
+        self.create_file_with(parent_dir, name, modif_time, |archive| {
+            let (content_addr, stored_len, hasher) = archive.write_stream(content)?;
+            Ok((content_addr, stored_len, hasher, None))
+        })
+    }
+
+    /// Create a new symbolic link
+    ///
+    /// Links are stored as files without any content, whose target is stored in their record
+    /// alongside their entry. They require version 2 of the format (see [`Archive::upgrade_to`]).
+    /// The target is not checked in any way.
+    ///
+    /// Modification time is in seconds since Unix' Epoch
+    pub fn create_symlink(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        target: String,
+    ) -> Result<FileId, ArchiveError> {
+        if !is_valid_symlink_target(&target) {
+            return Err(ArchiveError::InvalidSymlinkTarget);
+        }
+
+        self.ensure_records_supported()?;
+
+        self.create_file_entry(parent_dir, name, modif_time, |archive| {
+            let (content_addr, content_len, hasher) = archive.write_stream(std::io::empty())?;
+            let (sha3_checksum, chunks) = hasher.finalize();
+
+            let content_addr =
+                archive.share_identical_content(content_addr, content_len, sha3_checksum);
+
+            Ok((
+                content_addr,
+                content_len,
+                sha3_checksum,
+                ItemExtensions {
+                    chunks,
+                    symlink_target: Some(target),
+                    ..ItemExtensions::default()
+                },
+            ))
+        })
+    }
+
+    /// Create a new hard link to an existing file
+    ///
+    /// The link is a file entry referencing the exact same content as the target, which is not duplicated.
+    /// The content is only given back to the archive once every file referencing it has been removed.
+    /// Links share a group, which is stored in each file's record alongside its entry.
+    /// They require version 2 of the format (see [`Archive::upgrade_to`]).
+    ///
+    /// Replacing the content of one of the files only affects this file, which stops being linked to the others.
+    ///
+    /// The link gets the same modification time as its target.
+    pub fn create_hard_link(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        target: FileId,
+    ) -> Result<FileId, ArchiveError> {
+        if !self.files.contains_key(&target) {
+            return Err(ArchiveError::FileNotFound);
+        }
+
+        self.ensure_records_supported()?;
+
+        self.ensure_name_fits(&name)?;
+        self.ensure_no_duplicate_name(&name, parent_dir)?;
+
+        // The target joins the group first, so a crash in-between only leaves a group without links
+        let group = match self.hard_links.get(&target) {
+            Some(group) => *group,
+            None => {
+                let group = self.next_id;
+                self.next_id = NonZero::new(self.next_id.get() + 1).expect("ID overflow");
+
+                self.update_extensions(ItemId::File(target), |extensions| {
+                    extensions.hard_link_group = Some(group);
+                })?;
+
+                group
+            }
+        };
+
+        let target_file = self.files.get(&target).unwrap();
+
+        let File {
             modif_time,
             content_addr,
             content_len,
-            sha3_checksum: sha3_checksum.finalize().into(),
+            sha3_checksum,
+            ..
+        } = *target_file;
+
+        // The content is read the same way through the link
+        let extensions = ItemExtensions {
+            compression: target_file.extensions.compression,
+            chunks: target_file.extensions.chunks.clone(),
+            symlink_target: target_file.extensions.symlink_target.clone(),
+            hard_link_group: Some(group),
+            ..ItemExtensions::default()
+        };
+
+        let id = self.create_file_entry(parent_dir, name, modif_time, |archive| {
+            // Add a reference to the shared content
+            archive.coverage.mark_as_used(content_addr, content_len);
+
+            Ok((content_addr, content_len, sha3_checksum, extensions))
+        })?;
+
+        self.hard_links.insert(target, group);
+        self.hard_links.insert(id, group);
+
+        Ok(id)
+    }
+
+    /// Create a new file whose content is written progressively
+    ///
+    /// See [`FileWriter`] for more details
+    ///
+    /// Modification time is in seconds since Unix' Epoch
+    pub fn create_file_writer(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+    ) -> Result<FileWriter<'_, S>, ArchiveError> {
+        self.ensure_name_fits(&name)?;
+        self.ensure_no_duplicate_name(&name, parent_dir)?;
+
+        let addr = self.next_writable_addr();
+        let cipher = self.stream_cipher()?;
+
+        FileWriter::new(self, parent_dir, name, modif_time, addr, cipher)
+    }
+
+    fn create_file_with(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        write_content: impl FnOnce(
+            &mut Self,
+        ) -> Result<
+            (u64, u64, ContentHasher, Option<FileCompression>),
+            ArchiveError,
+        >,
+    ) -> Result<FileId, ArchiveError> {
+        self.create_file_entry(parent_dir, name, modif_time, |archive| {
+            let (content_addr, content_len, hasher, compression) = write_content(archive)?;
+            let (sha3_checksum, chunks) = hasher.finalize();
+
+            let content_addr =
+                archive.share_identical_content(content_addr, content_len, sha3_checksum);
+
+            Ok((
+                content_addr,
+                content_len,
+                sha3_checksum,
+                ItemExtensions {
+                    compression,
+                    chunks,
+                    ..ItemExtensions::default()
+                },
+            ))
+        })
+    }
+
+    /// Create a new file entry, once its content has been written and its checksums computed
+    fn create_file_entry(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        write_content: impl FnOnce(
+            &mut Self,
+        )
+            -> Result<(u64, u64, [u8; 32], ItemExtensions), ArchiveError>,
+    ) -> Result<FileId, ArchiveError> {
+        self.ensure_name_fits(&name)?;
+        self.ensure_no_duplicate_name(&name, parent_dir)?;
+
+        let SegmentEntry {
+            segment_index,
+            entry_index,
+            entry_addr,
+        } = self.get_addr_for_item_insert(ItemType::File)?;
+
+        // Write the file's content
+        let (content_addr, content_len, sha3_checksum, extensions) = write_content(self)?;
+
+        let record = self.store_record(&name, &extensions)?;
+
+        // Get a new ID for the file
+        let id = FileId(self.next_id);
+        self.next_id = NonZero::new(self.next_id.get() + 1).expect("ID overflow");
+
+        let file = File {
+            id,
+            parent_dir,
+            name,
+            record,
+            extensions,
+            modif_time: modif_time.for_version(self.header.version),
+            content_addr,
+            content_len,
+            sha3_checksum,
         };
 
         // Write the file's entry
-        self.source.set_position(entry_addr)?;
-        self.source.write_all(file.encode().as_ref())?;
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: file.encode(self.header.version, self.name_cipher()),
+        }])?;
 
         // Update names listing for parent directory
         let parent_dir_content = self.dirs_content.get_mut(&file.parent_dir).unwrap();
@@ -403,20 +1277,119 @@ impl<S: Read + Write + Seek> Archive<S> {
         // Update in-memory segments
         self.file_segments[segment_index].files[entry_index] = Some(file.clone());
 
+        self.register_content(&file);
+
         // Register the file
         assert!(self.files.insert(id, file).is_none());
 
         Ok(id)
     }
 
-    // TODO: re-use the space used by the file (if relevant)
-
     /// Overwrite an existing file's content and modification time
+    ///
+    /// If the new content fits in the space used by the current one, it is written in place
+    /// and the unused remainder is given back to the archive's free space.
+    /// Compressed contents (see [`ArchiveConfig::compression`]) are always written after all existing data,
+    /// and encrypted ones (see [`ArchiveConfig::encryption_key`]) are never written in place.
+    ///
+    /// Journaled archives and transactions never overwrite the current content in place,
+    /// so it remains valid until the new one has been committed.
     pub fn replace_file_content(
         &mut self,
         id: FileId,
         new_modif_time: Timestamp,
         new_content: impl Read + Seek,
+    ) -> Result<(), ArchiveError> {
+        let mut new_content = Source::new(new_content);
+        let content_len = new_content.seek_len()?;
+
+        if let Some((content_addr, hasher)) =
+            self.find_identical_content(&mut new_content, content_len)?
+        {
+            return self.replace_file_content_with(id, new_modif_time, |archive, old_content| {
+                // Add a reference to the shared content before giving back the current one,
+                // as they may be the same
+                archive.coverage.mark_as_used(content_addr, content_len);
+                archive.free_space(old_content);
+
+                Ok((content_addr, content_len, hasher, None))
+            });
+        }
+
+        let codec = self
+            .files
+            .get(&id)
+            .and_then(|file| self.codec_for(&file.name, content_len));
+
+        self.replace_file_content_with(id, new_modif_time, |archive, old_content| {
+            let written = if archive.journal.is_some() || archive.staging.is_some() {
+                let written = archive.write_content(new_content, content_len, codec)?;
+
+                archive.free_space(old_content);
+                written
+            } else {
+                let freed = archive.coverage.mark_as_free(old_content);
+
+                // Re-use the file's own space when possible
+                let written = (|| -> Result<_, ArchiveError> {
+                    if codec.is_none()
+                        && !archive.is_encrypted()
+                        && archive.coverage.is_free(old_content.start, content_len)
+                    {
+                        new_content.set_position(0)?;
+
+                        let hasher =
+                            archive.write_data_at(old_content.start, new_content, content_len)?;
+
+                        Ok((old_content.start, content_len, hasher, None))
+                    } else {
+                        archive.write_content(new_content, content_len, codec)
+                    }
+                })();
+
+                // The file still references its current content if writing failed
+                if written.is_err() && freed {
+                    archive
+                        .coverage
+                        .mark_as_used(old_content.start, old_content.len);
+                }
+
+                written?
+            };
+
+            Ok(written)
+        })
+    }
+
+    /// Overwrite an existing file's content and modification time from a stream of unknown length
+    ///
+    /// Unlike [`Self::replace_file_content`], the content doesn't need to be seekable.
+    /// It is always written after all existing data, and the space used by the current content
+    /// is given back to the archive afterwards.
+    pub fn replace_file_content_from_reader(
+        &mut self,
+        id: FileId,
+        new_modif_time: Timestamp,
+        new_content: impl Read,
+    ) -> Result<(), ArchiveError> {
+        self.replace_file_content_with(id, new_modif_time, |archive, old_content| {
+            let (content_addr, stored_len, hasher) = archive.write_stream(new_content)?;
+            archive.free_space(old_content);
+            Ok((content_addr, stored_len, hasher, None))
+        })
+    }
+
+    fn replace_file_content_with(
+        &mut self,
+        id: FileId,
+        new_modif_time: Timestamp,
+        write_content: impl FnOnce(
+            &mut Self,
+            Segment,
+        ) -> Result<
+            (u64, u64, ContentHasher, Option<FileCompression>),
+            ArchiveError,
+        >,
     ) -> Result<(), ArchiveError> {
         let SegmentEntry {
             segment_index,
@@ -426,42 +1399,63 @@ impl<S: Read + Write + Seek> Archive<S> {
             .get_item_entry(ItemId::File(id))
             .ok_or(ArchiveError::FileNotFound)?;
 
-        let mut new_content = Source::new(new_content);
-
         let file = self.files.get(&id).unwrap();
 
-        let content_len = new_content.seek_len()?;
-
-        let _ = self.coverage.mark_as_free(Segment {
+        let old_content = Segment {
             start: file.content_addr,
             len: file.content_len,
-        });
+        };
 
-        let (content_addr, sha3_checksum) = self.write_data_where_possible(new_content)?;
+        let (content_addr, content_len, hasher, compression) = write_content(self, old_content)?;
 
         // Update file metadata
-        let sha3_checksum_bytes = sha3_checksum.finalize();
+        let (sha3_checksum, chunks) = hasher.finalize();
+
+        let content_addr = self.share_identical_content(content_addr, content_len, sha3_checksum);
+
+        let file = self.files.get(&id).unwrap();
+        let name = file.name.clone();
+
+        // Other extensions (e.g. Unix attributes) are kept, but a symbolic link with some content
+        // becomes a regular file, and the file doesn't share its content anymore
+        let extensions = ItemExtensions {
+            compression,
+            chunks,
+            symlink_target: None,
+            hard_link_group: None,
+            ..file.extensions.clone()
+        };
+
+        let record = self.store_record(&name, &extensions)?;
 
         let new_file = self.files.get_mut(&id).unwrap();
         new_file.content_addr = content_addr;
         new_file.content_len = content_len;
-        new_file.sha3_checksum = sha3_checksum_bytes.into();
-        new_file.modif_time = new_modif_time;
+        new_file.sha3_checksum = sha3_checksum;
+        new_file.modif_time = new_modif_time.for_version(self.header.version);
+        new_file.extensions = extensions;
 
-        let encoded = new_file.encode();
-        self.source.set_position(entry_addr)?;
-        self.source.write_all(&encoded)?;
+        let old_record = std::mem::replace(&mut new_file.record, record);
+
+        let new_file = new_file.clone();
+        let encoded = new_file.encode(self.header.version, self.name_cipher());
+
+        self.register_content(&new_file);
+
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: encoded,
+        }])?;
+
+        if let Some(old_record) = old_record {
+            self.free_space(old_record);
+        }
 
         // Update in-memory file segment
-        *(self
-            .file_segments
-            .get_mut(segment_index)
-            .unwrap()
-            .files
-            .get_mut(entry_index)
-            .unwrap()
-            .as_mut()
-            .unwrap()) = new_file.clone();
+        self.file_segments[segment_index].files[entry_index] = Some(new_file);
+
+        // The file doesn't share its content anymore
+        self.unlink_hard_link(id);
 
         Ok(())
     }
@@ -484,20 +1478,31 @@ impl<S: Read + Write + Seek> Archive<S> {
         let parent_dir = dir.parent_dir;
         let old_name = dir.name.clone();
 
+        self.ensure_name_fits(&new_name)?;
+
         self.ensure_no_duplicate_name(&new_name, parent_dir)?;
 
-        self.source
-            .set_position(entry_addr + (DIRECTORY_NAME_OFFSET_IN_ENTRY as u64))?;
+        // The record holds the name, so it must be written again
+        let extensions = self.dirs.get(&id).unwrap().extensions.clone();
+        let record = self.store_record(&new_name, &extensions)?;
 
-        self.source.write_all(&new_name.encode())?;
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr + (DIRECTORY_NAME_OFFSET_IN_ENTRY as u64),
+            data: new_name
+                .encode_in_entry(self.name_cipher(), record)
+                .to_vec(),
+        }])?;
 
-        self.file_segments[segment_index].dirs[entry_index]
-            .as_mut()
-            .unwrap()
-            .name
-            .clone_from(&new_name);
+        let dir = self.dirs.get_mut(&id).unwrap();
+        dir.name.clone_from(&new_name);
 
-        self.dirs.get_mut(&id).unwrap().name.clone_from(&new_name);
+        let old_record = std::mem::replace(&mut dir.record, record);
+
+        self.file_segments[segment_index].dirs[entry_index] = Some(dir.clone());
+
+        if let Some(old_record) = old_record {
+            self.free_space(old_record);
+        }
 
         let parent_dir_content = self.dirs_content.get_mut(&parent_dir).unwrap();
         assert!(parent_dir_content.names.remove(&old_name));
@@ -520,20 +1525,31 @@ impl<S: Read + Write + Seek> Archive<S> {
         let parent_dir = file.parent_dir;
         let old_name = file.name.clone();
 
+        self.ensure_name_fits(&new_name)?;
+
         self.ensure_no_duplicate_name(&new_name, parent_dir)?;
 
-        self.source
-            .set_position(entry_addr + (FILE_NAME_OFFSET_IN_ENTRY as u64))?;
+        // The record holds the name, so it must be written again
+        let extensions = self.files.get(&id).unwrap().extensions.clone();
+        let record = self.store_record(&new_name, &extensions)?;
 
-        self.source.write_all(&new_name.encode())?;
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr + (FILE_NAME_OFFSET_IN_ENTRY as u64),
+            data: new_name
+                .encode_in_entry(self.name_cipher(), record)
+                .to_vec(),
+        }])?;
 
-        self.file_segments[segment_index].files[entry_index]
-            .as_mut()
-            .unwrap()
-            .name
-            .clone_from(&new_name);
+        let file = self.files.get_mut(&id).unwrap();
+        file.name.clone_from(&new_name);
+
+        let old_record = std::mem::replace(&mut file.record, record);
+
+        self.file_segments[segment_index].files[entry_index] = Some(file.clone());
 
-        self.files.get_mut(&id).unwrap().name.clone_from(&new_name);
+        if let Some(old_record) = old_record {
+            self.free_space(old_record);
+        }
 
         let parent_dir_content = self.dirs_content.get_mut(&parent_dir).unwrap();
         assert!(parent_dir_content.names.remove(&old_name));
@@ -542,6 +1558,161 @@ impl<S: Read + Write + Seek> Archive<S> {
         Ok(())
     }
 
+    /// Move a directory under another parent directory, and give it a (possibly different) name
+    ///
+    /// Only the directory's entry is rewritten, its content is left untouched.
+    pub fn move_dir(
+        &mut self,
+        id: DirectoryId,
+        new_parent_dir: DirectoryIdOrRoot,
+        new_name: ItemName,
+    ) -> Result<(), ArchiveError> {
+        let SegmentEntry {
+            segment_index,
+            entry_index,
+            entry_addr,
+        } = self
+            .get_item_entry(ItemId::Directory(id))
+            .ok_or(ArchiveError::DirectoryNotFound)?;
+
+        let dir = self.dirs.get(&id).unwrap();
+        let old_parent_dir = dir.parent_dir;
+        let old_name = dir.name.clone();
+
+        if old_parent_dir == new_parent_dir && old_name == new_name {
+            return Ok(());
+        }
+
+        // Ensure the directory is not moved inside itself or one of its descendants
+        let mut next = new_parent_dir;
+
+        while let DirectoryIdOrRoot::NonRoot(ancestor_id) = next {
+            if ancestor_id == id {
+                return Err(ArchiveError::MoveIntoDescendant);
+            }
+
+            next = self
+                .dirs
+                .get(&ancestor_id)
+                .ok_or(ArchiveError::DirectoryNotFound)?
+                .parent_dir;
+        }
+
+        self.ensure_name_fits(&new_name)?;
+
+        self.ensure_no_duplicate_name(&new_name, new_parent_dir)?;
+
+        let dir = self.dirs.get(&id).unwrap();
+        let old_record = dir.record;
+
+        // The record holds the name, so it must be written again if it changes
+        let record = if new_name == old_name {
+            old_record
+        } else {
+            let extensions = dir.extensions.clone();
+            self.store_record(&new_name, &extensions)?
+        };
+
+        let dir = self.dirs.get_mut(&id).unwrap();
+        dir.parent_dir = new_parent_dir;
+        dir.name.clone_from(&new_name);
+        dir.record = record;
+
+        let dir = dir.clone();
+
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: dir.encode(self.header.version, self.name_cipher()),
+        }])?;
+
+        self.file_segments[segment_index].dirs[entry_index] = Some(dir);
+
+        if let Some(old_record) = old_record.filter(|_| record != old_record) {
+            self.free_space(old_record);
+        }
+
+        // Update names listing for both parent directories
+        let old_parent_dir_content = self.dirs_content.get_mut(&old_parent_dir).unwrap();
+        assert!(old_parent_dir_content.dirs.remove(&id));
+        assert!(old_parent_dir_content.names.remove(&old_name));
+
+        let new_parent_dir_content = self.dirs_content.get_mut(&new_parent_dir).unwrap();
+        assert!(new_parent_dir_content.dirs.insert(id));
+        assert!(new_parent_dir_content.names.insert(new_name));
+
+        Ok(())
+    }
+
+    /// Move a file under another parent directory, and give it a (possibly different) name
+    ///
+    /// Only the file's entry is rewritten, its content is left untouched.
+    pub fn move_file(
+        &mut self,
+        id: FileId,
+        new_parent_dir: DirectoryIdOrRoot,
+        new_name: ItemName,
+    ) -> Result<(), ArchiveError> {
+        let SegmentEntry {
+            segment_index,
+            entry_index,
+            entry_addr,
+        } = self
+            .get_item_entry(ItemId::File(id))
+            .ok_or(ArchiveError::FileNotFound)?;
+
+        let file = self.files.get(&id).unwrap();
+        let old_parent_dir = file.parent_dir;
+        let old_name = file.name.clone();
+
+        if old_parent_dir == new_parent_dir && old_name == new_name {
+            return Ok(());
+        }
+
+        self.ensure_name_fits(&new_name)?;
+
+        self.ensure_no_duplicate_name(&new_name, new_parent_dir)?;
+
+        let file = self.files.get(&id).unwrap();
+        let old_record = file.record;
+
+        // The record holds the name, so it must be written again if it changes
+        let record = if new_name == old_name {
+            old_record
+        } else {
+            let extensions = file.extensions.clone();
+            self.store_record(&new_name, &extensions)?
+        };
+
+        let file = self.files.get_mut(&id).unwrap();
+        file.parent_dir = new_parent_dir;
+        file.name.clone_from(&new_name);
+        file.record = record;
+
+        let file = file.clone();
+
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: file.encode(self.header.version, self.name_cipher()),
+        }])?;
+
+        self.file_segments[segment_index].files[entry_index] = Some(file);
+
+        if let Some(old_record) = old_record.filter(|_| record != old_record) {
+            self.free_space(old_record);
+        }
+
+        // Update names listing for both parent directories
+        let old_parent_dir_content = self.dirs_content.get_mut(&old_parent_dir).unwrap();
+        assert!(old_parent_dir_content.files.remove(&id));
+        assert!(old_parent_dir_content.names.remove(&old_name));
+
+        let new_parent_dir_content = self.dirs_content.get_mut(&new_parent_dir).unwrap();
+        assert!(new_parent_dir_content.files.insert(id));
+        assert!(new_parent_dir_content.names.insert(new_name));
+
+        Ok(())
+    }
+
     /// Remove a directory, recursively
     ///
     /// Returns the removed directory entry
@@ -575,9 +1746,10 @@ impl<S: Read + Write + Seek> Archive<S> {
         }
 
         // Remove the directory entry itself
-        self.source.set_position(entry_addr)?;
-
-        self.source.write_all(&vec![0; DIRECTORY_ENTRY_SIZE])?;
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: vec![0; DIRECTORY_ENTRY_SIZE],
+        }])?;
 
         // Remove from in-memory file segments
         self.file_segments[segment_index].dirs[entry_index]
@@ -589,167 +1761,1212 @@ impl<S: Read + Write + Seek> Archive<S> {
 
         let parent_dir_content = self.dirs_content.get_mut(&dir.parent_dir).unwrap();
 
-        assert!(parent_dir_content.dirs.remove(&dir.id));
-        assert!(parent_dir_content.names.remove(&dir.name));
+        assert!(parent_dir_content.dirs.remove(&dir.id));
+        assert!(parent_dir_content.names.remove(&dir.name));
+
+        if let Some(record) = dir.record {
+            self.free_space(record);
+        }
+
+        // Remove the directory's content listing
+        let DirContent { dirs, files, names } = self
+            .dirs_content
+            .remove(&DirectoryIdOrRoot::NonRoot(dir.id))
+            .unwrap();
+
+        assert!(dirs.is_empty());
+        assert!(files.is_empty());
+        assert!(names.is_empty());
+
+        Ok(dir)
+    }
+
+    /// Remove a file
+    ///
+    /// Returns the removed file entry
+    pub fn remove_file(&mut self, id: FileId) -> Result<File, ArchiveError> {
+        let SegmentEntry {
+            segment_index,
+            entry_index,
+            entry_addr,
+        } = self
+            .get_item_entry(ItemId::File(id))
+            .ok_or(ArchiveError::FileNotFound)?;
+
+        // Remove the file entry itself
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr,
+            data: vec![0; FILE_ENTRY_SIZE],
+        }])?;
+
+        // Remove from in-memory file segments
+        self.file_segments[segment_index].files[entry_index]
+            .take()
+            .unwrap();
+
+        // Unregister the file and remove its name from the listing
+        let file = self.files.remove(&id).unwrap();
+
+        let parent_dir_content = self.dirs_content.get_mut(&file.parent_dir).unwrap();
+
+        assert!(parent_dir_content.files.remove(&file.id));
+        assert!(parent_dir_content.names.remove(&file.name));
+
+        // Update coverage
+        self.free_space(Segment {
+            start: file.content_addr,
+            len: file.content_len,
+        });
+
+        if let Some(record) = file.record {
+            self.free_space(record);
+        }
+
+        self.unlink_hard_link(id);
+
+        Ok(file)
+    }
+
+    /// Flush all changes, and wait until they are persisted (see [`SyncData`])
+    pub fn flush(&mut self) -> Result<(), ArchiveError> {
+        Ok(self.source.sync()?)
+    }
+
+    /// Close the archive
+    ///
+    /// Returns the original source provided at type construction
+    pub fn close(mut self) -> Result<S, ArchiveError> {
+        self.flush()?;
+        Ok(self.source.into_inner())
+    }
+
+    /// Check if checksums are available for each chunk of a file's content
+    ///
+    /// See [`ArchiveConfig::chunk_size`] for more details
+    pub fn has_chunk_checksums(&self, id: FileId) -> bool {
+        self.files
+            .get(&id)
+            .is_some_and(|file| file.extensions.chunks.is_some())
+    }
+
+    /// Set (or remove) the Unix attributes of an item
+    ///
+    /// Attributes are stored in the item's record, which is written alongside its entry.
+    /// They require version 2 of the format (see [`Archive::upgrade_to`]).
+    ///
+    /// Attributes are removed alongside their item.
+    pub fn set_unix_attributes(
+        &mut self,
+        id: ItemId,
+        attributes: Option<UnixAttributes>,
+    ) -> Result<(), ArchiveError> {
+        if attributes
+            .as_ref()
+            .is_some_and(|attributes| !attributes.is_valid())
+        {
+            return Err(ArchiveError::InvalidUnixAttributes);
+        }
+
+        self.update_extensions(id, |extensions| extensions.unix_attributes = attributes)
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData + Truncate> Archive<S> {
+    /// Compact the archive to give back the space left unused by removed or replaced items
+    ///
+    /// File contents and file table segments are moved towards the archive's beginning,
+    /// then the underlying stream is truncated.
+    ///
+    /// Items' IDs are preserved, only their location changes.
+    pub fn compact(&mut self) -> Result<CompactionReport, ArchiveError> {
+        let len_before = self.source.seek_len()?;
+
+        // Ensure the journal is empty and won't need to be moved while compacting
+        self.replay_journal()?;
+        self.reserve_journal(16 + FILE_ENTRY_SIZE as u64)?;
+
+        // Files sharing their content are relocated together
+        let mut contents = HashSet::new();
+
+        // The first segment is always located right after the header, so it never moves
+        let mut to_relocate = (1..self.file_segments.len())
+            .map(|segment_index| {
+                (
+                    self.segment_addr(segment_index),
+                    self.file_segments[segment_index].encoded_len(),
+                    Relocatable::Segment(segment_index),
+                )
+            })
+            .chain(
+                self.files
+                    .values()
+                    .filter(|file| {
+                        file.content_len > 0
+                            && contents.insert((file.content_addr, file.content_len))
+                    })
+                    .map(|file| {
+                        (
+                            file.content_addr,
+                            file.content_len,
+                            Relocatable::File(file.id),
+                        )
+                    }),
+            )
+            .chain(self.dirs.values().filter_map(|dir| {
+                dir.record.map(|record| {
+                    (
+                        record.start,
+                        record.len,
+                        Relocatable::ItemRecord(ItemId::Directory(dir.id)),
+                    )
+                })
+            }))
+            .chain(self.files.values().filter_map(|file| {
+                file.record.map(|record| {
+                    (
+                        record.start,
+                        record.len,
+                        Relocatable::ItemRecord(ItemId::File(file.id)),
+                    )
+                })
+            }))
+            .chain(
+                self.journal
+                    .as_ref()
+                    .map(|journal| (journal.addr, journal.encoded_len(), Relocatable::Journal)),
+            )
+            .collect::<Vec<_>>();
+
+        to_relocate.sort_by_key(|(addr, _, _)| *addr);
+
+        let first_addr = HEADER_SIZE as u64 + self.file_segments[0].encoded_len();
+
+        // Overlapping data can't be moved without corrupting it, so nothing is moved at all
+        let mut next_addr = first_addr;
+
+        for (addr, len, _) in &to_relocate {
+            if *addr < next_addr {
+                return Err(ArchiveError::OverlappingData { addr: *addr });
+            }
+
+            next_addr += len;
+        }
+
+        let mut next_addr = first_addr;
+
+        for (addr, len, item) in to_relocate {
+            if addr > next_addr {
+                // Moving overlapping data would overwrite it before its new location is recorded,
+                // so journaled archives first move it to the end of the archive
+                if self.journal.is_some() && addr - next_addr < len {
+                    let staging_addr = self.source.seek_len()?;
+
+                    self.copy_data(addr, staging_addr, len)?;
+                    self.relocate(item, staging_addr)?;
+
+                    self.copy_data(staging_addr, next_addr, len)?;
+                } else {
+                    self.copy_data(addr, next_addr, len)?;
+                }
+
+                self.relocate(item, next_addr)?;
+            }
+
+            next_addr += len;
+        }
+
+        self.source.sync()?;
+        self.source.truncate(next_addr)?;
+
+        self.coverage = compute_coverage(&self.regions(), next_addr);
+
+        Ok(CompactionReport {
+            len_before,
+            len_after: next_addr,
+        })
+    }
+}
+
+/// Internal functions
+impl<S: Read + Write + Seek + SyncData> Archive<S> {
+    fn segment_addr(&self, segment_index: usize) -> u64 {
+        assert!(segment_index < self.file_segments.len());
+
+        if segment_index == 0 {
+            HEADER_SIZE as u64
+        } else {
+            self.file_segments[segment_index - 1]
+                .next_segment_addr
+                .unwrap()
+        }
+    }
+
+    fn get_item_entry(&self, item_id: ItemId) -> Option<SegmentEntry> {
+        self.file_segments
+            .iter()
+            .enumerate()
+            .find_map(|(segment_index, segment)| {
+                let entry_index = match item_id {
+                    ItemId::Directory(id) => segment
+                        .dirs
+                        .iter()
+                        .position(|entry| entry.as_ref().is_some_and(|dir| dir.id == id)),
+
+                    ItemId::File(id) => segment
+                        .files
+                        .iter()
+                        .position(|entry| entry.as_ref().is_some_and(|file| file.id == id)),
+                };
+
+                entry_index.map(|entry_index| {
+                    let entry_index_u32 = u32::try_from(entry_index).unwrap();
+
+                    SegmentEntry {
+                        segment_index,
+                        entry_index,
+                        entry_addr: self.segment_addr(segment_index)
+                            + match item_id {
+                                ItemId::Directory(_) => segment
+                                    .dir_entry_offset(entry_index_u32)
+                                    .expect("entry index is in bounds"),
+                                ItemId::File(_) => segment
+                                    .file_entry_offset(entry_index_u32)
+                                    .expect("entry index is in bounds"),
+                            },
+                    }
+                })
+            })
+    }
+
+    /// Write some metadata (file table segment, journal, etc.) wherever there is some free space
+    fn write_bytes_where_possible(&mut self, bytes: &[u8]) -> Result<u64, ArchiveError> {
+        let len = u64::try_from(bytes.len()).unwrap();
+
+        let addr = match self.coverage.find_free_zone_for(len) {
+            Some(segment) => segment.start,
+            None => self.coverage.next_writable_addr(),
+        };
+
+        self.source.set_position(addr)?;
+        self.source.write_all(bytes)?;
+
+        if addr + len > self.coverage.len() {
+            self.coverage.grow_to(addr + len);
+        }
+
+        self.coverage.mark_as_used(addr, len);
+
+        Ok(addr)
+    }
+
+    /// Write some file content wherever there is some free space
+    fn write_data_where_possible(
+        &mut self,
+        data: impl Read,
+        len: u64,
+    ) -> Result<(u64, ContentHasher), ArchiveError> {
+        let addr = match self.coverage.find_free_zone_for(len) {
+            Some(segment) => segment.start,
+            None => self.coverage.next_writable_addr(),
+        };
+
+        let hasher = self.write_data_at(addr, data, len)?;
+
+        Ok((addr, hasher))
+    }
+
+    /// Write some file content at the provided address, which must not overlap any used zone
+    fn write_data_at(
+        &mut self,
+        addr: u64,
+        mut data: impl Read,
+        len: u64,
+    ) -> Result<ContentHasher, ArchiveError> {
+        debug_assert!(self.coverage.is_free(addr, len));
+
+        self.source.set_position(addr)?;
+
+        let mut hasher = self.content_hasher();
+        let mut written = 0;
+
+        // Progressively write the data using 4KB chunks and compute the checksum in the meantime
+        const CHUNK_SIZE: usize = 4096;
+
+        while written < len {
+            let mut buf = [0; CHUNK_SIZE];
+            let len = (CHUNK_SIZE as u64).min(len - written);
+            let len_usize = usize::try_from(len).unwrap();
+            data.read_exact(&mut buf[0..len_usize])?;
+
+            let data = &buf[0..len_usize];
+
+            self.source.write_all(data)?;
+            written += len;
+            hasher.update(data);
+        }
+
+        if addr + len > self.coverage.len() {
+            self.coverage.grow_to(addr + len);
+        }
+
+        self.coverage.mark_as_used(addr, len);
+
+        Ok(hasher)
+    }
+
+    /// Write a stream of unknown length after all existing data
+    ///
+    /// Returns the data's address and length
+    fn write_stream_at_end(
+        &mut self,
+        mut data: impl Read,
+    ) -> Result<(u64, u64, ContentHasher), ArchiveError> {
+        let addr = self.coverage.next_writable_addr();
+
+        self.source.set_position(addr)?;
+
+        let mut hasher = self.content_hasher();
+        let mut written = 0;
+
+        const CHUNK_SIZE: usize = 4096;
+
+        loop {
+            let mut buf = [0; CHUNK_SIZE];
+
+            let len = match data.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            let data = &buf[0..len];
+
+            self.source.write_all(data)?;
+            written += u64::try_from(len).unwrap();
+            hasher.update(data);
+        }
+
+        if addr + written > self.coverage.len() {
+            self.coverage.grow_to(addr + written);
+        }
+
+        self.coverage.mark_as_used(addr, written);
+
+        Ok((addr, written, hasher))
+    }
+
+    /// Write some data right after a reserved zone, and extend the reservation to cover it
+    ///
+    /// The zone must be located after all existing data.
+    pub(crate) fn append_to_reserved(
+        &mut self,
+        reserved: &mut Segment,
+        data: &[u8],
+    ) -> Result<(), ArchiveError> {
+        self.source.set_position(reserved.start + reserved.len)?;
+        self.source.write_all(data)?;
+
+        self.extend_reserved(reserved, u64::try_from(data.len()).unwrap());
+
+        Ok(())
+    }
+
+    /// Mark data written right after a reserved zone as part of it
+    pub(crate) fn extend_reserved(&mut self, reserved: &mut Segment, len: u64) {
+        let _ = self.coverage.mark_as_free(*reserved);
+
+        reserved.len += len;
+
+        if reserved.start + reserved.len > self.coverage.len() {
+            self.coverage.grow_to(reserved.start + reserved.len);
+        }
+
+        self.coverage.mark_as_used(reserved.start, reserved.len);
+    }
+
+    /// Give back a reserved zone that won't be used
+    pub(crate) fn release_reserved(&mut self, reserved: Segment) {
+        let _ = self.coverage.mark_as_free(reserved);
+    }
+
+    /// Create a new file whose content was written in a reserved zone, compressed as provided if it is
+    ///
+    /// Like for [`Self::create_file`], compressed contents which are too short or don't save enough space
+    /// are stored uncompressed instead: they are decompressed after all existing data, and the reserved zone
+    /// is given back to the archive.
+    pub(crate) fn create_reserved_file(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        reserved: Segment,
+        hasher: ContentHasher,
+        compression: Option<FileCompression>,
+    ) -> Result<FileId, ArchiveError> {
+        let keep_compressed = compression.is_some_and(|compression| {
+            let compressed_len = if self.is_encrypted() {
+                decrypted_content_len(reserved.len)
+            } else {
+                reserved.len
+            };
+
+            self.codec_for(&name, compression.uncompressed_len) == Some(compression.codec)
+                && self
+                    .conf
+                    .compression
+                    .as_ref()
+                    .unwrap()
+                    .is_worth_it(compression.uncompressed_len, compressed_len)
+        });
+
+        let mut decompressed = None;
+
+        let created =
+            self.create_file_with(parent_dir, name, modif_time, |archive| match compression {
+                Some(compression) if !keep_compressed => {
+                    let (sha3_checksum, _) = hasher.finalize();
+                    let (addr, len, hasher) =
+                        archive.decompress_reserved(reserved, sha3_checksum, compression)?;
+
+                    decompressed = Some(Segment { start: addr, len });
+
+                    Ok((addr, len, hasher, None))
+                }
+
+                compression => Ok((reserved.start, reserved.len, hasher, compression)),
+            });
+
+        // The caller gives the reserved zone back if the file couldn't be created,
+        // so it must hold it again instead of the decompressed content
+        if let (Err(_), Some(decompressed)) = (&created, decompressed) {
+            let _ = self.coverage.mark_as_free(decompressed);
+            self.coverage.mark_as_used(reserved.start, reserved.len);
+        }
+
+        created
+    }
+
+    /// Write the decompressed content of a reserved zone after all existing data, and give the zone back
+    ///
+    /// The content is decrypted and encrypted again if the archive is encrypted.
+    ///
+    /// Returns the content's address and stored length
+    fn decompress_reserved(
+        &mut self,
+        reserved: Segment,
+        sha3_checksum: [u8; 32],
+        compression: FileCompression,
+    ) -> Result<(u64, u64, ContentHasher), ArchiveError> {
+        let cipher = self.content_cipher(reserved.len)?;
+        let mut encryptor = self
+            .content_cipher(compression.uncompressed_len)?
+            .map(ContentEncryptor::new);
+
+        let addr = self.coverage.next_writable_addr();
+        let mut hasher = self.content_hasher();
+        let mut written = 0;
+
+        self.source.set_position(reserved.start)?;
+
+        let mut reader = FileReader::new(
+            &mut self.source,
+            reserved.start,
+            reserved.len,
+            sha3_checksum,
+            None,
+            Some(compression),
+            cipher,
+        );
+
+        loop {
+            let mut buf = [0; 4096];
+
+            let len = match reader.read(&mut buf) {
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            let data = match &mut encryptor {
+                Some(encryptor) if len > 0 => Cow::Owned(encryptor.update(&buf[..len])),
+                Some(_) => Cow::Owned(encryptor.take().unwrap().finish()),
+                None => Cow::Borrowed(&buf[..len]),
+            };
+
+            reader.with_stream(|source| {
+                source.set_position(addr + written)?;
+                source.write_all(&data)
+            })?;
+
+            written += u64::try_from(data.len()).unwrap();
+            hasher.update(&data);
+
+            if len == 0 {
+                break;
+            }
+        }
+
+        if addr + written > self.coverage.len() {
+            self.coverage.grow_to(addr + written);
+        }
+
+        self.coverage.mark_as_used(addr, written);
+        let _ = self.coverage.mark_as_free(reserved);
+
+        Ok((addr, written, hasher))
+    }
+
+    /// Get the address after which nothing is stored
+    pub(crate) fn next_writable_addr(&self) -> u64 {
+        self.coverage.next_writable_addr()
+    }
+
+    /// Get a hasher for new files' content
+    ///
+    /// Chunks' checksums are recorded in items' records, so they are never computed for archives before version 2.
+    pub(crate) fn content_hasher(&self) -> ContentHasher {
+        ContentHasher::new(
+            self.conf
+                .chunk_size
+                .filter(|_| self.header.version.has_feature_flags()),
+        )
+    }
+
+    /// Get the codec to compress a new file's content with, if any (see [`ArchiveConfig::compression`])
+    ///
+    /// Compression is recorded in items' records, so archives before version 2 are never compressed.
+    fn codec_for(&self, name: &ItemName, len: u64) -> Option<CompressionCodec> {
+        if !self.header.version.has_feature_flags() {
+            return None;
+        }
+
+        self.conf.compression.as_ref()?.codec_for(name, len)
+    }
+
+    /// Get the codec to compress a new file's content of yet unknown length with, if any
+    ///
+    /// See [`CompressionPolicy::codec_for_name`]
+    pub(crate) fn stream_codec_for(&self, name: &ItemName) -> Option<CompressionCodec> {
+        if !self.header.version.has_feature_flags() {
+            return None;
+        }
+
+        self.conf.compression.as_ref()?.codec_for_name(name)
+    }
+
+    /// Write some file content, compressed with the provided codec if it saves enough space,
+    /// and encrypted if the archive is (see [`ArchiveConfig::encryption_key`])
+    ///
+    /// Compressed content is written after all existing data, as its length is only known once written.
+    /// Other contents are written wherever there is some free space.
+    /// Encryption happens while writing, so the content is never held in memory.
+    ///
+    /// Returns the content's address and stored length, alongside its compression
+    fn write_content(
+        &mut self,
+        mut data: Source<impl Read + Seek>,
+        len: u64,
+        codec: Option<CompressionCodec>,
+    ) -> Result<(u64, u64, ContentHasher, Option<FileCompression>), ArchiveError> {
+        let cipher = self.content_cipher(len)?;
+
+        if let Some(codec) = codec {
+            data.set_position(0)?;
+
+            let compressor = codec.compressor(&mut data)?;
+
+            let (addr, stored_len, hasher) = match &cipher {
+                Some(cipher) => {
+                    self.write_stream_at_end(EncryptingReader::new(compressor, cipher.clone()))?
+                }
+                None => self.write_stream_at_end(compressor)?,
+            };
+
+            let compressed_len = match cipher {
+                Some(_) => decrypted_content_len(stored_len),
+                None => stored_len,
+            };
+
+            let policy = self.conf.compression.as_ref().unwrap();
+
+            if policy.is_worth_it(len, compressed_len) {
+                let compression = FileCompression {
+                    codec,
+                    uncompressed_len: len,
+                };
+
+                return Ok((addr, stored_len, hasher, Some(compression)));
+            }
+
+            // The compressed content was never referenced, so it can be overwritten right away
+            let _ = self.coverage.mark_as_free(Segment {
+                start: addr,
+                len: stored_len,
+            });
+        }
+
+        data.set_position(0)?;
+
+        let data = data.take(len);
+
+        let (addr, stored_len, hasher) = match cipher {
+            Some(cipher) => {
+                let stored_len = encrypted_content_len(len);
+                let (addr, hasher) = self
+                    .write_data_where_possible(EncryptingReader::new(data, cipher), stored_len)?;
+
+                (addr, stored_len, hasher)
+            }
+
+            None => {
+                let (addr, hasher) = self.write_data_where_possible(data, len)?;
+                (addr, len, hasher)
+            }
+        };
+
+        Ok((addr, stored_len, hasher, None))
+    }
+
+    /// Write a stream of unknown length after all existing data, encrypted if the archive is
+    ///
+    /// Returns the data's address and stored length
+    fn write_stream(&mut self, data: impl Read) -> Result<(u64, u64, ContentHasher), ArchiveError> {
+        match self.stream_cipher()? {
+            Some(cipher) => self.write_stream_at_end(EncryptingReader::new(data, cipher)),
+            None => self.write_stream_at_end(data),
+        }
+    }
+
+    /// Find an existing content with the provided checksum and length, if deduplication is enabled
+    ///
+    /// Encrypted contents are never deduplicated, as each of them is sealed with its own nonce.
+    ///
+    /// Returns the content's address
+    fn find_content(&mut self, sha3_checksum: [u8; 32], len: u64) -> Option<u64> {
+        if !self.conf.deduplicate || len == 0 || self.is_encrypted() {
+            return None;
+        }
+
+        let key = (sha3_checksum, len);
+        let candidate = self.contents.get(&key)?;
+
+        // The content of a file being replaced may already have been given back
+        let is_match = |file: &File| {
+            file.sha3_checksum == sha3_checksum
+                && file.content_len == len
+                && !self.coverage.is_free(file.content_addr, len)
+        };
+
+        if let Some(file) = self.files.get(candidate).filter(|file| is_match(file)) {
+            return Some(file.content_addr);
+        }
+
+        // The registered file was removed or its content replaced in the meantime
+        match self.files.values().find(|file| is_match(file)) {
+            Some(file) => {
+                let (id, addr) = (file.id, file.content_addr);
+                self.contents.insert(key, id);
+                Some(addr)
+            }
+
+            None => {
+                self.contents.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Read a seekable content to find an identical existing one, if deduplication is enabled
+    ///
+    /// Returns the existing content's address, alongside the hasher the provided content was read with
+    fn find_identical_content(
+        &mut self,
+        content: &mut Source<impl Read + Seek>,
+        len: u64,
+    ) -> Result<Option<(u64, ContentHasher)>, ArchiveError> {
+        if !self.conf.deduplicate || len == 0 || self.is_encrypted() {
+            return Ok(None);
+        }
+
+        content.set_position(0)?;
+
+        let mut hasher = self.content_hasher();
+        let mut read = 0;
+
+        const CHUNK_SIZE: usize = 4096;
+
+        while read < len {
+            let mut buf = [0; CHUNK_SIZE];
+            let len = (CHUNK_SIZE as u64).min(len - read);
+            let len_usize = usize::try_from(len).unwrap();
+            content.read_exact(&mut buf[0..len_usize])?;
+
+            read += len;
+            hasher.update(&buf[0..len_usize]);
+        }
+
+        let (sha3_checksum, _) = hasher.clone().finalize();
+
+        Ok(self
+            .find_content(sha3_checksum, len)
+            .map(|addr| (addr, hasher)))
+    }
+
+    /// Make newly written content reference an identical existing one instead, if deduplication is enabled
+    ///
+    /// The space used by the new content is given back in that case. Returns the address of the content to use.
+    fn share_identical_content(&mut self, addr: u64, len: u64, sha3_checksum: [u8; 32]) -> u64 {
+        match self.find_content(sha3_checksum, len) {
+            Some(existing_addr) if existing_addr != addr => {
+                self.coverage.mark_as_used(existing_addr, len);
+                self.free_space(Segment { start: addr, len });
+                existing_addr
+            }
+
+            _ => addr,
+        }
+    }
+
+    /// Record a file's content so that identical contents can reference it, if deduplication is enabled
+    fn register_content(&mut self, file: &File) {
+        if self.conf.deduplicate && file.content_len > 0 {
+            self.contents
+                .insert((file.sha3_checksum, file.content_len), file.id);
+        }
+    }
+
+    /// Copy some data inside the archive
+    ///
+    /// Source and destination may only overlap if the data is copied to a lower address.
+    fn copy_data(&mut self, from: u64, to: u64, len: u64) -> Result<(), ArchiveError> {
+        assert!(to <= from || to >= from + len);
+
+        const CHUNK_SIZE: usize = 4096;
+
+        let mut copied = 0;
+
+        while copied < len {
+            let mut buf = [0; CHUNK_SIZE];
+            let len = (CHUNK_SIZE as u64).min(len - copied);
+            let len_usize = usize::try_from(len).unwrap();
+
+            self.source.set_position(from + copied)?;
+            self.source.read_exact(&mut buf[0..len_usize])?;
+
+            self.source.set_position(to + copied)?;
+            self.source.write_all(&buf[0..len_usize])?;
+
+            copied += len;
+        }
+
+        Ok(())
+    }
+
+    /// Update the references to an item whose data was moved to another location
+    ///
+    /// Relocating a file's content also updates the files sharing it.
+    fn relocate(&mut self, item: Relocatable, new_addr: u64) -> Result<(), ArchiveError> {
+        match item {
+            Relocatable::Segment(segment_index) => {
+                // Update previous segment's 'next address'
+                self.write_metadata(vec![JournalWrite {
+                    addr: self.segment_addr(segment_index - 1),
+                    data: new_addr.to_le_bytes().to_vec(),
+                }])?;
+
+                self.file_segments[segment_index - 1].next_segment_addr = Some(new_addr);
+            }
+
+            Relocatable::File(id) => {
+                let file = self.files.get(&id).unwrap();
+                let content = (file.content_addr, file.content_len);
+
+                let sharing = self
+                    .files
+                    .values()
+                    .filter(|file| (file.content_addr, file.content_len) == content)
+                    .map(|file| file.id)
+                    .collect::<Vec<_>>();
+
+                for id in sharing {
+                    let SegmentEntry {
+                        segment_index,
+                        entry_index,
+                        entry_addr,
+                    } = self.get_item_entry(ItemId::File(id)).unwrap();
+
+                    let file = self.files.get_mut(&id).unwrap();
+                    file.content_addr = new_addr;
+
+                    let file = file.clone();
+
+                    self.write_metadata(vec![JournalWrite {
+                        addr: entry_addr,
+                        data: file.encode(self.header.version, self.name_cipher()),
+                    }])?;
+
+                    self.file_segments[segment_index].files[entry_index] = Some(file);
+                }
+            }
+
+            Relocatable::ItemRecord(id) => {
+                let SegmentEntry {
+                    segment_index,
+                    entry_index,
+                    entry_addr,
+                } = self.get_item_entry(id).unwrap();
+
+                match id {
+                    ItemId::Directory(id) => {
+                        let dir = self.dirs.get_mut(&id).unwrap();
+                        dir.record.as_mut().unwrap().start = new_addr;
+
+                        let dir = dir.clone();
+
+                        self.write_metadata(vec![JournalWrite {
+                            addr: entry_addr,
+                            data: dir.encode(self.header.version, self.name_cipher()),
+                        }])?;
+
+                        self.file_segments[segment_index].dirs[entry_index] = Some(dir);
+                    }
+
+                    ItemId::File(id) => {
+                        let file = self.files.get_mut(&id).unwrap();
+                        file.record.as_mut().unwrap().start = new_addr;
+
+                        let file = file.clone();
+
+                        self.write_metadata(vec![JournalWrite {
+                            addr: entry_addr,
+                            data: file.encode(self.header.version, self.name_cipher()),
+                        }])?;
+
+                        self.file_segments[segment_index].files[entry_index] = Some(file);
+                    }
+                }
+            }
+
+            Relocatable::Journal => {
+                self.source.sync()?;
+                self.write_journal_addr(new_addr)?;
+                self.journal.as_mut().unwrap().addr = new_addr;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write some metadata (file table entries, segments' next address, etc.)
+    ///
+    /// If the archive is journaled, the writes are recorded in the journal beforehand,
+    /// so that either all of them or none of them are applied in case of a crash.
+    ///
+    /// If a transaction is in progress, the writes are staged until it is committed instead.
+    fn write_metadata(&mut self, writes: Vec<JournalWrite>) -> Result<(), ArchiveError> {
+        if let Some(staging) = &mut self.staging {
+            staging.writes.extend(writes);
+            return Ok(());
+        }
+
+        if self.journal.is_none() {
+            return self.apply_writes(&writes);
+        }
+
+        self.commit_to_journal(writes)?;
+        self.replay_journal()
+    }
+
+    /// Record some metadata writes in the journal and commit them, without applying them yet
+    ///
+    /// Once committed, the writes are applied by [`Self::replay_journal`], or when opening the archive
+    /// if it didn't succeed.
+    fn commit_to_journal(&mut self, writes: Vec<JournalWrite>) -> Result<(), ArchiveError> {
+        // The journal can only hold a single record at once
+        self.replay_journal()?;
+
+        let record = JournalRecord { writes };
+        let encoded = record.encode();
+        let encoded_len = u64::try_from(encoded.len()).unwrap();
+
+        self.reserve_journal(encoded_len)?;
+
+        let journal = self.journal.as_ref().unwrap();
+        let (record_addr, commit_addr) = (journal.record_addr(), journal.commit_addr());
+
+        // Write the record, then commit it by writing its length and checksum
+        self.source.set_position(record_addr)?;
+        self.source.write_all(&encoded)?;
+        self.source.sync()?;
+
+        let mut commit = encoded_len.to_le_bytes().to_vec();
+        commit.extend(Sha3_256::digest(&encoded));
+
+        self.source.set_position(commit_addr)?;
+        self.source.write_all(&commit)?;
+        self.source.sync()?;
+
+        self.journal.as_mut().unwrap().pending = Some(record);
+
+        Ok(())
+    }
+
+    /// Give back some space to the archive
+    ///
+    /// If a transaction is in progress, the space is only given back once it is committed,
+    /// so that it can't be overwritten before then.
+    fn free_space(&mut self, segment: Segment) {
+        match &mut self.staging {
+            Some(staging) => staging.freed.push(segment),
+            None => {
+                let _ = self.coverage.mark_as_free(segment);
+            }
+        }
+    }
+
+    /// Start staging metadata writes, and recording changes to the in-memory state to undo them in case of a rollback
+    pub(crate) fn begin_transaction(&mut self) -> ArchiveSnapshot {
+        assert!(self.staging.is_none());
+
+        self.staging = Some(Staging::default());
+
+        self.file_segments.start_recording();
+        self.dirs.start_recording();
+        self.files.start_recording();
+        self.dirs_content.start_recording();
+        self.coverage.start_recording();
+        self.hard_links.start_recording();
+        self.contents.start_recording();
+
+        ArchiveSnapshot {
+            next_id: self.next_id,
+            features: self.header.features,
+        }
+    }
+
+    /// Write all staged metadata at once, as a single journal record
+    ///
+    /// If writing fails before the metadata is committed, the transaction is rolled back: the in-memory
+    /// state is restored, and so is the archive's content if it isn't journaled.
+    ///
+    /// Otherwise, the transaction is kept even if an error is returned, as the journal's record
+    /// will be applied again, at the latest when opening the archive.
+    pub(crate) fn commit_transaction(
+        &mut self,
+        snapshot: ArchiveSnapshot,
+    ) -> Result<(), ArchiveError> {
+        let Staging { writes, freed } = self.staging.take().unwrap();
+
+        let committed = if writes.is_empty() {
+            Ok(())
+        } else if self.journal.is_some() {
+            // The journal's address is written to the header right away if it is reallocated,
+            // so its new location must stay used even if the transaction is rolled back
+            let changes = self.coverage.pause_recording();
+            let committed = self.commit_to_journal(writes);
+            self.coverage.resume_recording(changes);
+            committed
+        } else {
+            self.apply_writes_or_restore(&writes)
+        };
+
+        if let Err(err) = committed {
+            self.rollback_transaction(snapshot);
+            return Err(err);
+        }
+
+        self.file_segments.keep_changes();
+        self.dirs.keep_changes();
+        self.files.keep_changes();
+        self.dirs_content.keep_changes();
+        self.coverage.keep_changes();
+        self.hard_links.keep_changes();
+        self.contents.keep_changes();
 
-        // Remove the directory's content listing
-        let DirContent { dirs, files, names } = self
-            .dirs_content
-            .remove(&DirectoryIdOrRoot::NonRoot(dir.id))
-            .unwrap();
+        for segment in freed {
+            let _ = self.coverage.mark_as_free(segment);
+        }
 
-        assert!(dirs.is_empty());
-        assert!(files.is_empty());
-        assert!(names.is_empty());
+        self.replay_journal()
+    }
 
-        Ok(dir)
+    /// Discard all staged metadata and restore the in-memory state
+    ///
+    /// Data written in the meantime is left in place, but the space it uses is considered free again.
+    pub(crate) fn rollback_transaction(&mut self, snapshot: ArchiveSnapshot) {
+        let ArchiveSnapshot { next_id, features } = snapshot;
+
+        self.staging = None;
+        self.file_segments.undo_changes();
+        self.dirs.undo_changes();
+        self.files.undo_changes();
+        self.dirs_content.undo_changes();
+        self.coverage.undo_changes();
+        self.hard_links.undo_changes();
+        self.contents.undo_changes();
+        self.next_id = next_id;
+        self.header.features = features;
     }
 
-    /// Remove a file
+    /// Apply some metadata writes directly, restoring the previous content if one of them fails
     ///
-    /// Returns the removed file entry
-    pub fn remove_file(&mut self, id: FileId) -> Result<File, ArchiveError> {
-        let SegmentEntry {
-            segment_index,
-            entry_index,
-            entry_addr,
-        } = self
-            .get_item_entry(ItemId::File(id))
-            .ok_or(ArchiveError::FileNotFound)?;
+    /// Used for transactions on non-journaled archives, which can't leave the archive partially written
+    /// unless restoring fails as well.
+    fn apply_writes_or_restore(&mut self, writes: &[JournalWrite]) -> Result<(), ArchiveError> {
+        let mut originals = Vec::with_capacity(writes.len());
 
-        // Remove the file entry itself
-        self.source.set_position(entry_addr)?;
+        for JournalWrite { addr, data } in writes {
+            let mut original = vec![0; data.len()];
 
-        self.source.write_all(&vec![0; FILE_ENTRY_SIZE])?;
+            self.source.set_position(*addr)?;
+            self.source.read_exact(&mut original)?;
 
-        // Remove from in-memory file segments
-        self.file_segments[segment_index].files[entry_index]
-            .take()
-            .unwrap();
+            originals.push(JournalWrite {
+                addr: *addr,
+                data: original,
+            });
+        }
 
-        // Unregister the file and remove its name from the listing
-        let file = self.files.remove(&id).unwrap();
+        self.apply_writes(writes).inspect_err(|_| {
+            originals.reverse();
+            let _ = self.apply_writes(&originals);
+        })
+    }
 
-        let parent_dir_content = self.dirs_content.get_mut(&file.parent_dir).unwrap();
+    /// Apply the journal's pending record (if any) and clear it
+    fn replay_journal(&mut self) -> Result<(), ArchiveError> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(());
+        };
 
-        assert!(parent_dir_content.files.remove(&file.id));
-        assert!(parent_dir_content.names.remove(&file.name));
+        let commit_addr = journal.commit_addr();
 
-        // Update coverage
-        let _ = self.coverage.mark_as_free(Segment {
-            start: file.content_addr,
-            len: file.content_len,
+        let Some(record) = journal.pending.take() else {
+            return Ok(());
+        };
+
+        let replayed = self.apply_writes(&record.writes).and_then(|()| {
+            self.source.sync()?;
+            self.source.set_position(commit_addr)?;
+            Ok(self.source.write_all(&0_u64.to_le_bytes())?)
         });
 
-        Ok(file)
-    }
+        // The record stays pending until it's entirely applied, so it isn't overwritten by the next one
+        if replayed.is_err() {
+            self.journal.as_mut().unwrap().pending = Some(record);
+        }
 
-    /// Flush all changes
-    pub fn flush(&mut self) -> Result<(), ArchiveError> {
-        Ok(self.source.flush()?)
+        replayed
     }
 
-    /// Close the archive
-    ///
-    /// Returns the original source provided at type construction
-    pub fn close(mut self) -> Result<S, ArchiveError> {
-        self.source.flush()?;
-        Ok(self.source.into_inner())
+    fn apply_writes(&mut self, writes: &[JournalWrite]) -> Result<(), ArchiveError> {
+        for JournalWrite { addr, data } in writes {
+            self.source.set_position(*addr)?;
+            self.source.write_all(data)?;
+        }
+
+        Ok(())
     }
-}
 
-/// Internal functions
-impl<S: Read + Write + Seek> Archive<S> {
-    fn segment_addr(&self, segment_index: usize) -> u64 {
-        assert!(segment_index < self.file_segments.len());
+    /// Ensure the journal (if any) is large enough to hold a record of the provided length
+    fn reserve_journal(&mut self, record_len: u64) -> Result<(), ArchiveError> {
+        match &self.journal {
+            Some(journal) if journal.capacity < record_len => {
+                self.allocate_journal(record_len.max(journal.capacity * 2))
+            }
 
-        if segment_index == 0 {
-            HEADER_SIZE as u64
-        } else {
-            self.file_segments[segment_index - 1]
-                .next_segment_addr
-                .unwrap()
+            Some(_) | None => Ok(()),
         }
     }
 
-    fn get_item_entry(&self, item_id: ItemId) -> Option<SegmentEntry> {
-        self.file_segments
-            .iter()
-            .enumerate()
-            .find_map(|(segment_index, segment)| {
-                let entry_index = match item_id {
-                    ItemId::Directory(id) => segment
-                        .dirs
-                        .iter()
-                        .position(|entry| entry.as_ref().is_some_and(|dir| dir.id == id)),
+    /// Allocate a new journal, replacing the existing one (if any)
+    ///
+    /// The existing journal must not have a pending record.
+    fn allocate_journal(&mut self, capacity: u64) -> Result<(), ArchiveError> {
+        let addr = self.write_bytes_where_possible(&Journal::encode_empty(capacity))?;
 
-                    ItemId::File(id) => segment
-                        .files
-                        .iter()
-                        .position(|entry| entry.as_ref().is_some_and(|file| file.id == id)),
-                };
+        self.source.sync()?;
+        self.write_journal_addr(addr)?;
 
-                entry_index.map(|entry_index| {
-                    let entry_index_u32 = u32::try_from(entry_index).unwrap();
+        let prev = self.journal.replace(Journal {
+            addr,
+            capacity,
+            pending: None,
+        });
 
-                    SegmentEntry {
-                        segment_index,
-                        entry_index,
-                        entry_addr: self.segment_addr(segment_index)
-                            + match item_id {
-                                ItemId::Directory(_) => segment
-                                    .dir_entry_offset(entry_index_u32)
-                                    .expect("entry index is in bounds"),
-                                ItemId::File(_) => segment
-                                    .file_entry_offset(entry_index_u32)
-                                    .expect("entry index is in bounds"),
-                            },
-                    }
-                })
-            })
-    }
+        if let Some(prev) = prev {
+            assert!(prev.pending.is_none());
 
-    /// Write some data (file table segment, file content, etc.) wherever there is some free space
-    fn write_data_where_possible(
-        &mut self,
-        mut data: Source<impl Read + Seek>,
-    ) -> Result<(u64, Sha3_256), ArchiveError> {
-        let len = data.seek_len()?;
+            let _ = self.coverage.mark_as_free(Segment {
+                start: prev.addr,
+                len: prev.encoded_len(),
+            });
+        }
+
+        Ok(())
+    }
 
-        let (addr, growing) = match self.coverage.find_free_zone_for(len) {
-            Some(segment) => (segment.start, false),
-            None => (self.coverage.next_writable_addr(), true),
+    /// Update the journal's address in the header
+    ///
+    /// This write isn't journaled, so the previous address is written back if it fails,
+    /// in order for the header to keep pointing to the journal known in memory.
+    fn write_journal_addr(&mut self, addr: u64) -> Result<(), ArchiveError> {
+        let mut write = |addr: u64| -> Result<(), ArchiveError> {
+            self.source.set_position(HEADER_JOURNAL_ADDR_OFFSET)?;
+            self.source.write_all(&addr.to_le_bytes())?;
+            Ok(self.source.sync()?)
         };
 
-        data.set_position(0)?;
-        self.source.set_position(addr)?;
+        if let Err(err) = write(addr) {
+            let _ = write(self.header.journal_addr.unwrap_or(0));
+            return Err(err);
+        }
 
-        let mut checksum = Sha3_256::new();
-        let mut written = 0;
+        self.header.journal_addr = Some(addr);
 
-        // Progressively write the data using 4KB chunks and compute the checksum in the meantime
-        const CHUNK_SIZE: usize = 4096;
+        Ok(())
+    }
 
-        while written < len {
-            let mut buf = [0; CHUNK_SIZE];
-            let len = (CHUNK_SIZE as u64).min(len - written);
-            let len_usize = usize::try_from(len).unwrap();
-            data.read_exact(&mut buf[0..len_usize])?;
+    /// Remove a file from its hard links group, if any
+    ///
+    /// If a single file remains in the group, it isn't a link anymore either.
+    /// Its record keeps the group, which is ignored when opening the archive as no other file is in it.
+    fn unlink_hard_link(&mut self, id: FileId) {
+        let Some(group) = self.hard_links.remove(&id) else {
+            return;
+        };
 
-            let data = &buf[0..len_usize];
+        let mut remaining = self
+            .hard_links
+            .iter()
+            .filter(|(_, other_group)| **other_group == group)
+            .map(|(other_id, _)| *other_id);
 
-            self.source.write_all(data)?;
-            written += len;
-            checksum.update(data);
+        if let (Some(last), None) = (remaining.next(), remaining.next()) {
+            self.hard_links.remove(&last);
         }
+    }
+
+    /// Compute the header's feature flags after an update
+    ///
+    /// Archives which don't declare their features keep them empty.
+    fn updated_features(&self, update: impl FnOnce(&mut FeatureFlags)) -> FeatureFlags {
+        let mut features = self.header.features;
 
-        if growing {
-            self.coverage.grow_to(self.source.seek_len()?);
+        if self.header.version.has_feature_flags() {
+            update(&mut features);
         }
 
-        self.coverage.mark_as_used(addr, len);
+        features
+    }
 
-        Ok((addr, checksum))
+    /// Get the write updating the header's feature flags, if they changed
+    fn features_write(&self, features: FeatureFlags) -> Option<JournalWrite> {
+        (features != self.header.features).then(|| JournalWrite {
+            addr: HEADER_FEATURES_OFFSET,
+            data: features.encode().to_vec(),
+        })
     }
 
     // returns address of first entry
@@ -768,16 +2985,14 @@ impl<S: Read + Write + Seek> Archive<S> {
         };
 
         // Write new segment
-        let (new_segment_addr, _) = self.write_data_where_possible(
-            // TODO: improve this mess
-            Source::new(Cursor::new(segment.encode())),
-        )?;
+        let new_segment_addr = self
+            .write_bytes_where_possible(&segment.encode(self.header.version, self.name_cipher()))?;
 
         // Update previous segment's 'next address'
-        self.source
-            .set_position(self.segment_addr(self.file_segments.len() - 1))?;
-
-        self.source.write_all(&new_segment_addr.to_le_bytes())?;
+        self.write_metadata(vec![JournalWrite {
+            addr: self.segment_addr(self.file_segments.len() - 1),
+            data: new_segment_addr.to_le_bytes().to_vec(),
+        }])?;
 
         // Update in-memory representation
         self.file_segments.last_mut().unwrap().next_segment_addr = Some(new_segment_addr);
@@ -851,7 +3066,146 @@ impl<S: Read + Write + Seek> Archive<S> {
         }
     }
 
-    fn ensure_no_duplicate_name(
+    /// Ensure a name can be stored, which may not be the case if it is too long to fit in its entry
+    ///
+    /// Long names can only be stored in items' records since version 2 (see [`FeatureFlags::REQUIRED_ITEM_RECORDS`]).
+    pub(crate) fn ensure_name_fits(&self, name: &ItemName) -> Result<(), ArchiveError> {
+        if self.header.version.has_feature_flags() || name.fits_in_entry(self.name_cipher()) {
+            return Ok(());
+        }
+
+        Err(match self.name_cipher() {
+            Some(_) => ArchiveError::NameTooLongToEncrypt {
+                max: MAX_ENCRYPTED_NAME_LEN,
+            },
+            None => ArchiveError::NameTooLong {
+                max: MAX_INLINE_NAME_LEN,
+            },
+        })
+    }
+
+    /// Update an item's extensions, then write its record and entry again if they changed
+    ///
+    /// Extensions are stored in the item's record, which requires version 2 of the format.
+    fn update_extensions(
+        &mut self,
+        id: ItemId,
+        update: impl FnOnce(&mut ItemExtensions),
+    ) -> Result<(), ArchiveError> {
+        let SegmentEntry {
+            segment_index,
+            entry_index,
+            entry_addr,
+        } = self.get_item_entry(id).ok_or(match id {
+            ItemId::Directory(_) => ArchiveError::DirectoryNotFound,
+            ItemId::File(_) => ArchiveError::FileNotFound,
+        })?;
+
+        let (name, old_extensions, name_offset) = match id {
+            ItemId::Directory(id) => {
+                let dir = self.dirs.get(&id).unwrap();
+                (&dir.name, &dir.extensions, DIRECTORY_NAME_OFFSET_IN_ENTRY)
+            }
+
+            ItemId::File(id) => {
+                let file = self.files.get(&id).unwrap();
+                (&file.name, &file.extensions, FILE_NAME_OFFSET_IN_ENTRY)
+            }
+        };
+
+        let mut extensions = old_extensions.clone();
+        update(&mut extensions);
+
+        if extensions == *old_extensions {
+            return Ok(());
+        }
+
+        self.ensure_records_supported()?;
+
+        let name = name.clone();
+        let record = self.store_record(&name, &extensions)?;
+
+        self.write_metadata(vec![JournalWrite {
+            addr: entry_addr + (name_offset as u64),
+            data: name.encode_in_entry(self.name_cipher(), record).to_vec(),
+        }])?;
+
+        let old_record = match id {
+            ItemId::Directory(id) => {
+                let dir = self.dirs.get_mut(&id).unwrap();
+                dir.extensions = extensions;
+
+                let old_record = std::mem::replace(&mut dir.record, record);
+                self.file_segments[segment_index].dirs[entry_index] = Some(dir.clone());
+                old_record
+            }
+
+            ItemId::File(id) => {
+                let file = self.files.get_mut(&id).unwrap();
+                file.extensions = extensions;
+
+                let old_record = std::mem::replace(&mut file.record, record);
+                self.file_segments[segment_index].files[entry_index] = Some(file.clone());
+                old_record
+            }
+        };
+
+        if let Some(old_record) = old_record {
+            self.free_space(old_record);
+        }
+
+        Ok(())
+    }
+
+    /// Ensure the archive's version supports item records, which hold items' extensions
+    fn ensure_records_supported(&self) -> Result<(), ArchiveError> {
+        if self.header.version.has_feature_flags() {
+            Ok(())
+        } else {
+            Err(ArchiveError::UnsupportedByVersion {
+                current: self.header.version.version_number(),
+                required: ArchiveVersion::Two.version_number(),
+            })
+        }
+    }
+
+    /// Store an item's record if its name is too long to fit in its entry, or if it has some extensions
+    ///
+    /// The record is written before the entry referencing it, so it is persisted by the time the entry is committed.
+    ///
+    /// Returns the record's location in that case.
+    fn store_record(
+        &mut self,
+        name: &ItemName,
+        extensions: &ItemExtensions,
+    ) -> Result<Option<Segment>, ArchiveError> {
+        if name.fits_in_entry(self.name_cipher()) && extensions.is_empty() {
+            return Ok(None);
+        }
+
+        debug_assert!(self.header.version.has_feature_flags());
+
+        let features = self.updated_features(|features| {
+            features.set_required(FeatureFlags::REQUIRED_ITEM_RECORDS, true);
+            extensions.declare_features(features);
+        });
+
+        // Readers must know about records and their content before encountering one
+        if let Some(write) = self.features_write(features) {
+            self.write_metadata(vec![write])?;
+            self.header.features = features;
+        }
+
+        let encoded = encode_record(name, self.name_cipher(), extensions);
+        let start = self.write_bytes_where_possible(&encoded)?;
+
+        Ok(Some(Segment {
+            start,
+            len: u64::try_from(encoded.len()).unwrap(),
+        }))
+    }
+
+    pub(crate) fn ensure_no_duplicate_name(
         &self,
         name: &str,
         parent_dir: DirectoryIdOrRoot,
@@ -934,6 +3288,10 @@ pub enum ArchiveMetadataDecodingError {
     #[error("{0}")]
     InvalidHeader(#[from] HeaderDecodingError),
 
+    /// The write-ahead journal is invalid
+    #[error("{0}")]
+    InvalidJournal(#[from] JournalDecodingError),
+
     /// One of the file tables' segments is invalid
     #[error("{0}")]
     InvalidFileTableSegment(#[from] FileTableSegmentDecodingError),
@@ -941,6 +3299,48 @@ pub enum ArchiveMetadataDecodingError {
     /// The file table contains some incorrect data
     #[error("File table correctness errors: {0:?}")]
     FileTableCorrectnessError(Vec<FileTableCorrectnessError>),
+
+    /// Orphaned items couldn't be reattached (see [`Archive::open_with_lost_and_found`])
+    #[error("Failed to reattach orphaned items: {0}")]
+    LostAndFound(ArchiveError),
+
+    /// The file table's segments chain loops back to a previous segment
+    #[error(
+        "File table segment at address {addr} is referenced twice, the segments chain is cyclic"
+    )]
+    SegmentsCycle {
+        /// Address of the segment referenced twice
+        addr: u64,
+    },
+
+    /// The file table has more segments than allowed by [`crate::OpenLimits::max_segments`]
+    #[error("File table has more than {max} segments")]
+    TooManySegments {
+        /// Maximum number of segments
+        max: usize,
+    },
+
+    /// The file table has more entries than allowed by [`crate::OpenLimits::max_entries`]
+    #[error("File table has more than {max} entries")]
+    TooManyEntries {
+        /// Maximum number of entries
+        max: u64,
+    },
+
+    /// The file table is larger than allowed by [`crate::OpenLimits::max_metadata_len`]
+    #[error("File table is larger than {max} bytes")]
+    MetadataTooLarge {
+        /// Maximum size of the file table, in bytes
+        max: u64,
+    },
+
+    /// The archive's names are encrypted, but no key was provided (see [`ArchiveConfig::encryption_key`])
+    #[error("Archive's names are encrypted, an encryption key is required to open it")]
+    MissingEncryptionKey,
+
+    /// The provided encryption key is not the archive's one
+    #[error("Encryption key doesn't match the archive's one")]
+    InvalidEncryptionKey,
 }
 
 /// Error while performing read/write operations on an archive
@@ -967,6 +3367,77 @@ pub enum ArchiveError {
     #[error("File was not found in archive")]
     FileNotFound,
 
+    /// A directory cannot be moved inside itself or one of its descendants
+    #[error("Cannot move a directory inside itself or one of its descendants")]
+    MoveIntoDescendant,
+
+    /// The owner or group name of Unix attributes is empty or longer than 255 bytes
+    #[error("Owner and group names must be between 1 and 255 bytes long")]
+    InvalidUnixAttributes,
+
+    /// The target of a symbolic link is empty, too long or contains a NUL byte
+    #[error("Symbolic link targets must be between 1 and 4096 bytes long, without NUL bytes")]
+    InvalidSymlinkTarget,
+
+    /// The requested file is not a symbolic link
+    #[error("File is not a symbolic link")]
+    NotASymlink,
+
+    /// The requested file's content is compressed, so its stored bytes can't be used as-is
+    #[error("File's content is compressed and must be read through a decompressing reader")]
+    FileIsCompressed,
+
+    /// The requested file's content is encrypted, so its stored bytes can't be used as-is
+    #[error("File's content is encrypted and must be read through a decrypting reader")]
+    FileIsEncrypted,
+
+    /// The archive is encrypted, but was opened without a key (see [`ArchiveConfig::encryption_key`])
+    #[error("Archive is encrypted, an encryption key is required to access files' content")]
+    MissingEncryptionKey,
+
+    /// The name is too long to be stored encrypted in an archive before version 2 (see [`crate::MAX_ENCRYPTED_NAME_LEN`])
+    #[error("Name is too long to be encrypted, encrypted names are limited to {max} bytes")]
+    NameTooLongToEncrypt {
+        /// Maximum length of an encrypted name, in bytes
+        max: usize,
+    },
+
+    /// The name is too long to be stored in an archive before version 2 (see [`Archive::upgrade_to`])
+    #[error("Name is too long, names are limited to {max} bytes in this archive's version")]
+    NameTooLong {
+        /// Maximum length of a name, in bytes
+        max: usize,
+    },
+
+    /// The operation requires a newer version of the archive's format (see [`Archive::upgrade_to`])
+    #[error("Operation requires archive version {required}, but the archive is version {current}")]
+    UnsupportedByVersion {
+        /// Current version of the archive
+        current: u32,
+        /// Version required by the operation
+        required: u32,
+    },
+
+    /// Archives can't be downgraded to an older version (see [`Archive::upgrade_to`])
+    #[error("Archive can't be downgraded from version {current} to version {requested}")]
+    VersionDowngrade {
+        /// Current version of the archive
+        current: u32,
+        /// Requested version
+        requested: u32,
+    },
+
+    /// Some of the archive's data overlaps the data located before it (see [`Archive::compact`])
+    #[error("Data at address {addr} overlaps other data of the archive")]
+    OverlappingData {
+        /// Address of the overlapping data
+        addr: u64,
+    },
+
+    /// The transaction was rolled back after one of its operations failed
+    #[error("Transaction was rolled back after a previous error")]
+    TransactionAborted,
+
     /// An error occurred while reading a file's content
     #[error("{0}")]
     FileReader(#[from] FileReaderError),
@@ -987,12 +3458,51 @@ enum ItemType {
     File,
 }
 
+/// Item whose data can be moved inside the archive
+#[derive(Clone, Copy)]
+enum Relocatable {
+    Segment(usize),
+    File(FileId),
+    ItemRecord(ItemId),
+    Journal,
+}
+
+/// Metadata writes and freed space of a transaction in progress
+#[derive(Default)]
+struct Staging {
+    writes: Vec<JournalWrite>,
+    freed: Vec<Segment>,
+}
+
+/// Part of an archive's in-memory state which isn't recorded by undo logs, captured at the beginning of a transaction
+pub(crate) struct ArchiveSnapshot {
+    next_id: NonZero<u64>,
+    features: FeatureFlags,
+}
+
 struct SegmentEntry {
     segment_index: usize,
     entry_index: usize,
     entry_addr: u64,
 }
 
+/// Outcome of an archive's compaction (see [`Archive::compact`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Length of the archive before compaction, in bytes
+    pub len_before: u64,
+
+    /// Length of the archive after compaction, in bytes
+    pub len_after: u64,
+}
+
+impl CompactionReport {
+    /// Number of bytes given back by the compaction
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.len_before - self.len_after
+    }
+}
+
 /// Entry in a directory
 #[derive(Debug, Clone)]
 pub enum DirEntry<'a> {
@@ -1037,20 +3547,73 @@ impl<'a> DirEntry<'a> {
     }
 }
 
-fn compute_coverage<'a>(
+/// List all regions of an archive holding some data
+///
+/// Files' content is listed from the file table segments, so that duplicate entries are not missed.
+/// Content shared by several files is listed once for each of them.
+fn list_regions<'a>(
     file_segments: impl IntoIterator<Item = (u64, &'a FileTableSegment)>,
-    len: u64,
-) -> Coverage {
-    let mut coverage = Coverage::new(len);
-    coverage.mark_as_used(0, HEADER_SIZE as u64);
+    journal: Option<&Journal>,
+) -> Vec<(ArchiveRegion, Segment)> {
+    let mut regions = vec![(
+        ArchiveRegion::Header,
+        Segment {
+            start: 0,
+            len: HEADER_SIZE as u64,
+        },
+    )];
 
-    for (segment_addr, segment) in file_segments.into_iter() {
-        coverage.mark_as_used(segment_addr, segment.encoded_len());
+    for (segment_addr, segment) in file_segments {
+        regions.push((
+            ArchiveRegion::FileTableSegment { addr: segment_addr },
+            Segment {
+                start: segment_addr,
+                len: segment.encoded_len(),
+            },
+        ));
 
         for file in segment.files.iter().flatten() {
-            coverage.mark_as_used(file.content_addr, file.content_len);
+            regions.push((
+                ArchiveRegion::FileContent(file.id),
+                Segment {
+                    start: file.content_addr,
+                    len: file.content_len,
+                },
+            ));
+        }
+
+        for dir in segment.dirs.iter().flatten() {
+            if let Some(record) = dir.record {
+                regions.push((ArchiveRegion::ItemRecord(ItemId::Directory(dir.id)), record));
+            }
+        }
+
+        for file in segment.files.iter().flatten() {
+            if let Some(record) = file.record {
+                regions.push((ArchiveRegion::ItemRecord(ItemId::File(file.id)), record));
+            }
         }
     }
 
+    if let Some(journal) = journal {
+        regions.push((
+            ArchiveRegion::Journal,
+            Segment {
+                start: journal.addr,
+                len: journal.encoded_len(),
+            },
+        ));
+    }
+
+    regions
+}
+
+fn compute_coverage(regions: &[(ArchiveRegion, Segment)], len: u64) -> Coverage {
+    let mut coverage = Coverage::new(len);
+
+    for (_, segment) in regions {
+        coverage.mark_as_used(segment.start, segment.len);
+    }
+
     coverage
 }
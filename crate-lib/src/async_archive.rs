@@ -0,0 +1,428 @@
+use std::{borrow::Cow, collections::HashSet, io::SeekFrom};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, DirEntry, DirectoryId,
+    DirectoryIdOrRoot, FileId, FileReaderError, ItemName, Timestamp,
+    async_file_reader::AsyncFileReader,
+    compression::decompress_to_vec,
+    coverage::Segment,
+    data::{
+        directory::{DIRECTORY_ENTRY_SIZE, DIRECTORY_NAME_OFFSET_IN_ENTRY},
+        file::{FILE_ENTRY_SIZE, FILE_NAME_OFFSET_IN_ENTRY},
+        ft_segment::FileTableSegment,
+        header::{FeatureFlags, HEADER_SIZE, Header},
+    },
+    encryption::{ContentDecryptor, ContentEncryptor},
+    journal::{JOURNAL_HEADER_SIZE, Journal, JournalOverlay},
+    metadata_cache::{CachedWrite, MetadataCache},
+    source::{FromSourceBytes, Source},
+};
+
+/// Asynchronous counterpart of [`Archive`], for streams implementing Tokio's I/O traits
+///
+/// The archive's metadata is entirely loaded in memory when opening it, then decoded and
+/// mutated exactly like with [`Archive`]. Every mutation's writes are then applied to the stream
+/// before the corresponding method returns.
+///
+/// Read-only operations on the metadata are available through [`AsyncArchive::archive`].
+pub struct AsyncArchive<S: AsyncRead + AsyncSeek + Unpin> {
+    archive: Archive<MetadataCache>,
+    stream: S,
+}
+
+impl<S: AsyncRead + AsyncSeek + Unpin> AsyncArchive<S> {
+    /// Open an existing archive
+    ///
+    /// Will read the entire archive's metadata segments before returning.
+    pub async fn open(
+        mut stream: S,
+        conf: ArchiveConfig,
+    ) -> Result<Self, ArchiveMetadataDecodingError> {
+        let len = stream
+            .seek(SeekFrom::End(0))
+            .await
+            .map_err(ArchiveMetadataDecodingError::IoError)?;
+
+        let mut cache = MetadataCache::new(len);
+
+        load_metadata(&mut stream, &mut cache, &conf)
+            .await
+            .map_err(ArchiveMetadataDecodingError::IoError)?;
+
+        let archive = Archive::open(cache, conf)?;
+
+        Ok(Self { archive, stream })
+    }
+
+    /// Get the archive's metadata
+    pub fn archive(&self) -> &Archive<MetadataCache> {
+        &self.archive
+    }
+
+    /// List the content of a directory
+    ///
+    /// The metadata being held in memory, this doesn't perform any I/O.
+    pub fn read_dir(
+        &self,
+        id: DirectoryIdOrRoot,
+    ) -> Result<impl Iterator<Item = DirEntry<'_>>, ArchiveError> {
+        self.archive.read_dir(id)
+    }
+
+    /// Get an [`AsyncFileReader`] over a file contained inside the archive
+    ///
+    /// Compressed or encrypted files can't be streamed this way, use [`AsyncArchive::read_file_to_vec`] instead.
+    pub fn read_file(&mut self, id: FileId) -> Result<AsyncFileReader<'_, S>, ArchiveError> {
+        if self.archive.get_compression(id).is_some() {
+            return Err(ArchiveError::FileIsCompressed);
+        }
+
+        if self.archive.is_encrypted()
+            && self
+                .archive
+                .get_file(id)
+                .is_some_and(|file| file.content_len > 0)
+        {
+            return Err(ArchiveError::FileIsEncrypted);
+        }
+
+        self.read_stored_content(id)
+    }
+
+    /// Get the content of a file contained inside the archive into a vector of bytes
+    ///
+    /// Encrypted contents are decrypted in memory, then compressed ones are decompressed.
+    pub async fn read_file_to_vec(&mut self, id: FileId) -> Result<Vec<u8>, ArchiveError> {
+        let compression = self.archive.get_compression(id);
+        let decryptor = match self.archive.get_file(id) {
+            Some(file) => self
+                .archive
+                .content_cipher(file.content_len)?
+                .map(|cipher| ContentDecryptor::new(cipher, file.content_len)),
+            None => None,
+        };
+
+        let mut content = self.read_stored_content(id)?.read_to_vec().await?;
+
+        if let Some(decryptor) = decryptor {
+            content = decryptor
+                .decrypt_all(&content)
+                .ok_or(FileReaderError::Decryption)?;
+        }
+
+        match compression {
+            None => Ok(content),
+            Some(compression) => decompress_to_vec(compression, &content)
+                .map_err(|err| FileReaderError::Decompression(err).into()),
+        }
+    }
+
+    /// Get an [`AsyncFileReader`] over a file's content, as it is stored in the archive
+    fn read_stored_content(&mut self, id: FileId) -> Result<AsyncFileReader<'_, S>, ArchiveError> {
+        let file = self
+            .archive
+            .get_file(id)
+            .ok_or(ArchiveError::FileNotFound)?;
+
+        Ok(AsyncFileReader::new(
+            &mut self.stream,
+            file.content_addr,
+            file.content_len,
+            file.sha3_checksum,
+        ))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + AsyncSeek + Unpin> AsyncArchive<S> {
+    /// Create a new archive
+    pub async fn create(mut stream: S, conf: ArchiveConfig) -> Result<Self, ArchiveError> {
+        let len = stream.seek(SeekFrom::End(0)).await?;
+        let archive = Archive::create(MetadataCache::new(len), conf)?;
+
+        let mut archive = Self { archive, stream };
+        archive.apply_writes().await?;
+
+        Ok(archive)
+    }
+
+    /// Create a new directory
+    pub async fn create_dir(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+    ) -> Result<DirectoryId, ArchiveError> {
+        let result = self.archive.create_dir(parent_dir, name, modif_time);
+        self.apply_writes().await?;
+        result
+    }
+
+    /// Create a new file from an asynchronous reader
+    ///
+    /// The content is written after all existing data. If the archive is encrypted
+    /// (see [`ArchiveConfig::encryption_key`]), it is encrypted by chunks while being written.
+    pub async fn create_file(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        mut content: impl AsyncRead + Unpin,
+    ) -> Result<FileId, ArchiveError> {
+        self.archive.ensure_name_fits(&name)?;
+        self.archive.ensure_no_duplicate_name(&name, parent_dir)?;
+
+        let mut reserved = Segment {
+            start: self.archive.next_writable_addr(),
+            len: 0,
+        };
+
+        let mut hasher = self.archive.content_hasher();
+
+        let mut encryptor = self.archive.stream_cipher()?.map(ContentEncryptor::new);
+
+        let written = async {
+            self.stream.seek(SeekFrom::Start(reserved.start)).await?;
+
+            let mut buf = vec![0; 4096];
+
+            loop {
+                let len = content.read(&mut buf).await?;
+
+                let data = match &mut encryptor {
+                    Some(encryptor) if len > 0 => Cow::Owned(encryptor.update(&buf[..len])),
+                    Some(_) => Cow::Owned(encryptor.take().unwrap().finish()),
+                    None => Cow::Borrowed(&buf[..len]),
+                };
+
+                if !data.is_empty() {
+                    self.stream.write_all(&data).await?;
+                    hasher.update(&data);
+
+                    self.archive
+                        .extend_reserved(&mut reserved, u64::try_from(data.len()).unwrap());
+                }
+
+                if len == 0 {
+                    return Ok::<_, std::io::Error>(());
+                }
+            }
+        }
+        .await;
+
+        if let Err(err) = written {
+            self.archive.release_reserved(reserved);
+            return Err(err.into());
+        }
+
+        self.archive
+            .stream_mut()
+            .grow_to(reserved.start + reserved.len);
+
+        let result = self
+            .archive
+            .create_reserved_file(parent_dir, name, modif_time, reserved, hasher, None);
+
+        self.apply_writes().await?;
+        result
+    }
+
+    /// Flush all changes to the underlying stream
+    pub async fn flush(&mut self) -> Result<(), ArchiveError> {
+        let result = self.archive.flush();
+        self.apply_writes().await?;
+        result?;
+
+        Ok(self.stream.flush().await?)
+    }
+
+    /// Close the archive
+    ///
+    /// Returns the original stream provided at type construction
+    pub async fn close(mut self) -> Result<S, ArchiveError> {
+        self.flush().await?;
+        Ok(self.stream)
+    }
+
+    /// Apply the writes performed on the metadata to the underlying stream
+    ///
+    /// The stream is flushed wherever the writes must be ordered, so it must persist data
+    /// when flushed for journaled archives to be crash-consistent.
+    async fn apply_writes(&mut self) -> Result<(), ArchiveError> {
+        for write in self.archive.stream_mut().take_writes() {
+            match write {
+                CachedWrite::Data { addr, data } => {
+                    self.stream.seek(SeekFrom::Start(addr)).await?;
+                    self.stream.write_all(&data).await?;
+                }
+
+                CachedWrite::Sync => self.stream.flush().await?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Load all of an archive's metadata into the cache
+///
+/// Decoding errors are not reported here: loading simply stops, and [`Archive::open`] will
+/// report them when decoding the cache's content.
+async fn load_metadata(
+    stream: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    cache: &mut MetadataCache,
+    conf: &ArchiveConfig,
+) -> std::io::Result<()> {
+    load(stream, cache, 0, HEADER_SIZE as u64).await?;
+
+    let Ok(header) = Header::decode(&mut Source::new(&mut *cache)).map(|decoded| decoded.header)
+    else {
+        return Ok(());
+    };
+
+    // Load the journal, as a pending record may modify the rest of the metadata
+    let mut pending_writes = vec![];
+
+    if let Some(addr) = header.journal_addr {
+        load(stream, cache, addr, JOURNAL_HEADER_SIZE).await?;
+
+        let capacity = read_value::<u64>(cache, &[], addr.saturating_add(8)).unwrap_or(0);
+        load(
+            stream,
+            cache,
+            addr,
+            JOURNAL_HEADER_SIZE.saturating_add(capacity),
+        )
+        .await?;
+
+        if let Ok(journal) = Journal::decode(&mut Source::new(&mut *cache), addr) {
+            pending_writes = journal.pending.map_or(vec![], |record| record.writes);
+        }
+    }
+
+    let Ok(header) = Header::decode(&mut Source::new(JournalOverlay::new(
+        &mut *cache,
+        &pending_writes,
+    )))
+    .map(|decoded| decoded.header) else {
+        return Ok(());
+    };
+
+    // Load the file table segments
+    let limits = &conf.open_limits;
+
+    let mut visited_segments = HashSet::new();
+    let mut metadata_len = 0_u64;
+    let mut next_segment_addr = Some(HEADER_SIZE as u64);
+
+    while let Some(addr) = next_segment_addr {
+        if visited_segments.len() == limits.max_segments || !visited_segments.insert(addr) {
+            break;
+        }
+
+        load(stream, cache, addr, 16).await?;
+
+        let (Some(next), Some(counts)) = (
+            read_value::<u64>(cache, &pending_writes, addr),
+            read_value::<u64>(cache, &pending_writes, addr.saturating_add(8)),
+        ) else {
+            break;
+        };
+
+        let dirs_count = u32::try_from(counts & 0xFFFF_FFFF).unwrap();
+        let files_count = u32::try_from(counts >> 32).unwrap();
+        let segment_len = FileTableSegment::encoded_len_for(dirs_count, files_count);
+
+        metadata_len = metadata_len.saturating_add(segment_len);
+
+        if metadata_len > limits.max_metadata_len {
+            break;
+        }
+
+        load(stream, cache, addr, segment_len).await?;
+
+        // Load the records referenced by the segment's entries
+        if header
+            .features
+            .requires(FeatureFlags::REQUIRED_ITEM_RECORDS)
+        {
+            let dir_entries = (0..u64::from(dirs_count)).map(|index| {
+                (
+                    index * (DIRECTORY_ENTRY_SIZE as u64),
+                    DIRECTORY_NAME_OFFSET_IN_ENTRY,
+                )
+            });
+
+            let file_entries = (0..u64::from(files_count)).map(|index| {
+                (
+                    u64::from(dirs_count) * (DIRECTORY_ENTRY_SIZE as u64)
+                        + index * (FILE_ENTRY_SIZE as u64),
+                    FILE_NAME_OFFSET_IN_ENTRY,
+                )
+            });
+
+            for (entry_offset, name_offset) in dir_entries.chain(file_entries) {
+                let entry_addr = addr.saturating_add(16 + entry_offset);
+                let name_addr = entry_addr.saturating_add(name_offset as u64);
+
+                // Empty entries and names held in the entry don't reference any record
+                let (Some(1..), Some(0), Some(record_addr), Some(record_len)) = (
+                    read_value::<u64>(cache, &pending_writes, entry_addr),
+                    read_value::<u8>(cache, &pending_writes, name_addr),
+                    read_value::<u64>(cache, &pending_writes, name_addr.saturating_add(1)),
+                    read_value::<u64>(cache, &pending_writes, name_addr.saturating_add(9)),
+                ) else {
+                    continue;
+                };
+
+                metadata_len = metadata_len.saturating_add(record_len);
+
+                if metadata_len > limits.max_metadata_len {
+                    break;
+                }
+
+                load(stream, cache, record_addr, record_len).await?;
+            }
+        }
+
+        next_segment_addr = (next != 0).then_some(next);
+    }
+
+    Ok(())
+}
+
+/// Load a region of the archive into the cache, if it isn't already
+///
+/// The region is cut to the archive's length.
+async fn load(
+    stream: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    cache: &mut MetadataCache,
+    addr: u64,
+    len: u64,
+) -> std::io::Result<()> {
+    let Some(missing) = cache.missing(addr, len) else {
+        return Ok(());
+    };
+
+    let mut buf = vec![0; usize::try_from(missing.len).unwrap()];
+
+    stream.seek(SeekFrom::Start(missing.start)).await?;
+    stream.read_exact(&mut buf).await?;
+
+    cache.insert(missing.start, &buf);
+
+    Ok(())
+}
+
+/// Read a value from the cache, as if the provided writes had been applied
+fn read_value<T: FromSourceBytes>(
+    cache: &mut MetadataCache,
+    writes: &[crate::journal::JournalWrite],
+    addr: u64,
+) -> Option<T> {
+    let mut source = Source::new(JournalOverlay::new(cache, writes));
+
+    source.set_position(addr).ok()?;
+    source.read_value::<T>().ok()
+}
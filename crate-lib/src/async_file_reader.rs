@@ -0,0 +1,197 @@
+use std::{
+    io::{Error, ErrorKind, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use sha3::{Digest, Sha3_256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, ReadBuf};
+
+use crate::FileReaderError;
+
+/// Asynchronous abstraction over a file with checksum verification
+///
+/// Obtained from [`crate::AsyncArchive::read_file`]
+///
+/// **NOTE:** Checksum validation only occurs *after* the very last byte has been read.
+///
+/// Like [`crate::FileReader`], seeking back to the file's beginning restarts the checksum computation,
+/// but seeking anywhere else disables it for the rest of the reading. Chunks' checksums are not used.
+#[derive(Debug)]
+pub struct AsyncFileReader<'a, S: AsyncRead + AsyncSeek + Unpin> {
+    stream: &'a mut S,
+    addr: u64,
+    len: u64,
+    expected_checksum: [u8; 32],
+    pending_checksum: Option<Sha3_256>,
+    pos: u64,
+    /// Position the stream must be moved to before the next read
+    pending_seek: Option<u64>,
+    seek_in_progress: bool,
+}
+
+impl<'a, S: AsyncRead + AsyncSeek + Unpin> AsyncFileReader<'a, S> {
+    pub(crate) fn new(stream: &'a mut S, addr: u64, len: u64, expected_checksum: [u8; 32]) -> Self {
+        Self {
+            stream,
+            addr,
+            len,
+            expected_checksum,
+            pending_checksum: Some(Sha3_256::new()),
+            pos: 0,
+            pending_seek: Some(addr),
+            seek_in_progress: false,
+        }
+    }
+
+    /// Get the file's length, in bytes
+    pub fn file_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the content read from now on will be verified
+    ///
+    /// This is not the case anymore after seeking somewhere else than the file's beginning.
+    pub fn is_checksum_verified_on_read(&self) -> bool {
+        self.pending_checksum.is_some()
+    }
+
+    /// Read the file's content to a `Vec<u8>`
+    pub async fn read_to_vec(mut self) -> Result<Vec<u8>, FileReaderError> {
+        let mut buf = Vec::with_capacity(usize::try_from(self.file_len()).unwrap());
+
+        self.read_to_end(&mut buf)
+            .await
+            .map_err(FileReaderError::from_io_error)?;
+
+        Ok(buf)
+    }
+
+    /// Read this file's content as a string
+    pub async fn read_to_string(self) -> Result<String, FileReaderError> {
+        let bytes = self.read_to_vec().await?;
+
+        String::from_utf8(bytes).map_err(FileReaderError::InvalidUtf8)
+    }
+
+    /// Move the underlying stream to the expected position, if required
+    fn poll_pending_seek(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            if self.seek_in_progress {
+                ready!(Pin::new(&mut *self.stream).poll_complete(cx))?;
+                self.seek_in_progress = false;
+            }
+
+            let Some(pos) = self.pending_seek.take() else {
+                return Poll::Ready(Ok(()));
+            };
+
+            Pin::new(&mut *self.stream).start_seek(SeekFrom::Start(pos))?;
+            self.seek_in_progress = true;
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncSeek + Unpin> AsyncRead for AsyncFileReader<'_, S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_pending_seek(cx))?;
+
+        let read_len = u64::try_from(buf.remaining())
+            .unwrap()
+            .min(this.len - this.pos);
+
+        if read_len == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let unfilled = buf.initialize_unfilled_to(usize::try_from(read_len).unwrap());
+        let mut inner_buf = ReadBuf::new(unfilled);
+
+        ready!(Pin::new(&mut *this.stream).poll_read(cx, &mut inner_buf))?;
+
+        let read = inner_buf.filled().len();
+
+        if read == 0 {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "archive ended before the file's content",
+            )));
+        }
+
+        if let Some(pending_checksum) = &mut this.pending_checksum {
+            pending_checksum.update(inner_buf.filled());
+        }
+
+        this.pos += u64::try_from(read).unwrap();
+
+        // When the entire file has been read, check its validity by comparing the checksums
+        // (nothing must be marked as read when returning an error)
+        if this.pos == this.len
+            && let Some(pending_checksum) = &this.pending_checksum
+        {
+            let hash: [u8; 32] = pending_checksum.clone().finalize().into();
+
+            if hash != this.expected_checksum {
+                return Poll::Ready(Err(Error::new(
+                    ErrorKind::InvalidData,
+                    FileReaderError::ChecksumMismatch {
+                        expected: this.expected_checksum,
+                        actual: hash,
+                    },
+                )));
+            }
+        }
+
+        buf.advance(read);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncSeek + Unpin> AsyncSeek for AsyncFileReader<'_, S> {
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => this.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => this.pos.checked_add_signed(offset),
+        };
+
+        let new_pos = new_pos
+            .filter(|new_pos| *new_pos <= this.len)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "cannot seek outside of the file's content",
+                )
+            })?;
+
+        if new_pos == this.pos {
+            return Ok(());
+        }
+
+        // Reading from the beginning again allows to compute the checksum from scratch
+        this.pending_checksum = if new_pos == 0 {
+            Some(Sha3_256::new())
+        } else {
+            None
+        };
+
+        this.pos = new_pos;
+        this.pending_seek = Some(this.addr + new_pos);
+
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        // The underlying stream is only moved before the next read
+        Poll::Ready(Ok(self.pos))
+    }
+}
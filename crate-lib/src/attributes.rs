@@ -0,0 +1,35 @@
+/// Unix attributes of a file or directory
+///
+/// See [`crate::Archive::set_unix_attributes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixAttributes {
+    /// Mode of the item, including its type and permission bits (`st_mode`)
+    pub mode: u32,
+
+    /// ID of the item's owner
+    pub uid: u32,
+
+    /// ID of the item's group
+    pub gid: u32,
+
+    /// Name of the item's owner, if known (up to 255 bytes)
+    pub owner: Option<String>,
+
+    /// Name of the item's group, if known (up to 255 bytes)
+    pub group: Option<String>,
+}
+
+impl UnixAttributes {
+    /// Get the permission bits of the mode (including setuid, setgid and sticky bits)
+    pub fn permissions(&self) -> u32 {
+        self.mode & 0o7777
+    }
+
+    /// Check if the owner and group names fit in the item's record
+    pub(crate) fn is_valid(&self) -> bool {
+        [&self.owner, &self.group]
+            .into_iter()
+            .flatten()
+            .all(|name| !name.is_empty() && name.len() <= 255)
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{Cursor, ErrorKind};
+
+use sha3::{Digest, Sha3_256};
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, FileId, FileReaderError,
+};
+
+/// Read-only archive held in memory, giving access to files' content without copying it
+///
+/// The bytes may come from anything that can be seen as a byte slice, e.g. a `Vec<u8>`,
+/// a `&'static [u8]` embedded in the binary, or a memory-mapped file (e.g. `memmap2::Mmap`).
+///
+/// The archive's metadata is decoded exactly like with [`Archive::open`], and can be accessed
+/// through [`BytesArchive::archive`]. Files' content is then borrowed directly from the bytes.
+pub struct BytesArchive<B: AsRef<[u8]>> {
+    archive: Archive<Cursor<B>>,
+}
+
+impl<B: AsRef<[u8]>> BytesArchive<B> {
+    /// Open an archive from its bytes
+    ///
+    /// Will decode the entire archive's metadata before returning.
+    pub fn open(bytes: B, conf: ArchiveConfig) -> Result<Self, ArchiveMetadataDecodingError> {
+        Archive::open(Cursor::new(bytes), conf).map(|archive| Self { archive })
+    }
+
+    /// Get the underlying archive, to access its metadata
+    pub fn archive(&self) -> &Archive<Cursor<B>> {
+        &self.archive
+    }
+
+    /// Get the archive's bytes
+    pub fn bytes(&self) -> &[u8] {
+        self.archive.stream().get_ref().as_ref()
+    }
+
+    /// Get the content of a file contained inside the archive
+    ///
+    /// **NOTE:** The content is not verified, see [`BytesArchive::file_bytes_verified`]
+    ///
+    /// Compressed and encrypted contents can't be borrowed, they must be read through
+    /// [`Archive::read_file_shared`] instead.
+    pub fn file_bytes(&self, id: FileId) -> Result<&[u8], ArchiveError> {
+        if self.archive.get_compression(id).is_some() {
+            return Err(ArchiveError::FileIsCompressed);
+        }
+
+        let file = self
+            .archive
+            .get_file(id)
+            .ok_or(ArchiveError::FileNotFound)?;
+
+        if self.archive.is_encrypted() && file.content_len > 0 {
+            return Err(ArchiveError::FileIsEncrypted);
+        }
+
+        // Opening the archive ensured the content is in bounds, but the bytes may have changed since
+        usize::try_from(file.content_addr)
+            .ok()
+            .zip(usize::try_from(file.content_len).ok())
+            .and_then(|(addr, len)| self.bytes().get(addr..addr.checked_add(len)?))
+            .ok_or_else(|| {
+                ArchiveError::Io(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "file's content is out of the archive's bounds",
+                ))
+            })
+    }
+
+    /// Get the content of a file contained inside the archive, after verifying its checksum
+    pub fn file_bytes_verified(&self, id: FileId) -> Result<&[u8], ArchiveError> {
+        let content = self.file_bytes(id)?;
+
+        let expected = self.archive.get_file(id).unwrap().sha3_checksum;
+        let actual: [u8; 32] = Sha3_256::digest(content).into();
+
+        if actual != expected {
+            return Err(FileReaderError::ChecksumMismatch { expected, actual }.into());
+        }
+
+        Ok(content)
+    }
+
+    /// Get the archive's bytes back
+    pub fn into_inner(self) -> B {
+        self.archive.into_stream().into_inner()
+    }
+}
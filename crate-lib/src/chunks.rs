@@ -0,0 +1,90 @@
+use std::num::NonZero;
+
+use sha3::{Digest, Sha3_256};
+
+/// Checksums of each fixed-size chunk of a file's content
+///
+/// The last chunk may be smaller than the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChunkChecksums {
+    pub chunk_size: u64,
+    pub checksums: Vec<[u8; 32]>,
+}
+
+impl ChunkChecksums {
+    /// Check if the checksums cover exactly the provided content length
+    pub fn matches_len(&self, content_len: u64) -> bool {
+        u64::try_from(self.checksums.len()).unwrap() == content_len.div_ceil(self.chunk_size)
+    }
+}
+
+/// Incremental hasher for a file's content
+///
+/// Computes the SHA-3 checksum of the entire content, and optionally the checksum of each chunk
+#[derive(Clone)]
+pub(crate) struct ContentHasher {
+    checksum: Sha3_256,
+    chunks: Option<ChunksHasher>,
+}
+
+#[derive(Clone)]
+struct ChunksHasher {
+    chunk_size: u64,
+    current: Sha3_256,
+    current_len: u64,
+    checksums: Vec<[u8; 32]>,
+}
+
+impl ContentHasher {
+    pub fn new(chunk_size: Option<NonZero<u64>>) -> Self {
+        Self {
+            checksum: Sha3_256::new(),
+            chunks: chunk_size.map(|chunk_size| ChunksHasher {
+                chunk_size: chunk_size.get(),
+                current: Sha3_256::new(),
+                current_len: 0,
+                checksums: vec![],
+            }),
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.checksum.update(data);
+
+        let Some(chunks) = &mut self.chunks else {
+            return;
+        };
+
+        while !data.is_empty() {
+            let remaining = chunks.chunk_size - chunks.current_len;
+            let len = usize::try_from(remaining.min(u64::try_from(data.len()).unwrap())).unwrap();
+
+            chunks.current.update(&data[..len]);
+            chunks.current_len += u64::try_from(len).unwrap();
+            data = &data[len..];
+
+            if chunks.current_len == chunks.chunk_size {
+                chunks
+                    .checksums
+                    .push(chunks.current.finalize_reset().into());
+                chunks.current_len = 0;
+            }
+        }
+    }
+
+    /// Get the content's checksum, and the chunks' checksums if they were computed
+    pub fn finalize(self) -> ([u8; 32], Option<ChunkChecksums>) {
+        let chunks = self.chunks.map(|mut chunks| {
+            if chunks.current_len > 0 {
+                chunks.checksums.push(chunks.current.finalize().into());
+            }
+
+            ChunkChecksums {
+                chunk_size: chunks.chunk_size,
+                checksums: chunks.checksums,
+            }
+        });
+
+        (self.checksum.finalize().into(), chunks)
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::{Read, Write},
+};
+
+use flate2::{Decompress, FlushDecompress};
+use zstd::stream::raw::{Decoder as ZstdDecoder, Operation};
+
+use crate::ItemName;
+
+/// Extensions of file formats which are already compressed
+static COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar", "jpeg", "jpg", "lz4",
+    "lzma", "m4a", "mkv", "mov", "mp3", "mp4", "ogg", "opus", "png", "rar", "tgz", "webm", "webp",
+    "xlsx", "xz", "zip", "zst",
+];
+
+/// Codec used to compress a file's content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionCodec {
+    /// Zstandard, fast with a good compression ratio
+    Zstd,
+
+    /// Raw Deflate, as used in ZIP archives
+    Deflate,
+}
+
+impl CompressionCodec {
+    pub(crate) fn decode(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::Zstd),
+            2 => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn encode(self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Deflate => 2,
+        }
+    }
+
+    /// Get a reader compressing the provided content
+    pub(crate) fn compressor<'a>(
+        self,
+        content: impl Read + 'a,
+    ) -> std::io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::Zstd => Box::new(zstd::stream::read::Encoder::new(content, 0)?),
+
+            Self::Deflate => Box::new(flate2::read::DeflateEncoder::new(
+                content,
+                flate2::Compression::default(),
+            )),
+        })
+    }
+}
+
+/// How a file's content is compressed
+///
+/// See [`crate::ArchiveConfig::compression`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileCompression {
+    /// Codec the content is compressed with
+    pub codec: CompressionCodec,
+
+    /// Length of the content once decompressed, in bytes
+    pub uncompressed_len: u64,
+}
+
+/// Policy deciding whether and how new files' content is compressed
+///
+/// See [`crate::ArchiveConfig::compression`]
+#[derive(Debug, Clone)]
+pub struct CompressionPolicy {
+    /// Codec to compress files with, unless their extension is listed in [`Self::by_extension`]
+    pub codec: CompressionCodec,
+
+    /// Codec to compress files with depending on their extension (lowercase, without the leading dot)
+    ///
+    /// Files whose extension is associated to `None` are never compressed.
+    pub by_extension: HashMap<String, Option<CompressionCodec>>,
+
+    /// Files whose content is smaller than this length (in bytes) are never compressed
+    pub min_len: u64,
+
+    /// Minimum part of the content's length compression must save, in percents
+    ///
+    /// Contents which don't compress well enough are stored uncompressed.
+    pub min_savings_percent: u8,
+}
+
+impl CompressionPolicy {
+    /// Compress files with the provided codec, except those whose extension denotes an already-compressed format
+    /// (e.g. images, videos or archives)
+    pub fn new(codec: CompressionCodec) -> Self {
+        Self {
+            codec,
+            by_extension: COMPRESSED_EXTENSIONS
+                .iter()
+                .map(|ext| (ext.to_string(), None))
+                .collect(),
+            min_len: 64,
+            min_savings_percent: 5,
+        }
+    }
+
+    /// Get the codec to compress a new file's content with, if any
+    pub(crate) fn codec_for(&self, name: &ItemName, len: u64) -> Option<CompressionCodec> {
+        if len == 0 || len < self.min_len {
+            return None;
+        }
+
+        self.codec_for_name(name)
+    }
+
+    /// Get the codec to compress a new file's content of yet unknown length with, if any
+    ///
+    /// Whether the content is long enough to be compressed must be checked once it is entirely written.
+    pub(crate) fn codec_for_name(&self, name: &ItemName) -> Option<CompressionCodec> {
+        let ext = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+
+        match ext.and_then(|ext| self.by_extension.get(&ext)) {
+            Some(codec) => *codec,
+            None => Some(self.codec),
+        }
+    }
+
+    /// Check if a compressed content saves enough space to be kept
+    pub(crate) fn is_worth_it(&self, uncompressed_len: u64, compressed_len: u64) -> bool {
+        let max_percent = 100 - u128::from(self.min_savings_percent.min(100));
+
+        u128::from(compressed_len) * 100 <= u128::from(uncompressed_len) * max_percent
+    }
+}
+
+/// Compressor for a file's content, fed with buffers
+pub(crate) enum Compressor {
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Deflate(flate2::write::DeflateEncoder<Vec<u8>>),
+}
+
+impl Compressor {
+    pub fn new(codec: CompressionCodec) -> std::io::Result<Self> {
+        Ok(match codec {
+            CompressionCodec::Zstd => Self::Zstd(zstd::stream::write::Encoder::new(vec![], 0)?),
+            CompressionCodec::Deflate => Self::Deflate(flate2::write::DeflateEncoder::new(
+                vec![],
+                flate2::Compression::default(),
+            )),
+        })
+    }
+
+    /// Compress some more data
+    ///
+    /// Returns the compressed bytes produced so far, which may not cover all the provided data yet.
+    pub fn compress(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                std::mem::take(encoder.get_mut())
+            }
+
+            Self::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                std::mem::take(encoder.get_mut())
+            }
+        })
+    }
+
+    /// Complete the compression
+    ///
+    /// Returns the remaining compressed bytes
+    pub fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Zstd(encoder) => encoder.finish(),
+            Self::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Debug for Compressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Zstd(_) => f.write_str("Compressor::Zstd"),
+            Self::Deflate(_) => f.write_str("Compressor::Deflate"),
+        }
+    }
+}
+
+/// Decompressor for a file's content, fed with buffers
+pub(crate) enum Decompressor {
+    Zstd(ZstdDecoder<'static>),
+    Deflate(Decompress),
+}
+
+impl Decompressor {
+    pub fn new(codec: CompressionCodec) -> std::io::Result<Self> {
+        Ok(match codec {
+            CompressionCodec::Zstd => Self::Zstd(ZstdDecoder::new()?),
+            CompressionCodec::Deflate => Self::Deflate(Decompress::new(false)),
+        })
+    }
+
+    /// Decompress as much of the input as possible into the output
+    ///
+    /// Returns the number of bytes consumed from the input and written to the output
+    pub fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> std::io::Result<(usize, usize)> {
+        match self {
+            Self::Zstd(decoder) => {
+                let status = decoder.run_on_buffers(input, output)?;
+                Ok((status.bytes_read, status.bytes_written))
+            }
+
+            Self::Deflate(decompress) => {
+                let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+
+                decompress.decompress(input, output, FlushDecompress::None)?;
+
+                Ok((
+                    usize::try_from(decompress.total_in() - total_in).unwrap(),
+                    usize::try_from(decompress.total_out() - total_out).unwrap(),
+                ))
+            }
+        }
+    }
+}
+
+/// Decompress an entire content held in memory
+#[cfg(feature = "async")]
+pub(crate) fn decompress_to_vec(
+    compression: FileCompression,
+    stored: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    let mut decompressor = Decompressor::new(compression.codec)?;
+
+    // The expected length comes from the archive, so the output only grows as data is produced
+    let mut output = vec![];
+    let mut buf = vec![0; 64 * 1024];
+    let mut input_pos = 0;
+
+    loop {
+        let (consumed, produced) = decompressor.decompress(&stored[input_pos..], &mut buf)?;
+
+        input_pos += consumed;
+        output.extend_from_slice(&buf[..produced]);
+
+        if u64::try_from(output.len()).unwrap() > compression.uncompressed_len
+            || (consumed == 0 && produced == 0)
+        {
+            break;
+        }
+    }
+
+    if input_pos != stored.len()
+        || u64::try_from(output.len()).unwrap() != compression.uncompressed_len
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "decompressed content doesn't match the expected length",
+        ));
+    }
+
+    Ok(output)
+}
+
+impl Debug for Decompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Zstd(_) => f.write_str("Decompressor::Zstd"),
+            Self::Deflate(_) => f.write_str("Decompressor::Deflate"),
+        }
+    }
+}
@@ -1,8 +1,19 @@
 use std::num::NonZero;
 
+use crate::{ArchiveVersion, CompressionPolicy, EncryptionKey};
+
 /// Configuration of an archive's behaviour
+///
+/// New options may be added in the future, so configurations are built from [`ArchiveConfig::default`]
+/// and customized with the `with_*` setters.
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct ArchiveConfig {
+    /// Format version to create new archives with
+    ///
+    /// Existing archives keep their version, unless upgraded with [`crate::Archive::upgrade_to`].
+    pub version: ArchiveVersion,
+
     /// Maximum number of directory entries per file table segment
     pub default_dirs_capacity_by_ft_segment: NonZero<u32>,
 
@@ -14,15 +25,217 @@ pub struct ArchiveConfig {
 
     /// Override the maximum number of file entries for the first table segment
     pub first_segment_files_capacity_override: Option<NonZero<u32>>,
+
+    /// Create archives with a write-ahead journal
+    ///
+    /// Before each metadata mutation, the writes it requires are recorded in the journal.
+    /// If the process crashes or the power is lost during the mutation, it will be replayed
+    /// (or discarded if it wasn't entirely recorded) the next time the archive is opened.
+    ///
+    /// Writes are ordered by waiting for the underlying stream to persist them (see [`crate::SyncData`]),
+    /// so that the record is never considered committed before all of it is persisted.
+    ///
+    /// Journaled archives require format support for the journal, and are slightly slower to mutate.
+    /// Existing archives can be journaled by using [`crate::Archive::enable_journal`].
+    pub journal: bool,
+
+    /// Compute a checksum for each chunk of this size (in bytes) of new files' content
+    ///
+    /// This allows [`crate::FileReader`] to verify every chunk it returns, even when reading only
+    /// a small part of a large file. Without it, the content can only be verified by reading it entirely.
+    ///
+    /// Chunks' checksums are stored in the file's record, which is written alongside its entry, so they are never
+    /// computed for archives before version 2. Files without chunks' checksums are still verified using their entire
+    /// content's checksum.
+    pub chunk_size: Option<NonZero<u64>>,
+
+    /// Store identical contents only once
+    ///
+    /// When a file is created or its content replaced, the archive looks for an existing content with the
+    /// same checksum and length. If there is one, the file references it instead of storing a new copy.
+    /// Shared contents are only given back to the archive once every file referencing them has been removed.
+    ///
+    /// Seekable contents are read once to compute their checksum before being written, so no space is used
+    /// for duplicates. Other contents are written first, and the space they use is given back if a duplicate is found.
+    ///
+    /// Unlike hard links (see [`crate::Archive::create_hard_link`]), files sharing their content this way
+    /// are not linked together.
+    pub deduplicate: bool,
+
+    /// Compress new files' content according to this policy
+    ///
+    /// Each file is compressed independently, so reading a file never requires decompressing others.
+    /// [`crate::FileReader`] decompresses the content transparently.
+    ///
+    /// Only contents of known length are compressed, i.e. not those written with [`crate::Archive::create_file_from_reader`]
+    /// or [`crate::Archive::replace_file_content_from_reader`]. Contents written with a [`crate::FileWriter`] are compressed
+    /// while being written, then decompressed if they don't save enough space once their length is known.
+    ///
+    /// Compressed contents are written after all existing data. The compression of each file is stored in its record,
+    /// which is written alongside its entry, so archives before version 2 are never compressed.
+    pub compression: Option<CompressionPolicy>,
+
+    /// Key to encrypt new archives with, and to decrypt existing ones
+    ///
+    /// Each file's content is split into chunks of 64 KiB, which are encrypted and authenticated on their own, with a nonce
+    /// made of a random prefix and the chunk's position. The key itself isn't stored, only a value derived from it, which
+    /// allows detecting wrong keys when opening the archive. Keys derived from a password use Argon2id, with a random salt
+    /// stored in the archive's header.
+    ///
+    /// [`crate::FileReader`] decrypts the content transparently. Each chunk is authenticated before any of its bytes is
+    /// returned, so only the chunk being read is held in memory, and seeking only requires decrypting the chunk the new
+    /// position is inside. Contents are encrypted chunk by chunk while being written, including from a stream.
+    ///
+    /// Encrypted archives whose names are not encrypted (see [`ArchiveConfig::encrypt_names`]) can be opened without the key,
+    /// but their files' content can't be read or written then. Existing archives which aren't encrypted ignore the key.
+    ///
+    /// Metadata other than items' names (e.g. modification times, Unix attributes or symbolic links' target) is not encrypted.
+    /// Neither is the length of files' content and names. Identical contents are never deduplicated (see [`ArchiveConfig::deduplicate`]).
+    pub encryption_key: Option<EncryptionKey>,
+
+    /// Encrypt items' names as well when creating an encrypted archive
+    ///
+    /// In archives created before version 2, names can't be longer than [`crate::MAX_ENCRYPTED_NAME_LEN`] bytes in that case.
+    pub encrypt_names: bool,
+
+    /// Limits applied when opening an archive
+    pub open_limits: OpenLimits,
 }
 
 impl Default for ArchiveConfig {
     fn default() -> Self {
         Self {
+            version: ArchiveVersion::LATEST,
             default_dirs_capacity_by_ft_segment: NonZero::new(100).unwrap(),
             default_files_capacity_by_ft_segment: NonZero::new(100).unwrap(),
             first_segment_files_capacity_override: Some(NonZero::new(10).unwrap()),
             first_segment_dirs_capacity_override: Some(NonZero::new(10).unwrap()),
+            journal: false,
+            chunk_size: None,
+            deduplicate: false,
+            compression: None,
+            encryption_key: None,
+            encrypt_names: false,
+            open_limits: OpenLimits::default(),
+        }
+    }
+}
+
+impl ArchiveConfig {
+    /// Set the format version to create new archives with (see [`ArchiveConfig::version`])
+    pub fn with_version(mut self, version: ArchiveVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the maximum number of directory entries per file table segment
+    pub fn with_default_dirs_capacity_by_ft_segment(mut self, capacity: NonZero<u32>) -> Self {
+        self.default_dirs_capacity_by_ft_segment = capacity;
+        self
+    }
+
+    /// Set the maximum number of file entries per file table segment
+    pub fn with_default_files_capacity_by_ft_segment(mut self, capacity: NonZero<u32>) -> Self {
+        self.default_files_capacity_by_ft_segment = capacity;
+        self
+    }
+
+    /// Override the maximum number of directory entries for the first table segment
+    pub fn with_first_segment_dirs_capacity_override(
+        mut self,
+        capacity: Option<NonZero<u32>>,
+    ) -> Self {
+        self.first_segment_dirs_capacity_override = capacity;
+        self
+    }
+
+    /// Override the maximum number of file entries for the first table segment
+    pub fn with_first_segment_files_capacity_override(
+        mut self,
+        capacity: Option<NonZero<u32>>,
+    ) -> Self {
+        self.first_segment_files_capacity_override = capacity;
+        self
+    }
+
+    /// Create archives with a write-ahead journal (see [`ArchiveConfig::journal`])
+    pub fn with_journal(mut self, journal: bool) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Compute a checksum for each chunk of new files' content (see [`ArchiveConfig::chunk_size`])
+    pub fn with_chunk_size(mut self, chunk_size: Option<NonZero<u64>>) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Store identical contents only once (see [`ArchiveConfig::deduplicate`])
+    pub fn with_deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Compress new files' content (see [`ArchiveConfig::compression`])
+    pub fn with_compression(mut self, compression: Option<CompressionPolicy>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set the key to encrypt or decrypt archives with (see [`ArchiveConfig::encryption_key`])
+    pub fn with_encryption_key(mut self, encryption_key: Option<EncryptionKey>) -> Self {
+        self.encryption_key = encryption_key;
+        self
+    }
+
+    /// Encrypt items' names as well (see [`ArchiveConfig::encrypt_names`])
+    pub fn with_encrypt_names(mut self, encrypt_names: bool) -> Self {
+        self.encrypt_names = encrypt_names;
+        self
+    }
+
+    /// Set the limits applied when opening an archive (see [`ArchiveConfig::open_limits`])
+    pub fn with_open_limits(mut self, open_limits: OpenLimits) -> Self {
+        self.open_limits = open_limits;
+        self
+    }
+}
+
+/// Limits applied when opening an archive (see [`ArchiveConfig::open_limits`])
+///
+/// As an archive's metadata is entirely loaded in memory when opening it, these prevent
+/// untrusted archives from exhausting the available memory.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenLimits {
+    /// Maximum number of file table segments
+    pub max_segments: usize,
+
+    /// Maximum number of directory and file entries (including empty ones) in the file table
+    pub max_entries: u64,
+
+    /// Maximum size of the file table, in bytes
+    pub max_metadata_len: u64,
+}
+
+impl OpenLimits {
+    /// Don't limit anything
+    ///
+    /// Should only be used for trusted archives.
+    pub fn unlimited() -> Self {
+        Self {
+            max_segments: usize::MAX,
+            max_entries: u64::MAX,
+            max_metadata_len: u64::MAX,
+        }
+    }
+}
+
+impl Default for OpenLimits {
+    fn default() -> Self {
+        Self {
+            max_segments: 1 << 16,
+            max_entries: 1 << 24,
+            max_metadata_len: 1 << 33,
         }
     }
 }
@@ -1,59 +1,136 @@
-use std::collections::{BTreeSet, btree_set};
+use std::collections::{BTreeMap, btree_map};
 
 // TODO: remove segments when empty?
-// TODO: shrink archive when needed?
 // TODO: update "len" when required
-// TODO: shrink archives when possible
 
 /// Compute which parts of an archive's memory is used or not
 ///
 /// Allows to quickly find unused space, compute wasted space, and shrink the archive if necessary
+///
+/// A zone may be used several times (e.g. by files sharing the same content), in which case
+/// it is only freed once it has been marked as free as many times.
 pub(crate) struct Coverage {
     len: u64,
-    segments: BTreeSet<Segment>,
+    segments: BTreeMap<Segment, u64>,
+    /// Zones marked as used (`true`) or freed (`false`) since recording started, to undo them
+    changes: Option<Vec<(Segment, bool)>>,
 }
 
 impl Coverage {
     pub fn new(len: u64) -> Self {
         Self {
             len,
-            segments: BTreeSet::new(),
+            segments: BTreeMap::new(),
+            changes: None,
         }
     }
 
+    /// Get the covered length
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
     pub fn grow_to(&mut self, new_len: u64) {
         assert!(new_len >= self.len);
         self.len = new_len;
     }
 
-    // TODO: shrink(&mut self, by: u64)
-
     /// Mark a zone as used
+    ///
+    /// If the exact same zone is already used, its references count is incremented instead.
     pub fn mark_as_used(&mut self, start: u64, len: u64) {
         if len == 0 {
             return;
         }
 
-        if let Some(prev) = self.segments.iter().find(|segment| segment.start <= start) {
+        if let Some(changes) = &mut self.changes {
+            changes.push((Segment { start, len }, true));
+        }
+
+        if let Some(refs) = self.segments.get_mut(&Segment { start, len }) {
+            *refs += 1;
+            return;
+        }
+
+        // Overlaps are rejected when opening an archive, so they can only come from a bug
+        if let Some(prev) = self
+            .segments
+            .range(
+                ..=Segment {
+                    start,
+                    len: u64::MAX,
+                },
+            )
+            .map(|(segment, _)| segment)
+            .next_back()
+        {
             assert!(prev.start + prev.len <= start);
         }
 
-        if let Some(next) = self.segments.iter().find(|segment| segment.start >= start) {
+        if let Some((next, _)) = self.segments.range(Segment { start, len: 0 }..).next() {
             assert!(start + len <= next.start);
         }
 
-        self.segments.insert(Segment { start, len });
+        self.segments.insert(Segment { start, len }, 1);
     }
 
     /// Mark as zone as free (unused)
     ///
+    /// If the zone is used several times, only one reference is dropped and it remains used.
+    ///
     /// Returns `true` if the segment was found and freed, `false` if it was not tracked.
     pub fn mark_as_free(&mut self, segment: Segment) -> bool {
-        if segment.len > 0 {
-            self.segments.remove(&segment)
-        } else {
-            false
+        let Some(refs) = self.segments.get_mut(&segment) else {
+            return false;
+        };
+
+        *refs -= 1;
+
+        if *refs == 0 {
+            self.segments.remove(&segment);
         }
+
+        if let Some(changes) = &mut self.changes {
+            changes.push((segment, false));
+        }
+
+        true
+    }
+
+    /// Start recording which zones are marked as used or free
+    ///
+    /// The covered length isn't recorded, as growing it only adds free space.
+    pub fn start_recording(&mut self) {
+        assert!(self.changes.is_none());
+        self.changes = Some(vec![]);
+    }
+
+    /// Stop recording changes, keeping them
+    pub fn keep_changes(&mut self) {
+        self.changes = None;
+    }
+
+    /// Stop recording changes, and undo them
+    pub fn undo_changes(&mut self) {
+        for (segment, used) in self.changes.take().unwrap().into_iter().rev() {
+            if used {
+                assert!(self.mark_as_free(segment));
+            } else {
+                self.mark_as_used(segment.start, segment.len);
+            }
+        }
+    }
+
+    /// Temporarily stop recording changes, so that the following ones can't be undone
+    ///
+    /// Recording is resumed with [`Self::resume_recording`].
+    pub fn pause_recording(&mut self) -> Option<Vec<(Segment, bool)>> {
+        self.changes.take()
+    }
+
+    /// Resume recording changes after [`Self::pause_recording`]
+    pub fn resume_recording(&mut self, changes: Option<Vec<(Segment, bool)>>) {
+        self.changes = changes;
     }
 
     /// Find the next free (unused) zones
@@ -69,10 +146,19 @@ impl Coverage {
             .min_by_key(|zone| zone.len)
     }
 
+    /// Check if a zone doesn't overlap any used one
+    ///
+    /// The zone may go beyond the covered length
+    pub fn is_free(&self, start: u64, len: u64) -> bool {
+        self.segments
+            .keys()
+            .all(|segment| segment.start + segment.len <= start || segment.start >= start + len)
+    }
+
     /// Find the next writable address (after every segment)
     pub fn next_writable_addr(&self) -> u64 {
-        match self.segments.last() {
-            Some(last) => last.start + last.len,
+        match self.segments.last_key_value() {
+            Some((last, _)) => last.start + last.len,
             None => 0,
         }
     }
@@ -100,7 +186,7 @@ impl PartialOrd for Segment {
 /// Iterator over a list of free segments
 pub struct FreeSegmentsIter<'a> {
     coverage: &'a Coverage,
-    segments_iter: btree_set::Iter<'a, Segment>,
+    segments_iter: btree_map::Keys<'a, Segment, u64>,
     prev_end: u64,
     yielded_last: bool,
 }
@@ -109,7 +195,7 @@ impl<'a> FreeSegmentsIter<'a> {
     fn new(coverage: &'a Coverage) -> Self {
         Self {
             coverage,
-            segments_iter: coverage.segments.iter(),
+            segments_iter: coverage.segments.keys(),
             prev_end: 0,
             yielded_last: false,
         }
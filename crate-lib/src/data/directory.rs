@@ -6,13 +6,15 @@ use std::{
 use thiserror::Error;
 
 use crate::{
-    ensure_only_one_version,
+    coverage::Segment,
+    encryption::Cipher,
     source::{FromSourceBytes, Source},
 };
 
 use super::{
-    header::SourceWithHeader,
-    name::{ItemName, NameDecodingError},
+    header::{ArchiveVersion, FeatureFlags, SourceWithHeader},
+    name::{EntryName, ItemName, NameDecodingError},
+    record::{ItemExtensions, ItemRecordDecodingError, read_record},
     timestamp::Timestamp,
 };
 
@@ -31,7 +33,13 @@ pub struct Directory {
     /// Name of the file (must be valid UTF-8)
     pub name: ItemName,
 
-    /// Modification time, in seconds since Unix' Epoch
+    /// Location of the item's record, if its name or extensions don't fit in its entry
+    pub(crate) record: Option<Segment>,
+
+    /// Informations which don't fit in the entry, stored in the item's record
+    pub(crate) extensions: ItemExtensions,
+
+    /// Modification time
     pub modif_time: Timestamp,
 }
 
@@ -40,8 +48,6 @@ impl Directory {
     pub(crate) fn consume_from_reader(
         input: &mut SourceWithHeader<impl Read + Seek>,
     ) -> Result<Option<Self>, DirectoryDecodingError> {
-        ensure_only_one_version!(input.header.version);
-
         let id = input
             .source
             .read_value::<u64>()
@@ -62,28 +68,48 @@ impl Directory {
             .read_value()
             .map_err(DirectoryDecodingError::InvalidEntry)?;
 
-        let name = ItemName::consume_from_reader(input.source)
+        let records = input
+            .header
+            .features
+            .requires(FeatureFlags::REQUIRED_ITEM_RECORDS);
+
+        let name = ItemName::consume_from_reader(input.source, records, input.name_cipher)
             .map_err(DirectoryDecodingError::InvalidEntry)?
             .map_err(DirectoryDecodingError::InvalidName)?;
 
-        let modif_time = input
-            .source
-            .read_value()
+        let (name, record, extensions) = match name {
+            EntryName::Inline(name) => (name, None, ItemExtensions::default()),
+
+            EntryName::Record(record) => {
+                let (name, extensions) = read_record(input.source, record, input.name_cipher)
+                    .map_err(DirectoryDecodingError::InvalidEntry)?
+                    .map_err(DirectoryDecodingError::InvalidRecord)?;
+
+                (name, Some(record), extensions.for_directory())
+            }
+        };
+
+        let modif_time = Timestamp::decode(input.source, input.header.version)
             .map_err(DirectoryDecodingError::InvalidEntry)?;
 
         Ok(Some(Self {
             id: DirectoryId(id),
             parent_dir,
             name,
+            record,
+            extensions,
             modif_time,
         }))
     }
 
-    pub(crate) fn encode(&self) -> Vec<u8> {
+    /// Encode the entry for the provided archive version, encrypting its name with the provided cipher if any
+    pub(crate) fn encode(&self, version: ArchiveVersion, name_cipher: Option<&Cipher>) -> Vec<u8> {
         let Self {
             id,
             parent_dir,
             name,
+            record,
+            extensions: _,
             modif_time,
         } = self;
 
@@ -97,8 +123,8 @@ impl Directory {
             }
             .to_le_bytes(),
         );
-        bytes.extend(name.encode());
-        bytes.extend(modif_time.encode());
+        bytes.extend(name.encode_in_entry(name_cipher, *record));
+        bytes.extend(modif_time.encode(version));
 
         debug_assert_eq!(bytes.len(), DIRECTORY_ENTRY_SIZE);
 
@@ -120,6 +146,10 @@ pub enum DirectoryDecodingError {
     /// The directory's name is invalid
     #[error("Invalid directory name: {0}")]
     InvalidName(#[from] NameDecodingError),
+
+    /// The directory's record is invalid
+    #[error("Invalid directory record: {0}")]
+    InvalidRecord(#[from] ItemRecordDecodingError),
 }
 
 /// ID of a directory, unique inside a given archive
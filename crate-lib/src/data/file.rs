@@ -5,12 +5,13 @@ use std::{
 
 use thiserror::Error;
 
-use crate::ensure_only_one_version;
+use crate::{coverage::Segment, encryption::Cipher};
 
 use super::{
     directory::DirectoryIdOrRoot,
-    header::SourceWithHeader,
-    name::{ItemName, NameDecodingError},
+    header::{ArchiveVersion, FeatureFlags, SourceWithHeader},
+    name::{EntryName, ItemName, NameDecodingError},
+    record::{FIELD_CHUNKS, ItemExtensions, ItemRecordDecodingError, read_record},
     timestamp::Timestamp,
 };
 
@@ -29,6 +30,12 @@ pub struct File {
     /// Name of the file (must be a valid UTF-8 string)
     pub name: ItemName,
 
+    /// Location of the item's record, if its name or extensions don't fit in its entry
+    pub(crate) record: Option<Segment>,
+
+    /// Informations which don't fit in the entry, stored in the item's record
+    pub(crate) extensions: ItemExtensions,
+
     /// Last modification time
     pub modif_time: Timestamp,
 
@@ -36,6 +43,8 @@ pub struct File {
     pub content_addr: u64,
 
     /// Length, in bytes, of the file's content
+    ///
+    /// For compressed files, this is the length of the compressed content (see [`crate::Archive::get_compression`])
     pub content_len: u64,
 
     /// SHA-3 checksum of the file's content
@@ -46,8 +55,6 @@ impl File {
     pub(crate) fn consume_from_reader(
         input: &mut SourceWithHeader<impl Read + Seek>,
     ) -> Result<Option<Self>, FileDecodingError> {
-        ensure_only_one_version!(input.header.version);
-
         let id = input
             .source
             .read_value::<u64>()
@@ -68,13 +75,28 @@ impl File {
             .read_value()
             .map_err(FileDecodingError::InvalidEntry)?;
 
-        let name = ItemName::consume_from_reader(input.source)
+        let records = input
+            .header
+            .features
+            .requires(FeatureFlags::REQUIRED_ITEM_RECORDS);
+
+        let name = ItemName::consume_from_reader(input.source, records, input.name_cipher)
             .map_err(FileDecodingError::InvalidEntry)?
             .map_err(FileDecodingError::InvalidName)?;
 
-        let modif_time = input
-            .source
-            .read_value()
+        let (name, record, extensions) = match name {
+            EntryName::Inline(name) => (name, None, ItemExtensions::default()),
+
+            EntryName::Record(record) => {
+                let (name, extensions) = read_record(input.source, record, input.name_cipher)
+                    .map_err(FileDecodingError::InvalidEntry)?
+                    .map_err(FileDecodingError::InvalidRecord)?;
+
+                (name, Some(record), extensions)
+            }
+        };
+
+        let modif_time = Timestamp::decode(input.source, input.header.version)
             .map_err(FileDecodingError::InvalidEntry)?;
 
         let content_addr = input
@@ -92,10 +114,27 @@ impl File {
             .read_value()
             .map_err(FileDecodingError::InvalidEntry)?;
 
+        // Chunks' checksums must cover the entire content
+        let chunks_mismatch = extensions
+            .chunks
+            .as_ref()
+            .is_some_and(|chunks| !chunks.matches_len(content_len));
+
+        if let Some(record) = record.filter(|_| chunks_mismatch) {
+            return Err(FileDecodingError::InvalidRecord(
+                ItemRecordDecodingError::InvalidField {
+                    addr: record.start,
+                    field_type: FIELD_CHUNKS,
+                },
+            ));
+        }
+
         Ok(Some(Self {
             id: FileId(id),
             parent_dir,
             name,
+            record,
+            extensions,
             modif_time,
             content_addr,
             content_len,
@@ -103,11 +142,14 @@ impl File {
         }))
     }
 
-    pub(crate) fn encode(&self) -> Vec<u8> {
+    /// Encode the entry for the provided archive version, encrypting its name with the provided cipher if any
+    pub(crate) fn encode(&self, version: ArchiveVersion, name_cipher: Option<&Cipher>) -> Vec<u8> {
         let Self {
             id,
             parent_dir,
             name,
+            record,
+            extensions: _,
             modif_time,
             content_addr,
             content_len,
@@ -124,8 +166,8 @@ impl File {
             }
             .to_le_bytes(),
         );
-        bytes.extend(name.encode());
-        bytes.extend(modif_time.encode());
+        bytes.extend(name.encode_in_entry(name_cipher, *record));
+        bytes.extend(modif_time.encode(version));
         bytes.extend(content_addr.to_le_bytes());
         bytes.extend(content_len.to_le_bytes());
         bytes.extend(sha3_checksum);
@@ -150,6 +192,10 @@ pub enum FileDecodingError {
     /// The file's name is invalid
     #[error("Invalid file name: {0}")]
     InvalidName(#[from] NameDecodingError),
+
+    /// The file's record is invalid
+    #[error("Invalid file record: {0}")]
+    InvalidRecord(#[from] ItemRecordDecodingError),
 }
 
 /// ID of a file, unique inside a given archive
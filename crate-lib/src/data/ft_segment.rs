@@ -2,16 +2,16 @@ use std::io::{Read, Seek};
 
 use thiserror::Error;
 
-use crate::ensure_only_one_version;
+use crate::{coverage::Segment, encryption::Cipher};
 
 use super::{
     directory::{DIRECTORY_ENTRY_SIZE, Directory, DirectoryDecodingError},
     file::{FILE_ENTRY_SIZE, File, FileDecodingError},
-    header::SourceWithHeader,
+    header::{ArchiveVersion, SourceWithHeader},
 };
 
 /// Representation of a file table segment
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FileTableSegment {
     /// Address of the next segment inside the archive
     pub next_segment_addr: Option<u64>,
@@ -28,9 +28,6 @@ impl FileTableSegment {
     pub fn decode(
         input: &mut SourceWithHeader<impl Read + Seek>,
     ) -> Result<Self, FileTableSegmentDecodingError> {
-        // Only there to ensure at compile time there is only one possible version
-        ensure_only_one_version!(input.header.version);
-
         let next_segment_addr = input
             .source
             .read_value::<u64>()
@@ -88,8 +85,8 @@ impl FileTableSegment {
         })
     }
 
-    /// Encode a raw file segment
-    pub fn encode(&self) -> Vec<u8> {
+    /// Encode a raw file segment for the provided archive version, encrypting items' names with the provided cipher if any
+    pub fn encode(&self, version: ArchiveVersion, name_cipher: Option<&Cipher>) -> Vec<u8> {
         let Self {
             next_segment_addr,
             dirs,
@@ -104,14 +101,14 @@ impl FileTableSegment {
 
         for dir in dirs {
             bytes.extend(match dir {
-                Some(dir) => dir.encode(),
+                Some(dir) => dir.encode(version, name_cipher),
                 None => vec![0; DIRECTORY_ENTRY_SIZE],
             });
         }
 
         for file in files {
             bytes.extend(match file {
-                Some(file) => file.encode(),
+                Some(file) => file.encode(version, name_cipher),
                 None => vec![0; FILE_ENTRY_SIZE],
             });
         }
@@ -146,23 +143,55 @@ impl FileTableSegment {
         &self.files
     }
 
-    pub fn consume_next_segment(
-        &self,
+    /// Get the location of the records of the segment's items
+    pub fn records(&self) -> impl Iterator<Item = Segment> + '_ {
+        let dirs = self.dirs.iter().flatten().filter_map(|dir| dir.record);
+        let files = self.files.iter().flatten().filter_map(|file| file.record);
+
+        dirs.chain(files)
+    }
+
+    /// Decode the number of directory and file entries of the segment located at the provided address
+    ///
+    /// The source is then positioned at the segment's beginning, ready for decoding it.
+    pub fn decode_entries_count(
         input: &mut SourceWithHeader<impl Read + Seek>,
-    ) -> Option<Result<(u64, Self), FileTableSegmentDecodingError>> {
-        self.next_segment_addr.map(|addr| {
-            input
-                .source
-                .set_position(addr)
-                .map_err(FileTableSegmentDecodingError::IoError)?;
-
-            Self::decode(input).map(|segment| (addr, segment))
-        })
+        addr: u64,
+    ) -> Result<(u32, u32), FileTableSegmentDecodingError> {
+        input
+            .source
+            .set_position(addr.saturating_add(8))
+            .map_err(FileTableSegmentDecodingError::IoError)?;
+
+        let dirs_count = input
+            .source
+            .read_value::<u32>()
+            .map_err(FileTableSegmentDecodingError::InvalidHeader)?;
+
+        let files_count = input
+            .source
+            .read_value::<u32>()
+            .map_err(FileTableSegmentDecodingError::InvalidHeader)?;
+
+        input
+            .source
+            .set_position(addr)
+            .map_err(FileTableSegmentDecodingError::IoError)?;
+
+        Ok((dirs_count, files_count))
     }
 
     pub fn encoded_len(&self) -> u64 {
-        16 + u64::try_from(self.dirs.len()).unwrap() * u64::try_from(DIRECTORY_ENTRY_SIZE).unwrap()
-            + u64::try_from(self.files.len()).unwrap() * (FILE_ENTRY_SIZE as u64)
+        Self::encoded_len_for(
+            u32::try_from(self.dirs.len()).unwrap(),
+            u32::try_from(self.files.len()).unwrap(),
+        )
+    }
+
+    /// Compute the encoded length of a segment with the provided number of entries
+    pub fn encoded_len_for(dirs_count: u32, files_count: u32) -> u64 {
+        16 + u64::from(dirs_count) * (DIRECTORY_ENTRY_SIZE as u64)
+            + u64::from(files_count) * (FILE_ENTRY_SIZE as u64)
     }
 }
 
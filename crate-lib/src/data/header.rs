@@ -2,10 +2,17 @@ use std::io::{Read, Seek};
 
 use thiserror::Error;
 
-use crate::{ensure_only_one_version, source::Source};
+use crate::{
+    encryption::{Cipher, ENCRYPTION_PARAMS_SIZE, EncryptionParams},
+    source::Source,
+};
 
 pub static MAGIC_NUMBER: &[u8] = b"BASICARC";
 pub static HEADER_SIZE: usize = 256;
+pub static HEADER_JOURNAL_ADDR_OFFSET: u64 = 12;
+pub static HEADER_ENCRYPTION_PARAMS_OFFSET: u64 = 20;
+pub static HEADER_VERSION_OFFSET: u64 = 8;
+pub static HEADER_FEATURES_OFFSET: u64 = 82;
 
 /// Representation of an archive's header
 ///
@@ -15,6 +22,15 @@ pub static HEADER_SIZE: usize = 256;
 pub struct Header {
     /// Version of the header
     pub version: ArchiveVersion,
+
+    /// Address of the write-ahead journal, if any
+    pub journal_addr: Option<u64>,
+
+    /// Encryption parameters, if the archive is encrypted
+    pub encryption: Option<EncryptionParams>,
+
+    /// Features the archive relies on (always empty before version 2)
+    pub features: FeatureFlags,
 }
 
 impl Header {
@@ -46,7 +62,41 @@ impl Header {
         let version = source.read_value::<u32>()?;
         let version = ArchiveVersion::decode(version)?;
 
-        ensure_only_one_version!(version);
+        let journal_addr = match source.read_value::<u64>()? {
+            0 => None,
+            addr => Some(addr),
+        };
+
+        debug_assert_eq!(source.position()?, HEADER_ENCRYPTION_PARAMS_OFFSET);
+
+        let encryption =
+            EncryptionParams::decode(source.read_into_array::<ENCRYPTION_PARAMS_SIZE>()?)?;
+
+        debug_assert_eq!(source.position()?, HEADER_FEATURES_OFFSET);
+
+        let features = match version {
+            // Feature flags are part of the padding
+            ArchiveVersion::One => FeatureFlags::default(),
+
+            ArchiveVersion::Two => {
+                let features = FeatureFlags {
+                    required: source.read_value::<u32>()?,
+                    optional: source.read_value::<u32>()?,
+                };
+
+                let unsupported = features.required & !FeatureFlags::KNOWN_REQUIRED;
+
+                if unsupported != 0 {
+                    return Err(HeaderDecodingError::UnsupportedFeatures { unsupported });
+                }
+
+                if features.requires(FeatureFlags::REQUIRED_ENCRYPTION) != encryption.is_some() {
+                    return Err(HeaderDecodingError::InvalidEncryptionParams);
+                }
+
+                features
+            }
+        };
 
         let padding_len = (HEADER_SIZE as u64) - source.position()?;
         let padding_len = usize::try_from(padding_len).unwrap();
@@ -60,16 +110,33 @@ impl Header {
 
         debug_assert_eq!(source.position()?, HEADER_SIZE as u64);
 
-        let header = Self { version };
+        let header = Self {
+            version,
+            journal_addr,
+            encryption,
+            features,
+        };
 
-        Ok(SourceWithHeader { source, header })
+        Ok(SourceWithHeader {
+            source,
+            header,
+            name_cipher: None,
+        })
     }
 
     pub fn encode(&self) -> Vec<u8> {
+        debug_assert!(
+            self.version.has_feature_flags() || self.features == FeatureFlags::default(),
+            "features can't be declared before version 2"
+        );
+
         let mut bytes = vec![];
 
         bytes.extend(MAGIC_NUMBER);
         bytes.extend(self.version.encode());
+        bytes.extend(self.journal_addr.unwrap_or(0).to_le_bytes());
+        bytes.extend(EncryptionParams::encode(self.encryption.as_ref()));
+        bytes.extend(self.features.encode());
         bytes.extend(vec![0; 256 - bytes.len()]);
 
         debug_assert_eq!(bytes.len(), HEADER_SIZE);
@@ -81,33 +148,132 @@ impl Header {
 impl Default for Header {
     fn default() -> Self {
         Self {
-            version: ArchiveVersion::One,
+            version: ArchiveVersion::LATEST,
+            journal_addr: None,
+            encryption: None,
+            features: FeatureFlags::default(),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Version of an archive's format
+///
+/// See [`crate::Archive::upgrade_to`] to migrate an existing archive to a newer version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ArchiveVersion {
+    /// Very first version
     One,
+
+    /// Adds feature flags to the header and item records, and stores modification times with nanosecond precision
+    Two,
 }
 
 impl ArchiveVersion {
+    /// Version new archives are created with by default
+    pub const LATEST: Self = Self::Two;
+
+    /// Decode a raw version number
     pub fn decode(input: u32) -> Result<ArchiveVersion, HeaderDecodingError> {
         match input {
             1 => Ok(Self::One),
+            2 => Ok(Self::Two),
             _ => Err(HeaderDecodingError::UnknownVersion { input }),
         }
     }
 
+    /// Get the raw version number
     pub fn version_number(&self) -> u32 {
         match self {
             ArchiveVersion::One => 1,
+            ArchiveVersion::Two => 2,
         }
     }
 
+    /// Encode the raw version number
     pub fn encode(&self) -> [u8; 4] {
         self.version_number().to_le_bytes()
     }
+
+    /// Check if the header declares the features the archive relies on
+    pub fn has_feature_flags(&self) -> bool {
+        match self {
+            ArchiveVersion::One => false,
+            ArchiveVersion::Two => true,
+        }
+    }
+}
+
+/// Features an archive relies on, declared in its header since [`ArchiveVersion::Two`]
+///
+/// Archives requiring a feature a reader doesn't know about are refused,
+/// while unknown optional features are ignored (and preserved).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeatureFlags {
+    /// Features readers must support to read the archive
+    pub required: u32,
+
+    /// Features readers may ignore
+    pub optional: u32,
+}
+
+impl FeatureFlags {
+    /// Files' content is encrypted (see [`crate::ArchiveConfig::encryption_key`])
+    pub const REQUIRED_ENCRYPTION: u32 = 1 << 0;
+
+    /// Some files' content is compressed (see [`crate::ArchiveConfig::compression`])
+    pub const REQUIRED_COMPRESSION: u32 = 1 << 1;
+
+    /// Some items have a record, which holds what doesn't fit in their entry
+    ///
+    /// This includes names too long to fit in the entry (see [`crate::MAX_INLINE_NAME_LEN`] and
+    /// [`crate::MAX_ENCRYPTED_NAME_LEN`]), the compression of files' content,
+    /// the checksums of its chunks, Unix attributes,
+    /// symbolic links' target and hard links' group.
+    /// Entries reference their record instead of holding their name, and the record holds the name instead.
+    pub const REQUIRED_ITEM_RECORDS: u32 = 1 << 2;
+
+    /// Checksums of the chunks of some files' content are available in their record (see [`crate::ArchiveConfig::chunk_size`])
+    pub const OPTIONAL_CHUNK_CHECKSUMS: u32 = 1 << 0;
+
+    /// Required features supported by this library
+    pub const KNOWN_REQUIRED: u32 =
+        Self::REQUIRED_ENCRYPTION | Self::REQUIRED_COMPRESSION | Self::REQUIRED_ITEM_RECORDS;
+
+    /// Optional features supported by this library
+    pub const KNOWN_OPTIONAL: u32 = Self::OPTIONAL_CHUNK_CHECKSUMS;
+
+    /// Check if a required feature is declared
+    pub fn requires(&self, flag: u32) -> bool {
+        self.required & flag != 0
+    }
+
+    /// Check if an optional feature is declared
+    pub fn uses(&self, flag: u32) -> bool {
+        self.optional & flag != 0
+    }
+
+    pub(crate) fn set_required(&mut self, flag: u32, enabled: bool) {
+        if enabled {
+            self.required |= flag;
+        } else {
+            self.required &= !flag;
+        }
+    }
+
+    pub(crate) fn set_optional(&mut self, flag: u32, enabled: bool) {
+        if enabled {
+            self.optional |= flag;
+        } else {
+            self.optional &= !flag;
+        }
+    }
+
+    pub(crate) fn encode(&self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[0..4].copy_from_slice(&self.required.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.optional.to_le_bytes());
+        bytes
+    }
 }
 
 /// A mutable reference to a readable source along with the read archive's header
@@ -120,6 +286,9 @@ pub struct SourceWithHeader<'s, S: Read> {
 
     /// Decoded and validated header
     pub header: Header,
+
+    /// Cipher to decrypt items' names with, if they are encrypted
+    pub(crate) name_cipher: Option<&'s Cipher>,
 }
 
 /// Error while decoding an archive header
@@ -142,10 +311,21 @@ pub enum HeaderDecodingError {
     #[error("Header padding is not filled with zeroes")]
     NonZeroPadding,
 
+    /// The encryption parameters are invalid
+    #[error("Invalid encryption parameters")]
+    InvalidEncryptionParams,
+
     /// The archive version is unknown/unsupported
     #[error("Unknown archive version: {input}")]
     UnknownVersion {
         /// Raw version value that was read
         input: u32,
     },
+
+    /// The archive requires features which are not supported
+    #[error("Archive requires unsupported features: {unsupported:#x}")]
+    UnsupportedFeatures {
+        /// Flags of the unsupported required features
+        unsupported: u32,
+    },
 }
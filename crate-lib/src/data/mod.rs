@@ -4,4 +4,5 @@ pub mod ft_segment;
 pub mod header;
 pub mod name;
 pub mod path;
+pub mod record;
 pub mod timestamp;
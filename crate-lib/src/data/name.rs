@@ -1,6 +1,23 @@
 use std::{borrow::Borrow, fmt::Display, io::Read, ops::Deref};
 
-use crate::source::Source;
+use crate::{
+    coverage::Segment,
+    encryption::{Cipher, ENCRYPTION_OVERHEAD},
+    source::Source,
+};
+
+/// Maximum length of an item's name, in bytes
+pub const MAX_NAME_LEN: usize = 4096;
+
+/// Maximum length of a name, in bytes, that can be stored inside its entry
+///
+/// Longer names are stored in the item's record (see [`crate::FeatureFlags::REQUIRED_ITEM_RECORDS`]).
+pub const MAX_INLINE_NAME_LEN: usize = 255;
+
+/// Maximum length of an encrypted name, in bytes, that can be stored inside its entry
+///
+/// Longer names are stored in the item's record (see [`crate::FeatureFlags::REQUIRED_ITEM_RECORDS`]).
+pub const MAX_ENCRYPTED_NAME_LEN: usize = MAX_INLINE_NAME_LEN - ENCRYPTION_OVERHEAD as usize;
 
 /// Representation of an item's (file or directory) name
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -18,7 +35,7 @@ impl ItemName {
             return Err(NameValidationError::NameIsEmpty);
         }
 
-        if name.len() > 255 {
+        if name.len() > MAX_NAME_LEN {
             return Err(NameValidationError::NameIsTooLong);
         }
 
@@ -37,10 +54,53 @@ impl ItemName {
         Ok(())
     }
 
+    /// Read a name, decrypting it with the provided cipher if any
+    ///
+    /// If item records are enabled, entries may reference their item's record instead of holding a name.
     pub(crate) fn consume_from_reader(
         source: &mut Source<impl Read>,
-    ) -> std::io::Result<Result<Self, NameDecodingError>> {
-        source.read_into_array::<256>().map(Self::decode)
+        records: bool,
+        cipher: Option<&Cipher>,
+    ) -> std::io::Result<Result<EntryName, NameDecodingError>> {
+        let bytes = source.read_into_array::<256>()?;
+
+        if bytes[0] != 0 || !records {
+            return Ok(match cipher {
+                Some(cipher) => Self::decode_encrypted(bytes, cipher),
+                None => Self::decode(bytes),
+            }
+            .map(EntryName::Inline));
+        }
+
+        Ok(Ok(EntryName::Record(Segment {
+            start: u64::from_le_bytes(bytes[1..9].try_into().unwrap()),
+            len: u64::from_le_bytes(bytes[9..17].try_into().unwrap()),
+        })))
+    }
+
+    /// Decode a name stored in an item's record (see [`ItemName::encode_stored`])
+    pub(crate) fn decode_stored(
+        bytes: &[u8],
+        cipher: Option<&Cipher>,
+    ) -> Result<Self, NameDecodingError> {
+        let decrypted = match cipher {
+            Some(cipher) => cipher.open(bytes).ok_or_else(|| NameDecodingError {
+                bytes: bytes.to_vec(),
+                cause: NameDecodingErrorReason::DecryptionFailed,
+            })?,
+
+            None => bytes.to_vec(),
+        };
+
+        let name = String::from_utf8(decrypted).map_err(|_| NameDecodingError {
+            bytes: bytes.to_vec(),
+            cause: NameDecodingErrorReason::InvalidUtf8,
+        })?;
+
+        Self::new(name).map_err(|err| NameDecodingError {
+            bytes: bytes.to_vec(),
+            cause: NameDecodingErrorReason::NameValidationFailed(err),
+        })
     }
 
     /// Decode an item name from a list of bytes
@@ -67,7 +127,77 @@ impl ItemName {
         })
     }
 
+    /// Decode an item name encrypted with [`ItemName::encode_with`]
+    fn decode_encrypted(bytes: [u8; 256], cipher: &Cipher) -> Result<Self, NameDecodingError> {
+        let len = usize::from(bytes[0]);
+
+        Self::decode_stored(&bytes[1..=len], Some(cipher)).map_err(|err| NameDecodingError {
+            bytes: bytes.to_vec(),
+            ..err
+        })
+    }
+
+    /// Check if the name can be stored inside its entry, once encrypted with the provided cipher if any
+    pub(crate) fn fits_in_entry(&self, cipher: Option<&Cipher>) -> bool {
+        self.0.len()
+            <= match cipher {
+                Some(_) => MAX_ENCRYPTED_NAME_LEN,
+                None => MAX_INLINE_NAME_LEN,
+            }
+    }
+
+    /// Encode the name for its entry, encrypting it with the provided cipher if any
+    ///
+    /// If the item has a record, the entry only references it.
+    pub(crate) fn encode_in_entry(
+        &self,
+        cipher: Option<&Cipher>,
+        record: Option<Segment>,
+    ) -> [u8; 256] {
+        let Some(record) = record else {
+            return self.encode_with(cipher);
+        };
+
+        // A zero length indicates the name is stored in the item's record
+        let mut bytes = [0; 256];
+
+        bytes[1..9].copy_from_slice(&record.start.to_le_bytes());
+        bytes[9..17].copy_from_slice(&record.len.to_le_bytes());
+
+        bytes
+    }
+
+    /// Encode the name to be stored in the item's record, encrypting it with the provided cipher if any
+    pub(crate) fn encode_stored(&self, cipher: Option<&Cipher>) -> Vec<u8> {
+        match cipher {
+            Some(cipher) => cipher.seal(self.0.as_bytes()),
+            None => self.0.as_bytes().to_vec(),
+        }
+    }
+
+    /// Encode the name, encrypting it with the provided cipher if any
+    ///
+    /// The name must fit in its entry (see [`ItemName::fits_in_entry`]).
+    fn encode_with(&self, cipher: Option<&Cipher>) -> [u8; 256] {
+        let Some(cipher) = cipher else {
+            return self.encode();
+        };
+
+        let sealed = cipher.seal(self.0.as_bytes());
+
+        let mut bytes = [0; 256];
+
+        bytes[0] = u8::try_from(sealed.len()).unwrap();
+        bytes[1..=sealed.len()].copy_from_slice(&sealed);
+
+        bytes
+    }
+
     /// Encode the name as a list of bytes
+    ///
+    /// # Panics
+    ///
+    /// If the name is longer than [`MAX_INLINE_NAME_LEN`] bytes
     pub fn encode(&self) -> [u8; 256] {
         let Self(name) = &self;
 
@@ -108,6 +238,15 @@ impl Borrow<str> for ItemName {
     }
 }
 
+/// Name field of an item's entry
+pub(crate) enum EntryName {
+    /// The name itself
+    Inline(ItemName),
+
+    /// Location of the item's record, which holds the name
+    Record(Segment),
+}
+
 impl Display for ItemName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -132,6 +271,9 @@ pub enum NameDecodingErrorReason {
 
     /// Name is invalid
     NameValidationFailed(NameValidationError),
+
+    /// The name is encrypted, and couldn't be decrypted
+    DecryptionFailed,
 }
 
 /// Cause of a name validation error
@@ -140,7 +282,7 @@ pub enum NameValidationError {
     /// The name is empty
     NameIsEmpty,
 
-    /// The name is too long (= longer than 255 bytes)
+    /// The name is too long (= longer than [`MAX_NAME_LEN`] bytes)
     NameIsTooLong,
 
     /// A forbidden character was found in the name
@@ -166,6 +308,7 @@ impl Display for NameDecodingErrorReason {
         match self {
             Self::InvalidUtf8 => write!(f, "Provided name is not a valid UTF-8 string"),
             Self::NameValidationFailed(err) => write!(f, "Name validation failed: {err}"),
+            Self::DecryptionFailed => write!(f, "Encrypted name couldn't be decrypted"),
         }
     }
 }
@@ -174,7 +317,7 @@ impl Display for NameValidationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NameIsEmpty => write!(f, "name is empty"),
-            Self::NameIsTooLong => write!(f, "name contains more than 255 bytes"),
+            Self::NameIsTooLong => write!(f, "name contains more than {MAX_NAME_LEN} bytes"),
             Self::ForbiddenChar(c) => write!(f, "name contains invalid character {c:?}"),
             Self::ForbiddenName(name) => write!(f, "name is reserved: '{name}'"),
         }
@@ -0,0 +1,396 @@
+use std::{
+    io::{Read, Seek},
+    num::NonZero,
+};
+
+use thiserror::Error;
+
+use crate::{
+    attributes::UnixAttributes,
+    chunks::ChunkChecksums,
+    compression::{CompressionCodec, FileCompression},
+    coverage::Segment,
+    encryption::Cipher,
+    source::Source,
+    symlinks::is_valid_symlink_target,
+};
+
+use super::{
+    header::FeatureFlags,
+    name::{ItemName, NameDecodingError},
+};
+
+/// Field holding how a file's content is compressed
+const FIELD_COMPRESSION: u8 = 1;
+
+/// Field holding the checksums of the chunks of a file's content
+pub(crate) const FIELD_CHUNKS: u8 = 2;
+
+/// Field holding the Unix attributes of an item
+const FIELD_UNIX_ATTRIBUTES: u8 = 3;
+
+/// Field holding the target of a symbolic link
+const FIELD_SYMLINK_TARGET: u8 = 4;
+
+/// Field holding the group of files a hard link shares its content with
+const FIELD_HARD_LINK_GROUP: u8 = 5;
+
+/// Informations about an item which don't fit in its entry
+///
+/// They are stored in the item's record alongside its name (see [`FeatureFlags::REQUIRED_ITEM_RECORDS`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ItemExtensions {
+    /// How the file's content is compressed, if it is
+    pub compression: Option<FileCompression>,
+
+    /// Checksums of the chunks of the file's content, if they were computed
+    pub chunks: Option<ChunkChecksums>,
+
+    /// Unix attributes of the item, if any were stored
+    pub unix_attributes: Option<UnixAttributes>,
+
+    /// Target of the file, if it is a symbolic link
+    pub symlink_target: Option<String>,
+
+    /// Group of files sharing their content with this one as hard links, if any
+    pub hard_link_group: Option<NonZero<u64>>,
+}
+
+impl ItemExtensions {
+    /// Check if there is no extension at all
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            compression,
+            chunks,
+            unix_attributes,
+            symlink_target,
+            hard_link_group,
+        } = self;
+
+        compression.is_none()
+            && chunks.is_none()
+            && unix_attributes.is_none()
+            && symlink_target.is_none()
+            && hard_link_group.is_none()
+    }
+
+    /// Keep only the extensions which apply to directories
+    pub fn for_directory(self) -> Self {
+        Self {
+            unix_attributes: self.unix_attributes,
+            ..Self::default()
+        }
+    }
+
+    /// Declare the features the extensions rely on
+    pub fn declare_features(&self, features: &mut FeatureFlags) {
+        if self.compression.is_some() {
+            features.set_required(FeatureFlags::REQUIRED_COMPRESSION, true);
+        }
+
+        if self.chunks.is_some() {
+            features.set_optional(FeatureFlags::OPTIONAL_CHUNK_CHECKSUMS, true);
+        }
+    }
+}
+
+/// Encode an item's record, encrypting its name with the provided cipher if any
+///
+/// The record is made of the name's length and the name itself, followed by one field for each extension.
+/// Each field is made of its type, the length of its payload and the payload itself.
+pub(crate) fn encode_record(
+    name: &ItemName,
+    name_cipher: Option<&Cipher>,
+    extensions: &ItemExtensions,
+) -> Vec<u8> {
+    let ItemExtensions {
+        compression,
+        chunks,
+        unix_attributes,
+        symlink_target,
+        hard_link_group,
+    } = extensions;
+
+    let mut bytes = vec![];
+
+    let name = name.encode_stored(name_cipher);
+    bytes.extend(u16::try_from(name.len()).unwrap().to_le_bytes());
+    bytes.extend(name);
+
+    let mut push_field = |field_type: u8, payload: &[u8]| {
+        bytes.push(field_type);
+        bytes.extend(u64::try_from(payload.len()).unwrap().to_le_bytes());
+        bytes.extend(payload);
+    };
+
+    if let Some(compression) = compression {
+        let mut payload = vec![compression.codec.encode()];
+        payload.extend(compression.uncompressed_len.to_le_bytes());
+
+        push_field(FIELD_COMPRESSION, &payload);
+    }
+
+    if let Some(chunks) = chunks {
+        let mut payload = chunks.chunk_size.to_le_bytes().to_vec();
+
+        for checksum in &chunks.checksums {
+            payload.extend(checksum);
+        }
+
+        push_field(FIELD_CHUNKS, &payload);
+    }
+
+    if let Some(attributes) = unix_attributes {
+        let mut payload = vec![];
+        payload.extend(attributes.mode.to_le_bytes());
+        payload.extend(attributes.uid.to_le_bytes());
+        payload.extend(attributes.gid.to_le_bytes());
+
+        // Missing names are encoded as empty ones
+        for name in [&attributes.owner, &attributes.group] {
+            let name = name.as_deref().unwrap_or("");
+
+            payload.push(u8::try_from(name.len()).unwrap());
+            payload.extend(name.as_bytes());
+        }
+
+        push_field(FIELD_UNIX_ATTRIBUTES, &payload);
+    }
+
+    if let Some(target) = symlink_target {
+        push_field(FIELD_SYMLINK_TARGET, target.as_bytes());
+    }
+
+    if let Some(group) = hard_link_group {
+        push_field(FIELD_HARD_LINK_GROUP, &group.get().to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Read the record located at the provided location, decrypting its name with the provided cipher if any
+///
+/// The source's position is restored afterwards.
+pub(crate) fn read_record(
+    source: &mut Source<impl Read + Seek>,
+    record: Segment,
+    name_cipher: Option<&Cipher>,
+) -> std::io::Result<Result<(ItemName, ItemExtensions), ItemRecordDecodingError>> {
+    let addr = record.start;
+
+    let source_len = source.seek_len()?;
+
+    // Don't allocate anything for corrupted locations
+    let in_bounds = record
+        .start
+        .checked_add(record.len)
+        .is_some_and(|end| end <= source_len);
+
+    if !in_bounds {
+        return Ok(Err(ItemRecordDecodingError::OutOfBounds { addr }));
+    }
+
+    let entry_pos = source.position()?;
+
+    let mut bytes = vec![0; usize::try_from(record.len).unwrap()];
+
+    source.set_position(record.start)?;
+    source.read_exact(&mut bytes)?;
+    source.set_position(entry_pos)?;
+
+    Ok(decode_record(&bytes, addr, name_cipher))
+}
+
+/// Decode an item's record (see [`encode_record`])
+///
+/// Fields of an unknown type are ignored.
+fn decode_record(
+    bytes: &[u8],
+    addr: u64,
+    name_cipher: Option<&Cipher>,
+) -> Result<(ItemName, ItemExtensions), ItemRecordDecodingError> {
+    let mut rest = bytes;
+
+    let name_len = u16::from_le_bytes(take(&mut rest, 2, addr)?.try_into().unwrap());
+
+    let name = ItemName::decode_stored(take(&mut rest, u64::from(name_len), addr)?, name_cipher)
+        .map_err(|err| ItemRecordDecodingError::InvalidName { addr, err })?;
+
+    let mut extensions = ItemExtensions::default();
+
+    while !rest.is_empty() {
+        let field_type = take(&mut rest, 1, addr)?[0];
+        let payload_len = u64::from_le_bytes(take(&mut rest, 8, addr)?.try_into().unwrap());
+        let payload = take(&mut rest, payload_len, addr)?;
+
+        let invalid_field = || ItemRecordDecodingError::InvalidField { addr, field_type };
+
+        match field_type {
+            FIELD_COMPRESSION => {
+                if payload.len() != 9 {
+                    return Err(invalid_field());
+                }
+
+                let compression = FileCompression {
+                    codec: CompressionCodec::decode(payload[0]).ok_or_else(invalid_field)?,
+                    uncompressed_len: u64::from_le_bytes(payload[1..9].try_into().unwrap()),
+                };
+
+                if extensions.compression.replace(compression).is_some() {
+                    return Err(invalid_field());
+                }
+            }
+
+            FIELD_CHUNKS => {
+                if payload.len() < 8 || (payload.len() - 8) % 32 != 0 {
+                    return Err(invalid_field());
+                }
+
+                let chunk_size = u64::from_le_bytes(payload[0..8].try_into().unwrap());
+
+                if chunk_size == 0 {
+                    return Err(invalid_field());
+                }
+
+                let chunks = ChunkChecksums {
+                    chunk_size,
+                    checksums: payload[8..]
+                        .chunks_exact(32)
+                        .map(|checksum| checksum.try_into().unwrap())
+                        .collect(),
+                };
+
+                if extensions.chunks.replace(chunks).is_some() {
+                    return Err(invalid_field());
+                }
+            }
+
+            FIELD_UNIX_ATTRIBUTES => {
+                let mut payload = payload;
+
+                let take_u32 = |payload: &mut &[u8]| {
+                    take(payload, 4, addr)
+                        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                        .map_err(|_| invalid_field())
+                };
+
+                let mode = take_u32(&mut payload)?;
+                let uid = take_u32(&mut payload)?;
+                let gid = take_u32(&mut payload)?;
+
+                let mut names = [None, None];
+
+                for name in &mut names {
+                    let name_len = take(&mut payload, 1, addr).map_err(|_| invalid_field())?[0];
+                    let bytes = take(&mut payload, u64::from(name_len), addr)
+                        .map_err(|_| invalid_field())?;
+
+                    if name_len > 0 {
+                        *name =
+                            Some(String::from_utf8(bytes.to_vec()).map_err(|_| invalid_field())?);
+                    }
+                }
+
+                if !payload.is_empty() {
+                    return Err(invalid_field());
+                }
+
+                let [owner, group] = names;
+
+                let attributes = UnixAttributes {
+                    mode,
+                    uid,
+                    gid,
+                    owner,
+                    group,
+                };
+
+                if extensions.unix_attributes.replace(attributes).is_some() {
+                    return Err(invalid_field());
+                }
+            }
+
+            FIELD_SYMLINK_TARGET => {
+                let target = std::str::from_utf8(payload)
+                    .ok()
+                    .filter(|target| is_valid_symlink_target(target))
+                    .ok_or_else(invalid_field)?;
+
+                if extensions
+                    .symlink_target
+                    .replace(target.to_owned())
+                    .is_some()
+                {
+                    return Err(invalid_field());
+                }
+            }
+
+            FIELD_HARD_LINK_GROUP => {
+                let group = <[u8; 8]>::try_from(payload)
+                    .ok()
+                    .and_then(|bytes| NonZero::new(u64::from_le_bytes(bytes)))
+                    .ok_or_else(invalid_field)?;
+
+                if extensions.hard_link_group.replace(group).is_some() {
+                    return Err(invalid_field());
+                }
+            }
+
+            // Fields written by newer versions of the library
+            _ => continue,
+        }
+    }
+
+    Ok((name, extensions))
+}
+
+/// Take the provided number of bytes from the beginning of a record's remaining bytes
+fn take<'a>(rest: &mut &'a [u8], len: u64, addr: u64) -> Result<&'a [u8], ItemRecordDecodingError> {
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= rest.len())
+        .ok_or(ItemRecordDecodingError::Truncated { addr })?;
+
+    let (taken, remaining) = rest.split_at(len);
+    *rest = remaining;
+
+    Ok(taken)
+}
+
+/// Error while decoding an item's record
+#[derive(Error, Debug)]
+pub enum ItemRecordDecodingError {
+    /// The record is located (even partially) outside of the archive
+    #[error("Item record at address {addr} is out of the archive's bounds")]
+    OutOfBounds {
+        /// Address of the record
+        addr: u64,
+    },
+
+    /// The record ends in the middle of its name or of a field
+    #[error("Item record at address {addr} is truncated")]
+    Truncated {
+        /// Address of the record
+        addr: u64,
+    },
+
+    /// One of the record's fields is invalid, or present several times
+    #[error("Invalid field of type {field_type} in item record at address {addr}")]
+    InvalidField {
+        /// Address of the record
+        addr: u64,
+
+        /// Type of the invalid field
+        field_type: u8,
+    },
+
+    /// The record's name is invalid
+    #[error("Invalid name in item record at address {addr}: {err}")]
+    InvalidName {
+        /// Address of the record
+        addr: u64,
+
+        /// Cause of the error
+        err: NameDecodingError,
+    },
+}
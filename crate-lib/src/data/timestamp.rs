@@ -5,13 +5,20 @@ use std::{
 
 use thiserror::Error;
 
-use crate::source::{FromSourceBytes, Source};
+use crate::source::Source;
+
+use super::header::ArchiveVersion;
 
 /// Representation of a timestamp
 ///
-/// Stores the number of seconds elapsed since Unix's EPOCH
+/// Stores the time elapsed since Unix's EPOCH, with nanosecond precision
+///
+/// Archives before [`ArchiveVersion::Two`] only store whole seconds.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Timestamp(u64);
+pub struct Timestamp {
+    secs: u64,
+    nanos: u32,
+}
 
 /// Error that can occur when constructing a [`Timestamp`]
 #[derive(Debug, Clone, Error)]
@@ -27,8 +34,59 @@ impl Timestamp {
         Self::try_from(SystemTime::now()).expect("SystemTime::now() is always after the Unix epoch")
     }
 
-    pub(crate) fn encode(&self) -> [u8; 8] {
-        self.0.to_le_bytes()
+    /// Get the number of whole seconds elapsed since Unix's EPOCH
+    pub fn secs(&self) -> u64 {
+        self.secs
+    }
+
+    /// Get the sub-second part of the timestamp, in nanoseconds
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Truncate the timestamp to the precision stored by the provided archive version
+    pub(crate) fn for_version(self, version: ArchiveVersion) -> Self {
+        match version {
+            ArchiveVersion::One => Self {
+                secs: self.secs,
+                nanos: 0,
+            },
+            ArchiveVersion::Two => self,
+        }
+    }
+
+    /// Decode a timestamp encoded for the provided archive version
+    pub(crate) fn decode(
+        source: &mut Source<impl Read>,
+        version: ArchiveVersion,
+    ) -> std::io::Result<Self> {
+        let value = source.read_value::<u64>()?;
+
+        Ok(match version {
+            ArchiveVersion::One => Self {
+                secs: value,
+                nanos: 0,
+            },
+
+            ArchiveVersion::Two => Self {
+                secs: value / 1_000_000_000,
+                nanos: u32::try_from(value % 1_000_000_000).unwrap(),
+            },
+        })
+    }
+
+    /// Encode the timestamp for the provided archive version
+    ///
+    /// Nanosecond timestamps saturate after year 2554.
+    pub(crate) fn encode(&self, version: ArchiveVersion) -> [u8; 8] {
+        match version {
+            ArchiveVersion::One => self.secs,
+            ArchiveVersion::Two => self
+                .secs
+                .saturating_mul(1_000_000_000)
+                .saturating_add(u64::from(self.nanos)),
+        }
+        .to_le_bytes()
     }
 }
 
@@ -36,25 +94,19 @@ impl TryFrom<SystemTime> for Timestamp {
     type Error = TimestampError;
 
     fn try_from(value: SystemTime) -> Result<Self, Self::Error> {
-        let secs = value
+        let elapsed = value
             .duration_since(SystemTime::UNIX_EPOCH)
-            .map_err(|_| TimestampError::BeforeEpoch)?
-            .as_secs();
-        Ok(Self(secs))
+            .map_err(|_| TimestampError::BeforeEpoch)?;
+
+        Ok(Self {
+            secs: elapsed.as_secs(),
+            nanos: elapsed.subsec_nanos(),
+        })
     }
 }
 
 impl From<Timestamp> for SystemTime {
     fn from(value: Timestamp) -> Self {
-        SystemTime::UNIX_EPOCH + Duration::from_secs(value.0)
-    }
-}
-
-impl FromSourceBytes for Timestamp {
-    fn read_from(source: &mut Source<impl Read>) -> std::io::Result<Self>
-    where
-        Self: Sized,
-    {
-        source.read_value::<u64>().map(Self)
+        SystemTime::UNIX_EPOCH + Duration::new(value.secs, value.nanos)
     }
 }
@@ -0,0 +1,616 @@
+use std::{fmt::Debug, io::Read};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    Key, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Generate},
+};
+use sha3::{Digest, Sha3_256};
+
+use crate::{ArchiveMetadataDecodingError, HeaderDecodingError};
+
+/// Size of the encryption parameters in the header
+pub(crate) const ENCRYPTION_PARAMS_SIZE: usize = 62;
+
+/// Number of bytes added to encrypted data: a 24-byte nonce and a 16-byte authentication tag
+pub(crate) const ENCRYPTION_OVERHEAD: u64 = 40;
+
+/// Size of the chunks files' content is split into to be encrypted, before encryption
+pub(crate) const CONTENT_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Size of the random nonce prefix stored at the beginning of encrypted contents
+const NONCE_PREFIX_SIZE: usize = 15;
+
+/// Size of the authentication tag of each encrypted chunk
+const TAG_SIZE: u64 = 16;
+
+/// Maximum memory cost (in KiB) of the password derivation, as stored in an archive's header
+const MAX_MEMORY_COST: u32 = 4 * 1024 * 1024;
+
+/// Maximum number of iterations and parallelism of the password derivation, as stored in an archive's header
+const MAX_TIME_AND_PARALLELISM_COST: u32 = 256;
+
+/// Key to encrypt an archive with
+///
+/// See [`crate::ArchiveConfig::encryption_key`]
+#[derive(Clone)]
+pub enum EncryptionKey {
+    /// Password the actual key is derived from, using Argon2id
+    Password(String),
+
+    /// Raw 256-bit key
+    Raw([u8; 32]),
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key itself
+        match self {
+            Self::Password(_) => f.write_str("EncryptionKey::Password(..)"),
+            Self::Raw(_) => f.write_str("EncryptionKey::Raw(..)"),
+        }
+    }
+}
+
+/// How an archive's key is obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyDerivation {
+    /// The key is provided as-is
+    Raw,
+
+    /// The key is derived from a password with Argon2id
+    Argon2id {
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    },
+}
+
+/// Encryption parameters of an archive, stored in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncryptionParams {
+    pub(crate) key_derivation: KeyDerivation,
+
+    /// Are items' names encrypted as well?
+    pub(crate) encrypt_names: bool,
+
+    pub(crate) salt: [u8; 16],
+
+    /// Value derived from the key, to check if a provided one is the right one
+    pub(crate) key_check: [u8; 32],
+}
+
+impl EncryptionParams {
+    /// Decode the parameters from the header's bytes
+    ///
+    /// Returns `None` if the archive isn't encrypted.
+    pub(crate) fn decode(
+        bytes: [u8; ENCRYPTION_PARAMS_SIZE],
+    ) -> Result<Option<Self>, HeaderDecodingError> {
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        let (memory_cost, time_cost, parallelism) = (read_u32(2), read_u32(6), read_u32(10));
+
+        let key_derivation = match bytes[0] {
+            0 => {
+                return if bytes.iter().all(|b| *b == 0) {
+                    Ok(None)
+                } else {
+                    Err(HeaderDecodingError::NonZeroPadding)
+                };
+            }
+
+            1 if memory_cost == 0 && time_cost == 0 && parallelism == 0 => KeyDerivation::Raw,
+
+            2 if (Params::MIN_M_COST..=MAX_MEMORY_COST).contains(&memory_cost)
+                && (1..=MAX_TIME_AND_PARALLELISM_COST).contains(&time_cost)
+                && (1..=MAX_TIME_AND_PARALLELISM_COST).contains(&parallelism)
+                && memory_cost >= 8 * parallelism =>
+            {
+                KeyDerivation::Argon2id {
+                    memory_cost,
+                    time_cost,
+                    parallelism,
+                }
+            }
+
+            _ => return Err(HeaderDecodingError::InvalidEncryptionParams),
+        };
+
+        let encrypt_names = match bytes[1] {
+            0 => false,
+            1 => true,
+            _ => return Err(HeaderDecodingError::InvalidEncryptionParams),
+        };
+
+        Ok(Some(Self {
+            key_derivation,
+            encrypt_names,
+            salt: bytes[14..30].try_into().unwrap(),
+            key_check: bytes[30..62].try_into().unwrap(),
+        }))
+    }
+
+    /// Encode the parameters, or their absence
+    pub(crate) fn encode(params: Option<&Self>) -> [u8; ENCRYPTION_PARAMS_SIZE] {
+        let mut bytes = [0; ENCRYPTION_PARAMS_SIZE];
+
+        let Some(params) = params else {
+            return bytes;
+        };
+
+        let (mode, costs) = match params.key_derivation {
+            KeyDerivation::Raw => (1, [0, 0, 0]),
+            KeyDerivation::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => (2, [memory_cost, time_cost, parallelism]),
+        };
+
+        bytes[0] = mode;
+        bytes[1] = u8::from(params.encrypt_names);
+
+        for (i, cost) in costs.into_iter().enumerate() {
+            bytes[2 + i * 4..6 + i * 4].copy_from_slice(&cost.to_le_bytes());
+        }
+
+        bytes[14..30].copy_from_slice(&params.salt);
+        bytes[30..62].copy_from_slice(&params.key_check);
+
+        bytes
+    }
+}
+
+/// Ciphers of an archive whose key is known
+#[derive(Clone)]
+pub(crate) struct ArchiveCipher {
+    pub contents: Cipher,
+
+    /// Only present if names are encrypted
+    pub names: Option<Cipher>,
+}
+
+impl ArchiveCipher {
+    /// Generate the encryption parameters of a new archive
+    pub fn create(key: &EncryptionKey, encrypt_names: bool) -> (Self, EncryptionParams) {
+        let key_derivation = match key {
+            EncryptionKey::Password(_) => KeyDerivation::Argon2id {
+                memory_cost: Params::DEFAULT_M_COST,
+                time_cost: Params::DEFAULT_T_COST,
+                parallelism: Params::DEFAULT_P_COST,
+            },
+
+            EncryptionKey::Raw(_) => KeyDerivation::Raw,
+        };
+
+        let salt = <[u8; 16]>::generate();
+        let master_key = derive_master_key(key, key_derivation, &salt)
+            .expect("default key derivation parameters should be valid");
+
+        let params = EncryptionParams {
+            key_derivation,
+            encrypt_names,
+            salt,
+            key_check: derive_subkey(b"key check", &salt, &master_key),
+        };
+
+        (Self::new(&master_key, &params), params)
+    }
+
+    /// Check the provided key against an archive's encryption parameters
+    ///
+    /// Returns `None` if the key is not the archive's one.
+    pub fn unlock(key: &EncryptionKey, params: &EncryptionParams) -> Option<Self> {
+        let master_key = derive_master_key(key, params.key_derivation, &params.salt)?;
+
+        if derive_subkey(b"key check", &params.salt, &master_key) != params.key_check {
+            return None;
+        }
+
+        Some(Self::new(&master_key, params))
+    }
+
+    /// Unlock an archive being opened, if it is encrypted
+    ///
+    /// Without a key, the archive can still be opened if its names are not encrypted,
+    /// but its files' content can't be read.
+    pub fn unlock_archive(
+        params: Option<&EncryptionParams>,
+        key: Option<&EncryptionKey>,
+    ) -> Result<Option<Self>, ArchiveMetadataDecodingError> {
+        match (params, key) {
+            (None, _) => Ok(None),
+
+            (Some(params), Some(key)) => Self::unlock(key, params)
+                .map(Some)
+                .ok_or(ArchiveMetadataDecodingError::InvalidEncryptionKey),
+
+            (Some(params), None) if params.encrypt_names => {
+                Err(ArchiveMetadataDecodingError::MissingEncryptionKey)
+            }
+
+            (Some(_), None) => Ok(None),
+        }
+    }
+
+    fn new(master_key: &[u8; 32], params: &EncryptionParams) -> Self {
+        Self {
+            contents: Cipher::new(derive_subkey(b"contents", &params.salt, master_key)),
+            names: params
+                .encrypt_names
+                .then(|| Cipher::new(derive_subkey(b"names", &params.salt, master_key))),
+        }
+    }
+}
+
+/// Authenticated cipher, sealing each piece of data (or chunk of a file's content) with its own nonce
+#[derive(Clone)]
+pub(crate) struct Cipher(XChaCha20Poly1305);
+
+impl Cipher {
+    fn new(key: [u8; 32]) -> Self {
+        Self(XChaCha20Poly1305::new(&Key::from(key)))
+    }
+
+    /// Encrypt some data
+    ///
+    /// The result is made of the nonce, followed by the encrypted data and its authentication tag.
+    pub fn seal(&self, data: &[u8]) -> Vec<u8> {
+        let nonce = <[u8; 24]>::generate();
+
+        let encrypted = self
+            .0
+            .encrypt(&XNonce::from(nonce), data)
+            .expect("data should not be too large to be encrypted");
+
+        let mut sealed = Vec::with_capacity(nonce.len() + encrypted.len());
+        sealed.extend(nonce);
+        sealed.extend(encrypted);
+        sealed
+    }
+
+    /// Decrypt some data sealed with [`Cipher::seal`]
+    ///
+    /// Returns `None` if the data was altered (or sealed with another key).
+    pub fn open(&self, sealed: &[u8]) -> Option<Vec<u8>> {
+        let (nonce, encrypted) = sealed.split_first_chunk::<24>()?;
+
+        self.0.decrypt(&XNonce::from(*nonce), encrypted).ok()
+    }
+
+    /// Encrypt a chunk of a file's content (see [`ContentEncryptor`])
+    ///
+    /// The result is made of the encrypted chunk, followed by its authentication tag.
+    pub fn seal_chunk(
+        &self,
+        nonce_prefix: &[u8; NONCE_PREFIX_SIZE],
+        index: u64,
+        last: bool,
+        chunk: &[u8],
+    ) -> Vec<u8> {
+        self.0
+            .encrypt(&chunk_nonce(nonce_prefix, index, last), chunk)
+            .expect("chunk should not be too large to be encrypted")
+    }
+
+    /// Decrypt a chunk sealed with [`Cipher::seal_chunk`]
+    ///
+    /// Returns `None` if the chunk was altered, moved, or sealed with another key.
+    pub fn open_chunk(
+        &self,
+        nonce_prefix: &[u8; NONCE_PREFIX_SIZE],
+        index: u64,
+        last: bool,
+        sealed: &[u8],
+    ) -> Option<Vec<u8>> {
+        self.0
+            .decrypt(&chunk_nonce(nonce_prefix, index, last), sealed)
+            .ok()
+    }
+}
+
+impl Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cipher")
+    }
+}
+
+/// Get the master key from the provided one
+///
+/// Returns `None` if the key's kind doesn't match the expected derivation.
+fn derive_master_key(
+    key: &EncryptionKey,
+    key_derivation: KeyDerivation,
+    salt: &[u8; 16],
+) -> Option<[u8; 32]> {
+    match (key, key_derivation) {
+        (EncryptionKey::Raw(key), KeyDerivation::Raw) => Some(*key),
+
+        (
+            EncryptionKey::Password(password),
+            KeyDerivation::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            },
+        ) => {
+            let params = Params::new(memory_cost, time_cost, parallelism, Some(32)).ok()?;
+
+            let mut master_key = [0; 32];
+
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(password.as_bytes(), salt, &mut master_key)
+                .ok()?;
+
+            Some(master_key)
+        }
+
+        (EncryptionKey::Raw(_), KeyDerivation::Argon2id { .. })
+        | (EncryptionKey::Password(_), KeyDerivation::Raw) => None,
+    }
+}
+
+/// Derive a key dedicated to a single purpose from the master key
+fn derive_subkey(purpose: &[u8], salt: &[u8; 16], master_key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"BAF ");
+    hasher.update(purpose);
+    hasher.update(salt);
+    hasher.update(master_key);
+    hasher.finalize().into()
+}
+
+/// Compute the nonce of an encrypted content's chunk
+///
+/// Marking the last chunk prevents the content from being truncated at a chunk's boundary.
+fn chunk_nonce(nonce_prefix: &[u8; NONCE_PREFIX_SIZE], index: u64, last: bool) -> XNonce {
+    let mut nonce = [0; 24];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(nonce_prefix);
+    nonce[NONCE_PREFIX_SIZE..23].copy_from_slice(&index.to_le_bytes());
+    nonce[23] = u8::from(last);
+    XNonce::from(nonce)
+}
+
+/// Get the stored length of a file's content of the provided length once encrypted
+pub(crate) fn encrypted_content_len(len: u64) -> u64 {
+    // Empty contents are never encrypted
+    if len == 0 {
+        return 0;
+    }
+
+    NONCE_PREFIX_SIZE as u64 + len + len.div_ceil(CONTENT_CHUNK_SIZE) * TAG_SIZE
+}
+
+/// Get the length of a file's content once decrypted, from its stored length
+///
+/// Stored lengths which can't be the result of an encryption give a best-effort length,
+/// as reading the content will fail anyway.
+pub(crate) fn decrypted_content_len(stored_len: u64) -> u64 {
+    let chunks_len = stored_len.saturating_sub(NONCE_PREFIX_SIZE as u64);
+    let chunks = chunks_len.div_ceil(CONTENT_CHUNK_SIZE + TAG_SIZE);
+
+    chunks_len.saturating_sub(chunks * TAG_SIZE)
+}
+
+/// Get the location of an encrypted content's chunk, as its offset and length inside the stored content
+///
+/// Both are clamped to the stored length.
+fn encrypted_chunk_location(index: u64, stored_len: u64) -> (u64, u64) {
+    let offset = index
+        .saturating_mul(CONTENT_CHUNK_SIZE + TAG_SIZE)
+        .saturating_add(NONCE_PREFIX_SIZE as u64)
+        .min(stored_len);
+
+    (
+        offset,
+        (CONTENT_CHUNK_SIZE + TAG_SIZE).min(stored_len - offset),
+    )
+}
+
+/// Streaming encryption of a file's content
+///
+/// The content is split into chunks of [`CONTENT_CHUNK_SIZE`] bytes, each of them sealed with its own nonce
+/// made of a random prefix, the chunk's index and whether it's the last one (STREAM construction).
+/// This allows reading any chunk independently, while detecting chunks being altered, reordered or removed.
+pub(crate) struct ContentEncryptor {
+    cipher: Cipher,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+    next_index: u64,
+    /// Data not sealed yet, as a full chunk can only be sealed once it is known whether it's the last one
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl ContentEncryptor {
+    pub fn new(cipher: Cipher) -> Self {
+        Self {
+            cipher,
+            nonce_prefix: <[u8; NONCE_PREFIX_SIZE]>::generate(),
+            next_index: 0,
+            pending: vec![],
+            started: false,
+        }
+    }
+
+    /// Encrypt some more data
+    ///
+    /// Returns the encrypted bytes to store, which don't cover the data held until the next chunk is complete.
+    pub fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut sealed = vec![];
+
+        if !self.started && !data.is_empty() {
+            sealed.extend(self.nonce_prefix);
+            self.started = true;
+        }
+
+        self.pending.extend_from_slice(data);
+
+        let chunk_size = usize::try_from(CONTENT_CHUNK_SIZE).unwrap();
+
+        while self.pending.len() > chunk_size {
+            let chunk = self.pending.drain(..chunk_size).collect::<Vec<_>>();
+            sealed.extend(self.seal_next(&chunk, false));
+        }
+
+        sealed
+    }
+
+    /// Encrypt the remaining data as the last chunk
+    ///
+    /// Returns the encrypted bytes to store, which are empty if no data was provided at all,
+    /// as empty contents are never encrypted.
+    pub fn finish(mut self) -> Vec<u8> {
+        if !self.started {
+            return vec![];
+        }
+
+        let chunk = std::mem::take(&mut self.pending);
+        self.seal_next(&chunk, true)
+    }
+
+    fn seal_next(&mut self, chunk: &[u8], last: bool) -> Vec<u8> {
+        let sealed = self
+            .cipher
+            .seal_chunk(&self.nonce_prefix, self.next_index, last, chunk);
+
+        self.next_index += 1;
+        sealed
+    }
+}
+
+/// Reader encrypting the content provided by another one (see [`ContentEncryptor`])
+pub(crate) struct EncryptingReader<R: Read> {
+    inner: R,
+    /// Taken once the inner reader is exhausted
+    encryptor: Option<ContentEncryptor>,
+    /// Encrypted bytes not returned yet
+    output: Vec<u8>,
+    output_pos: usize,
+}
+
+impl<R: Read> EncryptingReader<R> {
+    pub fn new(inner: R, cipher: Cipher) -> Self {
+        Self {
+            inner,
+            encryptor: Some(ContentEncryptor::new(cipher)),
+            output: vec![],
+            output_pos: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for EncryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.output_pos == self.output.len() {
+            let Some(encryptor) = &mut self.encryptor else {
+                return Ok(0);
+            };
+
+            let mut data = [0; 4096];
+            let len = self.inner.read(&mut data)?;
+
+            self.output = if len == 0 {
+                self.encryptor.take().unwrap().finish()
+            } else {
+                encryptor.update(&data[..len])
+            };
+
+            self.output_pos = 0;
+        }
+
+        let len = buf.len().min(self.output.len() - self.output_pos);
+        buf[..len].copy_from_slice(&self.output[self.output_pos..self.output_pos + len]);
+        self.output_pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Random-access decryption of a file's content (see [`ContentEncryptor`])
+#[derive(Debug)]
+pub(crate) struct ContentDecryptor {
+    cipher: Cipher,
+    stored_len: u64,
+    /// Read from the beginning of the stored content
+    nonce_prefix: Option<[u8; NONCE_PREFIX_SIZE]>,
+}
+
+impl ContentDecryptor {
+    pub fn new(cipher: Cipher, stored_len: u64) -> Self {
+        Self {
+            cipher,
+            stored_len,
+            nonce_prefix: None,
+        }
+    }
+
+    /// Get the content's length once decrypted
+    pub fn decrypted_len(&self) -> u64 {
+        decrypted_content_len(self.stored_len)
+    }
+
+    /// Get the offset inside the stored content to read the next chunk from, after the nonce prefix was read
+    pub fn chunk_offset(&self, index: u64) -> u64 {
+        encrypted_chunk_location(index, self.stored_len).0
+    }
+
+    /// Check if the nonce prefix was read, which must be done before reading any chunk
+    pub fn has_nonce_prefix(&self) -> bool {
+        self.nonce_prefix.is_some()
+    }
+
+    /// Read the nonce prefix from the beginning of the stored content
+    pub fn read_nonce_prefix(&mut self, mut stored: impl Read) -> std::io::Result<()> {
+        let mut nonce_prefix = [0; NONCE_PREFIX_SIZE];
+        stored.read_exact(&mut nonce_prefix)?;
+        self.nonce_prefix = Some(nonce_prefix);
+        Ok(())
+    }
+
+    /// Read and decrypt a chunk, from the stored content positioned at its beginning
+    ///
+    /// Returns `None` if the chunk was altered, moved, or sealed with another key.
+    pub fn read_chunk(
+        &self,
+        index: u64,
+        mut stored: impl Read,
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let nonce_prefix = self
+            .nonce_prefix
+            .as_ref()
+            .expect("nonce prefix should have been read");
+        let (offset, len) = encrypted_chunk_location(index, self.stored_len);
+
+        let mut sealed = vec![0; usize::try_from(len).unwrap()];
+        stored.read_exact(&mut sealed)?;
+
+        let last = offset + len == self.stored_len;
+
+        Ok(self.cipher.open_chunk(nonce_prefix, index, last, &sealed))
+    }
+
+    /// Decrypt an entire stored content
+    ///
+    /// Returns `None` if the content was altered, or encrypted with another key.
+    #[cfg(feature = "async")]
+    pub fn decrypt_all(mut self, stored: &[u8]) -> Option<Vec<u8>> {
+        let mut stored = stored;
+        let mut content = Vec::with_capacity(usize::try_from(self.decrypted_len()).unwrap());
+
+        self.read_nonce_prefix(&mut stored).ok()?;
+
+        for index in 0..self.decrypted_len().div_ceil(CONTENT_CHUNK_SIZE) {
+            content.extend(self.read_chunk(index, &mut stored).ok()??);
+        }
+
+        stored.is_empty().then_some(content)
+    }
+}
@@ -1,44 +1,127 @@
-use std::io::{Error, Read};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
 
 use sha3::{Digest, Sha3_256};
 use thiserror::Error;
 
-use crate::source::Source;
+use crate::{
+    chunks::ChunkChecksums,
+    compression::{Decompressor, FileCompression},
+    encryption::{CONTENT_CHUNK_SIZE, Cipher, ContentDecryptor},
+    source::{ReadAt, Source},
+};
 
 /// Abstraction over a file with checksum verification
 ///
 /// Designed to be used for reading / extracting files from BAF archives.
 ///
 /// **NOTE:** Checksum validation only occurs *after* the very last byte has been read.
+///
+/// The reader can seek anywhere inside the file. Seeking back to the file's beginning restarts
+/// the checksum computation, but seeking anywhere else disables it for the rest of the reading,
+/// as the content wouldn't be read sequentially. Use [`FileReader::verify`] to check the content
+/// explicitly in that case.
+///
+/// If the checksums of each chunk of the file are available (see [`crate::ArchiveConfig::chunk_size`]),
+/// every chunk is verified before any of its bytes is returned, wherever the reader seeks.
+///
+/// Compressed contents (see [`crate::ArchiveConfig::compression`]) are decompressed transparently.
+/// As they can only be decompressed sequentially, seeking backwards restarts the decompression
+/// from the file's beginning, and seeking forwards decompresses the content in-between. Checksums
+/// apply to the content as it is stored, so it is always verified in that case.
+///
+/// Encrypted contents (see [`crate::ArchiveConfig::encryption_key`]) are decrypted transparently.
+/// They are authenticated by chunks, so only the chunk being read is held in memory, and seeking
+/// only requires decrypting the chunk the new position is inside.
+///
+/// Readers obtained through [`crate::Archive::read_file_shared`] use positional reads, so several
+/// of them can be used at the same time, including from different threads.
 #[derive(Debug)]
 pub struct FileReader<'a, S: Read> {
-    source: &'a mut Source<S>,
+    source: ContentSource<'a, S>,
+    addr: u64,
     len: u64,
     expected_checksum: [u8; 32],
-    pending_checksum: Sha3_256,
+    pending_checksum: Option<Sha3_256>,
+    chunks: Option<ChunksVerifier<'a>>,
+    decompression: Option<Decompression>,
+    decryption: Option<Decryption>,
     pos: u64,
 }
 
 impl<'a, S: Read> FileReader<'a, S> {
-    pub(crate) fn new(source: &'a mut Source<S>, len: u64, expected_checksum: [u8; 32]) -> Self {
+    pub(crate) fn new(
+        source: &'a mut Source<S>,
+        addr: u64,
+        len: u64,
+        expected_checksum: [u8; 32],
+        chunk_checksums: Option<&'a ChunkChecksums>,
+        compression: Option<FileCompression>,
+        cipher: Option<Cipher>,
+    ) -> Self {
+        Self::with_source(
+            ContentSource::Stream(source),
+            addr,
+            len,
+            expected_checksum,
+            chunk_checksums,
+            compression,
+            cipher,
+        )
+    }
+
+    fn with_source(
+        source: ContentSource<'a, S>,
+        addr: u64,
+        len: u64,
+        expected_checksum: [u8; 32],
+        chunk_checksums: Option<&'a ChunkChecksums>,
+        compression: Option<FileCompression>,
+        cipher: Option<Cipher>,
+    ) -> Self {
         Self {
             source,
+            addr,
             len,
             expected_checksum,
-            pending_checksum: Sha3_256::new(),
+            pending_checksum: Some(Sha3_256::new()),
+            chunks: chunk_checksums.map(|checksums| ChunksVerifier {
+                checksums,
+                loaded: None,
+                buf: vec![],
+            }),
+            decompression: compression.map(Decompression::new),
+            decryption: cipher.map(|cipher| Decryption {
+                decryptor: ContentDecryptor::new(cipher, len),
+                chunk: None,
+                pos: 0,
+            }),
             pos: 0,
         }
     }
 
     /// Get the file's length, in bytes
+    ///
+    /// For compressed contents, this is the length once decompressed.
     pub fn file_len(&self) -> u64 {
-        self.len
+        match &self.decompression {
+            Some(decompression) => decompression.compression.uncompressed_len,
+            None => self.decrypted_len(),
+        }
+    }
+
+    /// Get how the file's content is compressed, if it is
+    pub fn compression(&self) -> Option<FileCompression> {
+        self.decompression
+            .as_ref()
+            .map(|decompression| decompression.compression)
     }
 
     /// Read the file's content to a `Vec<u8>`
     pub fn read_to_vec(mut self) -> Result<Vec<u8>, FileReaderError> {
-        let mut buf = Vec::with_capacity(usize::try_from(self.file_len()).unwrap());
-        self.read_to_end(&mut buf)?;
+        // The stored length is bounded by the archive's size, unlike the decompressed one
+        let mut buf = Vec::with_capacity(usize::try_from(self.len).unwrap());
+        self.read_to_end(&mut buf)
+            .map_err(FileReaderError::from_io_error)?;
 
         Ok(buf)
     }
@@ -49,10 +132,269 @@ impl<'a, S: Read> FileReader<'a, S> {
 
         String::from_utf8(bytes).map_err(FileReaderError::InvalidUtf8)
     }
+
+    /// Check if the content read from now on will be verified
+    ///
+    /// Without chunks' checksums, this is not the case anymore after seeking somewhere else
+    /// than the file's beginning, unless the content is compressed or encrypted.
+    pub fn is_checksum_verified_on_read(&self) -> bool {
+        self.pending_checksum.is_some()
+            || self.chunks.is_some()
+            || self.decompression.is_some()
+            || self.decryption.is_some()
+    }
+
+    /// Get the content's length once decrypted, or as it is stored if it isn't encrypted
+    fn decrypted_len(&self) -> u64 {
+        match &self.decryption {
+            Some(decryption) => decryption.decryptor.decrypted_len(),
+            None => self.len,
+        }
+    }
+
+    /// Get the position inside the decrypted content, or inside the stored one if it isn't encrypted
+    fn decrypted_pos(&self) -> u64 {
+        match &self.decryption {
+            Some(decryption) => decryption.pos,
+            None => self.pos,
+        }
+    }
+
+    /// Check if the checksums of each chunk of the file are available
+    pub fn has_chunk_checksums(&self) -> bool {
+        self.chunks.is_some()
+    }
 }
 
-impl<'a, S: Read> Read for FileReader<'a, S> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+impl<'a, S: Read + ReadAt> FileReader<'a, S> {
+    pub(crate) fn new_positional(
+        stream: &'a S,
+        addr: u64,
+        len: u64,
+        expected_checksum: [u8; 32],
+        chunk_checksums: Option<&'a ChunkChecksums>,
+        compression: Option<FileCompression>,
+        cipher: Option<Cipher>,
+    ) -> Self {
+        Self::with_source(
+            ContentSource::Positional {
+                stream,
+                read_exact_at: S::read_exact_at,
+                pos: addr,
+            },
+            addr,
+            len,
+            expected_checksum,
+            chunk_checksums,
+            compression,
+            cipher,
+        )
+    }
+}
+
+impl<'a, S: Read + Seek> FileReader<'a, S> {
+    /// Read the entire file to verify its checksum
+    ///
+    /// The reader's position is left unchanged.
+    pub fn verify(&mut self) -> Result<(), FileReaderError> {
+        self.source.set_position(self.addr)?;
+
+        let mut checksum = Sha3_256::new();
+        let mut read = 0;
+
+        const CHUNK_SIZE: usize = 4096;
+
+        while read < self.len {
+            let mut buf = [0; CHUNK_SIZE];
+            let len = (CHUNK_SIZE as u64).min(self.len - read);
+            let buf = &mut buf[0..usize::try_from(len).unwrap()];
+
+            self.source.read_exact(buf)?;
+            checksum.update(&*buf);
+
+            read += len;
+        }
+
+        self.restore_position()?;
+
+        let actual: [u8; 32] = checksum.finalize().into();
+
+        if actual != self.expected_checksum {
+            return Err(FileReaderError::ChecksumMismatch {
+                expected: self.expected_checksum,
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Use the underlying stream between two reads, e.g. to write the content being read somewhere else
+    ///
+    /// The stream is positioned back to where the next read expects it afterwards.
+    /// Readers obtained through [`crate::Archive::read_file_shared`] don't have access to it.
+    pub(crate) fn with_stream<T>(
+        &mut self,
+        f: impl FnOnce(&mut Source<S>) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let ContentSource::Stream(source) = &mut self.source else {
+            panic!("positional readers don't have an exclusive access to the stream");
+        };
+
+        let result = f(source);
+        self.restore_position()?;
+        result
+    }
+
+    /// Set the source's position back to where the next read expects it
+    fn restore_position(&mut self) -> std::io::Result<()> {
+        match &mut self.chunks {
+            // Chunks are always read entirely
+            Some(chunks) => {
+                chunks.loaded = None;
+                self.source
+                    .set_position(self.addr + chunks.chunk_start(self.pos))
+            }
+
+            None => self.source.set_position(self.addr + self.pos),
+        }
+    }
+}
+
+impl<'a, S: Read> FileReader<'a, S> {
+    /// Decompress some of the content
+    fn read_decompressed(
+        &mut self,
+        decompression: &mut Decompression,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        let remaining = decompression.compression.uncompressed_len - decompression.pos;
+        let read_len = std::cmp::min(u64::try_from(buf.len()).unwrap(), remaining);
+
+        if read_len == 0 {
+            return Ok(0);
+        }
+
+        let buf = &mut buf[0..usize::try_from(read_len).unwrap()];
+
+        loop {
+            if decompression.input_pos == decompression.input.len()
+                && self.decrypted_pos() < self.decrypted_len()
+            {
+                decompression.input.resize(4096, 0);
+
+                let len = self.read_decrypted(&mut decompression.input)?;
+
+                decompression.input.truncate(len);
+                decompression.input_pos = 0;
+            }
+
+            let decompressor = match &mut decompression.decompressor {
+                Some(decompressor) => decompressor,
+                None => decompression.decompressor.insert(
+                    Decompressor::new(decompression.compression.codec)
+                        .map_err(decompression_error)?,
+                ),
+            };
+
+            let (consumed, produced) = decompressor
+                .decompress(&decompression.input[decompression.input_pos..], buf)
+                .map_err(decompression_error)?;
+
+            decompression.input_pos += consumed;
+
+            if produced > 0 {
+                decompression.pos += u64::try_from(produced).unwrap();
+
+                // Read the rest of the stored content, so that its checksum gets verified
+                if decompression.pos == decompression.compression.uncompressed_len {
+                    let mut rest = [0; 4096];
+                    while self.read_decrypted(&mut rest)? > 0 {}
+                }
+
+                return Ok(produced);
+            }
+
+            // The decompressor can't make any progress, either because the stored content is exhausted
+            // or because the compressed stream ended before the rest of the stored content
+            if consumed == 0 {
+                return Err(decompression_error(
+                    if decompression.input_pos == decompression.input.len()
+                        && self.decrypted_pos() == self.decrypted_len()
+                    {
+                        Error::new(
+                            ErrorKind::UnexpectedEof,
+                            "decompressed content is shorter than expected",
+                        )
+                    } else {
+                        Error::new(
+                            ErrorKind::InvalidData,
+                            "compressed content ends before its stored bytes",
+                        )
+                    },
+                ));
+            }
+        }
+    }
+
+    /// Read some of the content once decrypted, or as it is stored if it isn't encrypted
+    fn read_decrypted(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(mut decryption) = self.decryption.take() else {
+            return self.read_stored(buf);
+        };
+
+        let offset = usize::try_from(decryption.pos % CONTENT_CHUNK_SIZE).unwrap();
+
+        let result = if decryption.pos < decryption.decryptor.decrypted_len() {
+            self.load_decrypted_chunk(&mut decryption).map(|chunk| {
+                let len = buf.len().min(chunk.len().saturating_sub(offset));
+
+                buf[0..len].copy_from_slice(&chunk[offset..offset + len]);
+                len
+            })
+        } else {
+            Ok(0)
+        };
+
+        if let Ok(len) = result {
+            decryption.pos += u64::try_from(len).unwrap();
+        }
+
+        self.decryption = Some(decryption);
+        result
+    }
+
+    /// Read and decrypt the chunk the decrypted position is inside, unless it was already
+    ///
+    /// Chunks are read sequentially, seeking takes care of moving to the right one otherwise.
+    fn load_decrypted_chunk<'d>(
+        &mut self,
+        decryption: &'d mut Decryption,
+    ) -> std::io::Result<&'d [u8]> {
+        let index = decryption.pos / CONTENT_CHUNK_SIZE;
+
+        if decryption.chunk.as_ref().map(|(loaded, _)| *loaded) != Some(index) {
+            decryption.chunk = None;
+
+            if !decryption.decryptor.has_nonce_prefix() {
+                decryption
+                    .decryptor
+                    .read_nonce_prefix(StoredContent(self))?;
+            }
+
+            let chunk = decryption
+                .decryptor
+                .read_chunk(index, StoredContent(self))?
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, FileReaderError::Decryption))?;
+
+            decryption.chunk = Some((index, chunk));
+        }
+
+        Ok(&decryption.chunk.as_ref().unwrap().1)
+    }
+
+    /// Read some of the content as it is stored in the archive
+    fn read_stored(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         // TODO: some typecasts are unneeded in this function
         let read_len = std::cmp::min(u64::try_from(buf.len()).unwrap(), self.len - self.pos);
 
@@ -62,25 +404,44 @@ impl<'a, S: Read> Read for FileReader<'a, S> {
 
         let read_len_usize = usize::try_from(read_len).unwrap();
 
-        let buf_slice = &mut buf[0..read_len_usize];
+        let read_len_usize = match &mut self.chunks {
+            Some(chunks) => chunks.read(
+                &mut self.source,
+                self.pos,
+                self.len,
+                &mut buf[0..read_len_usize],
+            )?,
 
-        self.source
-            .read_exact(buf_slice)
-            .map_err(Error::other)?;
+            None => {
+                self.source
+                    .read_exact(&mut buf[0..read_len_usize])
+                    .map_err(Error::other)?;
 
-        self.pending_checksum.update(buf_slice);
+                read_len_usize
+            }
+        };
+
+        let read_len = u64::try_from(read_len_usize).unwrap();
+        let buf_slice = &buf[0..read_len_usize];
 
         self.pos += read_len;
 
-        // When the entire file has been read, check its validity by comparing the checksums
-        if self.pos == self.len {
-            let hash: [u8; 32] = self.pending_checksum.clone().finalize().into();
+        if let Some(pending_checksum) = &mut self.pending_checksum {
+            pending_checksum.update(buf_slice);
+
+            // When the entire file has been read, check its validity by comparing the checksums
+            if self.pos == self.len {
+                let hash: [u8; 32] = pending_checksum.clone().finalize().into();
 
-            if hash != self.expected_checksum {
-                return Err(Error::other(format!(
-                    "File's hash doesn't match: expected {:#?}, got {hash:#?}",
-                    self.expected_checksum
-                )));
+                if hash != self.expected_checksum {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        FileReaderError::ChecksumMismatch {
+                            expected: self.expected_checksum,
+                            actual: hash,
+                        },
+                    ));
+                }
             }
         }
 
@@ -88,6 +449,301 @@ impl<'a, S: Read> Read for FileReader<'a, S> {
     }
 }
 
+impl<'a, S: Read> Read for FileReader<'a, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(mut decompression) = self.decompression.take() else {
+            return self.read_decrypted(buf);
+        };
+
+        let result = self.read_decompressed(&mut decompression, buf);
+        self.decompression = Some(decompression);
+        result
+    }
+}
+
+impl<'a, S: Read + Seek> FileReader<'a, S> {
+    /// Move inside the content as it is stored in the archive
+    fn seek_stored(&mut self, new_pos: u64) -> std::io::Result<()> {
+        if new_pos == self.pos {
+            return Ok(());
+        }
+
+        match &mut self.chunks {
+            Some(chunks) => {
+                // Keep the loaded chunk if the new position is inside it
+                if chunks.loaded != Some(chunks.chunk_index(new_pos)) {
+                    chunks.loaded = None;
+                    self.source
+                        .set_position(self.addr + chunks.chunk_start(new_pos))?;
+                }
+            }
+
+            None => self.source.set_position(self.addr + new_pos)?,
+        }
+
+        // Reading from the beginning again allows to compute the checksum from scratch
+        self.pending_checksum = if new_pos == 0 {
+            Some(Sha3_256::new())
+        } else {
+            None
+        };
+
+        self.pos = new_pos;
+
+        Ok(())
+    }
+
+    /// Move inside the decrypted content, or inside the stored one if it isn't encrypted
+    ///
+    /// Only the chunk the new position is inside will need to be decrypted.
+    fn seek_decrypted(&mut self, new_pos: u64) -> std::io::Result<()> {
+        let Some(mut decryption) = self.decryption.take() else {
+            return self.seek_stored(new_pos);
+        };
+
+        let result = self.seek_decrypted_chunk(&mut decryption, new_pos);
+        self.decryption = Some(decryption);
+        result
+    }
+
+    /// Move to the beginning of the chunk a position of the decrypted content is inside, unless it is loaded
+    fn seek_decrypted_chunk(
+        &mut self,
+        decryption: &mut Decryption,
+        new_pos: u64,
+    ) -> std::io::Result<()> {
+        let index = new_pos / CONTENT_CHUNK_SIZE;
+
+        decryption.pos = new_pos;
+
+        if decryption.chunk.as_ref().map(|(loaded, _)| *loaded) == Some(index) {
+            return Ok(());
+        }
+
+        decryption.chunk = None;
+
+        // Reading the nonce prefix again when moving back to the beginning restarts the checksum computation
+        if !decryption.decryptor.has_nonce_prefix() || index == 0 {
+            self.seek_stored(0)?;
+            decryption
+                .decryptor
+                .read_nonce_prefix(StoredContent(self))?;
+        }
+
+        self.seek_stored(decryption.decryptor.chunk_offset(index))
+    }
+}
+
+impl<'a, S: Read + Seek> Seek for FileReader<'a, S> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let Some(decompression) = &mut self.decompression else {
+            let new_pos = resolve_seek(pos, self.decrypted_pos(), self.decrypted_len())?;
+            self.seek_decrypted(new_pos)?;
+            return Ok(new_pos);
+        };
+
+        let current_pos = decompression.pos;
+        let new_pos = resolve_seek(pos, current_pos, decompression.compression.uncompressed_len)?;
+
+        // The content can only be decompressed sequentially
+        if new_pos < current_pos {
+            decompression.restart();
+            self.seek_decrypted(0)?;
+        }
+
+        let mut buf = [0; 4096];
+
+        while let Some(remaining) = new_pos
+            .checked_sub(self.decompression.as_ref().unwrap().pos)
+            .filter(|remaining| *remaining > 0)
+        {
+            let len = usize::try_from(remaining.min(4096)).unwrap();
+            self.read_exact(&mut buf[0..len])?;
+        }
+
+        Ok(new_pos)
+    }
+}
+
+/// Compute the position to seek to inside a content of the provided length
+fn resolve_seek(pos: SeekFrom, current_pos: u64, len: u64) -> std::io::Result<u64> {
+    let new_pos = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => len.checked_add_signed(offset),
+        SeekFrom::Current(offset) => current_pos.checked_add_signed(offset),
+    };
+
+    new_pos.filter(|new_pos| *new_pos <= len).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "cannot seek outside of the file's content",
+        )
+    })
+}
+
+/// Where a [`FileReader`] reads the file's content from
+#[derive(Debug)]
+enum ContentSource<'a, S: Read> {
+    /// Stream shared with the archive, whose position is moved by each read
+    Stream(&'a mut Source<S>),
+
+    /// Stream read with positional reads, which don't require an exclusive access
+    Positional {
+        stream: &'a S,
+        read_exact_at: fn(&S, &mut [u8], u64) -> std::io::Result<()>,
+        pos: u64,
+    },
+}
+
+impl<S: Read> ContentSource<'_, S> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            Self::Stream(source) => source.read_exact(buf),
+
+            Self::Positional {
+                stream,
+                read_exact_at,
+                pos,
+            } => {
+                read_exact_at(stream, buf, *pos)?;
+                *pos += u64::try_from(buf.len()).unwrap();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<S: Read + Seek> ContentSource<'_, S> {
+    fn set_position(&mut self, new_pos: u64) -> std::io::Result<()> {
+        match self {
+            Self::Stream(source) => source.set_position(new_pos),
+
+            Self::Positional { pos, .. } => {
+                *pos = new_pos;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Verifier for files whose chunks' checksums are available
+///
+/// Each chunk is entirely read and verified before any of its bytes is returned.
+/// The source is always positioned at the beginning of a chunk.
+#[derive(Debug)]
+struct ChunksVerifier<'a> {
+    checksums: &'a ChunkChecksums,
+    /// Index of the chunk currently held in the buffer
+    loaded: Option<u64>,
+    buf: Vec<u8>,
+}
+
+impl ChunksVerifier<'_> {
+    fn chunk_index(&self, pos: u64) -> u64 {
+        pos / self.checksums.chunk_size
+    }
+
+    fn chunk_start(&self, pos: u64) -> u64 {
+        self.chunk_index(pos) * self.checksums.chunk_size
+    }
+
+    fn read<S: Read>(
+        &mut self,
+        source: &mut ContentSource<'_, S>,
+        pos: u64,
+        file_len: u64,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        let chunk_index = self.chunk_index(pos);
+        let chunk_start = self.chunk_start(pos);
+
+        if self.loaded != Some(chunk_index) {
+            let chunk_len = self.checksums.chunk_size.min(file_len - chunk_start);
+
+            self.loaded = None;
+            self.buf.resize(usize::try_from(chunk_len).unwrap(), 0);
+            source.read_exact(&mut self.buf)?;
+
+            let expected = self.checksums.checksums[usize::try_from(chunk_index).unwrap()];
+            let actual: [u8; 32] = Sha3_256::digest(&self.buf).into();
+
+            if actual != expected {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    FileReaderError::ChunkChecksumMismatch {
+                        chunk_index,
+                        expected,
+                        actual,
+                    },
+                ));
+            }
+
+            self.loaded = Some(chunk_index);
+        }
+
+        let offset = usize::try_from(pos - chunk_start).unwrap();
+        let len = buf.len().min(self.buf.len() - offset);
+
+        buf[0..len].copy_from_slice(&self.buf[offset..offset + len]);
+
+        Ok(len)
+    }
+}
+
+/// Decompression state of a compressed content
+#[derive(Debug)]
+struct Decompression {
+    compression: FileCompression,
+    /// Created once the content starts being decompressed
+    decompressor: Option<Decompressor>,
+    /// Stored content which was read but not decompressed yet
+    input: Vec<u8>,
+    input_pos: usize,
+    /// Position inside the decompressed content
+    pos: u64,
+}
+
+impl Decompression {
+    fn new(compression: FileCompression) -> Self {
+        Self {
+            compression,
+            decompressor: None,
+            input: vec![],
+            input_pos: 0,
+            pos: 0,
+        }
+    }
+
+    /// Get ready to decompress the content from its beginning again
+    fn restart(&mut self) {
+        *self = Self::new(self.compression);
+    }
+}
+
+/// Decryption state of an encrypted content
+#[derive(Debug)]
+struct Decryption {
+    decryptor: ContentDecryptor,
+    /// Index and content of the last decrypted chunk
+    chunk: Option<(u64, Vec<u8>)>,
+    /// Position inside the decrypted content
+    pos: u64,
+}
+
+/// Content of a file as it is stored in the archive, to read encrypted chunks from
+struct StoredContent<'r, 'a, S: Read>(&'r mut FileReader<'a, S>);
+
+impl<'r, 'a, S: Read> Read for StoredContent<'r, 'a, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read_stored(buf)
+    }
+}
+
+/// Wrap a decompression error so it can be extracted by [`FileReaderError::from_io_error`]
+fn decompression_error(err: Error) -> Error {
+    Error::new(ErrorKind::InvalidData, FileReaderError::Decompression(err))
+}
+
 /// Error while reading a file from an archive
 #[derive(Error, Debug)]
 pub enum FileReaderError {
@@ -98,4 +754,48 @@ pub enum FileReaderError {
     /// File content is not valid UTF-8
     #[error("File content is not a valid UTF-8 string: {0}")]
     InvalidUtf8(#[from] std::string::FromUtf8Error),
+
+    /// File content doesn't match the expected checksum
+    #[error("File's hash doesn't match: expected {expected:x?}, got {actual:x?}")]
+    ChecksumMismatch {
+        /// Checksum stored in the file's entry
+        expected: [u8; 32],
+        /// Checksum of the actual content
+        actual: [u8; 32],
+    },
+
+    /// The file's compressed content is invalid, or doesn't decompress to the expected length
+    #[error("Failed to decompress file's content: {0}")]
+    Decompression(std::io::Error),
+
+    /// The file's encrypted content was altered, or encrypted with another key
+    #[error("Failed to decrypt file's content, it was altered or encrypted with another key")]
+    Decryption,
+
+    /// A chunk of the file's content doesn't match its expected checksum
+    #[error(
+        "Checksum of chunk {chunk_index} doesn't match: expected {expected:x?}, got {actual:x?}"
+    )]
+    ChunkChecksumMismatch {
+        /// Index of the chunk inside the file
+        chunk_index: u64,
+        /// Checksum stored in the file's record
+        expected: [u8; 32],
+        /// Checksum of the actual chunk
+        actual: [u8; 32],
+    },
+}
+
+impl FileReaderError {
+    /// Extract an error that was wrapped in an I/O one by [`FileReader`]'s [`Read`] implementation
+    pub(crate) fn from_io_error(err: Error) -> Self {
+        if err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<FileReaderError>())
+        {
+            *err.into_inner().unwrap().downcast().unwrap()
+        } else {
+            Self::Io(err)
+        }
+    }
 }
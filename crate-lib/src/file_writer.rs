@@ -0,0 +1,180 @@
+use std::{
+    borrow::Cow,
+    io::{Error, Read, Seek, Write},
+};
+
+use crate::{
+    Archive, ArchiveError, DirectoryIdOrRoot, FileId, ItemName, SyncData, Timestamp,
+    chunks::ContentHasher,
+    compression::{CompressionCodec, Compressor, FileCompression},
+    coverage::Segment,
+    encryption::{Cipher, ContentEncryptor},
+};
+
+/// Handle to write a new file's content progressively
+///
+/// Obtained from [`Archive::create_file_writer`]
+///
+/// The content is written after all existing data, and the file is only added to the archive
+/// when calling [`FileWriter::finish`]. If the writer is dropped before that, the space
+/// used by the written content is given back to the archive.
+///
+/// Every call to [`Write::write`] results in a write to the underlying stream,
+/// so wrapping the writer in a [`std::io::BufWriter`] is recommended for small writes.
+///
+/// The content is compressed while being written, according to [`crate::ArchiveConfig::compression`].
+/// As its length is only known once finished, a content which turns out to be too short or not to compress
+/// well enough is decompressed after all existing data by [`FileWriter::finish`], so that it is stored
+/// the same way [`Archive::create_file`] would have.
+///
+/// If the archive is encrypted (see [`crate::ArchiveConfig::encryption_key`]), the content is encrypted
+/// by chunks while being written, so at most one chunk is held in memory at any time.
+pub struct FileWriter<'a, S: Read + Write + Seek + SyncData> {
+    archive: &'a mut Archive<S>,
+    parent_dir: DirectoryIdOrRoot,
+    name: ItemName,
+    modif_time: Timestamp,
+    reserved: Segment,
+    hasher: Option<ContentHasher>,
+    /// Compression of the content, if the file's name allows it
+    compressor: Option<(CompressionCodec, Compressor)>,
+    /// Encryption of the content, if the archive is encrypted
+    encryptor: Option<ContentEncryptor>,
+    /// Number of bytes written so far, before compression and encryption
+    written_len: u64,
+    finished: bool,
+}
+
+impl<'a, S: Read + Write + Seek + SyncData> FileWriter<'a, S> {
+    pub(crate) fn new(
+        archive: &'a mut Archive<S>,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        addr: u64,
+        cipher: Option<Cipher>,
+    ) -> Result<Self, ArchiveError> {
+        let hasher = archive.content_hasher();
+
+        let compressor = match archive.stream_codec_for(&name) {
+            Some(codec) => Some((codec, Compressor::new(codec)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            archive,
+            parent_dir,
+            name,
+            modif_time,
+            reserved: Segment {
+                start: addr,
+                len: 0,
+            },
+            hasher: Some(hasher),
+            compressor,
+            encryptor: cipher.map(ContentEncryptor::new),
+            written_len: 0,
+            finished: false,
+        })
+    }
+
+    /// Get the number of bytes written so far
+    pub fn written_len(&self) -> u64 {
+        self.written_len
+    }
+
+    /// Add the file to the archive
+    ///
+    /// Returns the new file's ID
+    pub fn finish(mut self) -> Result<FileId, ArchiveError> {
+        let compression = match self.compressor.take() {
+            Some((codec, compressor)) => {
+                self.store(&compressor.finish()?)?;
+
+                Some(FileCompression {
+                    codec,
+                    uncompressed_len: self.written_len,
+                })
+            }
+
+            None => None,
+        };
+
+        if let Some(encryptor) = self.encryptor.take() {
+            self.append(&encryptor.finish())?;
+        }
+
+        let id = self.archive.create_reserved_file(
+            self.parent_dir,
+            self.name.clone(),
+            self.modif_time,
+            self.reserved,
+            self.hasher.take().unwrap(),
+            compression,
+        )?;
+
+        self.finished = true;
+
+        Ok(id)
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData> FileWriter<'_, S> {
+    /// Write some data once compressed, encrypting it if the archive is
+    fn store(&mut self, data: &[u8]) -> Result<(), ArchiveError> {
+        match &mut self.encryptor {
+            Some(encryptor) => {
+                let encrypted = encryptor.update(data);
+                self.append(&encrypted)
+            }
+
+            None => self.append(data),
+        }
+    }
+
+    /// Write some data as it is stored after the content written so far
+    fn append(&mut self, data: &[u8]) -> Result<(), ArchiveError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.archive.append_to_reserved(&mut self.reserved, data)?;
+        self.hasher.as_mut().unwrap().update(data);
+
+        Ok(())
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData> Write for FileWriter<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let data = match &mut self.compressor {
+            Some((_, compressor)) => Cow::Owned(compressor.compress(buf)?),
+            None => Cow::Borrowed(buf),
+        };
+
+        self.store(&data).map_err(into_io_error)?;
+
+        self.written_len += u64::try_from(buf.len()).unwrap();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.archive.flush().map_err(into_io_error)
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData> Drop for FileWriter<'_, S> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.archive.release_reserved(self.reserved);
+        }
+    }
+}
+
+fn into_io_error(err: ArchiveError) -> Error {
+    match err {
+        ArchiveError::Io(err) => err,
+        err => Error::other(err),
+    }
+}
@@ -3,17 +3,29 @@ use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
 use crate::{
-    DirectoryId, DirectoryIdOrRoot, FileId, ItemId, ItemName, data::ft_segment::FileTableSegment,
+    ArchiveRegion, DirectoryId, DirectoryIdOrRoot, FileId, ItemId, ItemName,
+    coverage::Segment,
+    data::ft_segment::FileTableSegment,
+    verify::{find_overlaps, is_in_bounds},
 };
 
-// TODO: return computed DirContent
+/// Check the correctness of an archive's file table
+///
+/// Returns the content of each directory alongside the errors found. Items whose parent directory
+/// doesn't exist are still listed in their parent's content, so that they can be moved elsewhere.
 pub fn check_file_table_correctness(
     segments: &[FileTableSegment],
-) -> std::result::Result<HashMap<DirectoryIdOrRoot, DirContent>, Vec<FileTableCorrectnessError>> {
+) -> (
+    HashMap<DirectoryIdOrRoot, DirContent>,
+    Vec<FileTableCorrectnessError>,
+) {
     let mut errors = vec![];
     let mut dirs_content = HashMap::from([(DirectoryIdOrRoot::Root, DirContent::default())]);
+    let mut parents = HashMap::new();
 
     for dir in segments.iter().flat_map(|segment| &segment.dirs).flatten() {
+        parents.entry(dir.id).or_insert(dir.parent_dir);
+
         if dirs_content
             .insert(DirectoryIdOrRoot::NonRoot(dir.id), DirContent::default())
             .is_some()
@@ -26,6 +38,16 @@ pub fn check_file_table_correctness(
     }
 
     for dir in segments.iter().flat_map(|segment| &segment.dirs).flatten() {
+        if let DirectoryIdOrRoot::NonRoot(parent_dir_id) = dir.parent_dir
+            && !parents.contains_key(&parent_dir_id)
+        {
+            errors.push(FileTableCorrectnessError::MissingParentDirectory {
+                faulty_item_id: ItemId::Directory(dir.id),
+                faulty_item_name: dir.name.clone(),
+                parent_dir_id,
+            });
+        }
+
         let parent_dir_content = match dir.parent_dir {
             DirectoryIdOrRoot::Root => dirs_content.get_mut(&DirectoryIdOrRoot::Root).unwrap(),
             DirectoryIdOrRoot::NonRoot(parent_dir) => dirs_content
@@ -50,6 +72,16 @@ pub fn check_file_table_correctness(
     }
 
     for file in segments.iter().flat_map(|segment| &segment.files).flatten() {
+        if let DirectoryIdOrRoot::NonRoot(parent_dir_id) = file.parent_dir
+            && !parents.contains_key(&parent_dir_id)
+        {
+            errors.push(FileTableCorrectnessError::MissingParentDirectory {
+                faulty_item_id: ItemId::File(file.id),
+                faulty_item_name: file.name.clone(),
+                parent_dir_id,
+            });
+        }
+
         let parent_dir_content = match file.parent_dir {
             DirectoryIdOrRoot::Root => dirs_content.get_mut(&DirectoryIdOrRoot::Root).unwrap(),
             DirectoryIdOrRoot::NonRoot(parent_dir) => dirs_content
@@ -73,11 +105,60 @@ pub fn check_file_table_correctness(
         }
     }
 
-    if errors.is_empty() {
-        Ok(dirs_content)
-    } else {
-        Err(errors)
+    // Directories whose parents chain loops never reach the root
+    let mut visited = HashSet::new();
+
+    for dir in segments.iter().flat_map(|segment| &segment.dirs).flatten() {
+        let mut chain = HashSet::new();
+        let mut next = Some(dir.id);
+
+        while let Some(dir_id) = next {
+            if !visited.insert(dir_id) {
+                if chain.contains(&dir_id) {
+                    errors.push(FileTableCorrectnessError::ParentCycle {
+                        faulty_dir_id: dir_id,
+                    });
+                }
+
+                break;
+            }
+
+            chain.insert(dir_id);
+
+            next = match parents.get(&dir_id) {
+                Some(DirectoryIdOrRoot::NonRoot(parent_dir_id)) => Some(*parent_dir_id),
+                Some(DirectoryIdOrRoot::Root) | None => None,
+            };
+        }
     }
+
+    (dirs_content, errors)
+}
+
+/// Check that no region of the archive is located outside of it or overlaps another one
+pub fn check_regions_layout(
+    regions: &[(ArchiveRegion, Segment)],
+    archive_len: u64,
+) -> Vec<FileTableCorrectnessError> {
+    let mut errors = regions
+        .iter()
+        .filter(|(_, segment)| !is_in_bounds(*segment, archive_len))
+        .map(|(region, _)| FileTableCorrectnessError::OutOfBounds {
+            region: *region,
+            archive_len,
+        })
+        .collect::<Vec<_>>();
+
+    errors.extend(
+        find_overlaps(regions.iter().copied())
+            .into_iter()
+            .map(|(region, other)| match other {
+                ArchiveRegion::Header => FileTableCorrectnessError::HeaderOverlap { region },
+                _ => FileTableCorrectnessError::Overlap { region, other },
+            }),
+    );
+
+    errors
 }
 
 /// Error while validating the correctness of an archive's file table
@@ -115,9 +196,60 @@ pub enum FileTableCorrectnessError {
         /// ID of the faulty items' parent directory
         parent_dir_id: DirectoryIdOrRoot,
     },
+
+    /// An item's parent directory doesn't exist
+    #[error(
+        "Parent directory {parent_dir_id:?} of item {faulty_item_id:?} (name: '{faulty_item_name}') doesn't exist"
+    )]
+    MissingParentDirectory {
+        /// Orphaned item
+        faulty_item_id: ItemId,
+
+        /// Orphaned item's name
+        faulty_item_name: ItemName,
+
+        /// ID of the missing parent directory
+        parent_dir_id: DirectoryId,
+    },
+
+    /// A directory's parents chain loops back to it, so it can't be reached from the root directory
+    ///
+    /// Only one directory is reported for each cycle.
+    #[error("Directory {faulty_dir_id:?} is its own ancestor")]
+    ParentCycle {
+        /// Directory inside the cycle
+        faulty_dir_id: DirectoryId,
+    },
+
+    /// A region of the archive is located (even partially) outside of it
+    #[error("{region:?} is out of the archive's bounds (length {archive_len})")]
+    OutOfBounds {
+        /// Faulty region
+        region: ArchiveRegion,
+
+        /// Length of the archive
+        archive_len: u64,
+    },
+
+    /// A region of the archive overlaps the archive's header
+    #[error("{region:?} overlaps the archive's header")]
+    HeaderOverlap {
+        /// Faulty region
+        region: ArchiveRegion,
+    },
+
+    /// Two regions of the archive overlap
+    #[error("{region:?} overlaps {other:?}")]
+    Overlap {
+        /// Region starting last
+        region: ArchiveRegion,
+
+        /// Region it overlaps
+        other: ArchiveRegion,
+    },
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DirContent {
     pub dirs: HashSet<DirectoryId>,
     pub files: HashSet<FileId>,
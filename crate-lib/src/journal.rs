@@ -0,0 +1,291 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+use crate::source::Source;
+
+pub(crate) static JOURNAL_MAGIC_NUMBER: &[u8] = b"BAFJOURN";
+pub(crate) static JOURNAL_HEADER_SIZE: u64 = 56;
+pub(crate) static DEFAULT_JOURNAL_CAPACITY: u64 = 4096;
+
+/// Representation of an archive's write-ahead journal
+///
+/// Before any metadata mutation, the list of writes that will be performed is recorded in the journal.
+/// If the process crashes while the writes are being applied, they are replayed when the archive is opened.
+/// If it crashes while the record is being written, the record is discarded and the mutation never happened.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    /// Address of the journal inside the archive
+    pub addr: u64,
+
+    /// Maximum length of an encoded record
+    pub capacity: u64,
+
+    /// Record that was committed but not cleared yet
+    ///
+    /// Its writes may or may not have been applied on disk, but they are always reflected in memory.
+    pub pending: Option<JournalRecord>,
+}
+
+impl Journal {
+    /// Decode a journal located at the provided address
+    pub fn decode(
+        source: &mut Source<impl Read + Seek>,
+        addr: u64,
+    ) -> Result<Self, JournalDecodingError> {
+        let source_len = source.seek_len()?;
+
+        if addr
+            .checked_add(JOURNAL_HEADER_SIZE)
+            .is_none_or(|end| end > source_len)
+        {
+            return Err(JournalDecodingError::OutOfBounds { addr });
+        }
+
+        source.set_position(addr)?;
+
+        let got_magic_number = source.read_into_array::<8>()?;
+
+        if got_magic_number != JOURNAL_MAGIC_NUMBER {
+            return Err(JournalDecodingError::InvalidMagicNumber { addr });
+        }
+
+        let capacity = source.read_value::<u64>()?;
+        let record_len = source.read_value::<u64>()?;
+        let checksum = source.read_value::<[u8; 32]>()?;
+
+        if (addr + JOURNAL_HEADER_SIZE)
+            .checked_add(capacity)
+            .is_none_or(|end| end > source_len)
+        {
+            return Err(JournalDecodingError::OutOfBounds { addr });
+        }
+
+        if record_len > capacity {
+            return Err(JournalDecodingError::RecordTooLarge {
+                record_len,
+                capacity,
+            });
+        }
+
+        let pending = if record_len == 0 {
+            None
+        } else {
+            let mut record = vec![0; usize::try_from(record_len).unwrap()];
+            source.read_exact(&mut record)?;
+
+            // If the checksum doesn't match, the record was not entirely written,
+            // which means none of its writes were applied: it can be safely discarded
+            if <[u8; 32]>::from(Sha3_256::digest(&record)) != checksum {
+                None
+            } else {
+                let record =
+                    JournalRecord::decode(&record).ok_or(JournalDecodingError::InvalidRecord)?;
+
+                // Writes must stay inside the archive, and can't overwrite the journal itself
+                let journal_end = addr + JOURNAL_HEADER_SIZE + capacity;
+
+                if record.writes.iter().any(|write| {
+                    write.end() > source_len || (write.addr < journal_end && write.end() > addr)
+                }) {
+                    return Err(JournalDecodingError::InvalidRecord);
+                }
+
+                Some(record)
+            }
+        };
+
+        Ok(Self {
+            addr,
+            capacity,
+            pending,
+        })
+    }
+
+    /// Encode an empty journal with the provided capacity
+    pub fn encode_empty(capacity: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        bytes.extend(JOURNAL_MAGIC_NUMBER);
+        bytes.extend(capacity.to_le_bytes());
+        bytes.extend(vec![
+            0;
+            usize::try_from(JOURNAL_HEADER_SIZE + capacity).unwrap()
+                - 16
+        ]);
+
+        bytes
+    }
+
+    /// Length of the journal inside the archive
+    pub fn encoded_len(&self) -> u64 {
+        JOURNAL_HEADER_SIZE + self.capacity
+    }
+
+    /// Address of the record's length and checksum, which are written together to commit it
+    pub fn commit_addr(&self) -> u64 {
+        self.addr + 16
+    }
+
+    /// Address of the record itself
+    pub fn record_addr(&self) -> u64 {
+        self.addr + JOURNAL_HEADER_SIZE
+    }
+}
+
+/// List of writes to perform atomically
+#[derive(Debug, Clone)]
+pub(crate) struct JournalRecord {
+    pub writes: Vec<JournalWrite>,
+}
+
+impl JournalRecord {
+    /// Decode a record, returning `None` if it is malformed
+    pub fn decode(mut bytes: &[u8]) -> Option<Self> {
+        let mut writes = vec![];
+
+        while !bytes.is_empty() {
+            let addr = u64::from_le_bytes(bytes.get(0..8)?.try_into().unwrap());
+            let len = u64::from_le_bytes(bytes.get(8..16)?.try_into().unwrap());
+            // The write must not overflow the address space
+            addr.checked_add(len)?;
+
+            let len = usize::try_from(len).ok()?;
+
+            let data = bytes.get(16..16_usize.checked_add(len)?)?.to_vec();
+
+            bytes = &bytes[16 + len..];
+
+            writes.push(JournalWrite { addr, data });
+        }
+
+        Some(Self { writes })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        for JournalWrite { addr, data } in &self.writes {
+            bytes.extend(addr.to_le_bytes());
+            bytes.extend(u64::try_from(data.len()).unwrap().to_le_bytes());
+            bytes.extend(data);
+        }
+
+        bytes
+    }
+}
+
+/// A single write inside a [`JournalRecord`]
+#[derive(Debug, Clone)]
+pub(crate) struct JournalWrite {
+    pub addr: u64,
+    pub data: Vec<u8>,
+}
+
+impl JournalWrite {
+    /// Address right after the written data
+    pub fn end(&self) -> u64 {
+        self.addr + u64::try_from(self.data.len()).unwrap()
+    }
+}
+
+/// Reader that reflects a record's writes on top of an underlying stream, without modifying it
+///
+/// Used to decode an archive's metadata as if a pending record had been replayed,
+/// including when the archive was opened in read-only mode.
+pub(crate) struct JournalOverlay<'a, S: Read + Seek> {
+    inner: &'a mut S,
+    writes: &'a [JournalWrite],
+    pos: u64,
+}
+
+impl<'a, S: Read + Seek> JournalOverlay<'a, S> {
+    pub fn new(inner: &'a mut S, writes: &'a [JournalWrite]) -> Self {
+        Self {
+            inner,
+            writes,
+            pos: 0,
+        }
+    }
+}
+
+impl<S: Read + Seek> Read for JournalOverlay<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+
+        let read = self.inner.read(buf)?;
+        let buf = &mut buf[..read];
+
+        let start = self.pos;
+        let end = start + u64::try_from(read).unwrap();
+
+        // Later writes take precedence over earlier ones
+        for write in self.writes {
+            let JournalWrite { addr, data } = write;
+            let write_end = write.end();
+
+            if write_end <= start || *addr >= end {
+                continue;
+            }
+
+            let from = start.max(*addr);
+            let to = end.min(write_end);
+
+            buf[usize::try_from(from - start).unwrap()..usize::try_from(to - start).unwrap()]
+                .copy_from_slice(
+                    &data[usize::try_from(from - addr).unwrap()
+                        ..usize::try_from(to - addr).unwrap()],
+                );
+        }
+
+        self.pos = end;
+
+        Ok(read)
+    }
+}
+
+impl<S: Read + Seek> Seek for JournalOverlay<'_, S> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Error while decoding an archive's journal
+#[derive(Error, Debug)]
+pub enum JournalDecodingError {
+    /// Native I/O error while reading the journal
+    #[error("I/O error reading journal: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The journal is located (even partially) outside of the archive
+    #[error("Journal at address {addr} is out of the archive's bounds")]
+    OutOfBounds {
+        /// Address of the journal
+        addr: u64,
+    },
+
+    /// Magic number does not match
+    #[error("Invalid magic number for journal at address {addr}")]
+    InvalidMagicNumber {
+        /// Address of the journal
+        addr: u64,
+    },
+
+    /// The pending record is larger than the journal's capacity
+    #[error(
+        "Journal record of {record_len} bytes exceeds the journal's capacity of {capacity} bytes"
+    )]
+    RecordTooLarge {
+        /// Length of the record
+        record_len: u64,
+        /// Capacity of the journal
+        capacity: u64,
+    },
+
+    /// The pending record has a valid checksum but cannot be decoded
+    #[error("Journal record is malformed")]
+    InvalidRecord,
+}
@@ -10,13 +10,31 @@
 #![warn(missing_docs)]
 
 mod archive;
+#[cfg(feature = "async")]
+mod async_archive;
+#[cfg(feature = "async")]
+mod async_file_reader;
+mod attributes;
+mod bytes_archive;
+mod chunks;
+mod compression;
 mod config;
 mod coverage;
 mod data;
+mod encryption;
 mod file_reader;
+mod file_writer;
 mod health;
 mod iter;
+mod journal;
+#[cfg(feature = "async")]
+mod metadata_cache;
+mod salvage;
 mod source;
+mod symlinks;
+mod transaction;
+mod undo;
+mod verify;
 mod with_paths;
 mod with_paths_mut;
 
@@ -25,35 +43,42 @@ mod tests;
 
 // Re-export useful types directly from the root
 pub use self::{
-    archive::{Archive, ArchiveError, ArchiveMetadataDecodingError, DirEntry, ItemId},
-    config::ArchiveConfig,
+    archive::{
+        Archive, ArchiveError, ArchiveMetadataDecodingError, CompactionReport, DirEntry, ItemId,
+        LOST_AND_FOUND_DIR_NAME,
+    },
+    attributes::UnixAttributes,
+    bytes_archive::BytesArchive,
+    compression::{CompressionCodec, CompressionPolicy, FileCompression},
+    config::{ArchiveConfig, OpenLimits},
     data::{
         directory::{Directory, DirectoryDecodingError, DirectoryId, DirectoryIdOrRoot},
         file::{File, FileDecodingError, FileId},
-        header::HeaderDecodingError,
-        name::{ItemName, NameDecodingError, NameDecodingErrorReason, NameValidationError},
+        header::{ArchiveVersion, FeatureFlags, HeaderDecodingError},
+        name::{
+            ItemName, MAX_ENCRYPTED_NAME_LEN, MAX_INLINE_NAME_LEN, MAX_NAME_LEN, NameDecodingError,
+            NameDecodingErrorReason, NameValidationError,
+        },
         path::{PathError, PathInArchive},
+        record::ItemRecordDecodingError,
         timestamp::{Timestamp, TimestampError},
     },
+    encryption::EncryptionKey,
     file_reader::{FileReader, FileReaderError},
+    file_writer::FileWriter,
     health::FileTableCorrectnessError,
     iter::ArchiveIter,
-    with_paths::{ItemIdOrRoot, PathAccessError, WithPaths},
+    journal::JournalDecodingError,
+    salvage::{SalvageDiagnostic, SalvageIssue, SalvagedArchive},
+    source::{ReadAt, SyncData, Truncate},
+    symlinks::MAX_SYMLINK_TARGET_LEN,
+    transaction::Transaction,
+    verify::{ArchiveRegion, IntegrityError, VerificationReport, VerifyOptions},
+    with_paths::{ItemIdOrRoot, MAX_SYMLINK_HOPS, PathAccessError, WithPaths},
     with_paths_mut::WithPathsMut,
 };
 
-/// This macro is used to ensure, at compile-time, that only one single
-/// version of the BAF archives are supported.
-///
-/// This allows to simplify code by not dealing with different versions.
-///
-/// This will be removed when multiple versions will exist.
-#[doc(hidden)]
-#[macro_export]
-macro_rules! ensure_only_one_version {
-    ($version: expr) => {
-        match $version {
-            $crate::data::header::ArchiveVersion::One => {}
-        }
-    };
-}
+#[cfg(feature = "async")]
+pub use self::{
+    async_archive::AsyncArchive, async_file_reader::AsyncFileReader, metadata_cache::MetadataCache,
+};
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{SyncData, coverage::Segment};
+
+/// In-memory copy of the parts of an archive that were loaded, used as the stream of an [`crate::Archive`]
+///
+/// This allows [`crate::AsyncArchive`] to rely on the synchronous decoding and mutation logic:
+/// the metadata is loaded asynchronously beforehand, and the writes are recorded so they can be
+/// applied asynchronously afterwards.
+///
+/// Reading a part of the archive that wasn't loaded results in an error.
+#[derive(Debug)]
+pub struct MetadataCache {
+    len: u64,
+    pos: u64,
+    loaded: BTreeMap<u64, Vec<u8>>,
+    writes: Vec<CachedWrite>,
+}
+
+/// Operation recorded by a [`MetadataCache`], to be applied to the archive's stream
+#[derive(Debug)]
+pub(crate) enum CachedWrite {
+    /// Write some data at the provided address
+    Data { addr: u64, data: Vec<u8> },
+
+    /// Persist all previous writes before applying the next ones
+    Sync,
+}
+
+impl MetadataCache {
+    /// Create a cache for an archive of the provided length, with nothing loaded yet
+    pub(crate) fn new(len: u64) -> Self {
+        Self {
+            len,
+            pos: 0,
+            loaded: BTreeMap::new(),
+            writes: vec![],
+        }
+    }
+
+    /// Make the archive at least as long as the provided length
+    pub(crate) fn grow_to(&mut self, len: u64) {
+        self.len = self.len.max(len);
+    }
+
+    /// Get the part of a region that needs to be loaded
+    ///
+    /// The region is cut to the archive's length. Returns `None` if it is already loaded.
+    pub(crate) fn missing(&self, addr: u64, len: u64) -> Option<Segment> {
+        let end = addr.saturating_add(len).min(self.len);
+
+        if addr >= end {
+            return None;
+        }
+
+        let loaded = self
+            .loaded
+            .range(..=addr)
+            .next_back()
+            .is_some_and(|(start, data)| start + u64::try_from(data.len()).unwrap() >= end);
+
+        (!loaded).then_some(Segment {
+            start: addr,
+            len: end - addr,
+        })
+    }
+
+    /// Store data loaded from the archive
+    pub(crate) fn insert(&mut self, addr: u64, data: &[u8]) {
+        let end = addr + u64::try_from(data.len()).unwrap();
+
+        // Merge with all loaded regions overlapping or touching the new one
+        let merged = self
+            .loaded
+            .range(..=end)
+            .filter(|(start, data)| *start + u64::try_from(data.len()).unwrap() >= addr)
+            .map(|(start, _)| *start)
+            .collect::<Vec<_>>();
+
+        let start = merged.first().copied().unwrap_or(addr).min(addr);
+
+        let mut buf = vec![];
+
+        for region_start in merged {
+            let region = self.loaded.remove(&region_start).unwrap();
+            let offset = usize::try_from(region_start - start).unwrap();
+
+            if buf.len() < offset + region.len() {
+                buf.resize(offset + region.len(), 0);
+            }
+
+            buf[offset..offset + region.len()].copy_from_slice(&region);
+        }
+
+        let offset = usize::try_from(addr - start).unwrap();
+
+        if buf.len() < offset + data.len() {
+            buf.resize(offset + data.len(), 0);
+        }
+
+        buf[offset..offset + data.len()].copy_from_slice(data);
+
+        self.loaded.insert(start, buf);
+    }
+
+    /// Take the writes performed since the last call, in order
+    pub(crate) fn take_writes(&mut self) -> Vec<CachedWrite> {
+        std::mem::take(&mut self.writes)
+    }
+}
+
+impl Read for MetadataCache {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let (start, data) = self
+            .loaded
+            .range(..=self.pos)
+            .next_back()
+            .filter(|(start, data)| *start + u64::try_from(data.len()).unwrap() > self.pos)
+            .ok_or_else(|| {
+                Error::other(format!(
+                    "archive's data at address {} was not loaded",
+                    self.pos
+                ))
+            })?;
+
+        let offset = usize::try_from(self.pos - start).unwrap();
+        let len = buf.len().min(data.len() - offset);
+
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.pos += u64::try_from(len).unwrap();
+
+        Ok(len)
+    }
+}
+
+impl Write for MetadataCache {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.insert(self.pos, buf);
+        self.writes.push(CachedWrite::Data {
+            addr: self.pos,
+            data: buf.to_vec(),
+        });
+
+        self.pos += u64::try_from(buf.len()).unwrap();
+        self.len = self.len.max(self.pos);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncData for MetadataCache {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        self.writes.push(CachedWrite::Sync);
+        Ok(())
+    }
+}
+
+impl Seek for MetadataCache {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        self.pos = new_pos.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        Ok(self.pos)
+    }
+}
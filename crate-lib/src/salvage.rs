@@ -0,0 +1,653 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque, hash_map::Entry},
+    io::{Read, Seek, Write},
+};
+
+use thiserror::Error;
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, Directory,
+    DirectoryDecodingError, DirectoryId, DirectoryIdOrRoot, File, FileDecodingError, FileId,
+    FileReader, FileReaderError, FileTableCorrectnessError, HeaderDecodingError, ItemId, ItemName,
+    JournalDecodingError, LOST_AND_FOUND_DIR_NAME, Timestamp,
+    config::OpenLimits,
+    coverage::Segment,
+    data::{
+        directory::DIRECTORY_ENTRY_SIZE,
+        file::FILE_ENTRY_SIZE,
+        ft_segment::FileTableSegment,
+        header::{HEADER_SIZE, Header, SourceWithHeader},
+    },
+    encryption::{ArchiveCipher, Cipher},
+    health::check_file_table_correctness,
+    journal::{Journal, JournalOverlay},
+    source::{Source, SyncData},
+    verify::is_in_bounds,
+};
+
+/// Data recovered from a damaged archive
+///
+/// Obtained from [`Archive::open_salvage`]
+///
+/// Recovered items may be inconsistent (e.g. a file's parent directory may not have been recovered),
+/// use [`SalvagedArchive::restore_into`] to rebuild a clean archive from them.
+pub struct SalvagedArchive<S: Read + Seek> {
+    source: Source<S>,
+    dirs: Vec<Directory>,
+    files: Vec<File>,
+    entries_addr: HashMap<ItemId, u64>,
+    diagnostics: Vec<SalvageDiagnostic>,
+    encrypted: bool,
+    cipher: Option<ArchiveCipher>,
+}
+
+impl<S: Read + Seek> SalvagedArchive<S> {
+    pub(crate) fn decode(
+        source: S,
+        conf: &ArchiveConfig,
+    ) -> Result<Self, ArchiveMetadataDecodingError> {
+        let mut source = Source::new(source);
+
+        let source_len = source
+            .seek_len()
+            .map_err(ArchiveMetadataDecodingError::IoError)?;
+
+        let mut diagnostics = vec![];
+
+        // Without a valid header, assume the archive uses the latest version
+        let header = match Header::decode(&mut source) {
+            Ok(decoded) => decoded.header,
+            Err(err) => {
+                diagnostics.push(SalvageDiagnostic {
+                    addr: Some(0),
+                    issue: SalvageIssue::InvalidHeader(err),
+                });
+
+                Header::default()
+            }
+        };
+
+        let journal = header.journal_addr.and_then(|addr| {
+            Journal::decode(&mut source, addr)
+                .map_err(|err| {
+                    diagnostics.push(SalvageDiagnostic {
+                        addr: Some(addr),
+                        issue: SalvageIssue::InvalidJournal(err),
+                    })
+                })
+                .ok()
+        });
+
+        // If a record is pending in the journal, recover the metadata as if it had been replayed
+        let pending_writes = journal
+            .as_ref()
+            .and_then(|journal| journal.pending.as_ref())
+            .map_or(&[] as &[_], |record| &record.writes);
+
+        let mut overlay = Source::new(JournalOverlay::new(source.get_mut(), pending_writes));
+
+        let encrypted = header.encryption.is_some();
+        let cipher = ArchiveCipher::unlock_archive(
+            header.encryption.as_ref(),
+            conf.encryption_key.as_ref(),
+        )?;
+
+        let mut input = SourceWithHeader {
+            source: &mut overlay,
+            header,
+            name_cipher: cipher.as_ref().and_then(|cipher| cipher.names.as_ref()),
+        };
+
+        let (dirs, files) =
+            decode_entries(&mut input, source_len, &conf.open_limits, &mut diagnostics);
+
+        let mut entries_addr = HashMap::new();
+
+        let dirs = dirs
+            .into_iter()
+            .filter(|(addr, dir)| {
+                keep_first_entry(
+                    &mut entries_addr,
+                    ItemId::Directory(dir.id),
+                    *addr,
+                    &mut diagnostics,
+                )
+            })
+            .map(|(_, dir)| dir)
+            .collect::<Vec<_>>();
+
+        let files = files
+            .into_iter()
+            .filter(|(addr, file)| {
+                if !keep_first_entry(
+                    &mut entries_addr,
+                    ItemId::File(file.id),
+                    *addr,
+                    &mut diagnostics,
+                ) {
+                    return false;
+                }
+
+                let content = Segment {
+                    start: file.content_addr,
+                    len: file.content_len,
+                };
+
+                if !is_in_bounds(content, source_len) {
+                    diagnostics.push(SalvageDiagnostic {
+                        addr: Some(*addr),
+                        issue: SalvageIssue::ContentOutOfBounds(file.id),
+                    });
+
+                    return false;
+                }
+
+                true
+            })
+            .map(|(_, file)| file)
+            .collect::<Vec<_>>();
+
+        // Report the items that won't be at their place in the restored archive
+        let recovered = FileTableSegment {
+            next_segment_addr: None,
+            dirs: dirs.iter().cloned().map(Some).collect(),
+            files: files.iter().cloned().map(Some).collect(),
+        };
+
+        let (_, errors) = check_file_table_correctness(&[recovered]);
+
+        diagnostics.extend(errors.into_iter().map(|err| {
+            let item_id = match &err {
+                FileTableCorrectnessError::MissingParentDirectory { faulty_item_id, .. }
+                | FileTableCorrectnessError::DuplicateItemInDirName { faulty_item_id, .. } => {
+                    Some(*faulty_item_id)
+                }
+                FileTableCorrectnessError::ParentCycle { faulty_dir_id } => {
+                    Some(ItemId::Directory(*faulty_dir_id))
+                }
+                _ => None,
+            };
+
+            SalvageDiagnostic {
+                addr: item_id.and_then(|item_id| entries_addr.get(&item_id).copied()),
+                issue: SalvageIssue::Misplaced(err),
+            }
+        }));
+
+        Ok(Self {
+            source,
+            dirs,
+            files,
+            entries_addr,
+            diagnostics,
+            encrypted,
+            cipher,
+        })
+    }
+
+    /// Get the recovered directories
+    pub fn dirs(&self) -> &[Directory] {
+        &self.dirs
+    }
+
+    /// Get the recovered files
+    ///
+    /// Their content is not verified until it is read.
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    /// Get the problems encountered while recovering the archive's items
+    pub fn diagnostics(&self) -> &[SalvageDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Get a [`FileReader`] over a recovered file
+    pub fn read_file(&mut self, id: FileId) -> Result<FileReader<'_, S>, ArchiveError> {
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.id == id)
+            .ok_or(ArchiveError::FileNotFound)?;
+
+        let cipher = self.content_cipher(file.content_len)?;
+
+        self.source.set_position(file.content_addr)?;
+
+        Ok(FileReader::new(
+            &mut self.source,
+            file.content_addr,
+            file.content_len,
+            file.sha3_checksum,
+            file.extensions.chunks.as_ref(),
+            file.extensions.compression,
+            cipher,
+        ))
+    }
+
+    /// Restore all recovered items into another archive
+    ///
+    /// Directories are restored before their content. Items whose parent directory wasn't recovered,
+    /// as well as directories whose parents chain loops back to them, are restored inside a
+    /// [`LOST_AND_FOUND_DIR_NAME`] directory at the target's root.
+    ///
+    /// Items keep their name if possible, or are named after their ID otherwise.
+    /// Files whose content doesn't match their checksum are not restored.
+    ///
+    /// Returns the problems that prevented some files from being restored.
+    pub fn restore_into<T: Read + Write + Seek + SyncData>(
+        &mut self,
+        target: &mut Archive<T>,
+    ) -> Result<Vec<SalvageDiagnostic>, ArchiveError> {
+        let mut lost_and_found = None;
+        let mut restored_dirs = HashMap::new();
+
+        let recovered_dirs = self.dirs.iter().map(|dir| dir.id).collect::<HashSet<_>>();
+
+        let mut children = HashMap::<_, Vec<_>>::new();
+
+        for dir in &self.dirs {
+            children.entry(dir.parent_dir).or_default().push(dir);
+        }
+
+        let mut queue = VecDeque::from([(DirectoryIdOrRoot::Root, DirectoryIdOrRoot::Root)]);
+
+        loop {
+            while let Some((old_parent_dir, new_parent_dir)) = queue.pop_front() {
+                for dir in children.remove(&old_parent_dir).unwrap_or_default() {
+                    // Directories inside a cycle may have been reattached already
+                    if restored_dirs.contains_key(&dir.id) {
+                        continue;
+                    }
+
+                    let id = restore_dir(target, dir, new_parent_dir)?;
+
+                    restored_dirs.insert(dir.id, id);
+                    queue.push_back((
+                        DirectoryIdOrRoot::NonRoot(dir.id),
+                        DirectoryIdOrRoot::NonRoot(id),
+                    ));
+                }
+            }
+
+            // Directories which are still not restored can't be reached from the root directory,
+            // prefer reattaching those whose parent wasn't recovered to keep the most of the hierarchy
+            let mut orphans = self
+                .dirs
+                .iter()
+                .filter(|dir| !restored_dirs.contains_key(&dir.id));
+
+            let orphan = orphans
+                .clone()
+                .find(|dir| match dir.parent_dir {
+                    DirectoryIdOrRoot::Root => false,
+                    DirectoryIdOrRoot::NonRoot(parent_dir) => !recovered_dirs.contains(&parent_dir),
+                })
+                .or_else(|| orphans.next());
+
+            let Some(orphan) = orphan else {
+                break;
+            };
+
+            let lost_and_found = get_lost_and_found(target, &mut lost_and_found)?;
+            let id = restore_dir(target, orphan, lost_and_found)?;
+
+            restored_dirs.insert(orphan.id, id);
+            queue.push_back((
+                DirectoryIdOrRoot::NonRoot(orphan.id),
+                DirectoryIdOrRoot::NonRoot(id),
+            ));
+        }
+
+        let mut issues = vec![];
+
+        for file in &self.files {
+            let parent_dir = match file.parent_dir {
+                DirectoryIdOrRoot::Root => DirectoryIdOrRoot::Root,
+                DirectoryIdOrRoot::NonRoot(parent_dir) => match restored_dirs.get(&parent_dir) {
+                    Some(id) => DirectoryIdOrRoot::NonRoot(*id),
+                    None => get_lost_and_found(target, &mut lost_and_found)?,
+                },
+            };
+
+            let cipher = self.content_cipher(file.content_len)?;
+
+            self.source.set_position(file.content_addr)?;
+
+            let mut reader = FileReader::new(
+                &mut self.source,
+                file.content_addr,
+                file.content_len,
+                file.sha3_checksum,
+                file.extensions.chunks.as_ref(),
+                file.extensions.compression,
+                cipher,
+            );
+
+            if let Err(err) = reader.verify() {
+                issues.push(SalvageDiagnostic {
+                    addr: self.entries_addr.get(&ItemId::File(file.id)).copied(),
+                    issue: SalvageIssue::InvalidContent {
+                        file_id: file.id,
+                        err,
+                    },
+                });
+
+                continue;
+            }
+
+            restore_with_name(&file.name, file.id.inner().get(), |name| {
+                target.create_file_from_reader(parent_dir, name, file.modif_time, &mut reader)
+            })?;
+        }
+
+        Ok(issues)
+    }
+
+    /// Get the cipher for a file's content of the provided length, if it is encrypted
+    fn content_cipher(&self, len: u64) -> Result<Option<Cipher>, ArchiveError> {
+        if !self.encrypted || len == 0 {
+            return Ok(None);
+        }
+
+        match &self.cipher {
+            Some(cipher) => Ok(Some(cipher.contents.clone())),
+            None => Err(ArchiveError::MissingEncryptionKey),
+        }
+    }
+}
+
+/// Directory and file entries, alongside their address
+type DecodedEntries = (Vec<(u64, Directory)>, Vec<(u64, File)>);
+
+/// Decode all file table entries that can be, in the order they appear in the segments
+fn decode_entries(
+    input: &mut SourceWithHeader<impl Read + Seek>,
+    source_len: u64,
+    limits: &OpenLimits,
+    diagnostics: &mut Vec<SalvageDiagnostic>,
+) -> DecodedEntries {
+    let mut dirs = vec![];
+    let mut files = vec![];
+
+    let mut visited_segments = HashSet::new();
+    let mut entries_count = 0_u64;
+    let mut metadata_len = 0_u64;
+    let mut next_segment_addr = Some(HEADER_SIZE as u64);
+
+    while let Some(segment_addr) = next_segment_addr {
+        let mut diagnose = |issue| {
+            diagnostics.push(SalvageDiagnostic {
+                addr: Some(segment_addr),
+                issue,
+            })
+        };
+
+        if !visited_segments.insert(segment_addr) {
+            diagnose(SalvageIssue::SegmentsCycle);
+            break;
+        }
+
+        let (next_addr, dirs_count, files_count) = match decode_segment_header(input, segment_addr)
+        {
+            Ok(header) => header,
+            Err(err) => {
+                diagnose(SalvageIssue::InvalidSegmentHeader(err));
+                break;
+            }
+        };
+
+        entries_count =
+            entries_count.saturating_add(u64::from(dirs_count) + u64::from(files_count));
+
+        metadata_len =
+            metadata_len.saturating_add(FileTableSegment::encoded_len_for(dirs_count, files_count));
+
+        if visited_segments.len() > limits.max_segments
+            || entries_count > limits.max_entries
+            || metadata_len > limits.max_metadata_len
+        {
+            diagnose(SalvageIssue::LimitExceeded);
+            break;
+        }
+
+        let entries = (0..dirs_count)
+            .map(|i| {
+                (
+                    true,
+                    segment_addr + 16 + u64::from(i) * (DIRECTORY_ENTRY_SIZE as u64),
+                )
+            })
+            .chain((0..files_count).map(|i| {
+                (
+                    false,
+                    segment_addr
+                        + 16
+                        + u64::from(dirs_count) * (DIRECTORY_ENTRY_SIZE as u64)
+                        + u64::from(i) * (FILE_ENTRY_SIZE as u64),
+                )
+            }));
+
+        let mut truncated = false;
+
+        for (is_dir, entry_addr) in entries {
+            let entry_size = if is_dir {
+                DIRECTORY_ENTRY_SIZE
+            } else {
+                FILE_ENTRY_SIZE
+            };
+
+            let entry = Segment {
+                start: entry_addr,
+                len: entry_size as u64,
+            };
+
+            if !is_in_bounds(entry, source_len) {
+                diagnostics.push(SalvageDiagnostic {
+                    addr: Some(entry_addr),
+                    issue: SalvageIssue::TruncatedSegment,
+                });
+
+                truncated = true;
+                break;
+            }
+
+            let issue = match input.source.set_position(entry_addr) {
+                Err(err) => {
+                    if is_dir {
+                        SalvageIssue::InvalidDirectoryEntry(DirectoryDecodingError::IoError(err))
+                    } else {
+                        SalvageIssue::InvalidFileEntry(FileDecodingError::IoError(err))
+                    }
+                }
+
+                Ok(()) if is_dir => match Directory::consume_from_reader(input) {
+                    Ok(dir) => {
+                        dirs.extend(dir.map(|dir| (entry_addr, dir)));
+                        continue;
+                    }
+                    Err(err) => SalvageIssue::InvalidDirectoryEntry(err),
+                },
+
+                Ok(()) => match File::consume_from_reader(input) {
+                    Ok(file) => {
+                        files.extend(file.map(|file| (entry_addr, file)));
+                        continue;
+                    }
+                    Err(err) => SalvageIssue::InvalidFileEntry(err),
+                },
+            };
+
+            diagnostics.push(SalvageDiagnostic {
+                addr: Some(entry_addr),
+                issue,
+            });
+        }
+
+        if truncated {
+            break;
+        }
+
+        next_segment_addr = next_addr;
+    }
+
+    (dirs, files)
+}
+
+/// Decode a segment's next segment address and entries count
+fn decode_segment_header(
+    input: &mut SourceWithHeader<impl Read + Seek>,
+    addr: u64,
+) -> std::io::Result<(Option<u64>, u32, u32)> {
+    input.source.set_position(addr)?;
+
+    let next_segment_addr = input.source.read_value::<u64>()?;
+    let dirs_count = input.source.read_value::<u32>()?;
+    let files_count = input.source.read_value::<u32>()?;
+
+    Ok((
+        Some(next_segment_addr).filter(|addr| *addr != 0),
+        dirs_count,
+        files_count,
+    ))
+}
+
+/// Check if an entry is the first one to use its ID
+fn keep_first_entry(
+    entries_addr: &mut HashMap<ItemId, u64>,
+    item_id: ItemId,
+    addr: u64,
+    diagnostics: &mut Vec<SalvageDiagnostic>,
+) -> bool {
+    match entries_addr.entry(item_id) {
+        Entry::Occupied(_) => {
+            diagnostics.push(SalvageDiagnostic {
+                addr: Some(addr),
+                issue: SalvageIssue::DuplicateId(item_id),
+            });
+
+            false
+        }
+
+        Entry::Vacant(entry) => {
+            entry.insert(addr);
+            true
+        }
+    }
+}
+
+fn restore_dir<T: Read + Write + Seek + SyncData>(
+    target: &mut Archive<T>,
+    dir: &Directory,
+    parent_dir: DirectoryIdOrRoot,
+) -> Result<DirectoryId, ArchiveError> {
+    restore_with_name(&dir.name, dir.id.inner().get(), |name| {
+        target.create_dir(parent_dir, name, dir.modif_time)
+    })
+}
+
+/// Create an item with its original name, or named after its ID if the name is already taken
+fn restore_with_name<T>(
+    name: &ItemName,
+    id: u64,
+    mut create: impl FnMut(ItemName) -> Result<T, ArchiveError>,
+) -> Result<T, ArchiveError> {
+    match create(name.clone()) {
+        Err(ArchiveError::DuplicateName { .. }) => create(ItemName::new(format!("#{id}")).unwrap()),
+        result => result,
+    }
+}
+
+/// Get the directory orphaned items are restored into, creating it if needed
+fn get_lost_and_found<T: Read + Write + Seek + SyncData>(
+    target: &mut Archive<T>,
+    lost_and_found: &mut Option<DirectoryIdOrRoot>,
+) -> Result<DirectoryIdOrRoot, ArchiveError> {
+    if let Some(lost_and_found) = lost_and_found {
+        return Ok(*lost_and_found);
+    }
+
+    let existing = target
+        .with_paths()
+        .get_dir_at(LOST_AND_FOUND_DIR_NAME)
+        .map(|dir| dir.id);
+
+    let id = match existing {
+        Some(id) => id,
+        None => target.create_dir(
+            DirectoryIdOrRoot::Root,
+            ItemName::new(LOST_AND_FOUND_DIR_NAME.to_owned()).unwrap(),
+            Timestamp::now(),
+        )?,
+    };
+
+    Ok(*lost_and_found.insert(DirectoryIdOrRoot::NonRoot(id)))
+}
+
+/// Problem encountered while recovering data from a damaged archive
+#[derive(Debug)]
+pub struct SalvageDiagnostic {
+    /// Address of the faulty data inside the archive, if known
+    pub addr: Option<u64>,
+
+    /// Description of the problem
+    pub issue: SalvageIssue,
+}
+
+/// Description of a problem encountered while recovering data from a damaged archive
+#[derive(Error, Debug)]
+pub enum SalvageIssue {
+    /// The header is invalid, the archive is assumed to use the latest version
+    #[error("{0}")]
+    InvalidHeader(HeaderDecodingError),
+
+    /// The write-ahead journal is invalid, its pending record (if any) was ignored
+    #[error("{0}")]
+    InvalidJournal(JournalDecodingError),
+
+    /// A file table segment's header can't be read, the following segments can't be found
+    #[error("Invalid file table segment header: {0}")]
+    InvalidSegmentHeader(std::io::Error),
+
+    /// A file table segment was referenced twice, the following segments were ignored
+    #[error("File table segment is referenced twice")]
+    SegmentsCycle,
+
+    /// A file table segment exceeds [`ArchiveConfig::open_limits`], it and the following segments were ignored
+    #[error("File table segment exceeds the open limits")]
+    LimitExceeded,
+
+    /// A file table segment is truncated, the following entries and segments can't be found
+    #[error("File table segment is truncated")]
+    TruncatedSegment,
+
+    /// A directory entry is invalid and was skipped
+    #[error("{0}")]
+    InvalidDirectoryEntry(DirectoryDecodingError),
+
+    /// A file entry is invalid and was skipped
+    #[error("{0}")]
+    InvalidFileEntry(FileDecodingError),
+
+    /// An item's ID is used by several entries, only the first one was recovered
+    #[error("ID {0:?} is used by several entries")]
+    DuplicateId(ItemId),
+
+    /// A file's content is located (even partially) outside of the archive, the file was skipped
+    #[error("Content of file {0:?} is out of the archive's bounds")]
+    ContentOutOfBounds(FileId),
+
+    /// An item was recovered but can't be restored at its original place
+    #[error("{0}")]
+    Misplaced(FileTableCorrectnessError),
+
+    /// A file's content can't be restored
+    #[error("Content of file {file_id:?} can't be restored: {err}")]
+    InvalidContent {
+        /// ID of the file
+        file_id: FileId,
+
+        /// Error encountered while reading the content
+        err: FileReaderError,
+    },
+}
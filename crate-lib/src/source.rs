@@ -1,4 +1,7 @@
-use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
+};
 
 /// Represent a source from which an [`crate::archive::Archive`] can be opened.
 ///
@@ -40,6 +43,18 @@ impl<S: Read> Source<S> {
         T::read_from(self)
     }
 
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        self.reader.get_ref()
+    }
+
+    /// Get a mutable reference to the underlying stream
+    ///
+    /// The stream's position must be set again before reading from the source afterwards
+    pub fn get_mut(&mut self) -> &mut S {
+        self.reader.get_mut()
+    }
+
     /// Get the underlying stream
     pub fn into_inner(self) -> S {
         self.reader.into_inner()
@@ -80,6 +95,12 @@ impl<S: Read + Seek> Source<S> {
     }
 }
 
+impl<S: Read> Read for Source<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
 // NOTE: In this impl block, we write without buffering (e.g. no `BufWriter`)
 //
 // The reason is that most writes are already made in chunks, and smaller ones
@@ -99,6 +120,158 @@ impl<S: Read + Write> Source<S> {
     }
 }
 
+impl<S: Read + Write + SyncData> Source<S> {
+    /// Flush all changes to the underlying stream, then wait until they are persisted
+    ///
+    /// All writes performed before calling this method are persisted before any write performed afterwards.
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.reader.get_mut().sync_data()
+    }
+}
+
+impl<S: Read + Seek + Truncate> Source<S> {
+    /// Truncate the underlying stream to the provided length
+    ///
+    /// The stream's position is reset to its beginning
+    pub fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.reader.get_mut().truncate(len)?;
+        self.set_position(0)
+    }
+}
+
+/// A stream whose length can be reduced
+///
+/// Required to give space back when compacting an archive (see [`crate::Archive::compact`])
+pub trait Truncate {
+    /// Truncate the stream to the provided length
+    fn truncate(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl Truncate for File {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.set_len(len)
+    }
+}
+
+impl Truncate for Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        let len = usize::try_from(len).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "length is too large")
+        })?;
+
+        self.get_mut().truncate(len);
+        Ok(())
+    }
+}
+
+impl Truncate for Cursor<&mut Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        let len = usize::try_from(len).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "length is too large")
+        })?;
+
+        self.get_mut().truncate(len);
+        Ok(())
+    }
+}
+
+/// A stream whose written data can be persisted
+///
+/// Required to order the writes of a mutation, so that a crash or a power loss never corrupts
+/// an archive (see [`crate::ArchiveConfig::journal`])
+pub trait SyncData {
+    /// Wait until all data written to the stream is persisted
+    fn sync_data(&mut self) -> std::io::Result<()>;
+}
+
+impl SyncData for File {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+impl SyncData for &File {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+// NOTE: In-memory streams are lost along with the process, so there is nothing to persist
+impl SyncData for Cursor<Vec<u8>> {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncData for Cursor<&mut Vec<u8>> {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A stream which can be read at any position without moving a shared cursor
+///
+/// Required to read several files of an archive at the same time (see [`crate::Archive::read_file_shared`])
+pub trait ReadAt {
+    /// Read as many bytes as needed to fill the provided buffer, starting at the provided position
+    ///
+    /// If not enough bytes can be read, an error will be returned.
+    fn read_exact_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<()>;
+}
+
+#[cfg(unix)]
+impl ReadAt for File {
+    fn read_exact_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<()> {
+        std::os::unix::fs::FileExt::read_exact_at(self, buf, pos)
+    }
+}
+
+// NOTE: On Windows, positional reads move the file's cursor, which is fine as the
+// archive always sets the position before reading from its source
+#[cfg(windows)]
+impl ReadAt for File {
+    fn read_exact_at(&self, mut buf: &mut [u8], mut pos: u64) -> std::io::Result<()> {
+        use std::os::windows::fs::FileExt;
+
+        while !buf.is_empty() {
+            match self.seek_read(buf, pos) {
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ));
+                }
+                Ok(read) => {
+                    buf = &mut buf[read..];
+                    pos += u64::try_from(read).unwrap();
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>> ReadAt for Cursor<T> {
+    fn read_exact_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<()> {
+        let bytes = usize::try_from(pos)
+            .ok()
+            .and_then(|pos| self.get_ref().as_ref().get(pos..)?.get(..buf.len()))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                )
+            })?;
+
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
 /// A trait representing a value that can be read from a source
 pub trait FromSourceBytes {
     /// Read the required bytes to make the value from the provided source
@@ -0,0 +1,7 @@
+/// Maximum length of a symbolic link's target, in bytes
+pub static MAX_SYMLINK_TARGET_LEN: usize = 4096;
+
+/// Check if a symbolic link's target can be stored in its record
+pub(crate) fn is_valid_symlink_target(target: &str) -> bool {
+    !target.is_empty() && target.len() <= MAX_SYMLINK_TARGET_LEN && !target.contains('\0')
+}
@@ -1,8 +1,9 @@
 use std::io::Cursor;
 
 use crate::{
-    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, DirEntry, DirectoryId,
-    DirectoryIdOrRoot, FileId, ItemName, Timestamp,
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, ArchiveRegion, DirEntry,
+    DirectoryId, DirectoryIdOrRoot, FileId, FileTableCorrectnessError, ItemName, OpenLimits,
+    Timestamp,
 };
 
 fn create_empty_archive() -> Archive<Cursor<Vec<u8>>> {
@@ -10,7 +11,7 @@ fn create_empty_archive() -> Archive<Cursor<Vec<u8>>> {
 }
 
 fn add_test_dir(
-    archive: &mut Archive<impl std::io::Read + std::io::Write + std::io::Seek>,
+    archive: &mut Archive<impl std::io::Read + std::io::Write + std::io::Seek + crate::SyncData>,
 ) -> DirectoryId {
     archive
         .create_dir(
@@ -351,7 +352,7 @@ fn test_replace_file_smaller() {
 #[test]
 fn test_version() {
     let archive = create_empty_archive();
-    assert_eq!(archive.version().version_number(), 1);
+    assert_eq!(archive.version().version_number(), 2);
 }
 
 #[test]
@@ -484,6 +485,351 @@ fn test_open_invalid_header_io() {
     ));
 }
 
+/// Create an archive with two files, and get its bytes alongside the files' IDs
+fn create_archive_bytes_with_two_files() -> (Vec<u8>, FileId, FileId) {
+    let mut archive = create_empty_archive();
+
+    let [first, second] = ["first", "second"].map(|name| {
+        archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                ItemName::new(name.to_owned()).unwrap(),
+                Timestamp::now(),
+                Cursor::new(name.as_bytes().to_vec()),
+            )
+            .unwrap()
+    });
+
+    (archive.close().unwrap().into_inner(), first, second)
+}
+
+/// Change the content address of a file's entry, located using its current content address and length
+fn patch_content_addr(bytes: &mut [u8], content_addr: u64, content_len: u64, new_addr: u64) {
+    let pattern = [content_addr.to_le_bytes(), content_len.to_le_bytes()].concat();
+
+    let pos = bytes
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .unwrap();
+
+    bytes[pos..pos + 8].copy_from_slice(&new_addr.to_le_bytes());
+}
+
+fn open_correctness_errors(bytes: Vec<u8>) -> Vec<FileTableCorrectnessError> {
+    match Archive::open(Cursor::new(bytes), ArchiveConfig::default()) {
+        Err(ArchiveMetadataDecodingError::FileTableCorrectnessError(errors)) => errors,
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Archive was opened successfully"),
+    }
+}
+
+#[test]
+fn test_open_overlapping_content() {
+    let (mut bytes, first, second) = create_archive_bytes_with_two_files();
+
+    let archive = Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+    let first_addr = archive.get_file(first).unwrap().content_addr;
+    let second_addr = archive.get_file(second).unwrap().content_addr;
+
+    patch_content_addr(&mut bytes, second_addr, 6, first_addr + 2);
+
+    let errors = open_correctness_errors(bytes);
+    assert!(matches!(
+        errors.as_slice(),
+        [FileTableCorrectnessError::Overlap {
+            region: ArchiveRegion::FileContent(region),
+            other: ArchiveRegion::FileContent(other),
+        }] if *region == second && *other == first
+    ));
+}
+
+#[test]
+fn test_open_content_overlapping_header() {
+    let (mut bytes, first, _) = create_archive_bytes_with_two_files();
+
+    let archive = Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+    let first_addr = archive.get_file(first).unwrap().content_addr;
+
+    patch_content_addr(&mut bytes, first_addr, 5, 10);
+
+    let errors = open_correctness_errors(bytes);
+    assert!(matches!(
+        errors.as_slice(),
+        [FileTableCorrectnessError::HeaderOverlap {
+            region: ArchiveRegion::FileContent(region),
+        }] if *region == first
+    ));
+}
+
+#[test]
+fn test_open_out_of_bounds_content() {
+    let (mut bytes, first, _) = create_archive_bytes_with_two_files();
+
+    let archive = Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+    let first_addr = archive.get_file(first).unwrap().content_addr;
+
+    patch_content_addr(&mut bytes, first_addr, 5, u64::MAX - 2);
+
+    let errors = open_correctness_errors(bytes);
+    assert!(matches!(
+        errors.as_slice(),
+        [FileTableCorrectnessError::OutOfBounds {
+            region: ArchiveRegion::FileContent(region),
+            ..
+        }] if *region == first
+    ));
+}
+
+#[test]
+fn test_open_cyclic_segments() {
+    let mut bytes = create_empty_archive().close().unwrap().into_inner();
+
+    // Make the first segment point to itself
+    bytes[256..264].copy_from_slice(&256_u64.to_le_bytes());
+
+    let err = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+        .err()
+        .unwrap();
+
+    assert!(matches!(
+        err,
+        ArchiveMetadataDecodingError::SegmentsCycle { addr: 256 }
+    ));
+}
+
+#[test]
+fn test_open_huge_entries_count() {
+    let mut bytes = create_empty_archive().close().unwrap().into_inner();
+
+    bytes[264..268].copy_from_slice(&u32::MAX.to_le_bytes());
+    bytes[268..272].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let err = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+        .err()
+        .unwrap();
+
+    assert!(matches!(
+        err,
+        ArchiveMetadataDecodingError::TooManyEntries { .. }
+    ));
+}
+
+#[test]
+fn test_open_limits() {
+    let conf = ArchiveConfig {
+        default_files_capacity_by_ft_segment: std::num::NonZero::new(2).unwrap(),
+        first_segment_files_capacity_override: Some(std::num::NonZero::new(2).unwrap()),
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    for i in 0..3 {
+        archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                ItemName::new(format!("file{i}")).unwrap(),
+                Timestamp::now(),
+                Cursor::new(vec![]),
+            )
+            .unwrap();
+    }
+
+    let bytes = archive.close().unwrap().into_inner();
+
+    let open_with_limits = |open_limits| {
+        Archive::open(
+            Cursor::new(bytes.clone()),
+            ArchiveConfig {
+                open_limits,
+                ..ArchiveConfig::default()
+            },
+        )
+    };
+
+    assert!(matches!(
+        open_with_limits(OpenLimits {
+            max_segments: 1,
+            ..OpenLimits::default()
+        }),
+        Err(ArchiveMetadataDecodingError::TooManySegments { max: 1 })
+    ));
+
+    assert!(matches!(
+        open_with_limits(OpenLimits {
+            max_entries: 20,
+            ..OpenLimits::default()
+        }),
+        Err(ArchiveMetadataDecodingError::TooManyEntries { max: 20 })
+    ));
+
+    assert!(matches!(
+        open_with_limits(OpenLimits {
+            max_metadata_len: 5000,
+            ..OpenLimits::default()
+        }),
+        Err(ArchiveMetadataDecodingError::MetadataTooLarge { max: 5000 })
+    ));
+
+    let archive = open_with_limits(OpenLimits::unlimited()).unwrap();
+    assert_eq!(archive.files().count(), 3);
+}
+
+/// Change the parent of a directory's entry, located using its current ID, parent and name
+fn patch_dir_parent(bytes: &mut [u8], dir: &crate::Directory, new_parent: u64) {
+    let old_parent = match dir.parent_dir {
+        DirectoryIdOrRoot::Root => 0,
+        DirectoryIdOrRoot::NonRoot(id) => id.inner().get(),
+    };
+
+    let pattern = [
+        dir.id.inner().get().to_le_bytes().as_slice(),
+        &old_parent.to_le_bytes(),
+        &[dir.name.len() as u8],
+        dir.name.as_bytes(),
+    ]
+    .concat();
+
+    let pos = bytes
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .unwrap();
+
+    bytes[pos + 8..pos + 16].copy_from_slice(&new_parent.to_le_bytes());
+}
+
+/// Create an archive with the `a/b/c` directories, and an `a/b/file` file
+fn create_archive_bytes_with_nested_dirs() -> (Vec<u8>, [crate::Directory; 3], FileId) {
+    let mut archive = create_empty_archive();
+
+    let mut add_dir = |parent_dir, name: &str| {
+        archive
+            .create_dir(
+                parent_dir,
+                ItemName::new(name.to_owned()).unwrap(),
+                Timestamp::now(),
+            )
+            .unwrap()
+    };
+
+    let a = add_dir(DirectoryIdOrRoot::Root, "a");
+    let b = add_dir(DirectoryIdOrRoot::NonRoot(a), "b");
+    let c = add_dir(DirectoryIdOrRoot::NonRoot(b), "c");
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(b),
+            ItemName::new("file".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"content".to_vec()),
+        )
+        .unwrap();
+
+    let dirs = [a, b, c].map(|id| archive.get_dir(id).unwrap().clone());
+
+    (archive.close().unwrap().into_inner(), dirs, file)
+}
+
+#[test]
+fn test_open_missing_parent() {
+    let (mut bytes, [_, _, c], _) = create_archive_bytes_with_nested_dirs();
+
+    patch_dir_parent(&mut bytes, &c, 999);
+
+    let errors = open_correctness_errors(bytes);
+    assert!(matches!(
+        errors.as_slice(),
+        [FileTableCorrectnessError::MissingParentDirectory {
+            faulty_item_id: crate::ItemId::Directory(faulty_item_id),
+            parent_dir_id,
+            ..
+        }] if *faulty_item_id == c.id && parent_dir_id.inner().get() == 999
+    ));
+}
+
+#[test]
+fn test_open_parent_cycle() {
+    let (mut bytes, [a, b, _], _) = create_archive_bytes_with_nested_dirs();
+
+    patch_dir_parent(&mut bytes, &a, b.id.inner().get());
+
+    let errors = open_correctness_errors(bytes);
+    assert!(matches!(
+        errors.as_slice(),
+        [FileTableCorrectnessError::ParentCycle { faulty_dir_id }]
+            if *faulty_dir_id == a.id || *faulty_dir_id == b.id
+    ));
+}
+
+#[test]
+fn test_open_with_lost_and_found() {
+    let (mut bytes, [a, b, c], file) = create_archive_bytes_with_nested_dirs();
+
+    // Make "a" and "b" form a cycle, and "c" an orphan
+    patch_dir_parent(&mut bytes, &a, b.id.inner().get());
+    patch_dir_parent(&mut bytes, &c, 999);
+
+    let (archive, orphans) =
+        Archive::open_with_lost_and_found(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(orphans.len(), 2);
+    assert!(orphans.contains(&crate::ItemId::Directory(c.id)));
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    let with_paths = archive.with_paths();
+    let lost_and_found = with_paths
+        .get_dir_at(crate::LOST_AND_FOUND_DIR_NAME)
+        .unwrap();
+    assert_eq!(lost_and_found.parent_dir, DirectoryIdOrRoot::Root);
+
+    assert_eq!(with_paths.get_dir_at("lost+found/c").unwrap().id, c.id);
+
+    // Every item can be reached again
+    assert_eq!(archive.items_iter().count(), 5);
+
+    let file_entry = archive
+        .items_iter()
+        .find(|item| item.id() == crate::ItemId::File(file))
+        .unwrap();
+
+    assert_eq!(file_entry.name().as_ref(), "file");
+}
+
+#[test]
+fn test_open_with_lost_and_found_name_conflict() {
+    let (mut bytes, [_, b, c], _) = create_archive_bytes_with_nested_dirs();
+    let b_id = b.id.inner().get();
+
+    patch_dir_parent(&mut bytes, &c, 999);
+    patch_dir_parent(&mut bytes, &b, 998);
+
+    // Give both orphans the same name
+    let pos = bytes
+        .windows(2)
+        .position(|window| window == [1, b'c'])
+        .unwrap();
+
+    bytes[pos + 1] = b'b';
+
+    let (archive, orphans) =
+        Archive::open_with_lost_and_found(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(orphans.len(), 2);
+
+    let with_paths = archive.with_paths();
+    assert!(with_paths.get_dir_at("lost+found/b").is_some());
+    assert!(
+        with_paths
+            .get_dir_at(&format!("lost+found/#{b_id}"))
+            .is_some()
+            || with_paths
+                .get_dir_at(&format!("lost+found/#{}", c.id.inner()))
+                .is_some()
+    );
+}
+
 #[test]
 fn test_open_from_file_readonly() {
     let dir = tempfile::tempdir().unwrap();
@@ -555,8 +901,290 @@ fn test_archive_error_display() {
 
 #[test]
 fn test_archive_metadata_decoding_error_display() {
-    let io_err = ArchiveMetadataDecodingError::IoError(std::io::Error::other(
-        "test io error",
-    ));
+    let io_err = ArchiveMetadataDecodingError::IoError(std::io::Error::other("test io error"));
     assert!(format!("{io_err}").contains("test io error"));
 }
+
+#[test]
+fn test_move_file() {
+    let mut archive = create_empty_archive();
+    let dir_id = add_test_dir(&mut archive);
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("f".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"content".to_vec()),
+        )
+        .unwrap();
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    archive
+        .move_file(
+            file_id,
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            ItemName::new("g".to_owned()).unwrap(),
+        )
+        .unwrap();
+
+    let (_, root_files) = archive.get_dir_content(DirectoryIdOrRoot::Root).unwrap();
+    assert!(root_files.is_empty());
+    let (_, dir_files) = archive
+        .get_dir_content(DirectoryIdOrRoot::NonRoot(dir_id))
+        .unwrap();
+    assert!(dir_files.contains(&file_id));
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    let file = archive.get_file(file_id).unwrap();
+    assert_eq!(file.parent_dir, DirectoryIdOrRoot::NonRoot(dir_id));
+    assert_eq!(file.name.as_ref(), "g");
+    assert_eq!(file.content_addr, content_addr);
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), b"content");
+}
+
+#[test]
+fn test_move_file_duplicate_name() {
+    let mut archive = create_empty_archive();
+    let dir_id = add_test_dir(&mut archive);
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("f".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"a".to_vec()),
+        )
+        .unwrap();
+    archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            ItemName::new("f".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"b".to_vec()),
+        )
+        .unwrap();
+    let err = archive
+        .move_file(
+            file_id,
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            ItemName::new("f".to_owned()).unwrap(),
+        )
+        .unwrap_err();
+    assert!(matches!(err, ArchiveError::DuplicateName { .. }));
+}
+
+#[test]
+fn test_move_dir() {
+    let mut archive = create_empty_archive();
+    let dir_a = archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("a".to_owned()).unwrap(),
+            Timestamp::now(),
+        )
+        .unwrap();
+    let dir_b = archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("b".to_owned()).unwrap(),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    archive
+        .move_dir(
+            dir_b,
+            DirectoryIdOrRoot::NonRoot(dir_a),
+            ItemName::new("b".to_owned()).unwrap(),
+        )
+        .unwrap();
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    let (root_dirs, _) = archive.get_dir_content(DirectoryIdOrRoot::Root).unwrap();
+    assert_eq!(root_dirs.len(), 1);
+    let (a_dirs, _) = archive
+        .get_dir_content(DirectoryIdOrRoot::NonRoot(dir_a))
+        .unwrap();
+    assert!(a_dirs.contains(&dir_b));
+}
+
+#[test]
+fn test_move_dir_into_descendant() {
+    let mut archive = create_empty_archive();
+    let dir_a = archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("a".to_owned()).unwrap(),
+            Timestamp::now(),
+        )
+        .unwrap();
+    let dir_b = archive
+        .create_dir(
+            DirectoryIdOrRoot::NonRoot(dir_a),
+            ItemName::new("b".to_owned()).unwrap(),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    for new_parent in [dir_a, dir_b] {
+        let err = archive
+            .move_dir(
+                dir_a,
+                DirectoryIdOrRoot::NonRoot(new_parent),
+                ItemName::new("a".to_owned()).unwrap(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, ArchiveError::MoveIntoDescendant));
+    }
+}
+
+#[test]
+fn test_compact() {
+    let config = ArchiveConfig {
+        default_dirs_capacity_by_ft_segment: std::num::NonZero::new(1).unwrap(),
+        default_files_capacity_by_ft_segment: std::num::NonZero::new(2).unwrap(),
+        first_segment_dirs_capacity_override: Some(std::num::NonZero::new(1).unwrap()),
+        first_segment_files_capacity_override: Some(std::num::NonZero::new(1).unwrap()),
+        ..ArchiveConfig::default()
+    };
+    let mut archive = Archive::create(Cursor::new(vec![]), config.clone()).unwrap();
+
+    let mut kept = vec![];
+
+    for i in 0..8_u8 {
+        let file_id = archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                ItemName::new(format!("file{i}")).unwrap(),
+                Timestamp::now(),
+                Cursor::new(vec![i; 1000 * usize::from(i + 1)]),
+            )
+            .unwrap();
+
+        if i % 2 == 0 {
+            kept.push((file_id, vec![i; 1000 * usize::from(i + 1)]));
+        } else {
+            archive.remove_file(file_id).unwrap();
+        }
+    }
+
+    let report = archive.compact().unwrap();
+    assert!(report.reclaimed_bytes() > 0);
+    assert_eq!(
+        report.len_before - report.reclaimed_bytes(),
+        report.len_after
+    );
+
+    let source = archive.close().unwrap();
+    assert_eq!(source.get_ref().len() as u64, report.len_after);
+
+    let mut archive = Archive::open(source, config).unwrap();
+    assert_eq!(archive.files().count(), kept.len());
+
+    for (file_id, content) in &kept {
+        assert_eq!(&archive.read_file_to_vec(*file_id).unwrap(), content);
+    }
+
+    // Compacting an already-compact archive should not change anything
+    assert_eq!(archive.compact().unwrap().reclaimed_bytes(), 0);
+
+    // Newly-written data must land after the compacted content
+    let new_file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("new".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"new content".to_vec()),
+        )
+        .unwrap();
+
+    for (file_id, content) in &kept {
+        assert_eq!(&archive.read_file_to_vec(*file_id).unwrap(), content);
+    }
+
+    assert_eq!(archive.read_file_to_vec(new_file).unwrap(), b"new content");
+}
+
+#[test]
+fn test_create_file_from_reader() {
+    let mut archive = create_empty_archive();
+    let content = (0..10_000_u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    // Slices only implement `Read`, not `Seek`
+    let file_id = archive
+        .create_file_from_reader(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("streamed".to_owned()).unwrap(),
+            Timestamp::now(),
+            &content[..],
+        )
+        .unwrap();
+
+    assert_eq!(archive.get_file(file_id).unwrap().content_len, 10_000);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content);
+}
+
+#[test]
+fn test_replace_file_content_from_reader() {
+    let mut archive = create_empty_archive();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("file".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    let old_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    archive
+        .replace_file_content_from_reader(file_id, Timestamp::now(), &[2; 3000][..])
+        .unwrap();
+
+    // Space used by the previous content can be re-used
+    let other_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("other".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![3; 1000]),
+        )
+        .unwrap();
+
+    assert_eq!(archive.get_file(other_id).unwrap().content_addr, old_addr);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![2; 3000]);
+    assert_eq!(archive.read_file_to_vec(other_id).unwrap(), vec![3; 1000]);
+}
+
+#[test]
+fn test_config_setters() {
+    let config = ArchiveConfig::default()
+        .with_first_segment_dirs_capacity_override(Some(std::num::NonZero::new(1).unwrap()))
+        .with_first_segment_files_capacity_override(None)
+        .with_default_files_capacity_by_ft_segment(std::num::NonZero::new(3).unwrap())
+        .with_journal(true);
+
+    assert_eq!(
+        config.first_segment_dirs_capacity_override,
+        Some(std::num::NonZero::new(1).unwrap())
+    );
+    assert_eq!(config.first_segment_files_capacity_override, None);
+    assert_eq!(config.default_files_capacity_by_ft_segment.get(), 3);
+    assert!(config.journal);
+
+    let mut archive = Archive::create(Cursor::new(vec![]), config.clone()).unwrap();
+    add_test_dir(&mut archive);
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, config).unwrap();
+    assert_eq!(archive.dirs().count(), 1);
+}
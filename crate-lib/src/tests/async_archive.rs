@@ -0,0 +1,451 @@
+use std::{
+    io::{Cursor, ErrorKind, SeekFrom},
+    num::NonZero,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{journal::CrashingStream, name};
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, AsyncArchive, CompressionCodec, CompressionPolicy,
+    DirectoryIdOrRoot, EncryptionKey, FileCompression, FileReaderError, ItemId, Timestamp,
+    UnixAttributes, compression::decompress_to_vec,
+};
+
+/// Run a future whose I/O never blocks (e.g. in-memory streams) to completion
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn small_segments_config() -> ArchiveConfig {
+    ArchiveConfig {
+        default_files_capacity_by_ft_segment: NonZero::new(2).unwrap(),
+        first_segment_files_capacity_override: Some(NonZero::new(2).unwrap()),
+        chunk_size: Some(NonZero::new(16).unwrap()),
+        journal: true,
+        ..ArchiveConfig::default()
+    }
+}
+
+#[test]
+fn test_async_archive_roundtrip() {
+    block_on(async {
+        let mut archive = AsyncArchive::create(Cursor::new(vec![]), small_segments_config())
+            .await
+            .unwrap();
+
+        let dir_id = archive
+            .create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+            .await
+            .unwrap();
+
+        for i in 0..5 {
+            archive
+                .create_file(
+                    DirectoryIdOrRoot::NonRoot(dir_id),
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    format!("content {i}").repeat(10).as_bytes(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert!(
+            archive
+                .create_file(
+                    DirectoryIdOrRoot::NonRoot(dir_id),
+                    name("file0"),
+                    Timestamp::now(),
+                    b"duplicate".as_slice(),
+                )
+                .await
+                .is_err()
+        );
+
+        assert_eq!(
+            archive
+                .read_dir(DirectoryIdOrRoot::NonRoot(dir_id))
+                .unwrap()
+                .count(),
+            5
+        );
+
+        let bytes = archive.close().await.unwrap().into_inner();
+
+        // The archive must be readable synchronously
+        let mut archive =
+            Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+
+        let file_id = archive.with_paths().get_file_at("dir/file3").unwrap().id;
+
+        assert!(archive.has_chunk_checksums(file_id));
+        assert_eq!(
+            archive.read_file_to_string(file_id).unwrap(),
+            "content 3".repeat(10)
+        );
+
+        // And asynchronously
+        let mut archive = AsyncArchive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(archive.archive().files().count(), 5);
+
+        let content = archive.read_file_to_vec(file_id).await.unwrap();
+        assert_eq!(content, "content 3".repeat(10).as_bytes());
+    });
+}
+
+#[test]
+fn test_async_file_reader_seek() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(b"0123456789".to_vec()),
+        )
+        .unwrap();
+
+    let mut bytes = archive.close().unwrap().into_inner();
+
+    block_on(async {
+        let mut archive = AsyncArchive::open(Cursor::new(bytes.clone()), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        let mut reader = archive.read_file(file_id).unwrap();
+        let mut buf = [0u8; 3];
+
+        assert_eq!(reader.seek(SeekFrom::Start(4)).await.unwrap(), 4);
+        assert!(!reader.is_checksum_verified_on_read());
+
+        reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"456");
+
+        assert_eq!(reader.seek(SeekFrom::End(-2)).await.unwrap(), 8);
+        reader.read_exact(&mut buf[..2]).await.unwrap();
+        assert_eq!(&buf[..2], b"89");
+
+        assert!(reader.seek(SeekFrom::Start(11)).await.is_err());
+
+        reader.rewind().await.unwrap();
+        assert!(reader.is_checksum_verified_on_read());
+    });
+
+    let content_addr = usize::try_from(
+        Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default())
+            .unwrap()
+            .get_file(file_id)
+            .unwrap()
+            .content_addr,
+    )
+    .unwrap();
+
+    bytes[content_addr] ^= 0xFF;
+
+    block_on(async {
+        let mut archive = AsyncArchive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        let err = archive
+            .read_file(file_id)
+            .unwrap()
+            .read_to_vec()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, FileReaderError::ChecksumMismatch { .. }));
+    });
+}
+
+#[test]
+fn test_async_open_replays_pending_journal() {
+    let conf = ArchiveConfig {
+        chunk_size: None,
+        ..small_segments_config()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("existing"),
+            Timestamp::now(),
+            Cursor::new(b"existing content".to_vec()),
+        )
+        .unwrap();
+
+    let base = archive.close().unwrap().into_inner();
+
+    // Crash at every possible write, so that some records are committed but not applied
+    for writes_left in 0..20 {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+
+        let mut archive = Archive::open(stream, ArchiveConfig::default()).unwrap();
+
+        for i in 0..3 {
+            let _ = archive.create_file(
+                DirectoryIdOrRoot::Root,
+                name(&format!("new{i}")),
+                Timestamp::now(),
+                Cursor::new(vec![i; 100]),
+            );
+        }
+
+        let bytes = archive.close().unwrap().inner.into_inner();
+
+        let expected = Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default())
+            .unwrap()
+            .items_iter()
+            .map(|item| item.name().to_string())
+            .collect::<Vec<_>>();
+
+        let archive = block_on(AsyncArchive::open(
+            Cursor::new(bytes),
+            ArchiveConfig::default(),
+        ))
+        .unwrap();
+
+        let got = archive
+            .archive()
+            .items_iter()
+            .map(|item| item.name().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(got, expected, "Mismatch after {writes_left} writes");
+    }
+}
+
+#[test]
+fn test_async_open_loads_unix_attributes() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let attributes = (0..10_u32)
+        .map(|i| {
+            let file_id = archive
+                .create_file(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    Cursor::new(vec![0; 100]),
+                )
+                .unwrap();
+
+            let attributes = UnixAttributes {
+                mode: 0o100644,
+                uid: i,
+                gid: i,
+                owner: (i % 2 == 0).then(|| "owner".repeat(usize::try_from(i).unwrap() + 1)),
+                group: (i % 3 == 0).then(|| "group".to_owned()),
+            };
+
+            archive
+                .set_unix_attributes(ItemId::File(file_id), Some(attributes.clone()))
+                .unwrap();
+
+            (ItemId::File(file_id), attributes)
+        })
+        .collect::<Vec<_>>();
+
+    let bytes = archive.close().unwrap().into_inner();
+
+    block_on(async {
+        let archive = AsyncArchive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        for (item_id, attributes) in attributes {
+            assert_eq!(
+                archive.archive().get_unix_attributes(item_id),
+                Some(&attributes)
+            );
+        }
+    });
+}
+
+#[test]
+fn test_async_open_loads_item_records() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let long_name = "long".repeat(100);
+
+    let dir_id = archive
+        .create_dir(DirectoryIdOrRoot::Root, name(&long_name), Timestamp::now())
+        .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            name(&long_name),
+            Timestamp::now(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+
+    block_on(async {
+        let archive = AsyncArchive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(*archive.archive().get_dir(dir_id).unwrap().name, long_name);
+        assert_eq!(
+            *archive.archive().get_file(file_id).unwrap().name,
+            long_name
+        );
+    });
+}
+
+#[test]
+fn test_async_archive_reads_compressed_contents() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            compression: Some(CompressionPolicy::new(CompressionCodec::Zstd)),
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    let content = b"compressible ".repeat(1000);
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(content.clone()),
+        )
+        .unwrap();
+
+    assert!(archive.get_compression(file_id).is_some());
+
+    let bytes = archive.close().unwrap().into_inner();
+
+    block_on(async {
+        let mut archive = AsyncArchive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(archive.read_file_to_vec(file_id).await.unwrap(), content);
+
+        assert!(matches!(
+            archive.read_file(file_id),
+            Err(ArchiveError::FileIsCompressed)
+        ));
+    });
+}
+
+#[test]
+fn test_decompressed_length_is_not_trusted() {
+    let content = b"compressible ".repeat(1000);
+    let stored = zstd::encode_all(content.as_slice(), 0).unwrap();
+
+    let decompress = |uncompressed_len| {
+        decompress_to_vec(
+            FileCompression {
+                codec: CompressionCodec::Zstd,
+                uncompressed_len,
+            },
+            &stored,
+        )
+    };
+
+    assert_eq!(
+        decompress(u64::try_from(content.len()).unwrap()).unwrap(),
+        content
+    );
+
+    // Nothing is allocated upfront for the claimed length
+    for uncompressed_len in [0, 100, u64::try_from(content.len()).unwrap() + 1, u64::MAX] {
+        assert_eq!(
+            decompress(uncompressed_len).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
+
+#[test]
+fn test_async_archive_encrypts_contents() {
+    let conf = ArchiveConfig {
+        encryption_key: Some(EncryptionKey::Raw([1; 32])),
+        encrypt_names: true,
+        ..ArchiveConfig::default()
+    };
+
+    let large_content = (0..150_000_u32)
+        .map(|i| u8::try_from(i % 251).unwrap())
+        .collect::<Vec<_>>();
+
+    let bytes = block_on(async {
+        let mut archive = AsyncArchive::create(Cursor::new(vec![]), conf.clone())
+            .await
+            .unwrap();
+
+        let file_id = archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name("secret"),
+                Timestamp::now(),
+                b"secret content".as_slice(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            archive.read_file_to_vec(file_id).await.unwrap(),
+            b"secret content"
+        );
+
+        // Spans several encryption chunks
+        let large_id = archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name("large"),
+                Timestamp::now(),
+                large_content.as_slice(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            archive.read_file_to_vec(large_id).await.unwrap(),
+            large_content
+        );
+
+        assert!(matches!(
+            archive.read_file(file_id),
+            Err(ArchiveError::FileIsEncrypted)
+        ));
+
+        archive.close().await.unwrap().into_inner()
+    });
+
+    assert!(!bytes.windows(6).any(|window| window == b"secret"));
+
+    let mut archive = Archive::open(Cursor::new(bytes), conf).unwrap();
+    let file_id = archive.with_paths().get_file_at("secret").unwrap().id;
+    let large_id = archive.with_paths().get_file_at("large").unwrap().id;
+
+    assert_eq!(
+        archive.read_file_to_vec(file_id).unwrap(),
+        b"secret content"
+    );
+    assert_eq!(archive.read_file_to_vec(large_id).unwrap(), large_content);
+}
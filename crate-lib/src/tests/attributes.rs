@@ -0,0 +1,286 @@
+use std::io::Cursor;
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveVersion, DirectoryIdOrRoot, ItemId, Timestamp,
+    UnixAttributes, VerifyOptions,
+};
+
+use super::{journal::CrashingStream, name};
+
+fn attributes(mode: u32, owner: Option<&str>) -> UnixAttributes {
+    UnixAttributes {
+        mode,
+        uid: 1000,
+        gid: 100,
+        owner: owner.map(str::to_owned),
+        group: Some("users".to_owned()),
+    }
+}
+
+fn create_archive() -> (Archive<Cursor<Vec<u8>>>, ItemId, ItemId) {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let dir_id = archive
+        .create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+        .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(b"Hello world!"),
+        )
+        .unwrap();
+
+    (archive, ItemId::Directory(dir_id), ItemId::File(file_id))
+}
+
+#[test]
+fn test_unix_attributes_roundtrip() {
+    let (mut archive, dir_id, file_id) = create_archive();
+
+    archive
+        .set_unix_attributes(dir_id, Some(attributes(0o40755, Some("alice"))))
+        .unwrap();
+
+    archive
+        .set_unix_attributes(file_id, Some(attributes(0o100640, None)))
+        .unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(
+        archive.get_unix_attributes(dir_id),
+        Some(&attributes(0o40755, Some("alice")))
+    );
+
+    let file_attributes = archive.get_unix_attributes(file_id).unwrap();
+    assert_eq!(file_attributes, &attributes(0o100640, None));
+    assert_eq!(file_attributes.permissions(), 0o640);
+
+    let report = archive.verify(VerifyOptions::default()).unwrap();
+    assert!(report.is_ok());
+}
+
+#[test]
+fn test_items_without_attributes_open() {
+    let (archive, dir_id, file_id) = create_archive();
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert!(archive.get_unix_attributes(dir_id).is_none());
+    assert!(archive.get_unix_attributes(file_id).is_none());
+}
+
+#[test]
+fn test_removed_items_lose_their_attributes() {
+    let (mut archive, dir_id, file_id) = create_archive();
+
+    archive
+        .set_unix_attributes(file_id, Some(attributes(0o100644, None)))
+        .unwrap();
+
+    archive.flush().unwrap();
+
+    let ItemId::Directory(id) = dir_id else {
+        unreachable!()
+    };
+
+    archive.remove_dir(id).unwrap();
+    assert!(archive.get_unix_attributes(file_id).is_none());
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert!(archive.get_unix_attributes(file_id).is_none());
+}
+
+#[test]
+fn test_attributes_survive_compaction() {
+    let (mut archive, dir_id, file_id) = create_archive();
+
+    let removed = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("removed"),
+            Timestamp::now(),
+            Cursor::new(vec![0; 1000]),
+        )
+        .unwrap();
+
+    archive
+        .set_unix_attributes(file_id, Some(attributes(0o100600, Some("bob"))))
+        .unwrap();
+
+    archive.flush().unwrap();
+    archive.remove_file(removed).unwrap();
+
+    assert!(archive.compact().unwrap().reclaimed_bytes() > 0);
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert!(archive.get_unix_attributes(dir_id).is_none());
+    assert_eq!(
+        archive.get_unix_attributes(file_id),
+        Some(&attributes(0o100600, Some("bob")))
+    );
+}
+
+#[test]
+fn test_attributes_survive_content_replacement() {
+    let (mut archive, _, file_id) = create_archive();
+
+    archive
+        .set_unix_attributes(file_id, Some(attributes(0o100755, Some("alice"))))
+        .unwrap();
+
+    let ItemId::File(id) = file_id else {
+        unreachable!()
+    };
+
+    archive
+        .replace_file_content(id, Timestamp::now(), Cursor::new(vec![1; 500]))
+        .unwrap();
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(
+        archive.get_unix_attributes(file_id),
+        Some(&attributes(0o100755, Some("alice")))
+    );
+}
+
+#[test]
+fn test_attributes_survive_power_loss() {
+    let conf = || ArchiveConfig {
+        journal: true,
+        ..ArchiveConfig::default()
+    };
+
+    let (archive, dir_id, file_id) = create_archive();
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+        let mut archive = Archive::open(stream, conf()).unwrap();
+
+        // The archive is never flushed, so the attributes must be persisted alongside each item
+        let result = archive
+            .set_unix_attributes(dir_id, Some(attributes(0o40755, Some("alice"))))
+            .and_then(|()| archive.set_unix_attributes(file_id, Some(attributes(0o100644, None))));
+
+        let crashed = result.is_err();
+
+        let stream = archive.close().unwrap();
+
+        // Writes which weren't synced yet may or may not have been persisted
+        for bytes in [stream.inner.into_inner(), stream.synced] {
+            let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+                .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+            if let Some(dir_attributes) = archive.get_unix_attributes(dir_id) {
+                assert_eq!(dir_attributes, &attributes(0o40755, Some("alice")));
+            }
+
+            if let Some(file_attributes) = archive.get_unix_attributes(file_id) {
+                assert_eq!(file_attributes, &attributes(0o100644, None));
+            }
+
+            if !crashed {
+                assert!(archive.get_unix_attributes(dir_id).is_some());
+                assert!(archive.get_unix_attributes(file_id).is_some());
+            }
+        }
+
+        if !crashed {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_attributes_require_version_2() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            version: ArchiveVersion::One,
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(b"Hello world!"),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        archive.set_unix_attributes(ItemId::File(file_id), Some(attributes(0o100644, None))),
+        Err(ArchiveError::UnsupportedByVersion {
+            current: 1,
+            required: 2
+        })
+    ));
+
+    // Removing attributes which were never set is a no-op
+    archive
+        .set_unix_attributes(ItemId::File(file_id), None)
+        .unwrap();
+
+    assert!(archive.get_unix_attributes(ItemId::File(file_id)).is_none());
+}
+
+#[test]
+fn test_rolled_back_transaction_restores_attributes() {
+    let (mut archive, _, file_id) = create_archive();
+
+    archive
+        .set_unix_attributes(file_id, Some(attributes(0o100644, None)))
+        .unwrap();
+
+    {
+        let mut tx = archive.transaction();
+        tx.set_unix_attributes(file_id, None).unwrap();
+        assert!(tx.get_unix_attributes(file_id).is_none());
+    }
+
+    assert_eq!(
+        archive.get_unix_attributes(file_id),
+        Some(&attributes(0o100644, None))
+    );
+}
+
+#[test]
+fn test_invalid_attributes_are_rejected() {
+    let (mut archive, _, file_id) = create_archive();
+
+    assert!(matches!(
+        archive.set_unix_attributes(file_id, Some(attributes(0o100644, Some("")))),
+        Err(ArchiveError::InvalidUnixAttributes)
+    ));
+
+    assert!(matches!(
+        archive.set_unix_attributes(file_id, Some(attributes(0o100644, Some(&"a".repeat(256))))),
+        Err(ArchiveError::InvalidUnixAttributes)
+    ));
+
+    archive
+        .remove_file(match file_id {
+            ItemId::File(id) => id,
+            ItemId::Directory(_) => unreachable!(),
+        })
+        .unwrap();
+
+    assert!(matches!(
+        archive.set_unix_attributes(file_id, Some(attributes(0o100644, None))),
+        Err(ArchiveError::FileNotFound)
+    ));
+}
@@ -0,0 +1,95 @@
+use std::{io::Cursor, num::NonZero};
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, BytesArchive, DirectoryIdOrRoot, FileId, FileReaderError,
+    ItemName, Timestamp,
+};
+
+fn create_archive_bytes() -> (Vec<u8>, FileId, FileId) {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let hello = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("hello.txt".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"Hello, world!".to_vec()),
+        )
+        .unwrap();
+
+    let empty = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("empty".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![]),
+        )
+        .unwrap();
+
+    (archive.close().unwrap().into_inner(), hello, empty)
+}
+
+#[test]
+fn file_bytes_are_borrowed() {
+    let (bytes, hello, empty) = create_archive_bytes();
+
+    let archive = BytesArchive::open(bytes.as_slice(), ArchiveConfig::default()).unwrap();
+
+    let content = archive.file_bytes(hello).unwrap();
+    assert_eq!(content, b"Hello, world!");
+
+    // The content must point inside the original bytes
+    assert!(bytes.as_ptr_range().contains(&content.as_ptr()));
+
+    assert_eq!(
+        archive.file_bytes_verified(hello).unwrap(),
+        b"Hello, world!"
+    );
+    assert_eq!(archive.file_bytes_verified(empty).unwrap(), b"");
+
+    assert!(
+        archive
+            .archive()
+            .with_paths()
+            .get_file_at("hello.txt")
+            .is_some()
+    );
+
+    assert!(matches!(
+        archive.file_bytes(FileId(NonZero::new(1000).unwrap())),
+        Err(ArchiveError::FileNotFound)
+    ));
+}
+
+#[test]
+fn owned_bytes() {
+    let (bytes, hello, _) = create_archive_bytes();
+
+    let archive = BytesArchive::open(bytes.clone(), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.file_bytes(hello).unwrap(), b"Hello, world!");
+    assert_eq!(archive.into_inner(), bytes);
+}
+
+#[test]
+fn corrupted_file_bytes() {
+    let (mut bytes, hello, _) = create_archive_bytes();
+
+    let addr = bytes
+        .windows(13)
+        .position(|window| window == b"Hello, world!")
+        .unwrap();
+
+    bytes[addr] = b'J';
+
+    let archive = BytesArchive::open(bytes, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.file_bytes(hello).unwrap(), b"Jello, world!");
+
+    assert!(matches!(
+        archive.file_bytes_verified(hello),
+        Err(ArchiveError::FileReader(
+            FileReaderError::ChecksumMismatch { .. }
+        ))
+    ));
+}
@@ -0,0 +1,220 @@
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    num::NonZero,
+};
+
+use super::name;
+use crate::{
+    Archive, ArchiveConfig, ArchiveVersion, DirectoryIdOrRoot, FileId, FileReaderError, Timestamp,
+};
+
+fn chunked_config() -> ArchiveConfig {
+    ArchiveConfig {
+        chunk_size: Some(NonZero::new(1000).unwrap()),
+        ..ArchiveConfig::default()
+    }
+}
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn create_chunked_archive() -> (Archive<Cursor<Vec<u8>>>, FileId) {
+    let mut archive = Archive::create(Cursor::new(vec![]), chunked_config()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(content(3500)),
+        )
+        .unwrap();
+
+    (archive, file_id)
+}
+
+#[test]
+fn test_chunk_checksums_roundtrip() {
+    let (archive, file_id) = create_chunked_archive();
+    assert!(archive.has_chunk_checksums(file_id));
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert!(archive.has_chunk_checksums(file_id));
+
+    let mut reader = archive.read_file(file_id).unwrap();
+    assert!(reader.has_chunk_checksums());
+
+    reader.seek(SeekFrom::Start(2990)).unwrap();
+    assert!(reader.is_checksum_verified_on_read());
+
+    let mut buf = [0; 20];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf.as_slice(), &content(3500)[2990..3010]);
+
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &content(3500)[3010..]);
+
+    reader.rewind().unwrap();
+    assert_eq!(reader.read_to_vec().unwrap(), content(3500));
+}
+
+#[test]
+fn test_corrupted_chunk_is_detected() {
+    let (archive, file_id) = create_chunked_archive();
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    let mut source = archive.close().unwrap();
+    source.get_mut()[content_addr as usize + 2500] ^= 0xFF;
+
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    let mut reader = archive.read_file(file_id).unwrap();
+
+    // Other chunks can still be read
+    let mut buf = [0; 100];
+    reader.seek(SeekFrom::Start(1500)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+
+    reader.seek(SeekFrom::Start(2900)).unwrap();
+    let err = reader.read_exact(&mut buf).unwrap_err();
+    assert!(err.to_string().contains("chunk 2"));
+
+    let err = archive
+        .read_file(file_id)
+        .unwrap()
+        .read_to_vec()
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        FileReaderError::ChunkChecksumMismatch { chunk_index: 2, .. }
+    ));
+}
+
+#[test]
+fn test_streamed_files_have_chunk_checksums() {
+    let mut archive = Archive::create(Cursor::new(vec![]), chunked_config()).unwrap();
+
+    let streamed = archive
+        .create_file_from_reader(
+            DirectoryIdOrRoot::Root,
+            name("streamed"),
+            Timestamp::now(),
+            &content(2500)[..],
+        )
+        .unwrap();
+
+    let mut writer = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("written"), Timestamp::now())
+        .unwrap();
+
+    for chunk in content(2500).chunks(300) {
+        writer.write_all(chunk).unwrap();
+    }
+
+    let written = writer.finish().unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    for file_id in [streamed, written] {
+        assert!(archive.has_chunk_checksums(file_id));
+        assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content(2500));
+    }
+}
+
+#[test]
+fn test_outdated_chunk_checksums_are_dropped() {
+    let (archive, file_id) = create_chunked_archive();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    // Archive is now opened without chunks
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(content(500)))
+        .unwrap();
+
+    assert!(!archive.has_chunk_checksums(file_id));
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert!(!archive.has_chunk_checksums(file_id));
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content(500));
+}
+
+#[test]
+fn test_chunk_checksums_survive_compaction() {
+    let mut archive = Archive::create(Cursor::new(vec![]), chunked_config()).unwrap();
+
+    let files = (0..6_u8)
+        .map(|i| {
+            archive
+                .create_file(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    Cursor::new(content(1500 * usize::from(i + 1))),
+                )
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    archive.flush().unwrap();
+
+    for file_id in files.iter().step_by(2) {
+        archive.remove_file(*file_id).unwrap();
+    }
+
+    assert!(archive.compact().unwrap().reclaimed_bytes() > 0);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    for (i, file_id) in files.into_iter().enumerate().skip(1).step_by(2) {
+        assert!(archive.has_chunk_checksums(file_id));
+        assert_eq!(
+            archive.read_file_to_vec(file_id).unwrap(),
+            content(1500 * (i + 1))
+        );
+    }
+}
+
+#[test]
+fn test_rolled_back_transaction_restores_chunk_checksums() {
+    let (mut archive, file_id) = create_chunked_archive();
+
+    {
+        let mut tx = archive.transaction();
+        tx.remove_file(file_id).unwrap();
+        assert!(!tx.has_chunk_checksums(file_id));
+    }
+
+    assert!(archive.has_chunk_checksums(file_id));
+}
+
+#[test]
+fn test_chunk_checksums_require_version_2() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            version: ArchiveVersion::One,
+            ..chunked_config()
+        },
+    )
+    .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(content(3500)),
+        )
+        .unwrap();
+
+    assert!(!archive.has_chunk_checksums(file_id));
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content(3500));
+}
@@ -5,9 +5,7 @@ use std::{
 
 use tempfile::NamedTempFile;
 
-use crate::{
-    Archive, ArchiveConfig, DirEntry, DirectoryIdOrRoot, ItemName, Timestamp,
-};
+use crate::{Archive, ArchiveConfig, DirEntry, DirectoryIdOrRoot, ItemName, SyncData, Timestamp};
 
 static FILE_CONTENT: &[u8] = b"Hello world!";
 
@@ -22,7 +20,7 @@ fn test_on_real_file() -> Result<(), Box<dyn std::error::Error>> {
     perform_tests_with(|| test_file.as_file())
 }
 
-fn perform_tests_with<S: Read + Write + Seek>(
+fn perform_tests_with<S: Read + Write + Seek + SyncData>(
     create_source: impl Fn() -> S + Clone,
 ) -> Result<(), Box<dyn std::error::Error>> {
     perform_complex_manipulations(create_source)?;
@@ -30,7 +28,7 @@ fn perform_tests_with<S: Read + Write + Seek>(
     Ok(())
 }
 
-fn perform_complex_manipulations<S: Read + Write + Seek>(
+fn perform_complex_manipulations<S: Read + Write + Seek + SyncData>(
     create_source: impl Fn() -> S,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create archive
@@ -154,11 +152,7 @@ fn perform_complex_manipulations<S: Read + Write + Seek>(
     // Ensure metadata are correctly updated on disk when updating a file
     let new_timestamp = Timestamp::now();
     archive
-        .replace_file_content(
-            file_id,
-            new_timestamp,
-            Cursor::new(vec![1]),
-        )
+        .replace_file_content(file_id, new_timestamp, Cursor::new(vec![1]))
         .unwrap();
 
     let file = archive.get_file(file_id).unwrap();
@@ -0,0 +1,415 @@
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use flate2::{Compression, write::DeflateEncoder};
+
+use super::{create_file, journal::CrashingStream, name};
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, BytesArchive, CompressionCodec, CompressionPolicy,
+    DirectoryIdOrRoot, EncryptionKey, FileCompression, FileReaderError, Timestamp, VerifyOptions,
+};
+
+fn compression_config(codec: CompressionCodec) -> ArchiveConfig {
+    ArchiveConfig {
+        compression: Some(CompressionPolicy::new(codec)),
+        ..ArchiveConfig::default()
+    }
+}
+
+fn compressible_content() -> Vec<u8> {
+    (0..20_000_u32)
+        .flat_map(|i| format!("line {}\n", i % 100).into_bytes())
+        .collect()
+}
+
+fn random_content() -> Vec<u8> {
+    let mut state = 1_u64;
+
+    (0..10_000)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_compressed_contents_roundtrip() {
+    let content = compressible_content();
+
+    for codec in [CompressionCodec::Zstd, CompressionCodec::Deflate] {
+        let mut archive = Archive::create(Cursor::new(vec![]), compression_config(codec)).unwrap();
+
+        let id = create_file(&mut archive, "file.txt", &content);
+
+        assert_eq!(
+            archive.get_compression(id),
+            Some(FileCompression {
+                codec,
+                uncompressed_len: u64::try_from(content.len()).unwrap()
+            })
+        );
+
+        assert!(
+            archive.get_file(id).unwrap().content_len < u64::try_from(content.len()).unwrap() / 4
+        );
+
+        let reader = archive.read_file(id).unwrap();
+        assert_eq!(reader.file_len(), u64::try_from(content.len()).unwrap());
+        assert_eq!(reader.read_to_vec().unwrap(), content);
+
+        let source = archive.close().unwrap();
+        let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+        assert_eq!(archive.get_compression(id).unwrap().codec, codec);
+        assert_eq!(archive.read_file_to_vec(id).unwrap(), content);
+        assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+    }
+}
+
+#[test]
+fn test_compressed_contents_can_be_seeked() {
+    let content = compressible_content();
+
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        compression_config(CompressionCodec::Zstd),
+    )
+    .unwrap();
+
+    let id = create_file(&mut archive, "file.txt", &content);
+
+    let mut reader = archive.read_file(id).unwrap();
+    let mut buf = [0; 100];
+
+    reader.seek(SeekFrom::Start(50_000)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, content[50_000..50_100]);
+
+    reader.seek(SeekFrom::Start(1_000)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, content[1_000..1_100]);
+
+    reader.seek(SeekFrom::End(-100)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, content[content.len() - 100..]);
+
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn test_policy_decides_which_files_are_compressed() {
+    let content = compressible_content();
+
+    let mut policy = CompressionPolicy::new(CompressionCodec::Zstd);
+    policy
+        .by_extension
+        .insert("log".to_owned(), Some(CompressionCodec::Deflate));
+
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            compression: Some(policy),
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    let text = create_file(&mut archive, "file.txt", &content);
+    let log = create_file(&mut archive, "file.LOG", &content);
+    let image = create_file(&mut archive, "image.png", &content);
+    let small = create_file(&mut archive, "small.txt", b"tiny");
+
+    let random = random_content();
+    let incompressible = create_file(&mut archive, "random.bin", &random);
+
+    assert_eq!(
+        archive.get_compression(text).unwrap().codec,
+        CompressionCodec::Zstd
+    );
+    assert_eq!(
+        archive.get_compression(log).unwrap().codec,
+        CompressionCodec::Deflate
+    );
+    assert_eq!(archive.get_compression(image), None);
+    assert_eq!(archive.get_compression(small), None);
+    assert_eq!(archive.get_compression(incompressible), None);
+
+    assert_eq!(archive.read_file_to_vec(image).unwrap(), content);
+    assert_eq!(archive.read_file_to_vec(incompressible).unwrap(), random);
+}
+
+#[test]
+fn test_written_contents_follow_the_policy() {
+    let content = compressible_content();
+    let random = random_content();
+
+    for encryption_key in [None, Some(EncryptionKey::Raw([3; 32]))] {
+        let conf = ArchiveConfig {
+            encryption_key,
+            ..compression_config(CompressionCodec::Zstd)
+        };
+
+        let mut archive = Archive::create(Cursor::new(vec![]), conf.clone()).unwrap();
+
+        let mut write_file = |file_name: &str, content: &[u8]| {
+            let mut writer = archive
+                .create_file_writer(DirectoryIdOrRoot::Root, name(file_name), Timestamp::now())
+                .unwrap();
+
+            for part in content.chunks(1_000) {
+                writer.write_all(part).unwrap();
+            }
+
+            writer.finish().unwrap()
+        };
+
+        let files = [
+            ("file.txt", content.as_slice()),
+            ("image.png", content.as_slice()),
+            ("small.txt", b"tiny".as_slice()),
+            ("empty.txt", b"".as_slice()),
+            ("random.bin", random.as_slice()),
+        ]
+        .map(|(file_name, content)| (write_file(file_name, content), content));
+
+        let [text, image, small, empty, incompressible] = files.map(|(id, _)| id);
+
+        assert_eq!(
+            archive.get_compression(text),
+            Some(FileCompression {
+                codec: CompressionCodec::Zstd,
+                uncompressed_len: u64::try_from(content.len()).unwrap()
+            })
+        );
+        assert!(archive.get_file(text).unwrap().content_len < 10_000);
+
+        assert_eq!(archive.get_compression(image), None);
+        assert_eq!(archive.get_compression(small), None);
+        assert_eq!(archive.get_compression(empty), None);
+        assert_eq!(archive.get_compression(incompressible), None);
+
+        assert_eq!(archive.get_file_len(small), Some(4));
+        assert_eq!(archive.get_file(empty).unwrap().content_len, 0);
+        assert_eq!(archive.get_file_len(incompressible), Some(10_000));
+
+        for (id, expected) in files {
+            assert_eq!(archive.read_file_to_vec(id).unwrap(), expected);
+        }
+
+        assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+
+        let bytes = archive.close().unwrap().into_inner();
+        let mut archive = Archive::open(Cursor::new(bytes), conf).unwrap();
+
+        for (id, expected) in files {
+            assert_eq!(archive.read_file_to_vec(id).unwrap(), expected);
+        }
+
+        // The compressed copy of the incompressible content was given back
+        assert!(archive.compact().unwrap().reclaimed_bytes() >= 10_000);
+        assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+    }
+}
+
+#[test]
+fn test_replaced_contents_are_compressed_again() {
+    let content = compressible_content();
+
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        compression_config(CompressionCodec::Deflate),
+    )
+    .unwrap();
+
+    let id = create_file(&mut archive, "file.txt", &content);
+
+    archive
+        .replace_file_content(id, Timestamp::now(), Cursor::new(b"short"))
+        .unwrap();
+
+    assert_eq!(archive.get_compression(id), None);
+    assert_eq!(archive.read_file_to_string(id).unwrap(), "short");
+
+    archive
+        .replace_file_content(id, Timestamp::now(), Cursor::new(&content))
+        .unwrap();
+
+    assert!(archive.get_compression(id).is_some());
+
+    // Contents written from a reader are never compressed
+    archive
+        .replace_file_content_from_reader(id, Timestamp::now(), content.as_slice())
+        .unwrap();
+
+    assert_eq!(archive.get_compression(id), None);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_compression(id), None);
+    assert_eq!(archive.read_file_to_vec(id).unwrap(), content);
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_compressed_contents_cannot_be_borrowed() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        compression_config(CompressionCodec::Zstd),
+    )
+    .unwrap();
+
+    let id = create_file(&mut archive, "file.txt", &compressible_content());
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = BytesArchive::open(bytes, ArchiveConfig::default()).unwrap();
+
+    assert!(matches!(
+        archive.file_bytes(id),
+        Err(ArchiveError::FileIsCompressed)
+    ));
+}
+
+#[test]
+fn test_trailing_stored_bytes_are_rejected() {
+    // Make the stored content larger than a single read, so it isn't verified before being decompressed
+    let mut content = compressible_content();
+    let mut state = 1_u32;
+
+    content.extend((0..20_000).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        state.to_le_bytes()[2]
+    }));
+
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        compression_config(CompressionCodec::Deflate),
+    )
+    .unwrap();
+
+    let id = create_file(&mut archive, "file.txt", &content);
+    let file = archive.get_file(id).unwrap().clone();
+
+    let mut bytes = archive.close().unwrap().into_inner();
+
+    // Replace the content with a shorter compressed stream followed by garbage
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(&content[..1000]).unwrap();
+    let mut stored = encoder.finish().unwrap();
+
+    let len = usize::try_from(file.content_len).unwrap();
+    assert!(len > 8192);
+    stored.resize(len, 0xFF);
+
+    let addr = usize::try_from(file.content_addr).unwrap();
+    bytes[addr..addr + len].copy_from_slice(&stored);
+
+    let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+    assert!(archive.get_compression(id).is_some());
+
+    let err = archive.read_file(id).unwrap().read_to_vec().unwrap_err();
+
+    assert!(matches!(
+        err,
+        FileReaderError::Decompression(err) if err.kind() == ErrorKind::InvalidData
+    ));
+}
+
+#[test]
+fn test_compression_survives_power_loss() {
+    let content = compressible_content();
+
+    let conf = || ArchiveConfig {
+        journal: true,
+        ..compression_config(CompressionCodec::Zstd)
+    };
+
+    let archive = Archive::create(Cursor::new(vec![]), conf()).unwrap();
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+        let mut archive = Archive::open(stream, conf()).unwrap();
+
+        // The archive is never flushed, so the compression must be persisted alongside each file
+        let result = (|| {
+            for i in 0..2 {
+                archive.create_file(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("file{i}.txt")),
+                    Timestamp::now(),
+                    Cursor::new(&content),
+                )?;
+            }
+
+            Ok::<_, ArchiveError>(())
+        })();
+
+        let crashed = result.is_err();
+
+        let stream = archive.close().unwrap();
+
+        // Writes which weren't synced yet may or may not have been persisted
+        for bytes in [stream.inner.into_inner(), stream.synced] {
+            let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+                .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+            let file_ids = archive.files().map(|file| file.id).collect::<Vec<_>>();
+
+            for file_id in file_ids {
+                assert!(archive.get_compression(file_id).is_some());
+                assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content);
+            }
+        }
+
+        if !crashed {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_failed_writes_give_decompressed_contents_back() {
+    let random = random_content();
+
+    let archive = Archive::create(
+        Cursor::new(vec![]),
+        compression_config(CompressionCodec::Zstd),
+    )
+    .unwrap();
+
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+        let mut archive =
+            Archive::open(stream, compression_config(CompressionCodec::Zstd)).unwrap();
+
+        let end = archive.next_writable_addr();
+
+        let result = (|| {
+            let mut writer = archive.create_file_writer(
+                DirectoryIdOrRoot::Root,
+                name("random.txt"),
+                Timestamp::now(),
+            )?;
+
+            writer.write_all(&random)?;
+            writer.finish()
+        })();
+
+        if result.is_ok() {
+            break;
+        }
+
+        // Neither the reserved zone nor the decompressed content may remain used
+        assert_eq!(
+            archive.next_writable_addr(),
+            end,
+            "Space leaked after {writes_left} writes"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Cursor;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
 use crate::{
     Archive, ArchiveConfig, DirectoryIdOrRoot, ItemName, Timestamp,
@@ -44,6 +44,23 @@ fn coverage() {
     assert_eq!(coverage.find_free_zones().nth(2), None);
 }
 
+#[test]
+fn shared_zones_are_reference_counted() {
+    let mut coverage = Coverage::new(100);
+
+    coverage.mark_as_used(10, 20);
+    coverage.mark_as_used(10, 20);
+
+    // The zone remains used until it has been freed as many times as it was used
+    assert!(coverage.mark_as_free(Segment { start: 10, len: 20 }));
+    assert!(!coverage.is_free(10, 20));
+
+    assert!(coverage.mark_as_free(Segment { start: 10, len: 20 }));
+    assert!(coverage.is_free(10, 20));
+
+    assert!(!coverage.mark_as_free(Segment { start: 10, len: 20 }));
+}
+
 #[test]
 fn reuse_file_space() {
     const REUSE: usize = 1000;
@@ -74,3 +91,158 @@ fn reuse_file_space() {
 
     assert_eq!(in_mem.get_ref().len(), len);
 }
+
+#[test]
+fn replace_file_content_in_place() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("config".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![0; 1000]),
+        )
+        .unwrap();
+
+    let other_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("other".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    // Smaller content is written in place
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(vec![2; 600]))
+        .unwrap();
+
+    assert_eq!(
+        archive.get_file(file_id).unwrap().content_addr,
+        content_addr
+    );
+
+    // The freed tail can be re-used by other files
+    let tail_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("tail".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![3; 400]),
+        )
+        .unwrap();
+
+    assert_eq!(
+        archive.get_file(tail_id).unwrap().content_addr,
+        content_addr + 600
+    );
+
+    // Larger content that doesn't fit anymore must be moved elsewhere
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(vec![4; 700]))
+        .unwrap();
+
+    assert_ne!(
+        archive.get_file(file_id).unwrap().content_addr,
+        content_addr
+    );
+
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![4; 700]);
+    assert_eq!(archive.read_file_to_vec(other_id).unwrap(), vec![1; 1000]);
+    assert_eq!(archive.read_file_to_vec(tail_id).unwrap(), vec![3; 400]);
+}
+
+#[test]
+fn replace_last_file_content_grows_in_place() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("log".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![0; 100]),
+        )
+        .unwrap();
+
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(vec![1; 5000]))
+        .unwrap();
+
+    assert_eq!(
+        archive.get_file(file_id).unwrap().content_addr,
+        content_addr
+    );
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![1; 5000]);
+
+    let in_mem = archive.close().unwrap();
+    assert_eq!(in_mem.get_ref().len() as u64, content_addr + 5000);
+}
+
+/// Content whose reads always fail
+struct UnreadableContent;
+
+impl Read for UnreadableContent {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("unreadable content"))
+    }
+}
+
+impl Seek for UnreadableContent {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            SeekFrom::End(0) => Ok(1000),
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn test_failed_replace_keeps_content_used() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("config".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![0; 1000]),
+        )
+        .unwrap();
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("other".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    assert!(
+        archive
+            .replace_file_content(file_id, Timestamp::now(), UnreadableContent)
+            .is_err()
+    );
+
+    // The file's current content must not be overwritten by new ones
+    let new_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("new".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(vec![2; 1000]),
+        )
+        .unwrap();
+
+    assert_ne!(archive.get_file(new_id).unwrap().content_addr, content_addr);
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![0; 1000]);
+}
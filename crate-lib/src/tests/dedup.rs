@@ -0,0 +1,143 @@
+use std::io::Cursor;
+
+use super::{create_file, name};
+use crate::{Archive, ArchiveConfig, DirectoryIdOrRoot, Timestamp, VerifyOptions};
+
+fn dedup_config() -> ArchiveConfig {
+    ArchiveConfig {
+        deduplicate: true,
+        ..ArchiveConfig::default()
+    }
+}
+
+#[test]
+fn test_identical_contents_are_stored_once() {
+    let mut archive = Archive::create(Cursor::new(vec![]), dedup_config()).unwrap();
+
+    let first = create_file(&mut archive, "first", &[1; 1000]);
+    let len_before = archive.stream().get_ref().len();
+
+    let second = create_file(&mut archive, "second", &[1; 1000]);
+    let other = create_file(&mut archive, "other", &[2; 1000]);
+
+    let first_addr = archive.get_file(first).unwrap().content_addr;
+
+    assert_eq!(archive.get_file(second).unwrap().content_addr, first_addr);
+    assert_ne!(archive.get_file(other).unwrap().content_addr, first_addr);
+
+    // Only the different content was written
+    assert!(archive.stream().get_ref().len() < len_before + 2000);
+
+    // Files sharing their content this way are not hard links
+    assert_eq!(archive.get_hard_links(first).count(), 0);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, dedup_config()).unwrap();
+
+    assert_eq!(archive.read_file_to_vec(second).unwrap(), vec![1; 1000]);
+    assert_eq!(archive.get_hard_links(second).count(), 0);
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+
+    // Contents of the reopened archive can be shared as well
+    let third = create_file(&mut archive, "third", &[2; 1000]);
+
+    assert_eq!(
+        archive.get_file(third).unwrap().content_addr,
+        archive.get_file(other).unwrap().content_addr
+    );
+}
+
+#[test]
+fn test_contents_are_not_shared_by_default() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let first = create_file(&mut archive, "first", &[1; 1000]);
+    let second = create_file(&mut archive, "second", &[1; 1000]);
+
+    assert_ne!(
+        archive.get_file(first).unwrap().content_addr,
+        archive.get_file(second).unwrap().content_addr
+    );
+}
+
+#[test]
+fn test_shared_content_is_freed_with_last_file() {
+    let mut archive = Archive::create(Cursor::new(vec![]), dedup_config()).unwrap();
+
+    let first = create_file(&mut archive, "first", &[1; 1000]);
+    let second = create_file(&mut archive, "second", &[1; 1000]);
+
+    let content_addr = archive.get_file(first).unwrap().content_addr;
+
+    archive.remove_file(first).unwrap();
+
+    // The content is still in use, so it must not be overwritten
+    let other = create_file(&mut archive, "other", &[2; 1000]);
+
+    assert_ne!(archive.get_file(other).unwrap().content_addr, content_addr);
+    assert_eq!(archive.read_file_to_vec(second).unwrap(), vec![1; 1000]);
+
+    // The remaining file's content can still be shared
+    let third = create_file(&mut archive, "third", &[1; 1000]);
+    assert_eq!(archive.get_file(third).unwrap().content_addr, content_addr);
+
+    archive.remove_file(second).unwrap();
+    archive.remove_file(third).unwrap();
+
+    let reusing = create_file(&mut archive, "reusing", &[3; 1000]);
+    assert_eq!(
+        archive.get_file(reusing).unwrap().content_addr,
+        content_addr
+    );
+}
+
+#[test]
+fn test_replaced_and_streamed_contents_are_shared() {
+    let mut archive = Archive::create(Cursor::new(vec![]), dedup_config()).unwrap();
+
+    let first = create_file(&mut archive, "first", &[1; 1000]);
+    let second = create_file(&mut archive, "second", &[2; 1000]);
+
+    let content_addr = archive.get_file(first).unwrap().content_addr;
+
+    archive
+        .replace_file_content(second, Timestamp::now(), Cursor::new(vec![1; 1000]))
+        .unwrap();
+
+    assert_eq!(archive.get_file(second).unwrap().content_addr, content_addr);
+
+    let streamed = archive
+        .create_file_from_reader(
+            DirectoryIdOrRoot::Root,
+            name("streamed"),
+            Timestamp::now(),
+            &[1; 1000][..],
+        )
+        .unwrap();
+
+    assert_eq!(
+        archive.get_file(streamed).unwrap().content_addr,
+        content_addr
+    );
+
+    // Replacing a file's content with the same one keeps it shared
+    archive
+        .replace_file_content(first, Timestamp::now(), Cursor::new(vec![1; 1000]))
+        .unwrap();
+
+    assert_eq!(archive.get_file(first).unwrap().content_addr, content_addr);
+
+    // Replacing a shared content only affects the file itself
+    archive
+        .replace_file_content(first, Timestamp::now(), Cursor::new(b"replaced"))
+        .unwrap();
+
+    assert_eq!(archive.read_file_to_string(first).unwrap(), "replaced");
+    assert_eq!(archive.read_file_to_vec(second).unwrap(), vec![1; 1000]);
+    assert_eq!(archive.read_file_to_vec(streamed).unwrap(), vec![1; 1000]);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, dedup_config()).unwrap();
+
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
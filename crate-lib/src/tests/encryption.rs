@@ -0,0 +1,463 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use sha3::{Digest, Sha3_256};
+
+use super::{contains, create_file, name};
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, ArchiveVersion,
+    BytesArchive, CompressionCodec, CompressionPolicy, DirectoryIdOrRoot, EncryptionKey, File,
+    FileReaderError, MAX_ENCRYPTED_NAME_LEN, Timestamp, VerifyOptions,
+};
+
+const KEY: [u8; 32] = [7; 32];
+
+fn encryption_config(encrypt_names: bool) -> ArchiveConfig {
+    ArchiveConfig {
+        encryption_key: Some(EncryptionKey::Raw(KEY)),
+        encrypt_names,
+        ..ArchiveConfig::default()
+    }
+}
+
+#[test]
+fn test_encrypted_contents_roundtrip() {
+    let content = b"Some secret content, which must not appear in the archive".repeat(10);
+
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let id = create_file(&mut archive, "secret.txt", &content);
+    let empty = create_file(&mut archive, "empty.txt", b"");
+
+    assert!(archive.is_encrypted());
+    assert!(!archive.has_encrypted_names());
+    assert_eq!(archive.read_file(id).unwrap().file_len(), 570);
+    assert_eq!(archive.get_file_len(id), Some(570));
+    assert_eq!(archive.get_file(id).unwrap().content_len, 601);
+    assert_eq!(archive.read_file_to_vec(id).unwrap(), content);
+    assert_eq!(archive.read_file_to_vec(empty).unwrap(), b"");
+
+    let bytes = archive.close().unwrap().into_inner();
+    assert!(!contains(&bytes, b"Some secret content"));
+    assert!(contains(&bytes, b"secret.txt"));
+
+    let mut archive = Archive::open(Cursor::new(bytes), encryption_config(false)).unwrap();
+
+    assert_eq!(archive.read_file_to_vec(id).unwrap(), content);
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_encrypted_names_require_the_key() {
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(true)).unwrap();
+
+    archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            name("hidden-dir"),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    let id = create_file(&mut archive, "hidden-file.txt", b"content");
+
+    archive.rename_file(id, name("renamed-file.txt")).unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+    assert!(!contains(&bytes, b"hidden-dir"));
+    assert!(!contains(&bytes, b"renamed-file.txt"));
+
+    assert!(matches!(
+        Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()),
+        Err(ArchiveMetadataDecodingError::MissingEncryptionKey)
+    ));
+
+    let mut archive = Archive::open(Cursor::new(bytes), encryption_config(true)).unwrap();
+
+    assert!(archive.has_encrypted_names());
+    assert_eq!(
+        archive.get_file(id).unwrap().name.as_ref(),
+        "renamed-file.txt"
+    );
+    assert_eq!(archive.dirs().next().unwrap().name.as_ref(), "hidden-dir");
+    assert_eq!(archive.read_file_to_vec(id).unwrap(), b"content");
+}
+
+#[test]
+fn test_wrong_key_is_rejected() {
+    let archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+    let bytes = archive.close().unwrap().into_inner();
+
+    for key in [
+        EncryptionKey::Raw([8; 32]),
+        EncryptionKey::Password("key".to_owned()),
+    ] {
+        let conf = ArchiveConfig {
+            encryption_key: Some(key),
+            ..ArchiveConfig::default()
+        };
+
+        assert!(matches!(
+            Archive::open(Cursor::new(bytes.clone()), conf),
+            Err(ArchiveMetadataDecodingError::InvalidEncryptionKey)
+        ));
+    }
+}
+
+#[test]
+fn test_plain_names_can_be_listed_without_the_key() {
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let id = create_file(&mut archive, "file.txt", b"content");
+    let bytes = archive.close().unwrap().into_inner();
+
+    let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_file(id).unwrap().name.as_ref(), "file.txt");
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+
+    assert!(matches!(
+        archive.read_file(id),
+        Err(ArchiveError::MissingEncryptionKey)
+    ));
+
+    assert!(matches!(
+        archive.create_file(
+            DirectoryIdOrRoot::Root,
+            name("other.txt"),
+            Timestamp::now(),
+            Cursor::new(b"content")
+        ),
+        Err(ArchiveError::MissingEncryptionKey)
+    ));
+}
+
+#[test]
+fn test_password_derived_key() {
+    let conf = ArchiveConfig {
+        encryption_key: Some(EncryptionKey::Password("correct horse".to_owned())),
+        encrypt_names: true,
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf.clone()).unwrap();
+    let id = create_file(&mut archive, "file.txt", b"content");
+    let bytes = archive.close().unwrap().into_inner();
+
+    let mut archive = Archive::open(Cursor::new(bytes.clone()), conf).unwrap();
+    assert_eq!(archive.read_file_to_vec(id).unwrap(), b"content");
+
+    let conf = ArchiveConfig {
+        encryption_key: Some(EncryptionKey::Password("wrong horse".to_owned())),
+        ..ArchiveConfig::default()
+    };
+
+    assert!(matches!(
+        Archive::open(Cursor::new(bytes), conf),
+        Err(ArchiveMetadataDecodingError::InvalidEncryptionKey)
+    ));
+}
+
+#[test]
+fn test_encrypted_contents_can_be_compressed_and_seeked() {
+    let content = (0..20_000_u32)
+        .flat_map(|i| format!("line {}\n", i % 100).into_bytes())
+        .collect::<Vec<_>>();
+
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            compression: Some(CompressionPolicy::new(CompressionCodec::Zstd)),
+            ..encryption_config(false)
+        },
+    )
+    .unwrap();
+
+    let compressed = create_file(&mut archive, "file.txt", &content);
+    let uncompressed = create_file(&mut archive, "file.png", &content);
+
+    assert!(archive.get_compression(compressed).is_some());
+    assert!(archive.get_compression(uncompressed).is_none());
+
+    for id in [compressed, uncompressed] {
+        let mut reader = archive.read_file(id).unwrap();
+        let mut buf = [0; 100];
+
+        assert_eq!(reader.file_len(), u64::try_from(content.len()).unwrap());
+
+        reader.seek(SeekFrom::Start(50_000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[50_000..50_100]);
+
+        reader.seek(SeekFrom::End(-100)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[content.len() - 100..]);
+
+        reader.seek(SeekFrom::Start(1_000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[1_000..1_100]);
+    }
+}
+
+/// Alter a file's stored content and update its checksum accordingly, so only its decryption can fail
+fn alter_content(bytes: &mut [u8], file: &File, alter: impl FnOnce(&mut [u8])) {
+    let addr = usize::try_from(file.content_addr).unwrap();
+    let len = usize::try_from(file.content_len).unwrap();
+    alter(&mut bytes[addr..addr + len]);
+
+    let checksum: [u8; 32] = Sha3_256::digest(&bytes[addr..addr + len]).into();
+    let checksum_addr = bytes
+        .windows(32)
+        .position(|window| window == file.sha3_checksum)
+        .unwrap();
+
+    bytes[checksum_addr..checksum_addr + 32].copy_from_slice(&checksum);
+}
+
+#[test]
+fn test_altered_contents_fail_to_decrypt() {
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let id = create_file(&mut archive, "file.txt", b"content");
+    let file = archive.get_file(id).unwrap().clone();
+
+    let mut bytes = archive.close().unwrap().into_inner();
+
+    alter_content(&mut bytes, &file, |content| content[20] ^= 1);
+
+    let mut archive = Archive::open(Cursor::new(bytes), encryption_config(false)).unwrap();
+
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+    assert!(matches!(
+        archive.read_file_to_vec(id),
+        Err(ArchiveError::FileReader(FileReaderError::Decryption))
+    ));
+}
+
+#[test]
+fn test_encrypted_names_length_is_limited_before_v2() {
+    let conf = ArchiveConfig {
+        version: ArchiveVersion::One,
+        ..encryption_config(true)
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    let long_name = "a".repeat(MAX_ENCRYPTED_NAME_LEN + 1);
+
+    assert!(matches!(
+        archive.create_dir(DirectoryIdOrRoot::Root, name(&long_name), Timestamp::now()),
+        Err(ArchiveError::NameTooLongToEncrypt {
+            max: MAX_ENCRYPTED_NAME_LEN
+        })
+    ));
+
+    let id = create_file(&mut archive, &long_name[1..], b"");
+
+    assert!(matches!(
+        archive.rename_file(id, name(&long_name)),
+        Err(ArchiveError::NameTooLongToEncrypt { .. })
+    ));
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = Archive::open(Cursor::new(bytes), encryption_config(true)).unwrap();
+
+    assert_eq!(
+        archive.get_file(id).unwrap().name.len(),
+        MAX_ENCRYPTED_NAME_LEN
+    );
+}
+
+#[test]
+fn test_streamed_contents_are_encrypted() {
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let streamed = archive
+        .create_file_from_reader(
+            DirectoryIdOrRoot::Root,
+            name("streamed.txt"),
+            Timestamp::now(),
+            b"Streamed secret".as_slice(),
+        )
+        .unwrap();
+
+    let mut writer = archive
+        .create_file_writer(
+            DirectoryIdOrRoot::Root,
+            name("written.txt"),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    writer.write_all(b"Written ").unwrap();
+    writer.write_all(b"secret").unwrap();
+    assert_eq!(writer.written_len(), 14);
+
+    let written = writer.finish().unwrap();
+
+    assert_eq!(
+        archive.read_file_to_vec(streamed).unwrap(),
+        b"Streamed secret"
+    );
+    assert_eq!(
+        archive.read_file_to_vec(written).unwrap(),
+        b"Written secret"
+    );
+
+    let bytes = archive.close().unwrap().into_inner();
+    assert!(!contains(&bytes, b"secret"));
+
+    let archive = BytesArchive::open(bytes, encryption_config(false)).unwrap();
+
+    assert!(matches!(
+        archive.file_bytes(written),
+        Err(ArchiveError::FileIsEncrypted)
+    ));
+    assert_eq!(
+        archive
+            .archive()
+            .read_file_shared(written)
+            .unwrap()
+            .read_to_vec()
+            .unwrap(),
+        b"Written secret"
+    );
+}
+
+/// Content spanning several encryption chunks, the last one being partial
+fn large_content() -> Vec<u8> {
+    (0..3 * 65_536 + 1_000_u32)
+        .map(|i| u8::try_from(i % 251).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_large_encrypted_contents_are_read_by_chunks() {
+    let content = large_content();
+    let len = u64::try_from(content.len()).unwrap();
+
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let created = create_file(&mut archive, "created.bin", &content);
+
+    let streamed = archive
+        .create_file_from_reader(
+            DirectoryIdOrRoot::Root,
+            name("streamed.bin"),
+            Timestamp::now(),
+            content.as_slice(),
+        )
+        .unwrap();
+
+    let mut writer = archive
+        .create_file_writer(
+            DirectoryIdOrRoot::Root,
+            name("written.bin"),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    for part in content.chunks(10_000) {
+        writer.write_all(part).unwrap();
+    }
+
+    let written = writer.finish().unwrap();
+
+    // Exactly two full chunks
+    let aligned = create_file(&mut archive, "aligned.bin", &content[..2 * 65_536]);
+
+    assert_eq!(
+        archive.get_file(aligned).unwrap().content_len,
+        15 + 2 * 65_536 + 2 * 16
+    );
+    assert_eq!(archive.get_file_len(aligned), Some(2 * 65_536));
+    assert_eq!(
+        archive.read_file_to_vec(aligned).unwrap(),
+        content[..2 * 65_536]
+    );
+
+    for id in [created, streamed, written] {
+        assert_eq!(archive.get_file(id).unwrap().content_len, 15 + len + 4 * 16);
+        assert_eq!(archive.get_file_len(id), Some(len));
+        assert_eq!(archive.read_file_to_vec(id).unwrap(), content);
+
+        let mut reader = archive.read_file(id).unwrap();
+        let mut buf = [0; 100];
+
+        for pos in [150_000, 65_500, 196_600, 10, 131_072] {
+            reader.seek(SeekFrom::Start(pos)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+
+            let pos = usize::try_from(pos).unwrap();
+            assert_eq!(buf, content[pos..pos + 100]);
+        }
+
+        reader.seek(SeekFrom::End(-10)).unwrap();
+        assert_eq!(reader.read_to_vec().unwrap(), content[content.len() - 10..]);
+    }
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = BytesArchive::open(bytes, encryption_config(false)).unwrap();
+
+    assert_eq!(
+        archive
+            .archive()
+            .read_file_shared(written)
+            .unwrap()
+            .read_to_vec()
+            .unwrap(),
+        content
+    );
+}
+
+#[test]
+fn test_altered_chunks_fail_to_decrypt() {
+    let content = large_content();
+
+    let mut archive = Archive::create(Cursor::new(vec![]), encryption_config(false)).unwrap();
+
+    let altered = create_file(&mut archive, "altered.bin", &content);
+    let swapped = create_file(&mut archive, "swapped.bin", &content);
+
+    let altered_file = archive.get_file(altered).unwrap().clone();
+    let swapped_file = archive.get_file(swapped).unwrap().clone();
+
+    let mut bytes = archive.close().unwrap().into_inner();
+
+    // Alter the second chunk only
+    alter_content(&mut bytes, &altered_file, |content| {
+        content[15 + 70_000] ^= 1
+    });
+
+    // Swap the first two chunks, which are both full
+    alter_content(&mut bytes, &swapped_file, |content| {
+        let (first, second) = content[15..].split_at_mut(65_536 + 16);
+        first.swap_with_slice(&mut second[..65_536 + 16]);
+    });
+
+    let mut archive = Archive::open(Cursor::new(bytes), encryption_config(false)).unwrap();
+
+    // Other chunks can still be read
+    for pos in [0, 140_000] {
+        let mut reader = archive.read_file(altered).unwrap();
+        let mut buf = [0; 100];
+
+        reader.seek(SeekFrom::Start(pos)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+
+        let pos = usize::try_from(pos).unwrap();
+        assert_eq!(buf, content[pos..pos + 100]);
+    }
+
+    let mut reader = archive.read_file(altered).unwrap();
+    reader.seek(SeekFrom::Start(70_000)).unwrap();
+
+    assert!(matches!(
+        reader.read_to_vec(),
+        Err(FileReaderError::Decryption)
+    ));
+
+    assert!(matches!(
+        archive.read_file_to_vec(swapped),
+        Err(ArchiveError::FileReader(FileReaderError::Decryption))
+    ));
+}
@@ -1,4 +1,7 @@
-use std::io::{Cursor, Read};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    num::NonZero,
+};
 
 use crate::{Archive, ArchiveConfig, DirectoryIdOrRoot, ItemName, Timestamp};
 
@@ -113,3 +116,219 @@ fn test_file_reader_error_display() {
     let displayed = format!("{err}");
     assert!(displayed.contains("not a valid UTF-8 string"));
 }
+
+#[test]
+fn test_seek() {
+    let content = b"0123456789";
+    let mut archive = create_archive_with_file(content);
+    let file_id = get_file_id(&archive);
+    let mut reader = archive.read_file(file_id).unwrap();
+    let mut buf = [0u8; 3];
+
+    assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"456");
+
+    assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 8);
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"89");
+
+    assert_eq!(reader.seek(SeekFrom::Current(-5)).unwrap(), 5);
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"567");
+
+    assert!(reader.seek(SeekFrom::Start(11)).is_err());
+    assert!(reader.seek(SeekFrom::Current(-9)).is_err());
+    assert_eq!(reader.stream_position().unwrap(), 8);
+}
+
+#[test]
+fn test_seek_disables_checksum_verification() {
+    let content = b"original content";
+    let archive = create_archive_with_file(content);
+    let file_id = get_file_id(&archive);
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    let mut cursor = archive.close().unwrap();
+    cursor.get_mut()[content_addr as usize + 4] ^= 0xFF;
+
+    let mut archive = Archive::open(cursor, ArchiveConfig::default()).unwrap();
+    let mut reader = archive.read_file(file_id).unwrap();
+
+    // Asking for the current position doesn't count as seeking
+    reader.stream_position().unwrap();
+    assert!(reader.is_checksum_verified_on_read());
+
+    reader.seek(SeekFrom::Start(9)).unwrap();
+    assert!(!reader.is_checksum_verified_on_read());
+
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"content");
+
+    // Seeking back to the beginning enables it again
+    reader.rewind().unwrap();
+    assert!(reader.is_checksum_verified_on_read());
+
+    let err = reader.read_to_end(&mut buf).unwrap_err();
+    assert!(format!("{err}").contains("hash doesn't match"));
+}
+
+#[test]
+fn test_verify() {
+    let content = b"original content";
+    let mut archive = create_archive_with_file(content);
+    let file_id = get_file_id(&archive);
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    let mut reader = archive.read_file(file_id).unwrap();
+    reader.seek(SeekFrom::Start(9)).unwrap();
+    reader.verify().unwrap();
+
+    // Position is preserved
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"content");
+
+    let mut cursor = archive.close().unwrap();
+    cursor.get_mut()[content_addr as usize] ^= 0xFF;
+
+    let mut archive = Archive::open(cursor, ArchiveConfig::default()).unwrap();
+    let err = archive.read_file(file_id).unwrap().verify().unwrap_err();
+    assert!(matches!(
+        err,
+        crate::FileReaderError::ChecksumMismatch { .. }
+    ));
+}
+
+#[test]
+fn test_shared_readers() {
+    let mut archive = create_archive_with_file(b"first file");
+
+    let second_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("second".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"0123456789".to_vec()),
+        )
+        .unwrap();
+
+    let first_id = archive
+        .with_paths()
+        .get_file_at("test")
+        .map(|file| file.id)
+        .unwrap();
+
+    // Both readers are used at the same time
+    let mut first = archive.read_file_shared(first_id).unwrap();
+    let mut second = archive.read_file_shared(second_id).unwrap();
+
+    let mut buf = [0u8; 5];
+
+    first.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"first");
+
+    second.seek(SeekFrom::Start(5)).unwrap();
+    second.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"56789");
+
+    first.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b" file");
+
+    second.verify().unwrap();
+    assert_eq!(second.read_to_vec().unwrap(), b"");
+}
+
+#[test]
+fn test_shared_readers_across_threads() {
+    let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+    std::fs::remove_file(&path).unwrap();
+
+    let contents = (0..8)
+        .map(|i| format!("content of file {i}").repeat(1000).into_bytes())
+        .collect::<Vec<_>>();
+
+    let mut archive = Archive::create_as_file(&path, ArchiveConfig::default()).unwrap();
+
+    let file_ids = contents
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            archive
+                .create_file(
+                    DirectoryIdOrRoot::Root,
+                    ItemName::new(format!("file{i}")).unwrap(),
+                    Timestamp::now(),
+                    Cursor::new(content.clone()),
+                )
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    archive.flush().unwrap();
+
+    let archive = Archive::open_from_file_readonly(&path, ArchiveConfig::default()).unwrap();
+
+    std::thread::scope(|scope| {
+        for (file_id, content) in file_ids.iter().zip(&contents) {
+            let archive = &archive;
+
+            scope.spawn(move || {
+                let read = archive
+                    .read_file_shared(*file_id)
+                    .unwrap()
+                    .read_to_vec()
+                    .unwrap();
+
+                assert_eq!(&read, content);
+            });
+        }
+    });
+}
+
+#[test]
+fn test_shared_reader_checksum_mismatch() {
+    let conf = ArchiveConfig {
+        chunk_size: Some(NonZero::new(4).unwrap()),
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            ItemName::new("test".to_owned()).unwrap(),
+            Timestamp::now(),
+            Cursor::new(b"original content".to_vec()),
+        )
+        .unwrap();
+
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    let mut cursor = archive.close().unwrap();
+    cursor.get_mut()[content_addr as usize + 9] ^= 0xFF;
+
+    let archive = Archive::open(cursor, ArchiveConfig::default()).unwrap();
+
+    // Chunks which weren't modified can still be read
+    let mut reader = archive.read_file_shared(file_id).unwrap();
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"original");
+
+    let err = reader.read_to_end(&mut vec![]).unwrap_err();
+    assert!(format!("{err}").contains("Checksum of chunk 2 doesn't match"));
+
+    let err = archive
+        .read_file_shared(file_id)
+        .unwrap()
+        .verify()
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        crate::FileReaderError::ChecksumMismatch { .. }
+    ));
+}
@@ -0,0 +1,107 @@
+use std::io::{Cursor, Write};
+
+use super::name;
+use crate::{Archive, ArchiveConfig, ArchiveError, DirectoryIdOrRoot, Timestamp};
+
+#[test]
+fn test_write_and_finish() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let mut writer = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("file"), Timestamp::now())
+        .unwrap();
+
+    for i in 0..100_u8 {
+        writer.write_all(&[i; 100]).unwrap();
+    }
+
+    assert_eq!(writer.written_len(), 10_000);
+
+    let file_id = writer.finish().unwrap();
+
+    let expected = (0..100_u8).flat_map(|i| [i; 100]).collect::<Vec<_>>();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), expected);
+}
+
+#[test]
+fn test_finish_with_new_segment() {
+    let conf = ArchiveConfig {
+        first_segment_files_capacity_override: Some(std::num::NonZero::new(1).unwrap()),
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    let first_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("first"),
+            Timestamp::now(),
+            Cursor::new(b"first".to_vec()),
+        )
+        .unwrap();
+
+    // The first segment is full, so finishing the writer requires creating a new one
+    let mut writer = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("second"), Timestamp::now())
+        .unwrap();
+
+    writer.write_all(b"second").unwrap();
+    let second_id = writer.finish().unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert_eq!(archive.read_file_to_vec(first_id).unwrap(), b"first");
+    assert_eq!(archive.read_file_to_vec(second_id).unwrap(), b"second");
+}
+
+#[test]
+fn test_drop_without_finishing() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let mut writer = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("file"), Timestamp::now())
+        .unwrap();
+
+    writer.write_all(&[1; 1000]).unwrap();
+    drop(writer);
+
+    assert_eq!(archive.files().count(), 0);
+
+    // Space used by the discarded content is given back
+    let mut writer = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("file"), Timestamp::now())
+        .unwrap();
+
+    writer.write_all(&[2; 500]).unwrap();
+    let file_id = writer.finish().unwrap();
+
+    // The new content was written where the discarded one started
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    let source = archive.close().unwrap();
+    assert_eq!(source.get_ref().len() as u64, content_addr + 1000);
+
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert_eq!(archive.files().count(), 1);
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![2; 500]);
+}
+
+#[test]
+fn test_duplicate_name() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    archive
+        .create_dir(DirectoryIdOrRoot::Root, name("dup"), Timestamp::now())
+        .unwrap();
+
+    let err = archive
+        .create_file_writer(DirectoryIdOrRoot::Root, name("dup"), Timestamp::now())
+        .err()
+        .unwrap();
+
+    assert!(matches!(err, ArchiveError::DuplicateName { .. }));
+}
@@ -0,0 +1,318 @@
+use std::io::Cursor;
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveVersion, DirectoryIdOrRoot, FileId, Timestamp,
+    VerifyOptions,
+};
+
+use super::{journal::CrashingStream, name};
+
+fn create_archive() -> (Archive<Cursor<Vec<u8>>>, FileId, FileId) {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    archive
+        .with_paths_mut()
+        .write_file_at("dir/file", Cursor::new(vec![1; 1000]), Timestamp::now())
+        .unwrap();
+
+    let link_id = archive
+        .with_paths_mut()
+        .create_hard_link_at("other/link", "dir/file")
+        .unwrap();
+
+    let file_id = archive.with_paths().get_file_at("dir/file").unwrap().id;
+
+    (archive, file_id, link_id)
+}
+
+#[test]
+fn test_hard_links_roundtrip() {
+    let (archive, file_id, link_id) = create_archive();
+    let len_before = archive.stream().get_ref().len();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    let file = archive.get_file(file_id).unwrap().clone();
+    let link = archive.get_file(link_id).unwrap().clone();
+
+    assert_eq!(link.content_addr, file.content_addr);
+    assert_eq!(link.content_len, file.content_len);
+    assert_eq!(link.modif_time, file.modif_time);
+
+    assert_eq!(
+        archive.get_hard_links(file_id).collect::<Vec<_>>(),
+        [link_id]
+    );
+    assert_eq!(
+        archive.get_hard_links(link_id).collect::<Vec<_>>(),
+        [file_id]
+    );
+
+    assert_eq!(archive.read_file_to_vec(link_id).unwrap(), vec![1; 1000]);
+
+    // The content is only stored once
+    assert!(archive.stream().get_ref().len() < len_before + 1000);
+
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_shared_content_is_freed_with_last_link() {
+    let (mut archive, file_id, link_id) = create_archive();
+    let content_addr = archive.get_file(file_id).unwrap().content_addr;
+
+    archive.remove_file(file_id).unwrap();
+    assert_eq!(archive.get_hard_links(link_id).count(), 0);
+
+    // The content is still in use, so it must not be overwritten
+    let other_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("other_file"),
+            Timestamp::now(),
+            Cursor::new(vec![2; 1000]),
+        )
+        .unwrap();
+
+    assert_ne!(
+        archive.get_file(other_id).unwrap().content_addr,
+        content_addr
+    );
+    assert_eq!(archive.read_file_to_vec(link_id).unwrap(), vec![1; 1000]);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.read_file_to_vec(link_id).unwrap(), vec![1; 1000]);
+
+    // Once the last link is removed, the space can be re-used
+    archive.remove_file(link_id).unwrap();
+
+    let reusing_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("reusing"),
+            Timestamp::now(),
+            Cursor::new(vec![3; 1000]),
+        )
+        .unwrap();
+
+    assert_eq!(
+        archive.get_file(reusing_id).unwrap().content_addr,
+        content_addr
+    );
+}
+
+#[test]
+fn test_replacing_content_detaches_link() {
+    let (mut archive, file_id, link_id) = create_archive();
+
+    archive
+        .replace_file_content(link_id, Timestamp::now(), Cursor::new(b"replaced"))
+        .unwrap();
+
+    assert_eq!(archive.get_hard_links(file_id).count(), 0);
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![1; 1000]);
+    assert_eq!(archive.read_file_to_string(link_id).unwrap(), "replaced");
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_hard_links(file_id).count(), 0);
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![1; 1000]);
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_relinking_replaced_file_starts_new_group() {
+    let (mut archive, file_id, link_id) = create_archive();
+
+    let other_link_id = archive
+        .create_hard_link(DirectoryIdOrRoot::Root, name("other_link"), file_id)
+        .unwrap();
+
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(b"replaced"))
+        .unwrap();
+
+    let new_link_id = archive
+        .create_hard_link(DirectoryIdOrRoot::Root, name("new_link"), file_id)
+        .unwrap();
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(
+        archive.get_hard_links(file_id).collect::<Vec<_>>(),
+        [new_link_id]
+    );
+    assert_eq!(
+        archive.get_hard_links(link_id).collect::<Vec<_>>(),
+        [other_link_id]
+    );
+}
+
+#[test]
+fn test_hard_links_survive_power_loss() {
+    let conf = || ArchiveConfig {
+        journal: true,
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf()).unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+        let mut archive = Archive::open(stream, conf()).unwrap();
+
+        // The archive is never flushed, so the group must be persisted alongside each link
+        let result = (|| {
+            for i in 0..2 {
+                archive.create_hard_link(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("link{i}")),
+                    file_id,
+                )?;
+            }
+
+            Ok::<_, ArchiveError>(())
+        })();
+
+        let crashed = result.is_err();
+
+        let stream = archive.close().unwrap();
+
+        // Writes which weren't synced yet may or may not have been persisted
+        for bytes in [stream.inner.into_inner(), stream.synced] {
+            let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+                .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+            let link_ids = archive
+                .files()
+                .map(|file| file.id)
+                .filter(|id| *id != file_id)
+                .collect::<Vec<_>>();
+
+            if !crashed {
+                assert_eq!(link_ids.len(), 2);
+            }
+
+            let mut linked = archive.get_hard_links(file_id).collect::<Vec<_>>();
+            linked.sort_by_key(|id| link_ids.iter().position(|link_id| link_id == id));
+
+            assert_eq!(linked, link_ids);
+
+            for link_id in link_ids {
+                assert_eq!(archive.read_file_to_vec(link_id).unwrap(), vec![1; 1000]);
+            }
+        }
+
+        if !crashed {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_hard_links_require_version_2() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            version: ArchiveVersion::One,
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    assert!(matches!(
+        archive.create_hard_link(DirectoryIdOrRoot::Root, name("link"), file_id),
+        Err(ArchiveError::UnsupportedByVersion {
+            current: 1,
+            required: 2
+        })
+    ));
+
+    assert!(archive.with_paths().get_item_at("link").is_none());
+}
+
+#[test]
+fn test_shared_content_survives_compaction() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let removed = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("removed"),
+            Timestamp::now(),
+            Cursor::new(vec![0; 1000]),
+        )
+        .unwrap();
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(vec![1; 1000]),
+        )
+        .unwrap();
+
+    let link_id = archive
+        .create_hard_link(DirectoryIdOrRoot::Root, name("link"), file_id)
+        .unwrap();
+
+    archive.flush().unwrap();
+    archive.remove_file(removed).unwrap();
+
+    assert!(archive.compact().unwrap().reclaimed_bytes() >= 1000);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(
+        archive.get_file(file_id).unwrap().content_addr,
+        archive.get_file(link_id).unwrap().content_addr
+    );
+
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), vec![1; 1000]);
+    assert_eq!(archive.read_file_to_vec(link_id).unwrap(), vec![1; 1000]);
+    assert_eq!(
+        archive.get_hard_links(file_id).collect::<Vec<_>>(),
+        [link_id]
+    );
+}
+
+#[test]
+fn test_hard_link_to_missing_file_is_rejected() {
+    let (mut archive, file_id, _) = create_archive();
+
+    archive.remove_file(file_id).unwrap();
+
+    assert!(matches!(
+        archive.create_hard_link(DirectoryIdOrRoot::Root, name("link"), file_id),
+        Err(ArchiveError::FileNotFound)
+    ));
+
+    assert!(archive.with_paths().get_item_at("link").is_none());
+}
@@ -1,7 +1,9 @@
 use std::io::Cursor;
 
 use crate::{
-    data::header::{Header, HeaderDecodingError, MAGIC_NUMBER},
+    data::header::{
+        FeatureFlags, HEADER_FEATURES_OFFSET, Header, HeaderDecodingError, MAGIC_NUMBER,
+    },
     source::Source,
 };
 
@@ -58,10 +60,64 @@ fn test_encode() {
     let encoded = header.encode();
     assert_eq!(encoded.len(), 256);
     assert_eq!(&encoded[0..8], MAGIC_NUMBER);
-    assert_eq!(u32::from_le_bytes(encoded[8..12].try_into().unwrap()), 1);
+    assert_eq!(u32::from_le_bytes(encoded[8..12].try_into().unwrap()), 2);
     assert!(encoded[12..].iter().all(|b| *b == 0));
 }
 
+fn header_with_features(version: u32, required: u32, optional: u32) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend(MAGIC_NUMBER);
+    bytes.extend(version.to_le_bytes());
+    bytes.extend(vec![0u8; HEADER_FEATURES_OFFSET as usize - bytes.len()]);
+    bytes.extend(required.to_le_bytes());
+    bytes.extend(optional.to_le_bytes());
+    bytes.extend(vec![0u8; 256 - bytes.len()]);
+    bytes
+}
+
+#[test]
+fn test_decode_features() {
+    let bytes = header_with_features(2, FeatureFlags::REQUIRED_COMPRESSION, 1 << 31);
+    let mut source = Source::new(Cursor::new(bytes));
+    let header = Header::decode(&mut source).unwrap().header;
+    assert!(header.features.requires(FeatureFlags::REQUIRED_COMPRESSION));
+    assert!(!header.features.requires(FeatureFlags::REQUIRED_ENCRYPTION));
+
+    // Unknown optional features are preserved
+    assert!(header.features.uses(1 << 31));
+    assert_eq!(
+        header.encode(),
+        header_with_features(2, FeatureFlags::REQUIRED_COMPRESSION, 1 << 31)
+    );
+}
+
+#[test]
+fn test_unsupported_features() {
+    let bytes = header_with_features(2, FeatureFlags::REQUIRED_COMPRESSION | 1 << 20, 0);
+    let mut source = Source::new(Cursor::new(bytes));
+    let err = Header::decode(&mut source).unwrap_err();
+    assert!(matches!(
+        err,
+        HeaderDecodingError::UnsupportedFeatures { unsupported } if unsupported == 1 << 20
+    ));
+}
+
+#[test]
+fn test_features_are_padding_before_v2() {
+    let bytes = header_with_features(1, FeatureFlags::REQUIRED_COMPRESSION, 0);
+    let mut source = Source::new(Cursor::new(bytes));
+    let err = Header::decode(&mut source).unwrap_err();
+    assert!(matches!(err, HeaderDecodingError::NonZeroPadding));
+}
+
+#[test]
+fn test_encryption_flag_without_params() {
+    let bytes = header_with_features(2, FeatureFlags::REQUIRED_ENCRYPTION, 0);
+    let mut source = Source::new(Cursor::new(bytes));
+    let err = Header::decode(&mut source).unwrap_err();
+    assert!(matches!(err, HeaderDecodingError::InvalidEncryptionParams));
+}
+
 #[test]
 fn test_header_decoding_error_display() {
     let err = HeaderDecodingError::InvalidMagicNumber {
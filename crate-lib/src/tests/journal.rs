@@ -0,0 +1,289 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use sha3::{Digest, Sha3_256};
+
+use super::name;
+use crate::{
+    Archive, ArchiveConfig, ArchiveMetadataDecodingError, DirectoryIdOrRoot, JournalDecodingError,
+    SyncData, Timestamp, Truncate,
+    data::header::Header,
+    journal::{JournalRecord, JournalWrite},
+    source::Source,
+};
+
+/// Stream that simulates a power loss after a given number of writes
+///
+/// The write during which the power is lost is only partially performed.
+pub(super) struct CrashingStream {
+    pub(super) inner: Cursor<Vec<u8>>,
+    pub(super) writes_left: usize,
+
+    /// Content of the stream the last time it was synced, i.e. what is left if no unsynced write was persisted
+    pub(super) synced: Vec<u8>,
+}
+
+impl CrashingStream {
+    pub(super) fn new(content: Vec<u8>, writes_left: usize) -> Self {
+        Self {
+            synced: content.clone(),
+            inner: Cursor::new(content),
+            writes_left,
+        }
+    }
+}
+
+impl Read for CrashingStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for CrashingStream {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl Write for CrashingStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.writes_left == 0 {
+            return Err(std::io::Error::other("simulated power loss"));
+        }
+
+        self.writes_left -= 1;
+
+        if self.writes_left == 0 {
+            self.inner.write_all(&buf[..buf.len() / 2])?;
+            return Err(std::io::Error::other("simulated power loss"));
+        }
+
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SyncData for CrashingStream {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        if self.writes_left > 0 {
+            self.synced = self.inner.get_ref().clone();
+        }
+
+        Ok(())
+    }
+}
+
+impl Truncate for CrashingStream {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        if self.writes_left == 0 {
+            return Err(std::io::Error::other("simulated power loss"));
+        }
+
+        self.writes_left -= 1;
+        self.inner.truncate(len)
+    }
+}
+
+fn journaled_config() -> ArchiveConfig {
+    ArchiveConfig {
+        default_files_capacity_by_ft_segment: std::num::NonZero::new(2).unwrap(),
+        first_segment_files_capacity_override: Some(std::num::NonZero::new(2).unwrap()),
+        journal: true,
+        ..ArchiveConfig::default()
+    }
+}
+
+#[test]
+fn test_journaled_archive_roundtrip() {
+    let mut archive = Archive::create(Cursor::new(vec![]), journaled_config()).unwrap();
+    assert!(archive.has_journal());
+
+    let file_id = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            Timestamp::now(),
+            Cursor::new(b"content".to_vec()),
+        )
+        .unwrap();
+
+    archive
+        .replace_file_content(file_id, Timestamp::now(), Cursor::new(b"new".to_vec()))
+        .unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert!(archive.has_journal());
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), b"new");
+}
+
+#[test]
+fn test_enable_journal_on_existing_archive() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+    assert!(!archive.has_journal());
+
+    archive.enable_journal().unwrap();
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+    assert!(archive.has_journal());
+}
+
+#[test]
+fn test_journaled_archive_compaction() {
+    let mut archive = Archive::create(Cursor::new(vec![]), journaled_config()).unwrap();
+
+    let mut files = vec![];
+
+    for i in 0..6_u8 {
+        let content = vec![i; 500 * usize::from(i + 1)];
+
+        let file_id = archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name(&format!("file{i}")),
+                Timestamp::now(),
+                Cursor::new(content.clone()),
+            )
+            .unwrap();
+
+        files.push((file_id, content));
+    }
+
+    for (file_id, _) in files.iter().step_by(2) {
+        archive.remove_file(*file_id).unwrap();
+    }
+
+    assert!(archive.compact().unwrap().reclaimed_bytes() > 0);
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    for (file_id, content) in files.into_iter().skip(1).step_by(2) {
+        assert_eq!(archive.read_file_to_vec(file_id).unwrap(), content);
+    }
+}
+
+#[test]
+fn test_power_loss_never_corrupts_archive() {
+    let mut archive = Archive::create(Cursor::new(vec![]), journaled_config()).unwrap();
+
+    let dir_id = archive
+        .create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+        .unwrap();
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            name("existing"),
+            Timestamp::now(),
+            Cursor::new(b"existing content".to_vec()),
+        )
+        .unwrap();
+
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+
+        let mut archive = Archive::open(stream, ArchiveConfig::default()).unwrap();
+
+        let result = (|| {
+            let with_paths = archive.with_paths();
+            let existing = with_paths.get_file_at("dir/existing").unwrap().id;
+
+            for i in 0..3 {
+                archive.create_file(
+                    DirectoryIdOrRoot::NonRoot(dir_id),
+                    name(&format!("new{i}")),
+                    Timestamp::now(),
+                    Cursor::new(vec![i; 100]),
+                )?;
+            }
+
+            archive.replace_file_content(
+                existing,
+                Timestamp::now(),
+                Cursor::new(b"replaced".to_vec()),
+            )?;
+
+            archive.rename_directory(dir_id, name("renamed"))?;
+            archive.move_file(existing, DirectoryIdOrRoot::Root, name("moved"))?;
+            archive.remove_dir(dir_id)?;
+            archive.compact()?;
+
+            Ok::<_, crate::ArchiveError>(())
+        })();
+
+        let crashed = result.is_err();
+
+        let stream = archive.close().unwrap();
+
+        // Writes which weren't synced yet may or may not have been persisted
+        for bytes in [stream.inner.into_inner(), stream.synced] {
+            let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+                .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+            let file_ids = archive.files().map(|file| file.id).collect::<Vec<_>>();
+
+            for file_id in file_ids {
+                archive.read_file_to_vec(file_id).unwrap();
+            }
+
+            if !crashed {
+                assert_eq!(archive.dirs().count(), 0);
+                assert_eq!(archive.files().count(), 1);
+            }
+        }
+
+        if !crashed {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_out_of_bounds_journal_writes_are_rejected() {
+    let bytes = Archive::create(Cursor::new(vec![]), journaled_config())
+        .unwrap()
+        .close()
+        .unwrap()
+        .into_inner();
+
+    let journal_addr = Header::decode(&mut Source::new(Cursor::new(&bytes)))
+        .unwrap()
+        .header
+        .journal_addr
+        .unwrap();
+
+    let archive_len = u64::try_from(bytes.len()).unwrap();
+
+    // Overflowing the address space, past the archive's end, and overwriting the journal itself
+    for addr in [u64::MAX - 4, archive_len - 8, journal_addr + 8] {
+        let record = JournalRecord {
+            writes: vec![JournalWrite {
+                addr,
+                data: vec![0xFF; 16],
+            }],
+        }
+        .encode();
+
+        let mut bytes = bytes.clone();
+        let journal_addr = usize::try_from(journal_addr).unwrap();
+
+        // Commit the record, with a valid checksum
+        bytes[journal_addr + 16..journal_addr + 24]
+            .copy_from_slice(&u64::try_from(record.len()).unwrap().to_le_bytes());
+        bytes[journal_addr + 24..journal_addr + 56].copy_from_slice(&Sha3_256::digest(&record));
+        bytes[journal_addr + 56..journal_addr + 56 + record.len()].copy_from_slice(&record);
+
+        assert!(matches!(
+            Archive::open(Cursor::new(bytes), ArchiveConfig::default()),
+            Err(ArchiveMetadataDecodingError::InvalidJournal(
+                JournalDecodingError::InvalidRecord
+            ))
+        ));
+    }
+}
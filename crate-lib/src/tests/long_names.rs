@@ -0,0 +1,219 @@
+use std::io::Cursor;
+
+use super::{contains, name};
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveVersion, DirectoryIdOrRoot, EncryptionKey,
+    FeatureFlags, MAX_INLINE_NAME_LEN, Timestamp, VerifyOptions,
+};
+
+/// A name taking 300 bytes once encoded
+fn long_name(char: char) -> String {
+    std::iter::repeat_n(char, 300 / char.len_utf8()).collect()
+}
+
+#[test]
+fn test_long_names_roundtrip() {
+    let dir_name = long_name('長');
+    let file_name = long_name('名');
+
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let dir = archive
+        .create_dir(DirectoryIdOrRoot::Root, name(&dir_name), Timestamp::now())
+        .unwrap();
+
+    assert!(
+        archive
+            .features()
+            .requires(FeatureFlags::REQUIRED_ITEM_RECORDS)
+    );
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir),
+            name(&file_name),
+            Timestamp::now(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    let short = archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir),
+            name("short.txt"),
+            Timestamp::now(),
+            Cursor::new(b"short"),
+        )
+        .unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+    let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_dir(dir).unwrap().name.as_ref(), dir_name);
+    assert_eq!(archive.get_file(file).unwrap().name.as_ref(), file_name);
+    assert_eq!(archive.get_file(short).unwrap().name.as_ref(), "short.txt");
+
+    let path = format!("{dir_name}/{file_name}");
+    assert_eq!(
+        archive.with_paths().get_file_at(&path).unwrap().id.inner(),
+        file.inner()
+    );
+
+    assert_eq!(archive.read_file_to_vec(file).unwrap(), b"content");
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_renaming_and_removing_frees_stored_names() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            journal: true,
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    let dir = archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('a')),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('b')),
+            Timestamp::now(),
+            Cursor::new(b"content".repeat(100)),
+        )
+        .unwrap();
+
+    archive.rename_directory(dir, name("short")).unwrap();
+    archive
+        .move_file(file, DirectoryIdOrRoot::NonRoot(dir), name(&long_name('c')))
+        .unwrap();
+
+    let removed = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('d')),
+            Timestamp::now(),
+            Cursor::new(b"removed"),
+        )
+        .unwrap();
+
+    archive.remove_file(removed).unwrap();
+
+    let report = archive.compact().unwrap();
+    assert!(report.reclaimed_bytes() > 0);
+
+    let bytes = archive.close().unwrap().into_inner();
+    let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_dir(dir).unwrap().name.as_ref(), "short");
+    assert_eq!(
+        archive.get_file(file).unwrap().name.as_ref(),
+        long_name('c')
+    );
+    assert_eq!(
+        archive.read_file_to_vec(file).unwrap(),
+        b"content".repeat(100)
+    );
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_long_names_can_be_encrypted() {
+    let conf = ArchiveConfig {
+        encryption_key: Some(EncryptionKey::Raw([7; 32])),
+        encrypt_names: true,
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = Archive::create(Cursor::new(vec![]), conf.clone()).unwrap();
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name(&"secret".repeat(40)),
+            Timestamp::now(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+    assert!(!contains(&bytes, b"secret"));
+
+    let mut archive = Archive::open(Cursor::new(bytes), conf).unwrap();
+
+    assert_eq!(
+        archive.get_file(file).unwrap().name.as_ref(),
+        "secret".repeat(40)
+    );
+    assert_eq!(archive.read_file_to_vec(file).unwrap(), b"content");
+}
+
+#[test]
+fn test_long_names_require_v2() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            version: ArchiveVersion::One,
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    assert!(matches!(
+        archive.create_dir(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('a')),
+            Timestamp::now()
+        ),
+        Err(ArchiveError::NameTooLong {
+            max: MAX_INLINE_NAME_LEN
+        })
+    ));
+
+    archive.upgrade_to(ArchiveVersion::Two).unwrap();
+
+    let dir = archive
+        .create_dir(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('a')),
+            Timestamp::now(),
+        )
+        .unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.get_dir(dir).unwrap().name.as_ref(), long_name('a'));
+}
+
+#[test]
+fn test_rolled_back_transaction_restores_features() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    {
+        let mut tx = archive.transaction();
+
+        tx.create_dir(
+            DirectoryIdOrRoot::Root,
+            name(&long_name('a')),
+            Timestamp::now(),
+        )
+        .unwrap();
+    }
+
+    assert_eq!(archive.features(), FeatureFlags::default());
+    assert_eq!(archive.dirs().count(), 0);
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.features(), FeatureFlags::default());
+}
@@ -1,13 +1,58 @@
+use std::io::Cursor;
+
+use crate::{Archive, DirectoryIdOrRoot, FileId, ItemName, Timestamp};
+
 mod archive;
+#[cfg(feature = "async")]
+mod async_archive;
+mod attributes;
+mod bytes_archive;
+mod chunks;
 mod complex;
+mod compression;
 mod coverage;
+mod dedup;
+mod encryption;
 mod file_reader;
+mod file_writer;
 mod fuzz_archive_open;
 mod fuzz_name_decode;
 mod fuzz_path_new;
 mod fuzz_roundtrip;
+mod hard_links;
 mod header;
 mod iter;
+mod journal;
+mod long_names;
 mod name;
 mod path;
+mod salvage;
+mod symlinks;
+mod transaction;
+mod verify;
+mod versions;
 mod with_paths;
+
+/// Build a valid item name
+fn name(name: &str) -> ItemName {
+    ItemName::new(name.to_owned()).unwrap()
+}
+
+/// Create a file at the archive's root
+fn create_file(archive: &mut Archive<Cursor<Vec<u8>>>, file_name: &str, content: &[u8]) -> FileId {
+    archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name(file_name),
+            Timestamp::now(),
+            Cursor::new(content),
+        )
+        .unwrap()
+}
+
+/// Check if some data appears in another
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
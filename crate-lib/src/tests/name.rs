@@ -1,4 +1,6 @@
-use crate::{ItemName, NameDecodingError, NameDecodingErrorReason, NameValidationError};
+use crate::{
+    ItemName, MAX_NAME_LEN, NameDecodingError, NameDecodingErrorReason, NameValidationError,
+};
 
 #[test]
 fn test_new_valid() {
@@ -15,7 +17,9 @@ fn test_new_empty() {
 
 #[test]
 fn test_new_too_long() {
-    let err = ItemName::new("a".repeat(256)).unwrap_err();
+    assert!(ItemName::new("a".repeat(MAX_NAME_LEN)).is_ok());
+
+    let err = ItemName::new("a".repeat(MAX_NAME_LEN + 1)).unwrap_err();
     assert!(matches!(err, NameValidationError::NameIsTooLong));
 }
 
@@ -115,7 +119,7 @@ fn test_display_on_name_validation_error() {
     );
     assert_eq!(
         format!("{}", NameValidationError::NameIsTooLong),
-        "name contains more than 255 bytes"
+        "name contains more than 4096 bytes"
     );
     assert_eq!(
         format!("{}", NameValidationError::ForbiddenChar('/')),
@@ -0,0 +1,208 @@
+use std::{io::Cursor, num::NonZero};
+
+use super::name;
+use crate::{Archive, ArchiveConfig, DirectoryIdOrRoot, FileReaderError, SalvageIssue, Timestamp};
+
+/// Create an archive with a `dir/nested` file and several files at the root
+fn create_archive_bytes(conf: ArchiveConfig) -> Vec<u8> {
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    let dir = archive
+        .create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+        .unwrap();
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::NonRoot(dir),
+            name("nested"),
+            Timestamp::now(),
+            Cursor::new(b"nested content".to_vec()),
+        )
+        .unwrap();
+
+    for i in 0..3 {
+        archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name(&format!("file{i}")),
+                Timestamp::now(),
+                Cursor::new(format!("content {i}").into_bytes()),
+            )
+            .unwrap();
+    }
+
+    archive.close().unwrap().into_inner()
+}
+
+/// Get the address of the entry with the provided name
+fn entry_addr(bytes: &[u8], name: &str) -> usize {
+    let pattern = [&[name.len() as u8], name.as_bytes()].concat();
+
+    bytes
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .unwrap()
+        - 16
+}
+
+fn restore(bytes: Vec<u8>) -> Archive<Cursor<Vec<u8>>> {
+    let mut salvaged = Archive::open_salvage(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+    let mut target = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    assert!(salvaged.restore_into(&mut target).unwrap().is_empty());
+
+    let bytes = target.close().unwrap().into_inner();
+    Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap()
+}
+
+#[test]
+fn test_salvage_healthy_archive() {
+    let bytes = create_archive_bytes(ArchiveConfig::default());
+
+    let salvaged =
+        Archive::open_salvage(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+
+    assert!(salvaged.diagnostics().is_empty());
+    assert_eq!(salvaged.dirs().len(), 1);
+    assert_eq!(salvaged.files().len(), 4);
+
+    let archive = restore(bytes);
+    let with_paths = archive.with_paths();
+
+    assert!(with_paths.get_file_at("dir/nested").is_some());
+    assert!(with_paths.get_file_at("file2").is_some());
+}
+
+#[test]
+fn test_salvage_skips_invalid_entries() {
+    let mut bytes = create_archive_bytes(ArchiveConfig::default());
+
+    // Make the directory's and a file's names invalid UTF-8
+    let dir_addr = entry_addr(&bytes, "dir");
+    bytes[dir_addr + 17] = 0xFF;
+
+    let file_addr = entry_addr(&bytes, "file1");
+    bytes[file_addr + 17] = 0xFF;
+
+    assert!(Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).is_err());
+
+    let salvaged =
+        Archive::open_salvage(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+
+    let diagnostics = salvaged
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| (diagnostic.addr, &diagnostic.issue))
+        .collect::<Vec<_>>();
+
+    assert!(matches!(
+        diagnostics.as_slice(),
+        [
+            (Some(dir), SalvageIssue::InvalidDirectoryEntry(_)),
+            (Some(file), SalvageIssue::InvalidFileEntry(_)),
+            (Some(_), SalvageIssue::Misplaced(_)),
+        ] if *dir == dir_addr as u64 && *file == file_addr as u64
+    ));
+
+    assert_eq!(salvaged.dirs().len(), 0);
+    assert_eq!(salvaged.files().len(), 3);
+
+    // The nested file lost its parent directory
+    let archive = restore(bytes);
+    let with_paths = archive.with_paths();
+
+    assert!(with_paths.get_file_at("lost+found/nested").is_some());
+    assert!(with_paths.get_file_at("file0").is_some());
+    assert!(with_paths.get_file_at("file1").is_none());
+    assert!(with_paths.get_file_at("file2").is_some());
+}
+
+#[test]
+fn test_salvage_truncated_segment() {
+    let conf = ArchiveConfig {
+        first_segment_files_capacity_override: Some(NonZero::new(2).unwrap()),
+        default_files_capacity_by_ft_segment: NonZero::new(2).unwrap(),
+        ..ArchiveConfig::default()
+    };
+
+    let mut bytes = create_archive_bytes(conf);
+
+    // Cut the second segment, which holds the last files, as well as the content of `file1`
+    let truncate_at = entry_addr(&bytes, "file2") + 100;
+    bytes.truncate(truncate_at);
+
+    assert!(Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).is_err());
+
+    let mut salvaged = Archive::open_salvage(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert!(matches!(
+        salvaged
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| &diagnostic.issue)
+            .collect::<Vec<_>>()
+            .as_slice(),
+        [
+            SalvageIssue::TruncatedSegment,
+            SalvageIssue::ContentOutOfBounds(_)
+        ]
+    ));
+
+    assert_eq!(salvaged.dirs().len(), 1);
+
+    let files = salvaged
+        .files()
+        .iter()
+        .map(|file| (file.id, file.name.to_string()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(files.len(), 2);
+
+    for (file_id, name) in files {
+        let content = salvaged.read_file(file_id).unwrap().read_to_vec().unwrap();
+
+        assert_eq!(
+            content,
+            match name.as_str() {
+                "nested" => b"nested content".as_slice(),
+                _ => b"content 0".as_slice(),
+            }
+        );
+    }
+}
+
+#[test]
+fn test_salvage_does_not_restore_corrupted_content() {
+    let mut bytes = create_archive_bytes(ArchiveConfig::default());
+
+    let salvaged =
+        Archive::open_salvage(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+
+    let file = salvaged
+        .files()
+        .iter()
+        .find(|file| file.name.as_ref() == "file0")
+        .unwrap()
+        .clone();
+
+    bytes[file.content_addr as usize] ^= 0xFF;
+
+    let mut salvaged = Archive::open_salvage(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+    let mut target = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let issues = salvaged.restore_into(&mut target).unwrap();
+
+    assert!(matches!(
+        issues.as_slice(),
+        [diagnostic] if matches!(
+            diagnostic.issue,
+            SalvageIssue::InvalidContent {
+                file_id,
+                err: FileReaderError::ChecksumMismatch { .. }
+            } if file_id == file.id
+        )
+    ));
+
+    assert_eq!(target.files().count(), 3);
+    assert!(target.with_paths().get_file_at("file0").is_none());
+}
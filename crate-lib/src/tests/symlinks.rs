@@ -0,0 +1,284 @@
+use std::io::Cursor;
+
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveVersion, DirectoryIdOrRoot, ItemIdOrRoot,
+    PathAccessError, PathError, Timestamp, VerifyOptions,
+};
+
+use super::{journal::CrashingStream, name};
+
+fn create_archive() -> Archive<Cursor<Vec<u8>>> {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    archive
+        .with_paths_mut()
+        .write_file_at("dir/file", Cursor::new(b"Hello world!"), Timestamp::now())
+        .unwrap();
+
+    archive
+        .with_paths_mut()
+        .create_symlink_at("dir/link", "file".to_owned(), Timestamp::now())
+        .unwrap();
+
+    archive
+        .with_paths_mut()
+        .create_symlink_at("dir_link", "dir".to_owned(), Timestamp::now())
+        .unwrap();
+
+    archive
+}
+
+#[test]
+fn test_symlinks_roundtrip() {
+    let archive = create_archive();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    let link_id = archive.with_paths().get_file_at("dir/link").unwrap().id;
+    assert_eq!(archive.get_file(link_id).unwrap().content_len, 0);
+    assert!(archive.is_symlink(link_id));
+    assert_eq!(archive.read_link(link_id).unwrap(), "file");
+
+    let file_id = archive.with_paths().get_file_at("dir/file").unwrap().id;
+    assert!(!archive.is_symlink(file_id));
+    assert!(matches!(
+        archive.read_link(file_id),
+        Err(ArchiveError::NotASymlink)
+    ));
+
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_resolve_paths_through_symlinks() {
+    let mut archive = create_archive();
+
+    let dir_id = archive.with_paths().get_dir_at("dir").unwrap().id;
+    let file_id = archive.with_paths().get_file_at("dir/file").unwrap().id;
+    let link_id = archive.with_paths().get_file_at("dir/link").unwrap().id;
+
+    // Links are not followed when using the non-resolving APIs
+    assert!(archive.with_paths().get_item_at("dir_link/file").is_none());
+
+    let with_paths = archive.with_paths();
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir_link/file", false),
+        Ok(ItemIdOrRoot::File(id)) if id == file_id
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir_link/link", false),
+        Ok(ItemIdOrRoot::File(id)) if id == link_id
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir_link/link", true),
+        Ok(ItemIdOrRoot::File(id)) if id == file_id
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir_link", true),
+        Ok(ItemIdOrRoot::NonRootDirectory(id)) if id == dir_id
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir_link/../dir_link/./file", true),
+        Ok(ItemIdOrRoot::File(id)) if id == file_id
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("dir/file/other", true),
+        Err(PathAccessError::FileCollision { .. })
+    ));
+
+    // Invalid links
+    for (path, target) in [
+        ("absolute", "/etc/passwd"),
+        ("escaping", "../.."),
+        ("loop", "loop"),
+    ] {
+        archive
+            .with_paths_mut()
+            .create_symlink_at(path, target.to_owned(), Timestamp::now())
+            .unwrap();
+    }
+
+    let with_paths = archive.with_paths();
+
+    assert!(matches!(
+        with_paths.resolve_item_at("absolute", true),
+        Err(PathAccessError::AbsoluteSymlinkTarget { target }) if target == "/etc/passwd"
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("escaping", true),
+        Err(PathAccessError::Path(PathError::RootEscape))
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("loop", true),
+        Err(PathAccessError::TooManySymlinks)
+    ));
+
+    assert!(matches!(
+        with_paths.resolve_item_at("loop", false),
+        Ok(ItemIdOrRoot::File(_))
+    ));
+}
+
+#[test]
+fn test_replaced_or_removed_symlinks_are_dropped() {
+    let mut archive = create_archive();
+
+    let link_id = archive.with_paths().get_file_at("dir/link").unwrap().id;
+    let dir_link_id = archive.with_paths().get_file_at("dir_link").unwrap().id;
+
+    archive.flush().unwrap();
+
+    // A link with some content becomes a regular file
+    archive
+        .replace_file_content(link_id, Timestamp::now(), Cursor::new(b"content"))
+        .unwrap();
+
+    assert!(!archive.is_symlink(link_id));
+
+    archive.remove_file(dir_link_id).unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert!(!archive.is_symlink(link_id));
+    assert_eq!(archive.read_file_to_string(link_id).unwrap(), "content");
+    assert!(matches!(
+        archive.read_link(dir_link_id),
+        Err(ArchiveError::FileNotFound)
+    ));
+}
+
+#[test]
+fn test_symlinks_survive_compaction() {
+    let mut archive = create_archive();
+
+    let removed = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("removed"),
+            Timestamp::now(),
+            Cursor::new(vec![0; 1000]),
+        )
+        .unwrap();
+
+    archive.flush().unwrap();
+    archive.remove_file(removed).unwrap();
+
+    assert!(archive.compact().unwrap().reclaimed_bytes() > 0);
+
+    let source = archive.close().unwrap();
+    let archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    let link_id = archive.with_paths().get_file_at("dir_link").unwrap().id;
+    assert_eq!(archive.read_link(link_id).unwrap(), "dir");
+}
+
+#[test]
+fn test_invalid_symlink_targets_are_rejected() {
+    let mut archive = create_archive();
+
+    for target in [String::new(), "a\0b".to_owned(), "a".repeat(4097)] {
+        assert!(matches!(
+            archive.create_symlink(
+                DirectoryIdOrRoot::Root,
+                name("invalid"),
+                Timestamp::now(),
+                target
+            ),
+            Err(ArchiveError::InvalidSymlinkTarget)
+        ));
+    }
+
+    assert!(archive.with_paths().get_item_at("invalid").is_none());
+}
+
+#[test]
+fn test_symlinks_survive_power_loss() {
+    let conf = || ArchiveConfig {
+        journal: true,
+        ..ArchiveConfig::default()
+    };
+
+    let archive = Archive::create(Cursor::new(vec![]), conf()).unwrap();
+    let base = archive.close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+        let mut archive = Archive::open(stream, conf()).unwrap();
+
+        // The archive is never flushed, so the target must be persisted alongside each link
+        let result = (|| {
+            for i in 0..2 {
+                archive.create_symlink(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("link{i}")),
+                    Timestamp::now(),
+                    format!("target{i}"),
+                )?;
+            }
+
+            Ok::<_, ArchiveError>(())
+        })();
+
+        let crashed = result.is_err();
+
+        let stream = archive.close().unwrap();
+
+        // Writes which weren't synced yet may or may not have been persisted
+        for bytes in [stream.inner.into_inner(), stream.synced] {
+            let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+                .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+            let links = archive.files().collect::<Vec<_>>();
+
+            if !crashed {
+                assert_eq!(links.len(), 2);
+            }
+
+            for link in links {
+                let target = link.name.replace("link", "target");
+                assert_eq!(archive.read_link(link.id).unwrap(), target);
+            }
+        }
+
+        if !crashed {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_symlinks_require_version_2() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            version: ArchiveVersion::One,
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    assert!(matches!(
+        archive.create_symlink(
+            DirectoryIdOrRoot::Root,
+            name("link"),
+            Timestamp::now(),
+            "target".to_owned()
+        ),
+        Err(ArchiveError::UnsupportedByVersion {
+            current: 1,
+            required: 2
+        })
+    ));
+
+    assert!(archive.with_paths().get_item_at("link").is_none());
+}
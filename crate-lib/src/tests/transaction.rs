@@ -0,0 +1,404 @@
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    num::NonZero,
+};
+
+use super::{journal::CrashingStream, name};
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, DirectoryIdOrRoot, SyncData, Timestamp, Truncate,
+    VerifyOptions,
+};
+
+fn create_archive_with_file(conf: ArchiveConfig) -> Archive<Cursor<Vec<u8>>> {
+    let mut archive = Archive::create(Cursor::new(vec![]), conf).unwrap();
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("existing"),
+            Timestamp::now(),
+            Cursor::new(b"existing content".to_vec()),
+        )
+        .unwrap();
+
+    archive
+}
+
+#[test]
+fn test_commit_transaction() {
+    let mut archive = create_archive_with_file(ArchiveConfig::default());
+    let existing = archive.with_paths().get_file_at("existing").unwrap().id;
+
+    let mut tx = archive.transaction();
+
+    let dir_id = tx
+        .create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+        .unwrap();
+
+    for i in 0..200_u8 {
+        tx.create_file(
+            DirectoryIdOrRoot::NonRoot(dir_id),
+            name(&format!("file{i}")),
+            Timestamp::now(),
+            Cursor::new(vec![i; 10]),
+        )
+        .unwrap();
+    }
+
+    tx.rename_file(existing, name("renamed")).unwrap();
+
+    // Changes are visible before being committed
+    assert!(tx.with_paths().get_file_at("dir/file199").is_some());
+
+    tx.commit().unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.files().count(), 201);
+
+    let file = archive.with_paths().get_file_at("dir/file42").unwrap().id;
+    assert_eq!(archive.read_file_to_vec(file).unwrap(), vec![42; 10]);
+
+    assert_eq!(
+        archive.read_file_to_vec(existing).unwrap(),
+        b"existing content"
+    );
+}
+
+#[test]
+fn test_drop_transaction_rolls_back() {
+    let mut archive = create_archive_with_file(ArchiveConfig::default());
+    let existing = archive.with_paths().get_file_at("existing").unwrap().id;
+
+    let created = {
+        let mut tx = archive.transaction();
+
+        tx.remove_file(existing).unwrap();
+
+        // Space freed by the removed file must not be overwritten before the commit
+        tx.create_file(
+            DirectoryIdOrRoot::Root,
+            name("new"),
+            Timestamp::now(),
+            Cursor::new(b"overwritten".to_vec()),
+        )
+        .unwrap()
+    };
+
+    assert!(archive.get_file(created).is_none());
+    assert_eq!(archive.files().count(), 1);
+    assert_eq!(
+        archive.read_file_to_vec(existing).unwrap(),
+        b"existing content"
+    );
+
+    // IDs are given out again
+    let recreated = archive
+        .create_dir(DirectoryIdOrRoot::Root, name("new"), Timestamp::now())
+        .unwrap();
+
+    assert_eq!(recreated.inner(), created.inner());
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    assert_eq!(archive.files().count(), 1);
+    assert_eq!(archive.dirs().count(), 1);
+    assert_eq!(
+        archive.read_file_to_vec(existing).unwrap(),
+        b"existing content"
+    );
+}
+
+#[test]
+fn test_rollback_restores_changed_items() {
+    let conf = ArchiveConfig {
+        default_files_capacity_by_ft_segment: NonZero::new(2).unwrap(),
+        ..ArchiveConfig::default()
+    };
+
+    let mut archive = create_archive_with_file(conf.clone());
+    let existing = archive.with_paths().get_file_at("existing").unwrap().id;
+
+    {
+        let mut tx = archive.transaction();
+
+        tx.rename_file(existing, name("renamed")).unwrap();
+
+        // Requires new file table segments
+        for i in 0..20_u8 {
+            tx.create_file(
+                DirectoryIdOrRoot::Root,
+                name(&format!("file{i}")),
+                Timestamp::now(),
+                Cursor::new(vec![i; 10]),
+            )
+            .unwrap();
+        }
+    }
+
+    assert_eq!(archive.files().count(), 1);
+    assert!(archive.with_paths().get_file_at("renamed").is_none());
+    assert_eq!(
+        archive.with_paths().get_file_at("existing").unwrap().id,
+        existing
+    );
+
+    // Segments are created again without conflicting with the discarded ones
+    for i in 0..20_u8 {
+        archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name(&format!("file{i}")),
+                Timestamp::now(),
+                Cursor::new(vec![i; 10]),
+            )
+            .unwrap();
+    }
+
+    // Moves the segments' entries, as they are known in memory
+    archive.compact().unwrap();
+
+    let source = archive.close().unwrap();
+    let mut archive = Archive::open(source, conf).unwrap();
+
+    assert_eq!(archive.files().count(), 21);
+    assert!(archive.with_paths().get_file_at("existing").is_some());
+    assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn test_failed_operation_aborts_transaction() {
+    let mut archive = create_archive_with_file(ArchiveConfig::default());
+
+    let mut tx = archive.transaction();
+
+    tx.create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())
+        .unwrap();
+
+    let err = tx
+        .create_dir(DirectoryIdOrRoot::Root, name("existing"), Timestamp::now())
+        .unwrap_err();
+
+    assert!(matches!(err, ArchiveError::DuplicateName { .. }));
+    assert!(tx.is_aborted());
+
+    let err = tx
+        .create_dir(DirectoryIdOrRoot::Root, name("other"), Timestamp::now())
+        .unwrap_err();
+
+    assert!(matches!(err, ArchiveError::TransactionAborted));
+    assert!(matches!(
+        tx.commit().unwrap_err(),
+        ArchiveError::TransactionAborted
+    ));
+
+    assert_eq!(archive.dirs().count(), 0);
+    assert!(archive.with_paths().get_dir_at("dir").is_none());
+}
+
+#[test]
+fn test_power_loss_during_commit_is_atomic() {
+    let conf = ArchiveConfig {
+        journal: true,
+        ..ArchiveConfig::default()
+    };
+
+    let base = create_archive_with_file(conf).close().unwrap().into_inner();
+
+    for writes_left in 0.. {
+        let stream = CrashingStream::new(base.clone(), writes_left);
+
+        let mut archive = Archive::open(stream, ArchiveConfig::default()).unwrap();
+        let existing = archive.with_paths().get_file_at("existing").unwrap().id;
+
+        let result = (|| {
+            let mut tx = archive.transaction();
+
+            let dir_id = tx.create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())?;
+
+            for i in 0..5_u8 {
+                tx.create_file(
+                    DirectoryIdOrRoot::NonRoot(dir_id),
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    Cursor::new(vec![i; 100]),
+                )?;
+            }
+
+            tx.move_file(existing, DirectoryIdOrRoot::NonRoot(dir_id), name("moved"))?;
+            tx.commit()
+        })();
+
+        let committed = result.is_ok();
+
+        let bytes = archive.close().unwrap().inner.into_inner();
+
+        let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default())
+            .unwrap_or_else(|err| panic!("Failed to open after {writes_left} writes: {err}"));
+
+        let with_paths = archive.with_paths();
+
+        match archive.files().count() {
+            1 => {
+                assert!(with_paths.get_file_at("existing").is_some());
+                assert_eq!(archive.dirs().count(), 0);
+            }
+
+            6 => assert!(with_paths.get_file_at("dir/moved").is_some()),
+
+            count => panic!("Transaction was partially applied ({count} files)"),
+        }
+
+        if committed {
+            assert_eq!(archive.files().count(), 6);
+            break;
+        }
+    }
+}
+
+/// Stream failing a single write, then working normally again
+struct FailingStream {
+    inner: Cursor<Vec<u8>>,
+    fail_at: usize,
+}
+
+impl Read for FailingStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for FailingStream {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl Write for FailingStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let fail = self.fail_at == 0;
+        self.fail_at = self.fail_at.wrapping_sub(1);
+
+        if fail {
+            self.inner.write_all(&buf[..buf.len() / 2])?;
+            return Err(std::io::Error::other("simulated write failure"));
+        }
+
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SyncData for FailingStream {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Truncate for FailingStream {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.inner.truncate(len)
+    }
+}
+
+/// Make a transaction fail at every possible write, and check the archive stays consistent afterwards
+///
+/// If `compact` is set, the archive is compacted before being reopened, which relies on the in-memory state
+/// matching the archive's content.
+fn check_failed_transactions(journal: bool, compact: bool) {
+    // Small segments, so that the space freed by a rollback is smaller than the reallocated journal
+    let conf = ArchiveConfig {
+        journal,
+        default_files_capacity_by_ft_segment: NonZero::new(10).unwrap(),
+        default_dirs_capacity_by_ft_segment: NonZero::new(1).unwrap(),
+        ..ArchiveConfig::default()
+    };
+
+    let base = create_archive_with_file(conf.clone())
+        .close()
+        .unwrap()
+        .into_inner();
+
+    for fail_at in 0.. {
+        let stream = FailingStream {
+            inner: Cursor::new(base.clone()),
+            fail_at,
+        };
+
+        let mut archive = Archive::open(stream, conf.clone()).unwrap();
+
+        // Enough metadata to require reallocating the journal
+        let result = (|| {
+            let mut tx = archive.transaction();
+
+            let dir_id = tx.create_dir(DirectoryIdOrRoot::Root, name("dir"), Timestamp::now())?;
+
+            for i in 0..30_u8 {
+                tx.create_file(
+                    DirectoryIdOrRoot::NonRoot(dir_id),
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    Cursor::new(vec![i; 100]),
+                )?;
+            }
+
+            tx.commit()
+        })();
+
+        // Past this point, no write fails anymore
+        if result.is_ok() {
+            assert_eq!(archive.files().count(), 31);
+            break;
+        }
+
+        let files = archive.files().count();
+
+        assert!(
+            files == 1 || files == 31,
+            "Transaction was partially applied in memory ({files} files)"
+        );
+
+        // The archive remains usable, and writing to its free space doesn't overwrite anything
+        // (the content is larger than the space freed by the rollback, but not larger than the journal)
+        archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name("after"),
+                Timestamp::now(),
+                Cursor::new(vec![0; 15_000]),
+            )
+            .unwrap();
+
+        if compact {
+            archive.compact().unwrap();
+        }
+
+        let bytes = archive.close().unwrap().inner.into_inner();
+
+        let mut archive = Archive::open(Cursor::new(bytes), conf.clone())
+            .unwrap_or_else(|err| panic!("Failed to open after failing write {fail_at}: {err}"));
+
+        assert_eq!(archive.files().count(), files + 1);
+        assert!(
+            archive.verify(VerifyOptions::default()).unwrap().is_ok(),
+            "Archive is corrupted after failing write {fail_at}"
+        );
+    }
+}
+
+#[test]
+fn test_failed_commit_restores_archive() {
+    check_failed_transactions(false, false);
+    check_failed_transactions(false, true);
+}
+
+#[test]
+fn test_failed_commit_restores_journaled_archive() {
+    check_failed_transactions(true, false);
+    check_failed_transactions(true, true);
+}
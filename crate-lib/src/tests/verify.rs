@@ -0,0 +1,134 @@
+use std::{io::Cursor, num::NonZero};
+
+use super::name;
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveRegion, DirectoryIdOrRoot, FileId,
+    FileReaderError, IntegrityError, Timestamp, VerifyOptions, coverage::Segment,
+    verify::find_overlaps,
+};
+
+fn create_archive_with_files() -> (Archive<Cursor<Vec<u8>>>, Vec<FileId>) {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let files = (0..3_u8)
+        .map(|i| {
+            archive
+                .create_file(
+                    DirectoryIdOrRoot::Root,
+                    name(&format!("file{i}")),
+                    Timestamp::now(),
+                    Cursor::new(vec![i; 100]),
+                )
+                .unwrap()
+        })
+        .collect();
+
+    (archive, files)
+}
+
+#[test]
+fn test_verify_healthy_archive() {
+    let (mut archive, _) = create_archive_with_files();
+
+    let report = archive.verify(VerifyOptions::default()).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.checked_files, 3);
+}
+
+#[test]
+fn test_verify_detects_checksum_mismatch() {
+    let (archive, files) = create_archive_with_files();
+    let file = archive.get_file(files[1]).unwrap().clone();
+
+    let mut source = archive.close().unwrap();
+    source.get_mut()[file.content_addr as usize + 50] ^= 0xFF;
+
+    let mut archive = Archive::open(source, ArchiveConfig::default()).unwrap();
+
+    let report = archive.verify(VerifyOptions::default()).unwrap();
+    assert_eq!(report.checked_files, 3);
+    assert!(matches!(
+        report.errors.as_slice(),
+        [IntegrityError::ChecksumMismatch { file_id, expected, actual }]
+            if *file_id == file.id && *expected == file.sha3_checksum && *actual != file.sha3_checksum
+    ));
+
+    // Reading the file reports the same typed error
+    assert!(matches!(
+        archive.read_file_to_vec(file.id),
+        Err(ArchiveError::FileReader(
+            FileReaderError::ChecksumMismatch { .. }
+        ))
+    ));
+
+    // Content can be left unchecked
+    let report = archive
+        .verify(VerifyOptions {
+            checksums: false,
+            ..VerifyOptions::default()
+        })
+        .unwrap();
+
+    assert!(report.is_ok());
+    assert_eq!(report.checked_files, 0);
+}
+
+#[test]
+fn test_verify_detects_out_of_bounds_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("archive.baf");
+
+    let (archive, files) = create_archive_with_files();
+    let content = archive.close().unwrap().into_inner();
+    std::fs::write(&path, &content).unwrap();
+
+    let mut archive = Archive::open_from_file_readonly(&path, ArchiveConfig::default()).unwrap();
+    let file = archive.get_file(files[2]).unwrap().clone();
+
+    // Truncate the archive after it was opened
+    let archive_len = content.len() as u64 - 10;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(archive_len)
+        .unwrap();
+
+    let report = archive.verify(VerifyOptions::default()).unwrap();
+    assert_eq!(report.checked_files, 2);
+    assert_eq!(
+        report.errors,
+        [IntegrityError::OutOfBounds {
+            file_id: file.id,
+            content_addr: file.content_addr,
+            content_len: file.content_len,
+            archive_len,
+        }]
+    );
+}
+
+#[test]
+fn test_find_overlapping_regions() {
+    let file = |id| ArchiveRegion::FileContent(FileId(NonZero::new(id).unwrap()));
+    let segment = |start, len| Segment { start, len };
+
+    let overlaps = find_overlaps([
+        (ArchiveRegion::Header, segment(0, 256)),
+        (file(1), segment(256, 100)),
+        (file(2), segment(300, 100)),
+        (file(3), segment(320, 10)),
+        (file(4), segment(400, 0)),
+        (file(5), segment(400, 10)),
+        (ArchiveRegion::Journal, segment(u64::MAX - 5, 10)),
+        (file(6), segment(u64::MAX - 1, 1)),
+    ]);
+
+    assert_eq!(
+        overlaps,
+        [
+            (file(2), file(1)),
+            (file(3), file(2)),
+            (file(6), ArchiveRegion::Journal),
+        ]
+    );
+}
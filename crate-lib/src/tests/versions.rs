@@ -0,0 +1,260 @@
+use std::{
+    io::Cursor,
+    time::{Duration, SystemTime},
+};
+
+use super::name;
+use crate::{
+    Archive, ArchiveConfig, ArchiveError, ArchiveMetadataDecodingError, ArchiveVersion,
+    CompressionCodec, CompressionPolicy, DirectoryIdOrRoot, FeatureFlags, HeaderDecodingError,
+    Timestamp, VerifyOptions, data::header::HEADER_FEATURES_OFFSET,
+};
+
+fn precise_time() -> Timestamp {
+    Timestamp::try_from(SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789)).unwrap()
+}
+
+fn v1_config() -> ArchiveConfig {
+    ArchiveConfig {
+        version: ArchiveVersion::One,
+        ..ArchiveConfig::default()
+    }
+}
+
+#[test]
+fn test_nanosecond_timestamps_roundtrip() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    let dir = archive
+        .create_dir(DirectoryIdOrRoot::Root, name("dir"), precise_time())
+        .unwrap();
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            precise_time(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    let bytes = archive.close().unwrap().into_inner();
+    let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(*archive.version(), ArchiveVersion::Two);
+    assert_eq!(archive.get_dir(dir).unwrap().modif_time, precise_time());
+    assert_eq!(archive.get_file(file).unwrap().modif_time, precise_time());
+    assert_eq!(precise_time().subsec_nanos(), 123_456_789);
+}
+
+#[test]
+fn test_v1_archives_store_whole_seconds() {
+    let mut archive = Archive::create(Cursor::new(vec![]), v1_config()).unwrap();
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file"),
+            precise_time(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    // The in-memory timestamp matches the stored one
+    let modif_time = archive.get_file(file).unwrap().modif_time;
+    assert_eq!(modif_time.secs(), 1_700_000_000);
+    assert_eq!(modif_time.subsec_nanos(), 0);
+
+    let bytes = archive.close().unwrap().into_inner();
+    assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 1);
+
+    let archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+    assert_eq!(*archive.version(), ArchiveVersion::One);
+    assert_eq!(archive.features(), FeatureFlags::default());
+    assert_eq!(archive.get_file(file).unwrap().modif_time, modif_time);
+}
+
+#[test]
+fn test_upgrade_preserves_items() {
+    for journal in [false, true] {
+        let mut archive = Archive::create(
+            Cursor::new(vec![]),
+            ArchiveConfig {
+                journal,
+                compression: Some(CompressionPolicy::new(CompressionCodec::Zstd)),
+                ..v1_config()
+            },
+        )
+        .unwrap();
+
+        let dir = archive
+            .create_dir(DirectoryIdOrRoot::Root, name("dir"), precise_time())
+            .unwrap();
+
+        let file = archive
+            .create_file(
+                DirectoryIdOrRoot::NonRoot(dir),
+                name("file.txt"),
+                precise_time(),
+                Cursor::new(b"content".repeat(100)),
+            )
+            .unwrap();
+
+        archive.flush().unwrap();
+        assert_eq!(archive.features(), FeatureFlags::default());
+
+        // Compression is only available since version 2
+        assert_eq!(archive.get_compression(file), None);
+
+        archive.upgrade_to(ArchiveVersion::Two).unwrap();
+
+        assert_eq!(*archive.version(), ArchiveVersion::Two);
+        assert_eq!(archive.features(), FeatureFlags::default());
+
+        // Only the new timestamps have a nanosecond precision
+        let new_file = archive
+            .create_file(
+                DirectoryIdOrRoot::Root,
+                name("new.txt"),
+                precise_time(),
+                Cursor::new(b"new".repeat(100)),
+            )
+            .unwrap();
+
+        assert!(archive.get_compression(new_file).is_some());
+
+        let bytes = archive.close().unwrap().into_inner();
+        let mut archive = Archive::open(Cursor::new(bytes), ArchiveConfig::default()).unwrap();
+
+        assert_eq!(*archive.version(), ArchiveVersion::Two);
+        assert!(
+            archive
+                .features()
+                .requires(FeatureFlags::REQUIRED_COMPRESSION)
+        );
+
+        assert_eq!(
+            archive.get_dir(dir).unwrap().modif_time.secs(),
+            1_700_000_000
+        );
+        assert_eq!(archive.get_dir(dir).unwrap().modif_time.subsec_nanos(), 0);
+        assert_eq!(
+            archive.get_file(new_file).unwrap().modif_time,
+            precise_time()
+        );
+
+        assert_eq!(
+            archive.read_file_to_vec(file).unwrap(),
+            b"content".repeat(100)
+        );
+        assert_eq!(
+            archive.read_file_to_vec(new_file).unwrap(),
+            b"new".repeat(100)
+        );
+        assert!(archive.verify(VerifyOptions::default()).unwrap().is_ok());
+    }
+}
+
+#[test]
+fn test_upgrade_to_current_version_does_nothing() {
+    let archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+    let bytes = archive.close().unwrap().into_inner();
+
+    let mut archive = Archive::open(Cursor::new(bytes.clone()), ArchiveConfig::default()).unwrap();
+    archive.upgrade_to(ArchiveVersion::LATEST).unwrap();
+
+    assert_eq!(archive.close().unwrap().into_inner(), bytes);
+}
+
+#[test]
+fn test_downgrades_are_refused() {
+    let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+
+    assert!(matches!(
+        archive.upgrade_to(ArchiveVersion::One),
+        Err(ArchiveError::VersionDowngrade {
+            current: 2,
+            requested: 1
+        })
+    ));
+
+    assert_eq!(*archive.version(), ArchiveVersion::Two);
+}
+
+#[test]
+fn test_features_follow_records() {
+    let mut archive = Archive::create(
+        Cursor::new(vec![]),
+        ArchiveConfig {
+            chunk_size: Some(std::num::NonZero::new(1000).unwrap()),
+            compression: Some(CompressionPolicy::new(CompressionCodec::Deflate)),
+            ..ArchiveConfig::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(archive.features(), FeatureFlags::default());
+
+    let file = archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file.txt"),
+            Timestamp::now(),
+            Cursor::new(b"content".repeat(1000)),
+        )
+        .unwrap();
+
+    archive.flush().unwrap();
+
+    let features = archive.features();
+    assert!(features.requires(FeatureFlags::REQUIRED_COMPRESSION));
+    assert!(!features.requires(FeatureFlags::REQUIRED_ENCRYPTION));
+    assert!(features.uses(FeatureFlags::OPTIONAL_CHUNK_CHECKSUMS));
+
+    archive.remove_file(file).unwrap();
+    archive.flush().unwrap();
+
+    // Features are never withdrawn, as it would require looking through every item's record
+    let features = archive.features();
+    assert!(features.requires(FeatureFlags::REQUIRED_COMPRESSION));
+    assert!(features.uses(FeatureFlags::OPTIONAL_CHUNK_CHECKSUMS));
+}
+
+#[test]
+fn test_unknown_features_are_checked_when_opening() {
+    let archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
+    let bytes = archive.close().unwrap().into_inner();
+
+    let offset = usize::try_from(HEADER_FEATURES_OFFSET).unwrap();
+
+    // Unknown optional features are ignored
+    let mut optional = bytes.clone();
+    optional[offset + 7] = 0x80;
+
+    let mut archive = Archive::open(Cursor::new(optional), ArchiveConfig::default()).unwrap();
+    assert!(archive.features().uses(1 << 31));
+
+    archive
+        .create_file(
+            DirectoryIdOrRoot::Root,
+            name("file.txt"),
+            Timestamp::now(),
+            Cursor::new(b"content"),
+        )
+        .unwrap();
+
+    archive.flush().unwrap();
+    assert!(archive.features().uses(1 << 31));
+
+    // Unknown required features are refused
+    let mut required = bytes;
+    required[offset + 3] = 0x80;
+
+    assert!(matches!(
+        Archive::open(Cursor::new(required), ArchiveConfig::default()),
+        Err(ArchiveMetadataDecodingError::InvalidHeader(
+            HeaderDecodingError::UnsupportedFeatures { unsupported }
+        )) if unsupported == 1 << 31
+    ));
+}
@@ -291,6 +291,30 @@ fn test_write_file_at_nested_path() {
     assert_eq!(content, b"deep");
 }
 
+#[test]
+fn test_write_file_from_reader_at() {
+    let mut archive = create_archive_with_structure();
+    let file_id = archive.with_paths().get_file_at("rootfile.txt").unwrap().id;
+
+    archive
+        .with_paths_mut()
+        .write_file_from_reader_at("rootfile.txt", &b"updated"[..], Timestamp::now())
+        .unwrap();
+
+    archive
+        .with_paths_mut()
+        .write_file_from_reader_at("deep/file.txt", &b"deep"[..], Timestamp::now())
+        .unwrap();
+
+    let content = archive.read_file_to_vec(file_id).unwrap();
+    assert_eq!(content, b"updated");
+
+    let binding = archive.with_paths();
+    let file = binding.get_file_at("deep/file.txt").unwrap();
+    let content = archive.read_file_to_vec(file.id).unwrap();
+    assert_eq!(content, b"deep");
+}
+
 #[test]
 fn test_create_file_at_success() {
     let mut archive = Archive::create(Cursor::new(vec![]), ArchiveConfig::default()).unwrap();
@@ -390,3 +414,51 @@ fn test_path_access_error_display() {
     };
     assert!(format!("{fnf}").contains("y"));
 }
+
+#[test]
+fn test_move_at() {
+    let mut archive = create_archive_with_structure();
+    archive
+        .with_paths_mut()
+        .move_at("rootfile.txt", "subdir/moved.txt")
+        .unwrap();
+    assert!(archive.with_paths().get_file_at("rootfile.txt").is_none());
+    let file_id = archive
+        .with_paths()
+        .get_file_at("subdir/moved.txt")
+        .unwrap()
+        .id;
+    assert_eq!(archive.read_file_to_vec(file_id).unwrap(), b"root content");
+
+    archive
+        .with_paths_mut()
+        .create_dir_at("other", Timestamp::now())
+        .unwrap();
+    archive
+        .with_paths_mut()
+        .move_at("subdir", "other/subdir")
+        .unwrap();
+    assert!(
+        archive
+            .with_paths()
+            .get_file_at("other/subdir/nested.txt")
+            .is_some()
+    );
+}
+
+#[test]
+fn test_move_at_errors() {
+    let mut archive = create_archive_with_structure();
+    let err = archive
+        .with_paths_mut()
+        .move_at("nope", "elsewhere")
+        .unwrap_err();
+    assert!(matches!(err, PathAccessError::ItemNotFound));
+    let err = archive
+        .with_paths_mut()
+        .move_at("subdir", "rootfile.txt/subdir")
+        .unwrap_err();
+    assert!(matches!(err, PathAccessError::FileCollision { .. }));
+    let err = archive.with_paths_mut().move_at("/", "root").unwrap_err();
+    assert!(matches!(err, PathAccessError::CannotMoveRoot));
+}
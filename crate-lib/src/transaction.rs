@@ -0,0 +1,221 @@
+use std::{
+    io::{Read, Seek, Write},
+    ops::Deref,
+};
+
+use crate::{
+    Archive, ArchiveError, Directory, DirectoryId, DirectoryIdOrRoot, File, FileId, ItemId,
+    ItemName, SyncData, Timestamp, UnixAttributes, archive::ArchiveSnapshot,
+};
+
+/// Group of changes applied to an archive in an all-or-nothing fashion
+///
+/// Obtained from [`Archive::transaction`]
+///
+/// Changes are immediately visible in memory, but metadata is only written to the archive
+/// when calling [`Transaction::commit`]. If the archive is journaled, all changes are
+/// committed as a single journal record, so that a crash can't leave them partially applied.
+///
+/// Space freed by removed or replaced files is only given back to the archive after the commit.
+///
+/// If an operation fails, or if the transaction is dropped without being committed,
+/// the archive's in-memory state is restored as it was before the transaction started.
+/// Only the changed items are tracked to do so, so a transaction's cost doesn't depend on the archive's size.
+pub struct Transaction<'a, S: Read + Write + Seek + SyncData> {
+    archive: &'a mut Archive<S>,
+    snapshot: Option<ArchiveSnapshot>,
+}
+
+impl<'a, S: Read + Write + Seek + SyncData> Transaction<'a, S> {
+    pub(crate) fn new(archive: &'a mut Archive<S>) -> Self {
+        let snapshot = archive.begin_transaction();
+
+        Self {
+            archive,
+            snapshot: Some(snapshot),
+        }
+    }
+
+    /// Create a new directory (see [`Archive::create_dir`])
+    pub fn create_dir(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+    ) -> Result<DirectoryId, ArchiveError> {
+        self.run(|archive| archive.create_dir(parent_dir, name, modif_time))
+    }
+
+    /// Create a new file (see [`Archive::create_file`])
+    pub fn create_file(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        content: impl Read + Seek,
+    ) -> Result<FileId, ArchiveError> {
+        self.run(|archive| archive.create_file(parent_dir, name, modif_time, content))
+    }
+
+    /// Create a new file from a stream of unknown length (see [`Archive::create_file_from_reader`])
+    pub fn create_file_from_reader(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        content: impl Read,
+    ) -> Result<FileId, ArchiveError> {
+        self.run(|archive| archive.create_file_from_reader(parent_dir, name, modif_time, content))
+    }
+
+    /// Create a new symbolic link (see [`Archive::create_symlink`])
+    pub fn create_symlink(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        modif_time: Timestamp,
+        target: String,
+    ) -> Result<FileId, ArchiveError> {
+        self.run(|archive| archive.create_symlink(parent_dir, name, modif_time, target))
+    }
+
+    /// Create a new hard link to an existing file (see [`Archive::create_hard_link`])
+    pub fn create_hard_link(
+        &mut self,
+        parent_dir: DirectoryIdOrRoot,
+        name: ItemName,
+        target: FileId,
+    ) -> Result<FileId, ArchiveError> {
+        self.run(|archive| archive.create_hard_link(parent_dir, name, target))
+    }
+
+    /// Overwrite an existing file's content and modification time (see [`Archive::replace_file_content`])
+    pub fn replace_file_content(
+        &mut self,
+        id: FileId,
+        new_modif_time: Timestamp,
+        new_content: impl Read + Seek,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.replace_file_content(id, new_modif_time, new_content))
+    }
+
+    /// Overwrite an existing file's content and modification time from a stream of unknown length
+    /// (see [`Archive::replace_file_content_from_reader`])
+    pub fn replace_file_content_from_reader(
+        &mut self,
+        id: FileId,
+        new_modif_time: Timestamp,
+        new_content: impl Read,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| {
+            archive.replace_file_content_from_reader(id, new_modif_time, new_content)
+        })
+    }
+
+    /// Rename a directory (see [`Archive::rename_directory`])
+    pub fn rename_directory(
+        &mut self,
+        id: DirectoryId,
+        new_name: ItemName,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.rename_directory(id, new_name))
+    }
+
+    /// Rename a file (see [`Archive::rename_file`])
+    pub fn rename_file(&mut self, id: FileId, new_name: ItemName) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.rename_file(id, new_name))
+    }
+
+    /// Move a directory (see [`Archive::move_dir`])
+    pub fn move_dir(
+        &mut self,
+        id: DirectoryId,
+        new_parent_dir: DirectoryIdOrRoot,
+        new_name: ItemName,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.move_dir(id, new_parent_dir, new_name))
+    }
+
+    /// Move a file (see [`Archive::move_file`])
+    pub fn move_file(
+        &mut self,
+        id: FileId,
+        new_parent_dir: DirectoryIdOrRoot,
+        new_name: ItemName,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.move_file(id, new_parent_dir, new_name))
+    }
+
+    /// Remove a directory, recursively (see [`Archive::remove_dir`])
+    pub fn remove_dir(&mut self, id: DirectoryId) -> Result<Directory, ArchiveError> {
+        self.run(|archive| archive.remove_dir(id))
+    }
+
+    /// Remove a file (see [`Archive::remove_file`])
+    pub fn remove_file(&mut self, id: FileId) -> Result<File, ArchiveError> {
+        self.run(|archive| archive.remove_file(id))
+    }
+
+    /// Set (or remove) the Unix attributes of an item (see [`Archive::set_unix_attributes`])
+    pub fn set_unix_attributes(
+        &mut self,
+        id: ItemId,
+        attributes: Option<UnixAttributes>,
+    ) -> Result<(), ArchiveError> {
+        self.run(|archive| archive.set_unix_attributes(id, attributes))
+    }
+
+    /// Write all changes to the archive
+    ///
+    /// If writing fails, the archive is restored as it was before the transaction started, both in memory
+    /// and on disk. The only exception is a journaled archive failing to apply the changes after committing
+    /// them to its journal: they are then kept, and applied again on the next write or when reopening the archive.
+    pub fn commit(mut self) -> Result<(), ArchiveError> {
+        let snapshot = self
+            .snapshot
+            .take()
+            .ok_or(ArchiveError::TransactionAborted)?;
+
+        self.archive.commit_transaction(snapshot)
+    }
+
+    /// Discard all changes
+    ///
+    /// This is equivalent to dropping the transaction.
+    pub fn rollback(self) {}
+
+    /// Check if the transaction was rolled back after one of its operations failed
+    pub fn is_aborted(&self) -> bool {
+        self.snapshot.is_none()
+    }
+
+    fn run<T>(
+        &mut self,
+        op: impl FnOnce(&mut Archive<S>) -> Result<T, ArchiveError>,
+    ) -> Result<T, ArchiveError> {
+        if self.snapshot.is_none() {
+            return Err(ArchiveError::TransactionAborted);
+        }
+
+        op(self.archive).inspect_err(|_| {
+            self.archive
+                .rollback_transaction(self.snapshot.take().unwrap())
+        })
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData> Deref for Transaction<'_, S> {
+    type Target = Archive<S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.archive
+    }
+}
+
+impl<S: Read + Write + Seek + SyncData> Drop for Transaction<'_, S> {
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            self.archive.rollback_transaction(snapshot);
+        }
+    }
+}
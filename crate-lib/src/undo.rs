@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    ops::{Deref, Index, IndexMut},
+};
+
+/// Hash map able to undo the changes made to it since it started recording (see [`crate::Transaction`])
+///
+/// Only the previous value of each changed key is kept, so recording costs nothing for untouched entries.
+pub(crate) struct UndoMap<K, V> {
+    map: HashMap<K, V>,
+    /// Value of each changed key before it was first changed, if any
+    undo: Option<HashMap<K, Option<V>>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> UndoMap<K, V> {
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.record(&key);
+        self.map.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.record(key);
+        self.map.remove(key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.record(key);
+        self.map.get_mut(key)
+    }
+
+    fn record(&mut self, key: &K) {
+        if let Some(undo) = &mut self.undo {
+            undo.entry(key.clone())
+                .or_insert_with(|| self.map.get(key).cloned());
+        }
+    }
+
+    /// Start recording changes
+    pub fn start_recording(&mut self) {
+        assert!(self.undo.is_none());
+        self.undo = Some(HashMap::new());
+    }
+
+    /// Stop recording changes, keeping them
+    pub fn keep_changes(&mut self) {
+        self.undo = None;
+    }
+
+    /// Stop recording changes, and undo them
+    pub fn undo_changes(&mut self) {
+        for (key, value) in self.undo.take().unwrap() {
+            match value {
+                Some(value) => self.map.insert(key, value),
+                None => self.map.remove(&key),
+            };
+        }
+    }
+}
+
+impl<K, V> From<HashMap<K, V>> for UndoMap<K, V> {
+    fn from(map: HashMap<K, V>) -> Self {
+        Self { map, undo: None }
+    }
+}
+
+impl<K, V> Deref for UndoMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+/// List able to undo the changes made to it since it started recording (see [`crate::Transaction`])
+pub(crate) struct UndoVec<T> {
+    vec: Vec<T>,
+    /// Length of the list when it started recording, and previous value of each changed item
+    undo: Option<(usize, HashMap<usize, T>)>,
+}
+
+impl<T: Clone> UndoVec<T> {
+    pub fn push(&mut self, value: T) {
+        self.vec.push(value);
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        let index = self.vec.len().checked_sub(1)?;
+        Some(&mut self[index])
+    }
+
+    /// Start recording changes
+    pub fn start_recording(&mut self) {
+        assert!(self.undo.is_none());
+        self.undo = Some((self.vec.len(), HashMap::new()));
+    }
+
+    /// Stop recording changes, keeping them
+    pub fn keep_changes(&mut self) {
+        self.undo = None;
+    }
+
+    /// Stop recording changes, and undo them
+    pub fn undo_changes(&mut self) {
+        let (len, changed) = self.undo.take().unwrap();
+
+        self.vec.truncate(len);
+
+        for (index, value) in changed {
+            self.vec[index] = value;
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for UndoVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self { vec, undo: None }
+    }
+}
+
+impl<T> Deref for UndoVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vec
+    }
+}
+
+impl<T> Index<usize> for UndoVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.vec[index]
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for UndoVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        if let Some((len, changed)) = &mut self.undo {
+            // Items pushed since recording started are removed entirely when undoing
+            if index < *len {
+                changed
+                    .entry(index)
+                    .or_insert_with(|| self.vec[index].clone());
+            }
+        }
+
+        &mut self.vec[index]
+    }
+}
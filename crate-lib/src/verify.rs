@@ -0,0 +1,161 @@
+use thiserror::Error;
+
+use crate::{FileId, ItemId, coverage::Segment};
+
+/// Options for verifying an archive's integrity (see [`crate::Archive::verify`])
+#[derive(Debug, Clone, Copy)]
+pub struct VerifyOptions {
+    /// Read every file's content to compare it with its checksum
+    ///
+    /// This requires reading the entire archive, which may take a long time for large archives.
+    pub checksums: bool,
+
+    /// Check that no file's content overlaps another file's content or the archive's metadata
+    pub overlaps: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            checksums: true,
+            overlaps: true,
+        }
+    }
+}
+
+/// Outcome of an archive's verification (see [`crate::Archive::verify`])
+#[derive(Debug)]
+pub struct VerificationReport {
+    /// Number of files whose content was compared with their checksum
+    pub checked_files: u64,
+
+    /// Problems found in the archive
+    pub errors: Vec<IntegrityError>,
+}
+
+impl VerificationReport {
+    /// Check if no problem was found
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Integrity problem found while verifying an archive
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// A file's content doesn't match its checksum
+    #[error(
+        "Content of file {file_id:?} doesn't match its checksum: expected {expected:x?}, got {actual:x?}"
+    )]
+    ChecksumMismatch {
+        /// ID of the file
+        file_id: FileId,
+        /// Checksum stored in the file's entry
+        expected: [u8; 32],
+        /// Checksum of the actual content
+        actual: [u8; 32],
+    },
+
+    /// A file's content is located (even partially) outside of the archive
+    #[error(
+        "Content of file {file_id:?} (address {content_addr}, length {content_len}) is out of the archive's bounds (length {archive_len})"
+    )]
+    OutOfBounds {
+        /// ID of the file
+        file_id: FileId,
+        /// Address of the file's content
+        content_addr: u64,
+        /// Length of the file's content
+        content_len: u64,
+        /// Length of the archive
+        archive_len: u64,
+    },
+
+    /// Two regions of the archive overlap
+    #[error("{region:?} overlaps {other:?}")]
+    Overlap {
+        /// Region starting last
+        region: ArchiveRegion,
+        /// Region it overlaps
+        other: ArchiveRegion,
+    },
+}
+
+/// Region of an archive holding some data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveRegion {
+    /// The archive's header
+    Header,
+
+    /// A file table segment
+    FileTableSegment {
+        /// Address of the segment
+        addr: u64,
+    },
+
+    /// The write-ahead journal
+    Journal,
+
+    /// A file's content
+    FileContent(FileId),
+
+    /// An item's record, holding what doesn't fit in its entry (see [`crate::FeatureFlags::REQUIRED_ITEM_RECORDS`])
+    ItemRecord(ItemId),
+}
+
+/// Check if a region is entirely located inside the archive
+///
+/// Empty regions hold no data, so they are always considered in bounds.
+pub(crate) fn is_in_bounds(segment: Segment, archive_len: u64) -> bool {
+    segment.len == 0
+        || segment
+            .start
+            .checked_add(segment.len)
+            .is_some_and(|end| end <= archive_len)
+}
+
+/// Find the regions overlapping a previous one
+///
+/// Each overlapping region is reported once, alongside the region it overlaps that ends last.
+/// Empty regions never overlap, and neither do files' contents located at the exact same place
+/// (see [`crate::Archive::create_hard_link`]).
+pub(crate) fn find_overlaps(
+    regions: impl IntoIterator<Item = (ArchiveRegion, Segment)>,
+) -> Vec<(ArchiveRegion, ArchiveRegion)> {
+    let mut regions = regions
+        .into_iter()
+        .filter(|(_, segment)| segment.len > 0)
+        .collect::<Vec<_>>();
+
+    regions.sort_by_key(|(_, segment)| *segment);
+
+    let mut overlaps = vec![];
+    let mut furthest: Option<(ArchiveRegion, u128)> = None;
+    let mut prev_content = None;
+
+    for (region, segment) in regions {
+        if let ArchiveRegion::FileContent(_) = region {
+            // Shared content is only stored once
+            if prev_content.replace(segment) == Some(segment) {
+                continue;
+            }
+        }
+
+        // Computing the end in 128 bits prevents overflows with corrupted addresses
+        let end = u128::from(segment.start) + u128::from(segment.len);
+
+        match furthest {
+            Some((other, other_end)) if u128::from(segment.start) < other_end => {
+                overlaps.push((region, other));
+
+                if end > other_end {
+                    furthest = Some((region, end));
+                }
+            }
+
+            _ => furthest = Some((region, end)),
+        }
+    }
+
+    overlaps
+}
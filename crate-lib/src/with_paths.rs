@@ -1,16 +1,22 @@
-use std::io::{Read, Seek};
+use std::{
+    collections::VecDeque,
+    io::{Read, Seek},
+};
 
 use thiserror::Error;
 
 use crate::{
-    Archive, ArchiveError, DirEntry, FileId, ItemName, PathError,
+    Archive, ArchiveError, DirEntry, FileId, ItemName,
     data::{
         directory::{Directory, DirectoryId, DirectoryIdOrRoot},
         file::File,
-        path::PathInArchive,
+        path::{PathError, PathInArchive},
     },
 };
 
+/// Maximum number of symbolic links followed while resolving a single path
+pub static MAX_SYMLINK_HOPS: usize = 40;
+
 /// Allows reading an archive using human-readable paths instead of IDs
 ///
 /// Obtained from [`Archive::with_paths`]
@@ -119,6 +125,99 @@ impl<'a, S: Read + Seek> WithPaths<'a, S> {
         curr_dir_entry
     }
 
+    /// Get the item located at the provided path, following symbolic links
+    ///
+    /// Links met in intermediate components are always followed, while a link in the last component
+    /// is only followed if `follow_last_link` is set.
+    ///
+    /// Relative targets are resolved from the link's parent directory. Absolute targets cannot be
+    /// resolved, as they point outside of the archive.
+    pub fn resolve_item_at(
+        &self,
+        path: &str,
+        follow_last_link: bool,
+    ) -> Result<ItemIdOrRoot, PathAccessError> {
+        let mut pending = PathInArchive::new(path)?
+            .components()
+            .iter()
+            .map(|component| component.to_string())
+            .collect::<VecDeque<_>>();
+
+        // Directories traversed to get to the current one (excluding root)
+        let mut traversed = Vec::<DirectoryId>::new();
+        let mut hops = 0;
+
+        while let Some(component) = pending.pop_front() {
+            match component.as_str() {
+                "" | "." => continue,
+
+                ".." => {
+                    if traversed.pop().is_none() {
+                        return Err(PathError::RootEscape.into());
+                    }
+
+                    continue;
+                }
+
+                _ => {}
+            }
+
+            let curr_dir = match traversed.last() {
+                Some(dir_id) => DirectoryIdOrRoot::NonRoot(*dir_id),
+                None => DirectoryIdOrRoot::Root,
+            };
+
+            let entry = self
+                .archive
+                .read_dir(curr_dir)?
+                .find(|item| item.name().as_ref() == component.as_str())
+                .ok_or(PathAccessError::ItemNotFound)?;
+
+            let file = match entry {
+                DirEntry::Directory(directory) => {
+                    traversed.push(directory.id);
+                    continue;
+                }
+
+                DirEntry::File(file) => file,
+            };
+
+            let target = match self.archive.read_link(file.id) {
+                Ok(target) if follow_last_link || !pending.is_empty() => target,
+
+                // Regular files and non-followed links
+                _ if pending.is_empty() => return Ok(ItemIdOrRoot::File(file.id)),
+
+                _ => {
+                    return Err(PathAccessError::FileCollision {
+                        path: self.compute_file_path(file.id)?,
+                    });
+                }
+            };
+
+            hops += 1;
+
+            if hops > MAX_SYMLINK_HOPS {
+                return Err(PathAccessError::TooManySymlinks);
+            }
+
+            if target.starts_with('/') {
+                return Err(PathAccessError::AbsoluteSymlinkTarget {
+                    target: target.to_owned(),
+                });
+            }
+
+            for component in target.split('/').rev() {
+                pending.push_front(component.to_owned());
+            }
+        }
+
+        Ok(match traversed.last() {
+            Some(dir_id) => ItemIdOrRoot::NonRootDirectory(*dir_id),
+            None => ItemIdOrRoot::Root,
+        })
+    }
+
     /// Get the directory located the provided path
     ///
     /// Will return [`None`] if a file exists at this location, or if the path points to the root
@@ -213,4 +312,19 @@ pub enum PathAccessError {
     /// Path cannot be empty
     #[error("Path cannot be empty")]
     EmptyPath,
+
+    /// The archive's root directory cannot be moved
+    #[error("The archive's root directory cannot be moved")]
+    CannotMoveRoot,
+
+    /// Too many symbolic links were met while resolving the path (see [`MAX_SYMLINK_HOPS`])
+    #[error("Too many levels of symbolic links")]
+    TooManySymlinks,
+
+    /// A symbolic link met while resolving the path points outside of the archive
+    #[error("Cannot follow symbolic link to absolute path '{target}'")]
+    AbsoluteSymlinkTarget {
+        /// Target of the link
+        target: String,
+    },
 }
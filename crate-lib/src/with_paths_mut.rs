@@ -1,8 +1,8 @@
 use std::io::{Read, Seek, Write};
 
 use crate::{
-    Archive, ArchiveError, DirEntry, Directory, DirectoryId, DirectoryIdOrRoot, FileReader,
-    PathAccessError, PathInArchive, Timestamp,
+    Archive, ArchiveError, DirEntry, Directory, DirectoryId, DirectoryIdOrRoot, FileId, FileReader,
+    ItemIdOrRoot, ItemName, PathAccessError, PathInArchive, SyncData, Timestamp,
 };
 
 /// Allows reading and manipulating an archive using human-readable paths instead of IDs
@@ -34,22 +34,14 @@ impl<'a, S: Read + Seek> WithPathsMut<'a, S> {
     }
 }
 
-impl<'a, S: Read + Write + Seek> WithPathsMut<'a, S> {
+impl<'a, S: Read + Write + Seek + SyncData> WithPathsMut<'a, S> {
     /// Create a directory at the provided path
     pub fn create_dir_at(
         &mut self,
         path: &str,
         modif_time: Timestamp,
     ) -> Result<DirectoryId, PathAccessError> {
-        let mut path = PathInArchive::new(path)?;
-
-        let filename = path.pop().ok_or(PathAccessError::EmptyPath)?;
-
-        let parent_dir = if path.is_empty() {
-            DirectoryIdOrRoot::Root
-        } else {
-            DirectoryIdOrRoot::NonRoot(self.get_or_create_dir_at(&path.to_string())?.id)
-        };
+        let (parent_dir, filename) = self.get_or_create_parent_dir_at(path)?;
 
         Ok(self.archive.create_dir(parent_dir, filename, modif_time)?)
     }
@@ -128,18 +120,33 @@ impl<'a, S: Read + Write + Seek> WithPathsMut<'a, S> {
                 .replace_file_content(file.id, modif_time, content)?);
         }
 
-        let mut path = PathInArchive::new(path)?;
+        let (parent_dir, filename) = self.get_or_create_parent_dir_at(path)?;
 
-        let filename = path.pop().ok_or(PathAccessError::EmptyPath)?;
+        self.archive
+            .create_file(parent_dir, filename, modif_time, content)?;
 
-        let parent_dir = if path.is_empty() {
-            DirectoryIdOrRoot::Root
-        } else {
-            DirectoryIdOrRoot::NonRoot(self.get_or_create_dir_at(&path.to_string())?.id)
-        };
+        Ok(())
+    }
+
+    /// Either create a file or replace an existing one at the provided path, from a stream of unknown length
+    ///
+    /// See [`Archive::create_file_from_reader`] for more details
+    pub fn write_file_from_reader_at(
+        &mut self,
+        path: &str,
+        content: impl Read,
+        modif_time: Timestamp,
+    ) -> Result<(), PathAccessError> {
+        if let Some(file) = self.archive.with_paths().get_file_at(path) {
+            return Ok(self
+                .archive
+                .replace_file_content_from_reader(file.id, modif_time, content)?);
+        }
+
+        let (parent_dir, filename) = self.get_or_create_parent_dir_at(path)?;
 
         self.archive
-            .create_file(parent_dir, filename, modif_time, content)?;
+            .create_file_from_reader(parent_dir, filename, modif_time, content)?;
 
         Ok(())
     }
@@ -178,6 +185,42 @@ impl<'a, S: Read + Write + Seek> WithPathsMut<'a, S> {
         self.write_file_at(path, content, modif_time)
     }
 
+    /// Create a symbolic link at the provided path (see [`Archive::create_symlink`])
+    pub fn create_symlink_at(
+        &mut self,
+        path: &str,
+        target: String,
+        modif_time: Timestamp,
+    ) -> Result<FileId, PathAccessError> {
+        let (parent_dir, filename) = self.get_or_create_parent_dir_at(path)?;
+
+        Ok(self
+            .archive
+            .create_symlink(parent_dir, filename, modif_time, target)?)
+    }
+
+    /// Create a hard link at the provided path to the file located at another path (see [`Archive::create_hard_link`])
+    pub fn create_hard_link_at(
+        &mut self,
+        path: &str,
+        target: &str,
+    ) -> Result<FileId, PathAccessError> {
+        let target = self
+            .archive
+            .with_paths()
+            .get_file_at(target)
+            .ok_or(PathAccessError::FileNotFound {
+                path: target.to_owned(),
+            })?
+            .id;
+
+        let (parent_dir, filename) = self.get_or_create_parent_dir_at(path)?;
+
+        Ok(self
+            .archive
+            .create_hard_link(parent_dir, filename, target)?)
+    }
+
     /// Remove the file at the provided path
     pub fn remove_file_at(&mut self, path: &str) -> Result<(), PathAccessError> {
         let file = self
@@ -193,4 +236,68 @@ impl<'a, S: Read + Write + Seek> WithPathsMut<'a, S> {
 
         Ok(())
     }
+
+    /// Move the item (file or directory) at the provided path to another path
+    ///
+    /// The destination path includes the item's new name, and its parent directory must already exist
+    pub fn move_at(&mut self, from: &str, to: &str) -> Result<(), PathAccessError> {
+        let item = self
+            .archive
+            .with_paths()
+            .get_item_at(from)
+            .ok_or(PathAccessError::ItemNotFound)?;
+
+        let mut to = PathInArchive::new(to)?;
+
+        let new_name = to.pop().ok_or(PathAccessError::EmptyPath)?;
+
+        let new_parent_dir = if to.is_empty() {
+            DirectoryIdOrRoot::Root
+        } else {
+            match self
+                .archive
+                .with_paths()
+                .get_item_at(&to.to_string())
+                .ok_or(PathAccessError::ItemNotFound)?
+            {
+                ItemIdOrRoot::Root => DirectoryIdOrRoot::Root,
+                ItemIdOrRoot::NonRootDirectory(dir_id) => DirectoryIdOrRoot::NonRoot(dir_id),
+                ItemIdOrRoot::File(_) => {
+                    return Err(PathAccessError::FileCollision {
+                        path: to.to_string(),
+                    });
+                }
+            }
+        };
+
+        match item {
+            ItemIdOrRoot::Root => Err(PathAccessError::CannotMoveRoot),
+
+            ItemIdOrRoot::NonRootDirectory(dir_id) => {
+                Ok(self.archive.move_dir(dir_id, new_parent_dir, new_name)?)
+            }
+
+            ItemIdOrRoot::File(file_id) => {
+                Ok(self.archive.move_file(file_id, new_parent_dir, new_name)?)
+            }
+        }
+    }
+
+    /// Get or create the parent directory of the provided path, and return it alongside the item's name
+    fn get_or_create_parent_dir_at(
+        &mut self,
+        path: &str,
+    ) -> Result<(DirectoryIdOrRoot, ItemName), PathAccessError> {
+        let mut path = PathInArchive::new(path)?;
+
+        let filename = path.pop().ok_or(PathAccessError::EmptyPath)?;
+
+        let parent_dir = if path.is_empty() {
+            DirectoryIdOrRoot::Root
+        } else {
+            DirectoryIdOrRoot::NonRoot(self.get_or_create_dir_at(&path.to_string())?.id)
+        };
+
+        Ok((parent_dir, filename))
+    }
 }